@@ -143,8 +143,8 @@
                revappend
                concatenate
                subseq
-               random-state-p
-               make-random-state
+               ;; `random-state-p' and `make-random-state' are primitives,
+               ;; which take random states that `random' understands.
                signum
                isqrt
                lcm
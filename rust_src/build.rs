@@ -714,6 +714,8 @@ fn run_bindgen() {
                 .blacklist_item("current_timespec")
                 .blacklist_item("timex")
                 .blacklist_item("clock_adjtime")
+                // use libc's definition, which hides glibc's anonymous unions
+                .blacklist_item("rusage")
                 // bindgen fails to generate this one correctly; it's hard
                 // https://github.com/rust-lang-nursery/rust-bindgen/issues/1318
                 .blacklist_item("max_align_t")
@@ -11,7 +11,7 @@ use std::io::{stdout, BufRead, BufReader, Write};
 use std::mem;
 use std::ptr;
 
-use remacs_util::parse_lisp_fn;
+use remacs_util::{is_option_type, parse_lisp_fn};
 
 #[allow(dead_code)]
 const INVALID: c_int = 0;
@@ -116,14 +116,29 @@ pub unsafe extern "C" fn scan_rust_file(
                 sig.extend(line_iter.next().unwrap());
             }
             let sig = sig.split(')').next().unwrap();
-            let has_many_args = sig.contains("&mut") || sig.contains("&[");
 
             // Split arg names and types
-            let splitters = [':', ','];
-            let args = sig.split_terminator(&splitters[..]).collect::<Vec<_>>();
-
-            let nargs = args.len() / 2;
-            let def_min_args = if has_many_args { 0 } else { nargs as i16 };
+            let args = sig
+                .split_terminator(',')
+                .filter(|arg| !arg.trim().is_empty())
+                .map(|arg| {
+                    let mut parts = arg.splitn(2, ':');
+                    let name = parts.next().unwrap().trim();
+                    let ty = parts.next().unwrap_or("").trim();
+                    (name, ty)
+                })
+                .collect::<Vec<_>>();
+
+            let is_rest = |ty: &str| ty.starts_with("&mut") || ty.starts_with("&[");
+            let has_many_args = args.iter().any(|&(_, ty)| is_rest(ty));
+
+            // Trailing `Option<T>` arguments are optional, as in the
+            // `lisp_fn` macro.
+            let nargs = args.len();
+            let def_min_args = args
+                .iter()
+                .rposition(|&(_, ty)| !is_rest(ty) && !is_option_type(ty))
+                .map_or(0, |i| i as i16 + 1);
             let attr_props = parse_lisp_fn(&attribute, name, def_min_args)
                 .unwrap_or_else(|e| panic!("Invalid #[lisp_fn] macro ({}): {}", attribute, e));
 
@@ -136,16 +151,15 @@ pub unsafe extern "C" fn scan_rust_file(
             } else {
                 // Create usage line (fn ARG1 ...) from signature if necessary
                 if docstring_usage.is_empty() {
-                    for (i, chunk) in args.chunks(2).enumerate() {
-                        if chunk[1].contains("&mut") || chunk[1].contains("&[") {
+                    for (i, &(argname, ty)) in args.iter().enumerate() {
+                        if is_rest(ty) {
                             docstring_usage.push(' ');
                             docstring_usage.push_str("&rest");
                         } else if i == attr_props.min as usize {
                             docstring_usage.push(' ');
                             docstring_usage.push_str("&optional");
                         }
-                        let argname = chunk[0]
-                            .trim()
+                        let argname = argname
                             .trim_left_matches("mut ")
                             .trim()
                             .to_uppercase()
@@ -5,17 +5,9 @@ type Result<T> = ::std::result::Result<T, &'static str>;
 pub enum LispFnType {
     /// A normal function with given max. number of arguments
     Normal(i16),
-    /// A function taking an arbitrary amount of arguments as a slice
-    Many,
-}
-
-impl LispFnType {
-    pub fn def_min_args(&self) -> i16 {
-        match *self {
-            LispFnType::Normal(n) => n,
-            LispFnType::Many => 0,
-        }
-    }
+    /// A function taking an arbitrary amount of arguments as a slice,
+    /// after the given number of fixed arguments
+    Many(i16),
 }
 
 pub struct Function {
@@ -27,6 +19,10 @@ pub struct Function {
 
     /// The function header
     pub args: Vec<syn::Ident>,
+
+    /// The minimum number of arguments: all the fixed arguments up to
+    /// and including the last one that is not an `Option<T>`
+    pub def_min_args: i16,
 }
 
 pub fn parse(item: &syn::Item) -> Result<Function> {
@@ -57,10 +53,13 @@ pub fn parse(item: &syn::Item) -> Result<Function> {
                 .map(get_fn_arg_ident_ty)
                 .collect::<Result<_>>()?;
 
+            let (fntype, def_min_args) = parse_function_type(&decl)?;
+
             Ok(Function {
                 name: ident.clone(),
-                fntype: parse_function_type(&decl)?,
-                args: args,
+                fntype,
+                args,
+                def_min_args,
             })
         }
         _ => Err("`lisp_fn` attribute can only be used on functions"),
@@ -85,37 +84,42 @@ fn get_fn_arg_ident_ty(fn_arg: &syn::FnArg) -> Result<syn::Ident> {
     }
 }
 
-fn parse_function_type(fndecl: &syn::FnDecl) -> Result<LispFnType> {
+fn parse_function_type(fndecl: &syn::FnDecl) -> Result<(LispFnType, i16)> {
     let nargs = fndecl.inputs.len() as i16;
-    for fnarg in &fndecl.inputs {
+    let mut min_args = 0;
+    for (i, fnarg) in fndecl.inputs.iter().enumerate() {
+        let i = i as i16;
         match *fnarg {
             syn::FnArg::Captured(syn::ArgCaptured { ref ty, .. }) | syn::FnArg::Ignored(ref ty) => {
                 match parse_arg_type(ty) {
-                    ArgType::LispObject => {}
                     ArgType::LispObjectSlice => {
-                        if fndecl.inputs.len() != 1 {
-                            return Err("`[LispObject]` cannot be mixed in with other types");
+                        if i != nargs - 1 {
+                            return Err("`[LispObject]` must be the last argument");
                         }
-                        return Ok(LispFnType::Many);
+                        return Ok((LispFnType::Many(i), min_args));
                     }
-                    ArgType::Other => {}
+                    ArgType::Optional => {}
+                    ArgType::LispObject | ArgType::Other => min_args = i + 1,
                 }
             }
             _ => return Err("lisp functions cannot have `self` arguments"),
         }
     }
-    Ok(LispFnType::Normal(nargs))
+    Ok((LispFnType::Normal(nargs), min_args))
 }
 
 enum ArgType {
     LispObject,
     LispObjectSlice,
+    Optional,
     Other,
 }
 
 fn parse_arg_type(fn_arg: &syn::Type) -> ArgType {
     if is_lisp_object(fn_arg) {
         ArgType::LispObject
+    } else if is_option(fn_arg) {
+        ArgType::Optional
     } else {
         match *fn_arg {
             syn::Type::Reference(syn::TypeReference {
@@ -157,3 +161,13 @@ fn is_lisp_object(ty: &syn::Type) -> bool {
         _ => false,
     }
 }
+
+fn is_option(ty: &syn::Type) -> bool {
+    match *ty {
+        syn::Type::Path(syn::TypePath {
+            qself: None,
+            ref path,
+        }) => remacs_util::is_option_type(&format!("{}", quote!(#path))),
+        _ => false,
+    }
+}
@@ -22,7 +22,7 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
     let lisp_fn_args = match remacs_util::parse_lisp_fn(
         &attr_ts.to_string(),
         &function.name,
-        function.fntype.def_min_args(),
+        function.def_min_args,
     ) {
         Ok(v) => v,
         Err(e) => panic!("Invalid lisp_fn attribute: {}", e),
@@ -49,7 +49,7 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
                 rargs.append_all(arg);
             }
         }
-        function::LispFnType::Many => {
+        function::LispFnType::Many(nfixed) => {
             let args = quote! {
                 nargs: libc::ptrdiff_t,
                 args: *mut crate::lisp::LispObject,
@@ -64,6 +64,26 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
             };
             body.append_all(b);
 
+            // Fixed arguments come first; any missing optional ones
+            // are nil, and the remaining arguments form the rest slice.
+            if nfixed > 0 {
+                let nfixed = nfixed as usize;
+                let b = quote! {
+                    let (fixed, args) = args.split_at_mut(std::cmp::min(#nfixed, args.len()));
+                };
+                body.append_all(b);
+
+                for (i, ident) in function.args.iter().take(nfixed).enumerate() {
+                    let b = quote! {
+                        let #ident = fixed.get(#i).cloned().unwrap_or(crate::remacs_sys::Qnil);
+                    };
+                    body.append_all(b);
+
+                    let arg = quote! { (#ident).into(), };
+                    rargs.append_all(arg);
+                }
+            }
+
             let arg = quote! { unsafe { std::mem::transmute(args) } };
             rargs.append_all(arg);
         }
@@ -92,7 +112,7 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
                 8 => quote! { a8 },
                 _ => panic!("max_args too high"),
             },
-            function::LispFnType::Many => quote! { aMANY },
+            function::LispFnType::Many(_) => quote! { aMANY },
         }
     };
 
@@ -101,7 +121,7 @@ pub fn lisp_fn(attr_ts: TokenStream, fn_ts: TokenStream) -> TokenStream {
     } else {
         match function.fntype {
             function::LispFnType::Normal(_) => quote! { #max_args },
-            function::LispFnType::Many => quote! { crate::lisp::MANY  },
+            function::LispFnType::Many(_) => quote! { crate::lisp::MANY  },
         }
     };
     let symbol_name = CByteLiteral(&lisp_fn_args.name);
@@ -84,3 +84,17 @@ where
             .and_then(|v| v.convert(def_name, def_min_args))
     }
 }
+
+/// Whether TY, the source text of an argument type, is an `Option<T>`.
+/// Trailing `Option` arguments of a `lisp_fn` are optional in Lisp.
+pub fn is_option_type(ty: &str) -> bool {
+    let ty: String = ty.chars().filter(|c| !c.is_whitespace()).collect();
+    [
+        "Option<",
+        "option::Option<",
+        "std::option::Option<",
+        "::std::option::Option<",
+    ]
+    .iter()
+    .any(|prefix| ty.starts_with(prefix))
+}
@@ -0,0 +1,250 @@
+//! Multi-pattern literal search.
+//!
+//! An Aho-Corasick automaton over characters, so that a whole list of
+//! strings can be looked for in a single pass over the text.  It runs
+//! over the same `Text` view as the regexp matcher, which reads both
+//! sides of the buffer gap in place.
+
+use std::collections::VecDeque;
+
+use crate::{
+    multibyte::{unibyte_to_char, Codepoint, LispStringRef},
+    regex::{CaseFold, Text},
+    remacs_sys::maybe_quit,
+};
+
+/// Number of characters scanned between checks for a pending quit.
+const QUIT_INTERVAL: usize = 0x1_0000;
+
+struct State {
+    /// Outgoing transitions, sorted by character.
+    next: Vec<(Codepoint, usize)>,
+    fail: usize,
+    /// Number of characters on the path from the root.
+    depth: usize,
+    /// The first listed pattern that ends in this state.
+    output: Option<usize>,
+    /// The nearest state along the failure chain that has an output.
+    dict: Option<usize>,
+}
+
+impl State {
+    fn new(depth: usize) -> Self {
+        State {
+            next: Vec::new(),
+            fail: 0,
+            depth,
+            output: None,
+            dict: None,
+        }
+    }
+
+    fn get(&self, c: Codepoint) -> Option<usize> {
+        self.next
+            .binary_search_by_key(&c, |&(k, _)| k)
+            .ok()
+            .map(|i| self.next[i].1)
+    }
+
+    fn insert(&mut self, c: Codepoint, target: usize) {
+        let i = self.next.binary_search_by_key(&c, |&(k, _)| k).unwrap_err();
+        self.next.insert(i, (c, target));
+    }
+}
+
+/// An occurrence found by `Automaton::find`, as byte offsets into the
+/// text, and the index of the pattern that matched.
+pub struct Match {
+    pub start: usize,
+    pub end: usize,
+    pub pattern: usize,
+}
+
+pub struct Automaton {
+    states: Vec<State>,
+    /// Length in characters of each pattern.
+    lengths: Vec<usize>,
+    longest: usize,
+    backward: bool,
+    fold: Option<CaseFold>,
+}
+
+impl Automaton {
+    /// Build an automaton for PATTERNS, folding case if the current
+    /// buffer's `case-fold-search' is on.  A BACKWARD automaton holds
+    /// the patterns reversed and scans the text from its end.
+    pub fn new(patterns: &[LispStringRef], backward: bool) -> Self {
+        let mut fold = CaseFold::current();
+        let mut states = vec![State::new(0)];
+        let mut lengths = Vec::with_capacity(patterns.len());
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            let multibyte = pattern.is_multibyte();
+            let mut chars: Vec<Codepoint> = pattern
+                .chars()
+                .map(|c| {
+                    let c = if multibyte { c } else { unibyte_to_char(c) };
+                    fold.as_mut().map_or(c, |fold| fold.translate(c))
+                })
+                .collect();
+            if backward {
+                chars.reverse();
+            }
+
+            let mut s = 0;
+            for &c in &chars {
+                s = match states[s].get(c) {
+                    Some(t) => t,
+                    None => {
+                        let t = states.len();
+                        let depth = states[s].depth + 1;
+                        states.push(State::new(depth));
+                        states[s].insert(c, t);
+                        t
+                    }
+                };
+            }
+            if states[s].output.is_none() {
+                states[s].output = Some(index);
+            }
+            lengths.push(chars.len());
+        }
+
+        // Failure links, breadth first so that shallower states are
+        // complete before their descendants need them.
+        let mut queue = VecDeque::new();
+        queue.push_back(0);
+        while let Some(s) = queue.pop_front() {
+            for i in 0..states[s].next.len() {
+                let (c, t) = states[s].next[i];
+                queue.push_back(t);
+                let fail = if s == 0 {
+                    0
+                } else {
+                    let mut f = states[s].fail;
+                    loop {
+                        if let Some(n) = states[f].get(c) {
+                            break n;
+                        }
+                        if f == 0 {
+                            break 0;
+                        }
+                        f = states[f].fail;
+                    }
+                };
+                states[t].fail = fail;
+                states[t].dict = if states[fail].output.is_some() {
+                    Some(fail)
+                } else {
+                    states[fail].dict
+                };
+            }
+        }
+
+        let longest = lengths.iter().cloned().max().unwrap_or(0);
+        Automaton {
+            states,
+            lengths,
+            longest,
+            backward,
+            fold,
+        }
+    }
+
+    fn step(&self, mut s: usize, c: Codepoint) -> usize {
+        loop {
+            if let Some(t) = self.states[s].get(c) {
+                return t;
+            }
+            if s == 0 {
+                return 0;
+            }
+            s = self.states[s].fail;
+        }
+    }
+
+    /// Find the occurrence of a pattern nearest to byte offset FROM in
+    /// TEXT, going no further than TO.  Of the occurrences that begin
+    /// equally near, the one whose pattern was listed first wins.
+    pub fn find(&mut self, text: &Text, from: usize, to: usize) -> Option<Match> {
+        // Byte offsets of the most recent character boundaries, enough
+        // to find where the longest pattern began.
+        let mut bounds = VecDeque::with_capacity(self.longest + 1);
+        bounds.push_back(from);
+        // The best occurrence so far, with the number of characters
+        // scanned before it began.
+        let mut best: Option<(usize, Match)> = None;
+        let mut state = 0;
+        let mut scanned = 0;
+        let mut pos = from;
+
+        loop {
+            let mut out = if self.states[state].output.is_some() {
+                Some(state)
+            } else {
+                self.states[state].dict
+            };
+            while let Some(o) = out {
+                if let Some(pattern) = self.states[o].output {
+                    let len = self.lengths[pattern];
+                    let begin = scanned - len;
+                    let better = best.as_ref().map_or(true, |&(b, ref m)| {
+                        begin < b || (begin == b && pattern < m.pattern)
+                    });
+                    if better {
+                        let other = bounds[bounds.len() - 1 - len];
+                        let (start, end) = if self.backward {
+                            (pos, other)
+                        } else {
+                            (other, pos)
+                        };
+                        best = Some((
+                            begin,
+                            Match {
+                                start,
+                                end,
+                                pattern,
+                            },
+                        ));
+                    }
+                }
+                out = self.states[o].dict;
+            }
+
+            // Anything found later begins no more than `depth' characters
+            // before the current position.
+            if let Some(&(begin, _)) = best.as_ref() {
+                if begin + self.states[state].depth < scanned {
+                    break;
+                }
+            }
+            if pos == to {
+                break;
+            }
+
+            let (c, next) = if self.backward {
+                let prev = text.prev_char_pos(pos);
+                (text.char_at(prev).0, prev)
+            } else {
+                let (c, len) = text.char_at(pos);
+                (c, pos + len)
+            };
+            let c = match self.fold {
+                Some(ref mut fold) => fold.translate(c),
+                None => c,
+            };
+            state = self.step(state, c);
+            pos = next;
+            scanned += 1;
+            bounds.push_back(pos);
+            if bounds.len() > self.longest + 1 {
+                bounds.pop_front();
+            }
+            if scanned % QUIT_INTERVAL == 0 {
+                unsafe { maybe_quit() };
+            }
+        }
+
+        best.map(|(_, m)| m)
+    }
+}
@@ -0,0 +1,267 @@
+//! Arbitrary-precision integers.
+//!
+//! Integers that don't fit in a fixnum are stored as bignums, a
+//! pseudovector holding a `BigInt`.  Bignums are always normalized:
+//! any value within fixnum range is represented as a fixnum, so that
+//! `eq' keeps working on small integers and a bignum is never `='
+//! to a fixnum.
+
+use std::cmp::Ordering;
+use std::ptr;
+use std::slice;
+
+use libc::{c_char, c_int, ptrdiff_t};
+use num_bigint::BigInt;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, ExternalPtr, LispObject},
+    remacs_sys::{make_unibyte_string, pvec_type, vectorlike_header, Lisp_Type, Lisp_Vector},
+    remacs_sys::{EmacsInt, EmacsUint, INTMASK},
+    remacs_sys::{Qinteger_or_marker_p, Qintegerp, Qoverflow_error},
+};
+
+/// The largest number of bits a bignum may have.  Operations whose
+/// result would be larger, such as `ash' and `expt' with huge
+/// arguments, signal an `overflow-error' rather than exhausting memory.
+pub const MAX_BIGNUM_BITS: usize = 65536;
+
+/// An integer too large to be a fixnum.
+#[repr(C)]
+pub struct Lisp_Bignum {
+    header: vectorlike_header,
+    /// The value, owned by this object and freed by `finalize_bignum'.
+    value: *mut BigInt,
+}
+
+pub type LispBignumRef = ExternalPtr<Lisp_Bignum>;
+
+impl LispBignumRef {
+    fn allocate(value: BigInt) -> Self {
+        let ptr = allocate_pseudovector!(Lisp_Bignum, value, pvec_type::PVEC_BIGNUM);
+        let mut bignum = LispBignumRef::new(ptr);
+        bignum.value = Box::into_raw(Box::new(value));
+        bignum
+    }
+
+    pub fn value(&self) -> &BigInt {
+        unsafe { &*self.value }
+    }
+
+    pub fn to_float(self) -> f64 {
+        bigint_to_float(self.value())
+    }
+}
+
+impl LispObject {
+    pub fn is_bignum(self) -> bool {
+        self.as_vectorlike()
+            .map_or(false, |v| v.is_pseudovector(pvec_type::PVEC_BIGNUM))
+    }
+
+    pub fn as_bignum(self) -> Option<LispBignumRef> {
+        if self.is_bignum() {
+            Some(LispBignumRef::new(
+                self.get_untaggedptr() as *mut Lisp_Bignum
+            ))
+        } else {
+            None
+        }
+    }
+
+    /// Make an integer from N, as a bignum if it is out of fixnum range.
+    pub fn from_integer(n: EmacsInt) -> LispObject {
+        if LispObject::fixnum_overflow(n) {
+            LispBignumRef::allocate(BigInt::from(n)).into()
+        } else {
+            LispObject::from_fixnum(n)
+        }
+    }
+
+    /// Return the value of an integer, be it a fixnum or a bignum.
+    pub fn as_bigint(self) -> Option<BigInt> {
+        if let Some(n) = self.as_fixnum() {
+            Some(BigInt::from(n))
+        } else {
+            self.as_bignum().map(|b| b.value().clone())
+        }
+    }
+
+    pub fn as_bigint_or_error(self) -> BigInt {
+        self.as_bigint()
+            .unwrap_or_else(|| wrong_type!(Qintegerp, self))
+    }
+
+    pub fn as_bigint_coerce_marker_or_error(self) -> BigInt {
+        if let Some(m) = self.as_marker() {
+            BigInt::from(m.charpos_or_error() as EmacsInt)
+        } else {
+            self.as_bigint()
+                .unwrap_or_else(|| wrong_type!(Qinteger_or_marker_p, self))
+        }
+    }
+}
+
+impl From<BigInt> for LispObject {
+    fn from(n: BigInt) -> Self {
+        match n.to_i64() {
+            Some(n) if !LispObject::fixnum_overflow(n as EmacsInt) => {
+                LispObject::from_fixnum(n as EmacsInt)
+            }
+            _ => LispBignumRef::allocate(n).into(),
+        }
+    }
+}
+
+impl From<LispBignumRef> for LispObject {
+    fn from(b: LispBignumRef) -> Self {
+        LispObject::tag_ptr(b, Lisp_Type::Lisp_Vectorlike)
+    }
+}
+
+/// Convert N to the nearest float, or to an infinity if it is too
+/// large.
+pub fn bigint_to_float(n: &BigInt) -> f64 {
+    n.to_f64().unwrap_or_else(|| {
+        if n.is_negative() {
+            ::std::f64::NEG_INFINITY
+        } else {
+            ::std::f64::INFINITY
+        }
+    })
+}
+
+/// Convert the integral part of F to an integer, or return `None` if
+/// F is an infinity or a NaN.
+pub fn float_to_bigint(f: f64) -> Option<BigInt> {
+    if f.is_finite() {
+        BigInt::from_f64(f.trunc())
+    } else {
+        None
+    }
+}
+
+/// Compare the integer N with the float F, exactly.  Return `None` if F
+/// is a NaN.
+pub fn compare_bigint_float(n: &BigInt, f: f64) -> Option<Ordering> {
+    if f.is_nan() {
+        None
+    } else if f.is_infinite() {
+        Some(if f > 0.0 {
+            Ordering::Less
+        } else {
+            Ordering::Greater
+        })
+    } else {
+        let int_part = f.trunc();
+        match n.cmp(&float_to_bigint(int_part).unwrap()) {
+            Ordering::Equal => 0.0.partial_cmp(&(f - int_part)),
+            order => Some(order),
+        }
+    }
+}
+
+/// Signal an `overflow-error' unless a result of BITS bits is
+/// acceptable for a bignum.
+pub fn check_bignum_bits(bits: usize) {
+    if bits > MAX_BIGNUM_BITS {
+        xsignal!(Qoverflow_error);
+    }
+}
+
+/// Return N shifted left by COUNT bits, or right if COUNT is negative.
+/// Right shifts round towards negative infinity, as if N were in
+/// two's complement.
+pub fn bigint_shift(n: &BigInt, count: EmacsInt) -> BigInt {
+    if n.is_zero() {
+        BigInt::zero()
+    } else if count >= 0 {
+        check_bignum_bits(n.bits().saturating_add(count as usize));
+        n << count as usize
+    } else {
+        let count = count.checked_neg().unwrap_or(EmacsInt::max_value()) as usize;
+        if count >= n.bits() {
+            if n.is_negative() {
+                -BigInt::one()
+            } else {
+                BigInt::zero()
+            }
+        } else if n.is_negative() {
+            // -((-n - 1) >> count) - 1 rounds towards negative infinity.
+            let magnitude = -n - BigInt::one();
+            -(magnitude >> count) - BigInt::one()
+        } else {
+            n >> count
+        }
+    }
+}
+
+/// Free the value of a bignum being garbage collected.
+#[no_mangle]
+pub extern "C" fn finalize_bignum(vector: *mut Lisp_Vector) {
+    let mut bignum = LispBignumRef::new(vector as *mut Lisp_Bignum);
+    if !bignum.value.is_null() {
+        unsafe { Box::from_raw(bignum.value) };
+        bignum.value = ptr::null_mut();
+    }
+}
+
+/// Return true if the bignums A and B have the same value.
+#[no_mangle]
+pub extern "C" fn bignum_equal(a: LispObject, b: LispObject) -> bool {
+    match (a.as_bignum(), b.as_bignum()) {
+        (Some(a), Some(b)) => a.value() == b.value(),
+        _ => false,
+    }
+}
+
+/// Return a hash code for the bignum OBJ, clipped to INTMASK like the
+/// result of `sxhash'.
+#[no_mangle]
+pub extern "C" fn bignum_hash(obj: LispObject) -> EmacsUint {
+    let bignum = obj.as_bignum().unwrap();
+    let (sign, bytes) = bignum.value().to_bytes_le();
+    let hash = bytes.iter().fold(sign as EmacsUint, |hash, &byte| {
+        hash.rotate_left(4).wrapping_add(EmacsUint::from(byte))
+    });
+    (hash ^ (hash >> 32)) & INTMASK as EmacsUint
+}
+
+/// Return the representation of the bignum OBJ in BASE, as a unibyte
+/// string.
+#[no_mangle]
+pub extern "C" fn bignum_to_string(obj: LispObject, base: c_int) -> LispObject {
+    let digits = obj.as_bignum().unwrap().value().to_str_radix(base as u32);
+    unsafe { make_unibyte_string(digits.as_ptr() as *const c_char, digits.len() as ptrdiff_t) }
+}
+
+/// Return the integer written as the LEN DIGITS in BASE, negated if
+/// NEGATIVE.  This is used by the reader for integers that don't fit
+/// in a fixnum.
+#[no_mangle]
+pub extern "C" fn bignum_from_digits(
+    digits: *const c_char,
+    len: ptrdiff_t,
+    base: c_int,
+    negative: bool,
+) -> LispObject {
+    let digits = unsafe { slice::from_raw_parts(digits as *const u8, len as usize) };
+    let n = BigInt::parse_bytes(digits, base as u32).unwrap();
+    LispObject::from(if negative { -n } else { n })
+}
+
+/// Return t if OBJECT is a bignum.
+#[lisp_fn]
+pub fn bignump(object: LispObject) -> bool {
+    object.is_bignum()
+}
+
+/// Return t if OBJECT is a fixnum.
+#[lisp_fn]
+pub fn fixnump(object: LispObject) -> bool {
+    object.is_fixnum()
+}
+
+include!(concat!(env!("OUT_DIR"), "/bignum_exports.rs"));
@@ -0,0 +1,154 @@
+//! Read-only views of buffer text.
+//!
+//! A buffer keeps its text in one block of memory with a gap in it, so
+//! any stretch of text is two byte slices, one on either side of the
+//! gap.  `BufferText` hands out those slices safely: while a view is
+//! alive the buffer counts it as a reader, and the C code refuses to
+//! change the characters of, move or free text that has readers.
+//! Text properties may still change, for instance when a search runs
+//! `syntax-propertize'.
+
+use std::cell::Cell;
+use std::rc::Rc;
+use std::slice;
+
+use libc::{c_void, ptrdiff_t};
+
+use crate::{
+    buffers::LispBufferRef,
+    eval::unbind_to,
+    remacs_sys::Qnil,
+    remacs_sys::{buffer_text, record_unwind_protect_ptr},
+    threads::c_specpdl_index,
+};
+
+/// The reader registered by a `BufferText`.  The view shares it with
+/// an unwind-protect entry, so that a non-local exit past the view
+/// still releases the text, and whichever of the two goes first
+/// releases it.
+struct Reader {
+    text: *mut buffer_text,
+    active: Cell<bool>,
+}
+
+impl Reader {
+    fn release(&self) {
+        if self.active.replace(false) {
+            unsafe { (*self.text).readers -= 1 };
+        }
+    }
+}
+
+extern "C" fn release_reader(ptr: *mut c_void) {
+    let reader = unsafe { Box::from_raw(ptr as *mut Rc<Reader>) };
+    reader.release();
+}
+
+/// A read-only view of a stretch of a buffer's text.  Slices borrowed
+/// from the view cannot outlive it, and so cannot outlive the guard.
+pub struct BufferText {
+    before: (*const u8, usize),
+    after: (*const u8, usize),
+    multibyte: bool,
+    reader: Rc<Reader>,
+    count: ptrdiff_t,
+}
+
+impl BufferText {
+    /// View the accessible portion of BUFFER.
+    pub fn new(buffer: LispBufferRef) -> Self {
+        Self::region(buffer, buffer.begv_byte, buffer.zv_byte)
+    }
+
+    /// View the whole text of BUFFER, ignoring any narrowing.
+    pub fn whole(buffer: LispBufferRef) -> Self {
+        Self::region(buffer, buffer.beg_byte(), buffer.z_byte())
+    }
+
+    /// View the text of BUFFER between byte positions START and END.
+    pub fn region(buffer: LispBufferRef, start: ptrdiff_t, end: ptrdiff_t) -> Self {
+        assert!(buffer.beg_byte() <= start && start <= end && end <= buffer.z_byte());
+
+        let gpt = buffer.gpt_byte();
+        let before_end = end.min(gpt).max(start);
+        let after_start = start.max(gpt);
+        let after_end = end.max(after_start);
+        let (before, after) = unsafe {
+            (
+                (
+                    buffer.beg_addr().offset(start - buffer.beg_byte()) as *const u8,
+                    (before_end - start) as usize,
+                ),
+                (
+                    buffer
+                        .beg_addr()
+                        .offset(after_start + buffer.gap_size() - buffer.beg_byte())
+                        as *const u8,
+                    (after_end - after_start) as usize,
+                ),
+            )
+        };
+
+        let count = c_specpdl_index();
+        let reader = Rc::new(Reader {
+            text: buffer.text,
+            active: Cell::new(true),
+        });
+        unsafe {
+            (*buffer.text).readers += 1;
+            let entry = Box::into_raw(Box::new(Rc::clone(&reader)));
+            record_unwind_protect_ptr(Some(release_reader), entry as *mut c_void);
+        }
+
+        BufferText {
+            before,
+            after,
+            multibyte: buffer.multibyte_characters_enabled(),
+            reader,
+            count,
+        }
+    }
+
+    /// The text before and after the gap.  Either may be empty.
+    pub fn as_slices(&self) -> (&[u8], &[u8]) {
+        unsafe {
+            (
+                slice::from_raw_parts(self.before.0, self.before.1),
+                slice::from_raw_parts(self.after.0, self.after.1),
+            )
+        }
+    }
+
+    /// Copy the text into a single vector.
+    pub fn to_vec(&self) -> Vec<u8> {
+        let (before, after) = self.as_slices();
+        let mut bytes = Vec::with_capacity(self.len_bytes());
+        bytes.extend_from_slice(before);
+        bytes.extend_from_slice(after);
+        bytes
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.before.1 + self.after.1
+    }
+
+    pub fn is_multibyte(&self) -> bool {
+        self.multibyte
+    }
+}
+
+impl Drop for BufferText {
+    fn drop(&mut self) {
+        // While our unwind-protect entry is pending it holds the other
+        // reference, and nothing above it can have been unwound.
+        let pending = Rc::strong_count(&self.reader) > 1;
+        if pending && c_specpdl_index() == self.count + 1 {
+            // Our entry is the innermost one, so unwind it now.
+            unbind_to(self.count, Qnil);
+        } else {
+            // Either the entry already ran, or it will free its
+            // reference when it unwinds.
+            self.reader.release();
+        }
+    }
+}
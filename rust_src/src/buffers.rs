@@ -127,6 +127,7 @@ impl LispBufferRef {
         }
     }
 
+    #[allow(dead_code)]
     pub fn z_addr(self) -> *mut c_uchar {
         unsafe {
             (*self.text)
@@ -655,7 +656,7 @@ pub fn overlayp(object: LispObject) -> bool {
 
 /// Return non-nil if OBJECT is a buffer which has not been killed.
 /// Value is nil if OBJECT is not a buffer or if it has been killed.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn buffer_live_p(object: Option<LispBufferRef>) -> bool {
     object.map_or(false, |m| m.is_live())
 }
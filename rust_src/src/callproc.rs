@@ -1,14 +1,37 @@
 //! Synchronous subprocess invocation for GNU Emacs.
 
+use std::ffi::OsStr;
+use std::io::{self, ErrorKind, Read, Write};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::AsRawFd;
+use std::os::unix::process::{CommandExt, ExitStatusExt};
+use std::process::{Child, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use libc::{c_char, c_void, ptrdiff_t};
+
 use crate::{
     eval::unbind_to,
     lisp::{defsubr, LispObject},
+    lists::{list, plist_get},
+    multibyte::LispStringRef,
+    process::{child_environment, decide_process_coding_systems, find_program},
     remacs_macros::lisp_fn,
     remacs_sys::Fexpand_file_name,
-    remacs_sys::NULL_DEVICE,
     remacs_sys::{
-        build_string, call_process, close_file_unwind, emacs_open, encode_file_name,
-        record_unwind_protect_int, report_file_error,
+        build_string, call_process, clear_unwind_protect, close_file_unwind,
+        code_convert_string_norecord, complement_process_encoding_system, emacs_open,
+        encode_current_directory, encode_file_name, make_unibyte_string, maybe_quit,
+        record_deleted_pid, record_unwind_protect_int, record_unwind_protect_ptr,
+        remove_slash_colon, report_file_errno, report_file_error, restore_nofile_limit,
+        Ffile_accessible_directory_p,
+    },
+    remacs_sys::{EmacsInt, NULL_DEVICE},
+    remacs_sys::{
+        QCcoding, QCcommand, QCcwd, QCenv, QCexit_code, QCsignal, QCstderr, QCstdin_string,
+        QCstdout, QCtimed_out, QCtimeout, Qcall_process, Qnil, Qt,
     },
     threads::{c_specpdl_index, ThreadState},
 };
@@ -79,4 +102,375 @@ pub fn call_process_lisp(args: &mut [LispObject]) -> LispObject {
     })
 }
 
+/// How long a timed out process gets to exit after SIGTERM before it
+/// is killed with SIGKILL, in milliseconds.
+const KILL_GRACE_PERIOD_MS: u64 = 1000;
+
+/// How often to check whether a synchronous process has exited, in
+/// milliseconds.
+const POLL_INTERVAL_MS: u64 = 10;
+
+/// The largest `:timeout' accepted by `call-process*', in seconds.
+const MAX_TIMEOUT_SECONDS: u32 = u32::max_value();
+
+/// When the threads reading the output of a program should give up,
+/// even if the pipes are still open.  None means to read until EOF.
+type ReadDeadline = Arc<Mutex<Option<Instant>>>;
+
+/// Return the "NAME=VALUE" strings for the `:env' alist ENV.
+/// A binding whose value is nil removes NAME from the environment.
+fn environment_overrides(env: LispObject) -> Vec<Vec<u8>> {
+    env.iter_cars_safe()
+        .map(|binding| {
+            let binding = binding.as_cons_or_error();
+            let name = binding.car().as_string_or_error();
+            if name.as_slice().contains(&b'=') {
+                error!("Invalid environment variable name: {}", name);
+            }
+            let value = binding.cdr();
+            if value.is_nil() {
+                name.as_slice().to_vec()
+            } else {
+                let value = value.as_string_or_error();
+                [name.as_slice(), b"=", value.as_slice()].concat()
+            }
+        })
+        .collect()
+}
+
+/// Return the encoded directory a process run with `:cwd' CWD starts in.
+fn encode_working_directory(cwd: LispObject) -> LispStringRef {
+    if cwd.is_nil() {
+        return unsafe { encode_current_directory() }.as_string_or_error();
+    }
+    let dir = unsafe {
+        Fexpand_file_name(
+            cwd.as_string_or_error().into(),
+            ThreadState::current_buffer().directory_,
+        )
+    };
+    if unsafe { Ffile_accessible_directory_p(dir) }.is_nil() {
+        unsafe {
+            report_file_error(
+                b"Setting current directory\0".as_ptr() as *const c_char,
+                cwd,
+            )
+        };
+    }
+    unsafe { encode_file_name(remove_slash_colon(dir)) }.as_string_or_error()
+}
+
+/// Encode STRING with the coding system CODING if it is multibyte.
+fn encode_process_string(string: LispObject, coding: LispObject) -> LispObject {
+    if string.as_string_or_error().is_multibyte() && coding.is_not_nil() {
+        unsafe { code_convert_string_norecord(string, coding, true) }
+    } else {
+        string
+    }
+}
+
+/// Read everything from PIPE in a separate thread, so that a child
+/// filling one pipe can't block while we wait on another.  The thread
+/// stops at EOF or once DEADLINE has passed, since a process that
+/// escaped being killed may keep the pipe open indefinitely.
+fn read_in_thread<R: Read + AsRawFd + Send + 'static>(
+    pipe: Option<R>,
+    deadline: &ReadDeadline,
+) -> Option<JoinHandle<Vec<u8>>> {
+    pipe.map(|mut pipe| {
+        let deadline = Arc::clone(deadline);
+        thread::spawn(move || {
+            let mut output = Vec::new();
+            let mut buf = [0; 4096];
+            loop {
+                if let Some(time) = *deadline.lock().unwrap() {
+                    if Instant::now() >= time {
+                        break;
+                    }
+                }
+                let mut pollfd = libc::pollfd {
+                    fd: pipe.as_raw_fd(),
+                    events: libc::POLLIN,
+                    revents: 0,
+                };
+                let ready = unsafe { libc::poll(&mut pollfd, 1, POLL_INTERVAL_MS as libc::c_int) };
+                if ready < 0 && io::Error::last_os_error().kind() != ErrorKind::Interrupted {
+                    break;
+                }
+                if ready <= 0 {
+                    continue;
+                }
+                match pipe.read(&mut buf) {
+                    Ok(0) => break,
+                    Ok(n) => output.extend_from_slice(&buf[..n]),
+                    Err(ref err) if err.kind() == ErrorKind::Interrupted => {}
+                    Err(_) => break,
+                }
+            }
+            output
+        })
+    })
+}
+
+/// Return the output collected by READER, decoded with DECODING.
+fn collect_output(reader: Option<JoinHandle<Vec<u8>>>, decoding: LispObject) -> LispObject {
+    let output = reader
+        .and_then(|reader| reader.join().ok())
+        .unwrap_or_default();
+    let string =
+        unsafe { make_unibyte_string(output.as_ptr() as *const c_char, output.len() as ptrdiff_t) };
+    if decoding.is_nil() {
+        string
+    } else {
+        unsafe { code_convert_string_norecord(string, decoding, false) }
+    }
+}
+
+/// Send SIGNAL to the process group of CHILD, so that any processes
+/// it started are killed along with it.
+fn kill_child(child: &Child, signal: libc::c_int) {
+    unsafe { libc::kill(-(child.id() as libc::pid_t), signal) };
+}
+
+/// Make the readers in DEADLINE give up after the kill grace period.
+fn stop_reading_after_grace_period(deadline: &ReadDeadline) {
+    *deadline.lock().unwrap() = Some(Instant::now() + Duration::from_millis(KILL_GRACE_PERIOD_MS));
+}
+
+/// What to clean up if `call-process*' exits non-locally while its
+/// program is still running.
+struct RunningChild {
+    pid: libc::pid_t,
+    read_deadline: ReadDeadline,
+}
+
+/// Kill the process group of the `RunningChild' at PTR and free it.
+/// The SIGCHLD handler reaps the program later.
+extern "C" fn kill_running_child(ptr: *mut c_void) {
+    let running = unsafe { Box::from_raw(ptr as *mut RunningChild) };
+    unsafe {
+        libc::kill(-running.pid, libc::SIGKILL);
+        record_deleted_pid(running.pid, Qnil);
+    }
+    stop_reading_after_grace_period(&running.read_deadline);
+}
+
+/// Run a program synchronously and return what it did, as a plist.
+///
+/// The arguments are specified as keyword/argument pairs.  The following
+/// arguments are defined:
+///
+/// :command COMMAND -- COMMAND is a list starting with the program file
+/// name, followed by strings to give to the program as arguments.
+///
+/// :env ENV -- ENV is an alist of (NAME . VALUE) pairs of environment
+/// variables, which take precedence over `process-environment'.  A nil
+/// VALUE removes NAME from the environment of the program.
+///
+/// :cwd DIRECTORY -- Run the program in DIRECTORY instead of
+/// `default-directory'.
+///
+/// :timeout SECONDS -- If the program is still running after SECONDS,
+/// send it and the processes it started SIGTERM, and SIGKILL if it has
+/// not exited a second later.  Output still arriving a second after
+/// that is discarded.
+///
+/// :stdin-string STRING -- Send STRING to the standard input of the
+/// program.  Otherwise the standard input is the null device.
+///
+/// :coding CODING -- If CODING is a symbol, it specifies the coding
+/// system used both to decode the output and to encode STRING.  If
+/// CODING is a cons (DECODING . ENCODING), DECODING is used for the
+/// output, and ENCODING for STRING.
+///
+/// The value is a plist with the following properties:
+///
+/// :exit-code -- the exit status of the program, or nil if it was
+/// killed by a signal.
+/// :signal -- the number of the signal that killed the program, or nil.
+/// :timed-out -- non-nil if the program was killed because of `:timeout'.
+/// :stdout -- the standard output of the program, as a string.
+/// :stderr -- the standard error of the program, as a string.
+///
+/// If you quit, the program is killed with SIGKILL.
+///
+/// usage: (call-process* &rest ARGS)
+#[lisp_fn(name = "call-process*", c_name = "call_process_star")]
+pub fn call_process_star(args: &mut [LispObject]) -> LispObject {
+    let contact = list(args);
+
+    let command = plist_get(contact, QCcommand);
+    let program = command.as_cons_or_error().car().as_string_or_error();
+    let arguments: Vec<LispObject> = command.as_cons_or_error().cdr().iter_cars_safe().collect();
+
+    let current_dir = encode_working_directory(plist_get(contact, QCcwd));
+    let overrides = environment_overrides(plist_get(contact, QCenv));
+
+    let timeout = plist_get(contact, QCtimeout);
+    let timeout = if timeout.is_nil() {
+        None
+    } else {
+        let seconds = timeout.any_to_float_or_error();
+        // This also rejects NaNs.
+        if !(seconds >= 0.0 && seconds <= f64::from(MAX_TIMEOUT_SECONDS)) {
+            args_out_of_range!(
+                timeout,
+                LispObject::from(0),
+                LispObject::from(EmacsInt::from(MAX_TIMEOUT_SECONDS))
+            );
+        }
+        Some(Duration::from_millis((seconds * 1000.0) as u64))
+    };
+
+    // Decide the coding systems the way `call-process' does.
+    let mut operation = vec![Qcall_process, program.into(), Qnil, Qt, Qnil];
+    operation.extend_from_slice(&arguments);
+    let (decoding, encoding) =
+        decide_process_coding_systems(plist_get(contact, QCcoding), &mut operation);
+
+    let stdin_string = plist_get(contact, QCstdin_string);
+    let stdin_string = if stdin_string.is_nil() {
+        None
+    } else {
+        Some(encode_process_string(stdin_string, encoding))
+    };
+
+    let file = unsafe { encode_file_name(find_program(program)) };
+    let arg_encoding = unsafe { complement_process_encoding_system(encoding) };
+    let arguments: Vec<LispObject> = arguments
+        .into_iter()
+        .map(|arg| encode_process_string(arg, arg_encoding))
+        .collect();
+
+    let mut command = Command::new(OsStr::from_bytes(file.as_string_or_error().as_slice()));
+    command
+        .args(
+            arguments
+                .iter()
+                .map(|arg| OsStr::from_bytes(arg.as_string_or_error().as_slice())),
+        )
+        .current_dir(OsStr::from_bytes(current_dir.as_slice()))
+        .env_clear()
+        .envs(child_environment(current_dir, overrides))
+        .stdin(if stdin_string.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+    unsafe {
+        command.before_exec(|| {
+            // Run the program in its own process group, so that a
+            // timeout or a quit can kill everything it started.
+            libc::setpgid(0, 0);
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+            libc::signal(libc::SIGPROF, libc::SIG_DFL);
+            restore_nofile_limit();
+            Ok(())
+        })
+    };
+
+    let mut child = match command.spawn() {
+        Ok(child) => child,
+        Err(err) => {
+            unsafe {
+                report_file_errno(
+                    b"Spawning child process\0".as_ptr() as *const c_char,
+                    program.into(),
+                    err.raw_os_error().unwrap_or(0),
+                )
+            };
+            unreachable!();
+        }
+    };
+
+    let stdin_writer = child.stdin.take().map(|mut pipe| {
+        let input =
+            stdin_string.map_or_else(Vec::new, |s| s.as_string_or_error().as_slice().to_vec());
+        thread::spawn(move || {
+            // The program may exit without reading all of its input.
+            let _ = pipe.write_all(&input);
+        })
+    });
+    let read_deadline: ReadDeadline = Arc::new(Mutex::new(None));
+    let stdout_reader = read_in_thread(child.stdout.take(), &read_deadline);
+    let stderr_reader = read_in_thread(child.stderr.take(), &read_deadline);
+
+    // Until the program has been reaped, a quit or an error kills it
+    // and everything it started.
+    let count = c_specpdl_index();
+    let running = Box::into_raw(Box::new(RunningChild {
+        pid: child.id() as libc::pid_t,
+        read_deadline: Arc::clone(&read_deadline),
+    }));
+    unsafe { record_unwind_protect_ptr(Some(kill_running_child), running as *mut c_void) };
+
+    let mut deadline = timeout.map(|timeout| Instant::now() + timeout);
+    let mut timed_out = false;
+    let status = loop {
+        match child.try_wait() {
+            Ok(Some(status)) => break status,
+            Ok(None) => {}
+            Err(err) => {
+                unsafe {
+                    report_file_errno(
+                        b"Waiting for child process\0".as_ptr() as *const c_char,
+                        program.into(),
+                        err.raw_os_error().unwrap_or(0),
+                    )
+                };
+            }
+        }
+
+        unsafe { maybe_quit() };
+
+        if let Some(time) = deadline {
+            if Instant::now() >= time {
+                if timed_out {
+                    kill_child(&child, libc::SIGKILL);
+                    deadline = None;
+                } else {
+                    timed_out = true;
+                    kill_child(&child, libc::SIGTERM);
+                    deadline = Some(time + Duration::from_millis(KILL_GRACE_PERIOD_MS));
+                }
+            }
+        }
+
+        thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+    };
+
+    // The program has been reaped, so its process ID may be reused.
+    unsafe {
+        clear_unwind_protect(count);
+        drop(Box::from_raw(running));
+    }
+    unbind_to(count, Qnil);
+
+    if timed_out {
+        // Something that survived the kill may still hold the pipes
+        // open.  Don't wait for it, and leave the input writer behind.
+        stop_reading_after_grace_period(&read_deadline);
+    } else if let Some(writer) = stdin_writer {
+        let _ = writer.join();
+    }
+    let stdout = collect_output(stdout_reader, decoding);
+    let stderr = collect_output(stderr_reader, decoding);
+
+    list(&[
+        QCexit_code,
+        status.code().map_or(Qnil, LispObject::from),
+        QCsignal,
+        status.signal().map_or(Qnil, LispObject::from),
+        QCtimed_out,
+        timed_out.into(),
+        QCstdout,
+        stdout,
+        QCstderr,
+        stderr,
+    ])
+}
+
 include!(concat!(env!("OUT_DIR"), "/callproc_exports.rs"));
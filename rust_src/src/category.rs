@@ -10,7 +10,7 @@ use crate::{
 use remacs_macros::lisp_fn;
 
 /// Return t if ARG is a category table.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn category_table_p(arg: Option<LispCharTableRef>) -> bool {
     arg.map_or(false, |table| table.purpose.eq(Qcategory_table))
 }
@@ -1,18 +1,23 @@
 #![allow(dead_code)] // XXX unused code belongs into translation of new extract_data_from_object fn
 
+use blake2::{Blake2b, Blake2s};
 use libc::ptrdiff_t;
 use md5;
 use sha1;
 use sha2::{Digest, Sha224, Sha256, Sha384, Sha512};
+use sha3::{Sha3_256, Sha3_512};
 use std;
+use std::ptr;
 use std::slice;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffer_text::BufferText,
     buffers::{buffer_file_name, LispBufferOrName, LispBufferRef},
     lisp::defsubr,
-    lisp::LispObject,
+    lisp::{ExternalPtr, LispObject},
+    lists::list,
     multibyte::LispStringRef,
     remacs_sys::{
         code_convert_string, extract_data_from_object, preferred_coding_system,
@@ -23,9 +28,11 @@ use crate::{
     },
     remacs_sys::{globals, Ffind_operation_coding_system, Flocal_variable_p},
     remacs_sys::{make_specified_string, make_uninit_string, EmacsInt},
+    remacs_sys::{pvec_type, vectorlike_header, Lisp_Type, Lisp_Vector},
     remacs_sys::{
-        Qbuffer_file_coding_system, Qcoding_system_error, Qmd5, Qnil, Qraw_text, Qsha1, Qsha224,
-        Qsha256, Qsha384, Qsha512, Qstringp, Qwrite_region,
+        Qblake2b, Qblake2s, Qbuffer_file_coding_system, Qcoding_system_error, Qhash_context_p,
+        Qmd5, Qnil, Qraw_text, Qsha1, Qsha224, Qsha256, Qsha384, Qsha3_256, Qsha3_512, Qsha512,
+        Qstringp, Qwrite_region,
     },
     symbols::{fboundp, symbol_name},
     threads::ThreadState,
@@ -39,6 +46,10 @@ enum HashAlg {
     SHA256,
     SHA384,
     SHA512,
+    SHA3_256,
+    SHA3_512,
+    BLAKE2B,
+    BLAKE2S,
 }
 
 static MD5_DIGEST_LEN: usize = 16;
@@ -47,6 +58,204 @@ static SHA224_DIGEST_LEN: usize = 224 / 8;
 static SHA256_DIGEST_LEN: usize = 256 / 8;
 static SHA384_DIGEST_LEN: usize = 384 / 8;
 static SHA512_DIGEST_LEN: usize = 512 / 8;
+static SHA3_256_DIGEST_LEN: usize = 256 / 8;
+static SHA3_512_DIGEST_LEN: usize = 512 / 8;
+static BLAKE2B_DIGEST_LEN: usize = 64;
+static BLAKE2S_DIGEST_LEN: usize = 32;
+
+type HashFn = fn(&[u8], &mut [u8]);
+
+impl HashAlg {
+    /// Return the digest size in bytes and the function computing it.
+    fn digest_fn(self) -> (usize, HashFn) {
+        match self {
+            HashAlg::MD5 => (MD5_DIGEST_LEN, md5_buffer as HashFn),
+            HashAlg::SHA1 => (SHA1_DIGEST_LEN, sha1_buffer as HashFn),
+            HashAlg::SHA224 => (SHA224_DIGEST_LEN, sha224_buffer as HashFn),
+            HashAlg::SHA256 => (SHA256_DIGEST_LEN, sha256_buffer as HashFn),
+            HashAlg::SHA384 => (SHA384_DIGEST_LEN, sha384_buffer as HashFn),
+            HashAlg::SHA512 => (SHA512_DIGEST_LEN, sha512_buffer as HashFn),
+            HashAlg::SHA3_256 => (SHA3_256_DIGEST_LEN, sha3_256_buffer as HashFn),
+            HashAlg::SHA3_512 => (SHA3_512_DIGEST_LEN, sha3_512_buffer as HashFn),
+            HashAlg::BLAKE2B => (BLAKE2B_DIGEST_LEN, blake2b_buffer as HashFn),
+            HashAlg::BLAKE2S => (BLAKE2S_DIGEST_LEN, blake2s_buffer as HashFn),
+        }
+    }
+
+    /// Return the size in bytes of the blocks the hash function
+    /// operates on, as needed for HMAC.
+    fn block_size(self) -> usize {
+        match self {
+            HashAlg::MD5 | HashAlg::SHA1 | HashAlg::SHA224 | HashAlg::SHA256 => 64,
+            HashAlg::SHA384 | HashAlg::SHA512 => 128,
+            // The rate of the Keccak sponge.
+            HashAlg::SHA3_256 => 136,
+            HashAlg::SHA3_512 => 72,
+            HashAlg::BLAKE2B => 128,
+            HashAlg::BLAKE2S => 64,
+        }
+    }
+}
+
+/// The running state of an incremental hash computation.
+enum HashState {
+    MD5(md5::Context),
+    SHA1(sha1::Sha1),
+    SHA224(Sha224),
+    SHA256(Sha256),
+    SHA384(Sha384),
+    SHA512(Sha512),
+    SHA3_256(Sha3_256),
+    SHA3_512(Sha3_512),
+    BLAKE2B(Blake2b),
+    BLAKE2S(Blake2s),
+}
+
+impl HashAlg {
+    fn new_state(self) -> HashState {
+        match self {
+            HashAlg::MD5 => HashState::MD5(md5::Context::new()),
+            HashAlg::SHA1 => HashState::SHA1(sha1::Sha1::new()),
+            HashAlg::SHA224 => HashState::SHA224(Sha224::new()),
+            HashAlg::SHA256 => HashState::SHA256(Sha256::new()),
+            HashAlg::SHA384 => HashState::SHA384(Sha384::new()),
+            HashAlg::SHA512 => HashState::SHA512(Sha512::new()),
+            HashAlg::SHA3_256 => HashState::SHA3_256(Sha3_256::default()),
+            HashAlg::SHA3_512 => HashState::SHA3_512(Sha3_512::default()),
+            HashAlg::BLAKE2B => HashState::BLAKE2B(Blake2b::default()),
+            HashAlg::BLAKE2S => HashState::BLAKE2S(Blake2s::default()),
+        }
+    }
+}
+
+impl HashState {
+    fn update(&mut self, buffer: &[u8]) {
+        match *self {
+            HashState::MD5(ref mut ctx) => ctx.consume(buffer),
+            HashState::SHA1(ref mut ctx) => ctx.update(buffer),
+            HashState::SHA224(ref mut ctx) => ctx.input(buffer),
+            HashState::SHA256(ref mut ctx) => ctx.input(buffer),
+            HashState::SHA384(ref mut ctx) => ctx.input(buffer),
+            HashState::SHA512(ref mut ctx) => ctx.input(buffer),
+            HashState::SHA3_256(ref mut ctx) => sha3::Digest::input(ctx, buffer),
+            HashState::SHA3_512(ref mut ctx) => sha3::Digest::input(ctx, buffer),
+            HashState::BLAKE2B(ref mut ctx) => sha3::Digest::input(ctx, buffer),
+            HashState::BLAKE2S(ref mut ctx) => sha3::Digest::input(ctx, buffer),
+        }
+    }
+
+    /// Write the final digest to `dest_buf`, following the same
+    /// convention as the `HashFn` functions.
+    fn finish(self, dest_buf: &mut [u8]) {
+        match self {
+            HashState::MD5(ctx) => {
+                let output = ctx.compute();
+                dest_buf[..output.len()].copy_from_slice(&*output)
+            }
+            HashState::SHA1(ctx) => {
+                let output = ctx.digest().bytes();
+                dest_buf[..output.len()].copy_from_slice(&output)
+            }
+            HashState::SHA224(ctx) => sha2_finish(ctx, dest_buf),
+            HashState::SHA256(ctx) => sha2_finish(ctx, dest_buf),
+            HashState::SHA384(ctx) => sha2_finish(ctx, dest_buf),
+            HashState::SHA512(ctx) => sha2_finish(ctx, dest_buf),
+            HashState::SHA3_256(ctx) => digest_finish(ctx, dest_buf),
+            HashState::SHA3_512(ctx) => digest_finish(ctx, dest_buf),
+            HashState::BLAKE2B(ctx) => digest_finish(ctx, dest_buf),
+            HashState::BLAKE2S(ctx) => digest_finish(ctx, dest_buf),
+        }
+    }
+}
+
+fn sha2_finish<D: Digest>(hasher: D, dest_buf: &mut [u8]) {
+    let output = hasher.result();
+    dest_buf[..output.len()].copy_from_slice(&output)
+}
+
+fn digest_finish<D: sha3::Digest>(hasher: D, dest_buf: &mut [u8]) {
+    let output = hasher.result();
+    dest_buf[..output.len()].copy_from_slice(&output)
+}
+
+/// A hash computation in progress, as returned by `make-hash-context'.
+#[repr(C)]
+pub struct Lisp_Hash_Context {
+    header: vectorlike_header,
+    /// The algorithm symbol, kept for printing.
+    algorithm: LispObject,
+    /// The digest state, or null once the context has been finished.
+    state: *mut HashState,
+}
+
+pub type LispHashContextRef = ExternalPtr<Lisp_Hash_Context>;
+
+impl LispHashContextRef {
+    fn allocate(algorithm: LispObject, state: HashState) -> Self {
+        let ctx_ptr =
+            allocate_pseudovector!(Lisp_Hash_Context, state, pvec_type::PVEC_HASH_CONTEXT);
+        let mut ctx = LispHashContextRef::new(ctx_ptr);
+        ctx.algorithm = algorithm;
+        ctx.state = Box::into_raw(Box::new(state));
+        ctx
+    }
+
+    fn algorithm(self) -> HashAlg {
+        hash_alg(self.algorithm)
+    }
+
+    fn state_or_error(&mut self) -> &mut HashState {
+        match unsafe { self.state.as_mut() } {
+            Some(state) => state,
+            None => error!("Hash context has already been finished"),
+        }
+    }
+
+    /// Take the digest state out of the context, leaving it finished.
+    fn take_state(mut self) -> HashState {
+        self.state_or_error();
+        let state = unsafe { Box::from_raw(self.state) };
+        self.state = ptr::null_mut();
+        *state
+    }
+}
+
+impl LispObject {
+    pub fn is_hash_context(self) -> bool {
+        self.as_vectorlike()
+            .map_or(false, |v| v.is_pseudovector(pvec_type::PVEC_HASH_CONTEXT))
+    }
+
+    pub fn as_hash_context_or_error(self) -> LispHashContextRef {
+        if self.is_hash_context() {
+            LispHashContextRef::new(self.get_untaggedptr() as *mut Lisp_Hash_Context)
+        } else {
+            wrong_type!(Qhash_context_p, self);
+        }
+    }
+}
+
+impl From<LispObject> for LispHashContextRef {
+    fn from(o: LispObject) -> Self {
+        o.as_hash_context_or_error()
+    }
+}
+
+impl From<LispHashContextRef> for LispObject {
+    fn from(ctx: LispHashContextRef) -> Self {
+        LispObject::tag_ptr(ctx, Lisp_Type::Lisp_Vectorlike)
+    }
+}
+
+/// Free the digest state of a hash context being garbage collected.
+#[no_mangle]
+pub extern "C" fn finalize_hash_context(vector: *mut Lisp_Vector) {
+    let mut ctx = LispHashContextRef::new(vector as *mut Lisp_Hash_Context);
+    if !ctx.state.is_null() {
+        unsafe { Box::from_raw(ctx.state) };
+        ctx.state = ptr::null_mut();
+    }
+}
 
 fn hash_alg(algorithm: LispObject) -> HashAlg {
     algorithm.as_symbol_or_error();
@@ -62,6 +271,14 @@ fn hash_alg(algorithm: LispObject) -> HashAlg {
         HashAlg::SHA384
     } else if algorithm == Qsha512 {
         HashAlg::SHA512
+    } else if algorithm == Qsha3_256 {
+        HashAlg::SHA3_256
+    } else if algorithm == Qsha3_512 {
+        HashAlg::SHA3_512
+    } else if algorithm == Qblake2b {
+        HashAlg::BLAKE2B
+    } else if algorithm == Qblake2s {
+        HashAlg::BLAKE2S
     } else {
         let name = symbol_name(algorithm.as_symbol_or_error()).as_string_or_error();
         error!("Invalid algorithm arg: {:?}\0", &name.as_slice());
@@ -313,7 +530,8 @@ pub fn md5(
 
 /// Return the secure hash of OBJECT, a buffer or string.
 /// ALGORITHM is a symbol specifying the hash to use:
-/// md5, sha1, sha224, sha256, sha384 or sha512.
+/// md5, sha1, sha224, sha256, sha384, sha512, sha3-256, sha3-512,
+/// blake2b or blake2s.
 ///
 /// The two optional arguments START and END are positions specifying for
 /// which part of OBJECT to compute the hash.  If nil or omitted, uses the
@@ -333,6 +551,142 @@ pub fn secure_hash(
     _secure_hash(hash_alg(algorithm), object, start, end, Qnil, Qnil, binary)
 }
 
+/// Return a list of all the supported `secure_hash' algorithms.
+#[lisp_fn]
+pub fn secure_hash_algorithms() -> LispObject {
+    list(&[
+        Qmd5, Qsha1, Qsha224, Qsha256, Qsha384, Qsha512, Qsha3_256, Qsha3_512, Qblake2b, Qblake2s,
+    ])
+}
+
+/// Return the HMAC of OBJECT, a buffer or string, keyed with KEY.
+/// ALGORITHM is a symbol specifying the underlying hash function, as in
+/// `secure-hash'.  KEY is a string; like OBJECT, it is encoded with
+/// the preferred coding system if it is multibyte.
+///
+/// The two optional arguments START and END are positions specifying for
+/// which part of OBJECT to compute the HMAC.  If nil or omitted, uses the
+/// whole OBJECT.
+///
+/// If BINARY is non-nil, returns a string in binary form.
+#[lisp_fn(min = "3")]
+pub fn secure_hmac(
+    algorithm: LispObject,
+    key: LispObject,
+    object: LispObject,
+    start: LispObject,
+    end: LispObject,
+    binary: LispObject,
+) -> LispObject {
+    let algorithm = hash_alg(algorithm);
+    let (digest_size, hash_func) = algorithm.digest_fn();
+    let block_size = algorithm.block_size();
+
+    let key_string = key.as_string_or_error();
+    let key = get_input(key, &mut Some(key_string), &None, Qnil, Qnil, Qnil, Qnil);
+    let input = get_input(
+        object,
+        &mut object.as_string(),
+        &object.as_buffer(),
+        start,
+        end,
+        Qnil,
+        Qnil,
+    );
+
+    // Keys longer than a block are hashed first; shorter ones are
+    // padded with zeros.
+    let mut padded_key = vec![0; block_size];
+    if key.len_bytes() as usize > block_size {
+        hash_func(key.as_slice(), &mut padded_key[..digest_size]);
+    } else {
+        padded_key[..key.len_bytes() as usize].copy_from_slice(key.as_slice());
+    }
+
+    let mut inner: Vec<u8> = padded_key.iter().map(|b| b ^ 0x36).collect();
+    inner.extend_from_slice(input.as_slice());
+    let mut outer: Vec<u8> = padded_key.iter().map(|b| b ^ 0x5c).collect();
+    let inner_start = outer.len();
+    outer.resize(inner_start + digest_size, 0);
+    hash_func(&inner, &mut outer[inner_start..]);
+
+    let buffer_size = if binary.is_nil() {
+        digest_size * 2
+    } else {
+        digest_size
+    };
+    let digest = unsafe { make_uninit_string(buffer_size as EmacsInt) };
+    let mut digest_str = digest.as_string_or_error();
+    hash_func(&outer, digest_str.as_mut_slice());
+    if binary.is_nil() {
+        hexify_digest_string(digest_str.as_mut_slice(), digest_size);
+    }
+    digest
+}
+
+/// Return t if OBJECT is a hash context.
+#[lisp_fn]
+pub fn hash_context_p(object: LispObject) -> bool {
+    object.is_hash_context()
+}
+
+/// Return a context for computing the secure hash of some data
+/// incrementally.  ALGORITHM is a symbol specifying the hash to use, as
+/// in `secure-hash'.
+///
+/// Feed data to the context with `hash-context-update' and retrieve the
+/// digest with `hash-context-finish'.
+#[lisp_fn]
+pub fn make_hash_context(algorithm: LispObject) -> LispHashContextRef {
+    let state = hash_alg(algorithm).new_state();
+    LispHashContextRef::allocate(algorithm, state)
+}
+
+/// Add the contents of OBJECT, a buffer or string, to the hash context CTX.
+///
+/// The two optional arguments START and END are positions specifying
+/// which part of OBJECT to add.  If nil or omitted, uses the whole
+/// OBJECT.  Multibyte text is encoded the same way as by `secure-hash'.
+#[lisp_fn(min = "2")]
+pub fn hash_context_update(
+    mut ctx: LispHashContextRef,
+    object: LispObject,
+    start: LispObject,
+    end: LispObject,
+) {
+    // Extracting the input can run Lisp, which may finish CTX, so only
+    // look at its state afterwards.
+    let input = get_input(
+        object,
+        &mut object.as_string(),
+        &object.as_buffer(),
+        start,
+        end,
+        Qnil,
+        Qnil,
+    );
+    ctx.state_or_error().update(input.as_slice());
+}
+
+/// Return the secure hash of all the data added to the hash context CTX.
+/// After this, CTX can no longer be updated.
+///
+/// If BINARY is non-nil, returns a string in binary form.
+#[lisp_fn(min = "1")]
+pub fn hash_context_finish(ctx: LispHashContextRef, binary: bool) -> LispObject {
+    let (digest_size, _) = ctx.algorithm().digest_fn();
+    let state = ctx.take_state();
+
+    let buffer_size = if binary { digest_size } else { digest_size * 2 };
+    let digest = unsafe { make_uninit_string(buffer_size as EmacsInt) };
+    let mut digest_str = digest.as_string_or_error();
+    state.finish(digest_str.as_mut_slice());
+    if !binary {
+        hexify_digest_string(digest_str.as_mut_slice(), digest_size);
+    }
+    digest
+}
+
 fn _secure_hash(
     algorithm: HashAlg,
     object: LispObject,
@@ -342,8 +696,6 @@ fn _secure_hash(
     noerror: LispObject,
     binary: LispObject,
 ) -> LispObject {
-    type HashFn = fn(&[u8], &mut [u8]);
-
     let spec = list!(object, start, end, coding_system, noerror);
     let mut start_byte: ptrdiff_t = 0;
     let mut end_byte: ptrdiff_t = 0;
@@ -360,14 +712,7 @@ fn _secure_hash(
         )
     };
 
-    let (digest_size, hash_func) = match algorithm {
-        HashAlg::MD5 => (MD5_DIGEST_LEN, md5_buffer as HashFn),
-        HashAlg::SHA1 => (SHA1_DIGEST_LEN, sha1_buffer as HashFn),
-        HashAlg::SHA224 => (SHA224_DIGEST_LEN, sha224_buffer as HashFn),
-        HashAlg::SHA256 => (SHA256_DIGEST_LEN, sha256_buffer as HashFn),
-        HashAlg::SHA384 => (SHA384_DIGEST_LEN, sha384_buffer as HashFn),
-        HashAlg::SHA512 => (SHA512_DIGEST_LEN, sha512_buffer as HashFn),
-    };
+    let (digest_size, hash_func) = algorithm.digest_fn();
 
     let buffer_size = if binary.is_nil() {
         (digest_size * 2) as EmacsInt
@@ -445,6 +790,34 @@ fn sha512_buffer(buffer: &[u8], dest_buf: &mut [u8]) {
     sha2_hash_buffer(Sha512::new(), buffer, dest_buf);
 }
 
+/// Like `sha2_hash_buffer', for the hashes built on the newer `Digest'
+/// trait shared by the sha3 and blake2 crates.
+fn digest_hash_buffer<D>(hasher: D, buffer: &[u8], dest_buf: &mut [u8])
+where
+    D: sha3::Digest,
+{
+    let mut hasher = hasher;
+    hasher.input(buffer);
+    let output = hasher.result();
+    dest_buf[..output.len()].copy_from_slice(&output)
+}
+
+fn sha3_256_buffer(buffer: &[u8], dest_buf: &mut [u8]) {
+    digest_hash_buffer(Sha3_256::default(), buffer, dest_buf);
+}
+
+fn sha3_512_buffer(buffer: &[u8], dest_buf: &mut [u8]) {
+    digest_hash_buffer(Sha3_512::default(), buffer, dest_buf);
+}
+
+fn blake2b_buffer(buffer: &[u8], dest_buf: &mut [u8]) {
+    digest_hash_buffer(Blake2b::default(), buffer, dest_buf);
+}
+
+fn blake2s_buffer(buffer: &[u8], dest_buf: &mut [u8]) {
+    digest_hash_buffer(Blake2s::default(), buffer, dest_buf);
+}
+
 /// Return a hash of the contents of BUFFER-OR-NAME.
 /// This hash is performed on the raw internal format of the buffer,
 /// disregarding any coding systems.  If nil, use the current buffer.
@@ -453,17 +826,10 @@ pub fn buffer_hash(buffer_or_name: Option<LispBufferOrName>) -> LispObject {
     let b = buffer_or_name.map_or_else(ThreadState::current_buffer, |b| b.into());
     let mut ctx = sha1::Sha1::new();
 
-    ctx.update(unsafe {
-        slice::from_raw_parts(b.beg_addr(), (b.gpt_byte() - b.beg_byte()) as usize)
-    });
-    if b.gpt_byte() < b.z_byte() {
-        ctx.update(unsafe {
-            slice::from_raw_parts(
-                b.gap_end_addr(),
-                b.z_addr() as usize - b.gap_end_addr() as usize,
-            )
-        });
-    }
+    let text = BufferText::whole(b);
+    let (before, after) = text.as_slices();
+    ctx.update(before);
+    ctx.update(after);
 
     let formatted = ctx.digest().to_string();
     let digest = unsafe { make_uninit_string(formatted.len() as EmacsInt) };
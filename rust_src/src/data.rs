@@ -6,12 +6,13 @@ use libc::c_int;
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::per_buffer_idx,
+    buffers::{per_buffer_idx, LispBufferRef},
+    eval::unbind_to,
     frames::selected_frame,
     keymap::get_keymap,
     lisp::{defsubr, is_autoload},
     lisp::{LispObject, LispSubrRef, LiveBufferIter},
-    lists::{get, member, memq, put},
+    lists::{assoc, delq, get, member, memq, put},
     math::leq,
     multibyte::{is_ascii, is_single_byte_char},
     obarray::{loadhist_attach, map_obarray},
@@ -19,24 +20,26 @@ use crate::{
     remacs_sys::Vautoload_queue,
     remacs_sys::{
         aset_multibyte_string, bool_vector_binop_driver, buffer_defaults, build_string, globals,
-        rust_count_one_bits, set_default_internal, set_internal, symbol_trapped_write,
-        valid_lisp_object_p, wrong_choice, wrong_range, CHAR_TABLE_SET, CHECK_IMPURE,
+        record_unwind_protect, rust_count_one_bits, set_default_internal, set_internal,
+        symbol_trapped_write, valid_lisp_object_p, wrong_choice, wrong_range, CHAR_TABLE_SET,
+        CHECK_IMPURE,
     },
     remacs_sys::{buffer_local_flags, per_buffer_default, symbol_redirect},
     remacs_sys::{pvec_type, BoolVectorOp, EmacsInt, Lisp_Misc_Type, Lisp_Type, Set_Internal_Bind},
-    remacs_sys::{Fdelete, Fget, Fpurecopy},
+    remacs_sys::{Fdelete, Fget, Flocal_variable_if_set_p, Fpurecopy},
     remacs_sys::{Lisp_Buffer, Lisp_Subr_Lang},
     remacs_sys::{
         Qargs_out_of_range, Qarrayp, Qautoload, Qbool_vector, Qbuffer, Qchar_table, Qchoice,
         Qcompiled_function, Qcondition_variable, Qcons, Qcyclic_function_indirection,
-        Qdefalias_fset_function, Qdefun, Qfinalizer, Qfloat, Qfont, Qfont_entity, Qfont_object,
-        Qfont_spec, Qframe, Qfunction_documentation, Qhash_table, Qinteger, Qmany, Qmarker,
-        Qmodule_function, Qmutex, Qnil, Qnone, Qoverlay, Qprocess, Qrange, Qsetting_constant,
-        Qstring, Qsubr, Qsymbol, Qt, Qterminal, Qthread, Qunbound, Qunevalled, Quser_ptr, Qvector,
-        Qvoid_variable, Qwatchers, Qwindow, Qwindow_configuration,
+        Qdefalias_fset_function, Qdefun, Qdefvaralias, Qfinalizer, Qfloat, Qfont, Qfont_entity,
+        Qfont_object, Qfont_spec, Qframe, Qfunction_documentation, Qhash_context, Qhash_table,
+        Qinteger, Qlet, Qmakunbound, Qmany, Qmarker, Qmodule_function, Qmutex, Qnil, Qnone,
+        Qoverlay, Qprocess, Qrandom_state, Qrange, Qset, Qset_default, Qsetting_constant, Qstring,
+        Qsubr, Qsymbol, Qt, Qterminal, Qthread, Qunbound, Qunevalled, Qunlet, Quser_ptr, Qvector,
+        Qvoid_variable, Qwatcher_filters, Qwatchers, Qwindow, Qwindow_configuration,
     },
     symbols::LispSymbolRef,
-    threads::ThreadState,
+    threads::{c_specpdl_index, ThreadState},
 };
 
 // Lisp_Fwd predicates which can go away as the callers are ported to Rust
@@ -131,6 +134,9 @@ pub fn type_of(object: LispObject) -> LispObject {
                 pvec_type::PVEC_CONDVAR => Qcondition_variable,
                 pvec_type::PVEC_TERMINAL => Qterminal,
                 pvec_type::PVEC_MODULE_FUNCTION => Qmodule_function,
+                pvec_type::PVEC_HASH_CONTEXT => Qhash_context,
+                pvec_type::PVEC_BIGNUM => Qinteger,
+                pvec_type::PVEC_RANDOM_STATE => Qrandom_state,
                 pvec_type::PVEC_FONT => {
                     if object.is_font_spec() {
                         Qfont_spec
@@ -711,6 +717,67 @@ extern "C" fn harmonize_variable_watchers(alias: LispObject, base_variable: Lisp
     }
 }
 
+/// Restore the trap on SYMBOL once its watchers have run.
+extern "C" fn restore_symbol_trapped_write(symbol: LispObject) {
+    symbol
+        .as_symbol_or_error()
+        .set_trapped_write(symbol_trapped_write::SYMBOL_TRAPPED_WRITE);
+}
+
+/// Return true if a watcher registered with FILTER, an element of the
+/// `watcher-filters' property of the form (FUNCTION OPERATIONS . WHERE),
+/// should be told about OPERATION on the value in WHERE.  A watcher
+/// registered without a filter is told about everything.
+fn watcher_filter_matches(filter: LispObject, operation: LispObject, where_: LispObject) -> bool {
+    match filter.as_cons() {
+        None => true,
+        Some(filter) => {
+            let (operations, buffer) = filter.cdr().as_cons_or_error().as_tuple();
+            (operations.is_nil() || memq(operation, operations).is_not_nil())
+                && (buffer.is_nil() || buffer.eq(where_))
+        }
+    }
+}
+
+/// Call the watchers of SYMBOL about to be changed to NEWVAL by
+/// OPERATION, in the buffer WHERE or globally if WHERE is nil.
+#[no_mangle]
+pub extern "C" fn notify_variable_watchers(
+    symbol: LispObject,
+    newval: LispObject,
+    operation: LispObject,
+    mut where_: LispObject,
+) {
+    let symbol = symbol.as_symbol_or_error().get_indirect_variable();
+
+    let count = c_specpdl_index();
+    unsafe { record_unwind_protect(Some(restore_symbol_trapped_write), symbol.into()) };
+    // Avoid recursion.
+    symbol.set_trapped_write(symbol_trapped_write::SYMBOL_UNTRAPPED_WRITE);
+
+    if where_.is_nil() && !operation.eq(Qset_default) && !operation.eq(Qmakunbound) {
+        let buffer: LispObject = ThreadState::current_buffer().into();
+        if unsafe { Flocal_variable_if_set_p(symbol.into(), buffer) }.is_not_nil() {
+            where_ = buffer;
+        }
+    }
+
+    let operation = if operation.eq(Qset_default) {
+        Qset
+    } else {
+        operation
+    };
+
+    let filters = unsafe { Fget(symbol.into(), Qwatcher_filters) };
+    for watcher in unsafe { Fget(symbol.into(), Qwatchers) }.iter_cars() {
+        if watcher_filter_matches(assoc(watcher, filters, Qnil), operation, where_) {
+            call!(watcher, symbol.into(), newval, operation, where_);
+        }
+    }
+
+    unbind_to(count, Qnil);
+}
+
 /// Cause WATCH-FUNCTION to be called when SYMBOL is set.
 ///
 /// It will be called with 4 arguments: (SYMBOL NEWVAL OPERATION WHERE).
@@ -721,9 +788,28 @@ extern "C" fn harmonize_variable_watchers(alias: LispObject, base_variable: Lisp
 /// WHERE is a buffer if the buffer-local value of the variable is being
 /// changed, nil otherwise.
 ///
+/// If OPERATIONS is non-nil, it is a list of the operations above, and
+/// WATCH-FUNCTION is only called for those.  If WHERE is a buffer,
+/// WATCH-FUNCTION is only called for changes to the buffer-local value
+/// of SYMBOL in that buffer.  Adding WATCH-FUNCTION again replaces its
+/// filters.
+///
 /// All writes to aliases of SYMBOL will call WATCH-FUNCTION too.
-#[lisp_fn]
-pub fn add_variable_watcher(symbol: LispSymbolRef, watch_function: LispObject) {
+/// usage: (fn SYMBOL WATCH-FUNCTION &optional OPERATIONS WHERE)
+#[lisp_fn(min = "2")]
+pub fn add_variable_watcher(
+    symbol: LispSymbolRef,
+    watch_function: LispObject,
+    operations: LispObject,
+    where_: Option<LispBufferRef>,
+) {
+    let valid_operations = list!(Qset, Qlet, Qunlet, Qmakunbound, Qdefvaralias);
+    for operation in operations.iter_cars() {
+        if memq(operation, valid_operations).is_nil() {
+            unsafe { wrong_choice(valid_operations, operation) };
+        }
+    }
+
     let symbol = symbol.get_indirect_variable();
 
     symbol.set_trapped_write(symbol_trapped_write::SYMBOL_TRAPPED_WRITE);
@@ -744,6 +830,32 @@ pub fn add_variable_watcher(symbol: LispSymbolRef, watch_function: LispObject) {
             LispObject::cons(watch_function, watchers),
         );
     }
+
+    let filters = remove_watcher_filter(symbol, watch_function);
+    let where_ = where_.map_or(Qnil, LispObject::from);
+    if operations.is_not_nil() || where_.is_not_nil() {
+        put(
+            symbol,
+            Qwatcher_filters,
+            LispObject::cons(
+                LispObject::cons(watch_function, LispObject::cons(operations, where_)),
+                filters,
+            ),
+        );
+    }
+}
+
+/// Remove the filters of WATCH-FUNCTION from SYMBOL's `watcher-filters'
+/// property, and return the new value of the property.
+fn remove_watcher_filter(symbol: LispSymbolRef, watch_function: LispObject) -> LispObject {
+    let filters = unsafe { Fget(symbol.into(), Qwatcher_filters) };
+    let filter = assoc(watch_function, filters, Qnil);
+    if filter.is_nil() {
+        return filters;
+    }
+    let filters = delq(filter, filters);
+    put(symbol, Qwatcher_filters, filters);
+    filters
 }
 
 /// Undo the effect of `add-variable-watcher'.
@@ -767,6 +879,7 @@ pub fn remove_variable_watcher(symbol: LispSymbolRef, watch_function: LispObject
     }
 
     put(symbol, Qwatchers, watchers);
+    remove_watcher_filter(symbol, watch_function);
 }
 
 /// Return a list of SYMBOL's active watchers.
@@ -1,38 +1,200 @@
-//! Interface to zlib.
+//! Interface to zlib and other compression libraries.
+use std::cell::Cell;
 use std::cmp::min;
-use std::io::prelude::Read;
+use std::io::{self, prelude::Read};
+use std::mem;
+use std::rc::Rc;
 use std::slice;
 
+use bzip2::read::BzDecoder;
 use flate2::read::{DeflateDecoder, GzDecoder, ZlibDecoder};
+use flate2::{Compress, Compression, Crc, FlushCompress, Status};
+use xz2::read::XzDecoder;
+use zstd;
+
+use libc::c_char;
+
 use remacs_macros::lisp_fn;
 
 use crate::{
-    buffers::validate_region,
+    buffers::{validate_region, LispBufferRef},
+    eval::unbind_to,
     lisp::defsubr,
     lisp::LispObject,
+    marker::{build_marker, marker_byte_position, marker_position, set_marker},
     remacs_sys::{
         buf_charpos_to_bytepos, del_range_2, insert_from_gap, make_gap, maybe_quit, modify_text,
-        move_gap_both, signal_after_change, update_compositions, CHECK_HEAD,
+        move_gap_both, signal_after_change, update_compositions, wrong_choice, EmacsInt,
+        CHECK_HEAD,
+    },
+    remacs_sys::{
+        coding_system, decode_coding_object, setup_coding_system, Fcheck_coding_system,
+        CODING_MODE_LAST_BLOCK,
+    },
+    remacs_sys::{
+        del_range, insert1, make_unibyte_string, record_unwind_current_buffer, set_buffer_internal,
+        temp_set_point_both, Qnil, Qt,
     },
-    threads::ThreadState,
+    remacs_sys::{Qbzip2, Qgzip, Qlzma, Qraw, Qxz, Qzlib, Qzstd},
+    threads::{c_specpdl_index, ThreadState},
 };
 
+/// Size of the chunks written to the gap at a time.
+const GAP_CHUNK_SIZE: isize = 16 * 1024;
+
 /// Return t if zlib decompression is available in this instance of Emacs.
 #[lisp_fn]
 pub fn zlib_available_p() -> bool {
     true
 }
 
-fn create_buffer_decoder<'a>(buffer: &'a [u8]) -> Box<Read + 'a> {
-    let magic_number = buffer[0];
+#[derive(Clone, Copy, PartialEq)]
+enum DecompressionFormat {
+    Zlib,
+    Gzip,
+    Raw,
+    Zstd,
+    Xz,
+    Lzma,
+    Bzip2,
+}
+
+impl DecompressionFormat {
+    /// Guess the format of zlib-compatible data from its first byte.
+    fn sniff_zlib(magic: &[u8]) -> Self {
+        match magic.first() {
+            Some(0x78) => DecompressionFormat::Zlib,
+            Some(0x1F) => DecompressionFormat::Gzip,
+            // Assume the data is raw, if neither zlib nor gzib header can be found.
+            _ => DecompressionFormat::Raw,
+        }
+    }
+
+    /// Guess the format of compressed data from its magic number.
+    fn sniff(magic: &[u8]) -> Self {
+        if magic.starts_with(&[0x28, 0xB5, 0x2F, 0xFD]) {
+            DecompressionFormat::Zstd
+        } else if magic.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) {
+            DecompressionFormat::Xz
+        } else if magic.starts_with(b"BZh") {
+            DecompressionFormat::Bzip2
+        } else if magic.starts_with(&[0x5D, 0x00, 0x00]) {
+            // The properties byte used by virtually all .lzma files.
+            DecompressionFormat::Lzma
+        } else {
+            Self::sniff_zlib(magic)
+        }
+    }
+
+    fn from_symbol(format: LispObject) -> Self {
+        if format.eq(Qzlib) {
+            DecompressionFormat::Zlib
+        } else if format.eq(Qgzip) {
+            DecompressionFormat::Gzip
+        } else if format.eq(Qraw) {
+            DecompressionFormat::Raw
+        } else if format.eq(Qzstd) {
+            DecompressionFormat::Zstd
+        } else if format.eq(Qxz) {
+            DecompressionFormat::Xz
+        } else if format.eq(Qlzma) {
+            DecompressionFormat::Lzma
+        } else if format.eq(Qbzip2) {
+            DecompressionFormat::Bzip2
+        } else {
+            unsafe { wrong_choice(decompress_available_formats(), format) }
+        }
+    }
+
+    fn decoder<R: Read + 'static>(self, input: R) -> io::Result<Box<Read>> {
+        Ok(match self {
+            DecompressionFormat::Zlib => Box::new(ZlibDecoder::new(input)),
+            DecompressionFormat::Gzip => Box::new(GzDecoder::new(input)),
+            DecompressionFormat::Raw => Box::new(DeflateDecoder::new(input)),
+            DecompressionFormat::Zstd => Box::new(zstd::stream::read::Decoder::new(input)?),
+            DecompressionFormat::Xz => Box::new(XzDecoder::new(input)),
+            DecompressionFormat::Lzma => {
+                let stream = xz2::stream::Stream::new_lzma_decoder(u64::max_value())?;
+                Box::new(XzDecoder::new_stream(input, stream))
+            }
+            DecompressionFormat::Bzip2 => Box::new(BzDecoder::new(input)),
+        })
+    }
+}
+
+/// Reads the bytes of a unibyte buffer between two byte positions.
+/// The buffer text is looked up on each read, so it's fine for the
+/// text to be relocated, e.g. while the gap grows.
+struct BufferRegionReader {
+    buffer: LispBufferRef,
+    pos: isize,
+    end: isize,
+}
+
+impl Read for BufferRegionReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let gpt = self.buffer.gpt_byte();
+        let (n, offset) = if self.pos < gpt {
+            (min(self.end, gpt) - self.pos, 0)
+        } else {
+            (self.end - self.pos, self.buffer.gap_size())
+        };
+        let n = min(n, buf.len() as isize);
+        let region = unsafe {
+            slice::from_raw_parts(
+                self.buffer
+                    .beg_addr()
+                    .offset(self.pos - self.buffer.beg_byte() + offset),
+                n as usize,
+            )
+        };
+        buf[..n as usize].copy_from_slice(region);
+        self.pos += n;
+        Ok(n as usize)
+    }
+}
+
+/// Reads the bytes of a unibyte string, looking up its data on each
+/// read as the garbage collector may move it.
+struct StringReader {
+    string: LispObject,
+    pos: usize,
+}
+
+impl Read for StringReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let data = self.string.as_string_or_error();
+        let rest = &data.as_slice()[self.pos..];
+        let n = min(buf.len(), rest.len());
+        buf[..n].copy_from_slice(&rest[..n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
 
-    match magic_number {
-        // Zlib
-        0x78 => Box::new(ZlibDecoder::new(buffer)),
-        // Gzlib
-        0x1F => Box::new(GzDecoder::new(buffer)),
-        // Assume the data is raw, if neither zlib nor gzib header can be found.
-        _ => Box::new(DeflateDecoder::new(buffer)),
+/// The compressed input of `decompress-to-buffer', counting how many
+/// bytes have been consumed.
+struct SourceReader {
+    reader: Box<Read>,
+    /// The source buffer and its modification count, to detect changes
+    /// made by the progress function.
+    buffer: Option<(LispBufferRef, EmacsInt)>,
+    consumed: Rc<Cell<usize>>,
+}
+
+impl Read for SourceReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if let Some((buffer, modiff)) = self.buffer {
+            if !buffer.is_live() || buffer.modifications() != modiff {
+                return Err(io::Error::new(
+                    io::ErrorKind::Other,
+                    "Source buffer modified during decompression",
+                ));
+            }
+        }
+        let n = self.reader.read(buf)?;
+        self.consumed.set(self.consumed.get() + n);
+        Ok(n)
     }
 }
 
@@ -41,7 +203,41 @@ fn create_buffer_decoder<'a>(buffer: &'a [u8]) -> Box<Read + 'a> {
 /// On failure, return nil and leave the data in place.
 /// This function can be called only in unibyte buffers.
 #[lisp_fn]
-pub fn zlib_decompress_region(mut start: LispObject, mut end: LispObject) -> bool {
+pub fn zlib_decompress_region(start: LispObject, end: LispObject) -> bool {
+    decompress_region_1(start, end, DecompressionFormat::sniff_zlib)
+}
+
+/// Decompress a compressed region.
+/// Replace the text in the region by the decompressed data.
+///
+/// FORMAT is a symbol specifying the format of the data, one of those
+/// returned by `decompress-available-formats'.  If nil or omitted, the
+/// format is guessed from the data: zstd, xz, lzma, bzip2, gzip and
+/// zlib streams are recognized by their headers, and anything else is
+/// assumed to be raw deflate data.
+///
+/// On failure, return nil and leave the data in place.
+/// This function can be called only in unibyte buffers.
+#[lisp_fn(min = "2")]
+pub fn decompress_region(start: LispObject, end: LispObject, format: LispObject) -> bool {
+    if format.is_nil() {
+        decompress_region_1(start, end, DecompressionFormat::sniff)
+    } else {
+        let format = DecompressionFormat::from_symbol(format);
+        decompress_region_1(start, end, |_| format)
+    }
+}
+
+/// Return a list of the formats `decompress-region' can handle.
+#[lisp_fn]
+pub fn decompress_available_formats() -> LispObject {
+    list!(Qzlib, Qgzip, Qraw, Qzstd, Qxz, Qlzma, Qbzip2)
+}
+
+fn decompress_region_1<F>(mut start: LispObject, mut end: LispObject, detect: F) -> bool
+where
+    F: FnOnce(&[u8]) -> DecompressionFormat,
+{
     unsafe { validate_region(&mut start, &mut end) };
 
     let mut current_buffer = ThreadState::current_buffer();
@@ -71,32 +267,31 @@ pub fn zlib_decompress_region(mut start: LispObject, mut end: LispObject) -> boo
     let old_pt = current_buffer.pt;
     current_buffer.set_pt_both(charpos, bytepos);
 
-    let compressed_buffer = unsafe {
+    let magic = unsafe {
         slice::from_raw_parts(
             current_buffer.byte_pos_addr(istart),
-            (iend - istart) as usize,
+            min(iend - istart, 6) as usize,
         )
     };
+    let input = BufferRegionReader {
+        buffer: current_buffer,
+        pos: istart,
+        end: iend,
+    };
 
     // The decompressor
-    let mut decoder = create_buffer_decoder(compressed_buffer);
+    let mut decoder = match detect(magic).decoder(input) {
+        Ok(decoder) => decoder,
+        Err(_) => {
+            undo_region_conversion(current_buffer, istart, iend, 0, old_pt);
+            return false;
+        }
+    };
 
     let mut decompressed_bytes: isize = 0;
 
     loop {
-        let avail_out: isize = 16 * 1024;
-
-        let old_gap_size = current_buffer.gap_size();
-
-        if old_gap_size < avail_out {
-            unsafe { make_gap(avail_out - old_gap_size) };
-        }
-
-        let new_gap_size = avail_out;
-
-        let gap_writer = unsafe {
-            slice::from_raw_parts_mut(current_buffer.gap_start_addr(), new_gap_size as usize)
-        };
+        let gap_writer = gap_for_writing(current_buffer);
 
         match decoder.read(gap_writer) {
             // Decompress all data finished.
@@ -127,35 +322,447 @@ pub fn zlib_decompress_region(mut start: LispObject, mut end: LispObject) -> boo
 
             // Decompress failed.
             _ => {
-                // Delete any uncompressed data already inserted on error, but
-                // without calling the change hooks.
+                undo_region_conversion(current_buffer, istart, iend, decompressed_bytes, old_pt);
+                return false;
+            }
+        };
+    }
+}
 
-                let data_orig = istart;
-                let data_start = iend;
-                let data_end = iend + decompressed_bytes;
+/// Decompress SOURCE, a buffer or string, and insert the result into
+/// the buffer TARGET at its point.
+///
+/// SOURCE must be unibyte.  If it is a buffer, its accessible portion
+/// is decompressed.  FORMAT is the format of the compressed data, as in
+/// `decompress-region'; if nil or omitted, it is guessed from the data.
+///
+/// If CODING-SYSTEM is non-nil, the decompressed text is decoded with it
+/// chunk by chunk, as it is inserted.  Otherwise the bytes are inserted
+/// as they are, i.e. as raw bytes if TARGET is multibyte.
+///
+/// The data is processed in chunks.  After each chunk, `quit' is
+/// checked for and PROGRESS, if non-nil, is called with two arguments:
+/// the number of compressed bytes read so far and the number of
+/// decompressed bytes inserted so far.
+///
+/// Return the number of decompressed bytes.  If the data can't be
+/// decompressed, signal an error and remove any text already inserted
+/// into TARGET.
+#[lisp_fn(min = "2")]
+pub fn decompress_to_buffer(
+    source: LispObject,
+    target: LispBufferRef,
+    format: LispObject,
+    coding_system: LispObject,
+    progress: LispObject,
+) -> EmacsInt {
+    if !target.is_live() {
+        error!("Selecting deleted buffer");
+    }
 
-                unsafe {
-                    del_range_2(
-                        data_start, data_start, // byte, char offsets the same
-                        data_end, data_end, false,
-                    );
-                    update_compositions(data_start, data_start, CHECK_HEAD as i32);
-                    // "Balance" the before-change-functions call, which would
-                    // otherwise be left "hanging".
-                    signal_after_change(data_orig, data_start - data_orig, data_start - data_orig);
+    let (reader, magic, source_buffer): (Box<Read>, Vec<u8>, _) =
+        if let Some(string) = source.as_string() {
+            if string.is_multibyte() {
+                error!("This function can only decompress unibyte strings");
+            }
+            let magic = string.as_slice()[..min(string.len_bytes() as usize, 6)].to_vec();
+            (
+                Box::new(StringReader {
+                    string: source,
+                    pos: 0,
+                }),
+                magic,
+                None,
+            )
+        } else {
+            let buffer = source.as_buffer_or_error();
+            if buffer.as_ptr() == target.as_ptr() {
+                error!("The source and target buffers must be different");
+            }
+            if buffer.multibyte_characters_enabled() {
+                error!("This function can only decompress unibyte buffers");
+            }
+            let mut reader = BufferRegionReader {
+                buffer,
+                pos: buffer.begv_byte,
+                end: buffer.zv_byte,
+            };
+            // A single read stops at the gap, so keep reading until
+            // there are enough bytes to recognize the format.
+            let mut magic = Vec::with_capacity(6);
+            let _ = reader.by_ref().take(6).read_to_end(&mut magic);
+            reader.pos = buffer.begv_byte;
+            (
+                Box::new(reader),
+                magic,
+                Some((buffer, buffer.modifications())),
+            )
+        };
+
+    let format = if format.is_nil() {
+        DecompressionFormat::sniff(&magic)
+    } else {
+        DecompressionFormat::from_symbol(format)
+    };
+    let consumed = Rc::new(Cell::new(0));
+    let input = SourceReader {
+        reader,
+        buffer: source_buffer,
+        consumed: consumed.clone(),
+    };
+    let mut decoder = match format.decoder(input) {
+        Ok(decoder) => decoder,
+        Err(e) => error!("Decompression failed: {}", e),
+    };
+
+    let count = c_specpdl_index();
+    let mut target = target;
+    unsafe {
+        record_unwind_current_buffer();
+        set_buffer_internal(target.as_mut());
+    }
+
+    // The inserted text lies between these markers, which keep track of
+    // it whatever the progress function does to point or to the text
+    // around it.
+    let start = unsafe { build_marker(target.as_mut(), target.pt, target.pt_byte) };
+    let end = unsafe { build_marker(target.as_mut(), target.pt, target.pt_byte) };
+    end.as_marker_or_error().set_insertion_type(true);
+
+    let mut decoder_state = if coding_system.is_nil() {
+        None
+    } else {
+        Some(ChunkDecoder::new(coding_system))
+    };
+    let mut chunk = vec![0; GAP_CHUNK_SIZE as usize];
+    let mut decompressed_bytes: EmacsInt = 0;
+
+    loop {
+        match decoder.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(n) => {
+                let text = match decoder_state {
+                    Some(ref mut state) => state.decode(&chunk[..n], false),
+                    None => unsafe {
+                        make_unibyte_string(chunk.as_ptr() as *const c_char, n as isize)
+                    },
                 };
+                insert_at_marker(target, end, text);
+                decompressed_bytes += n as EmacsInt;
+
+                unsafe { maybe_quit() };
+                if progress.is_not_nil() {
+                    call!(
+                        progress,
+                        LispObject::from(consumed.get()),
+                        LispObject::from(decompressed_bytes)
+                    );
+                    // The progress function may have switched buffers.
+                    unsafe { set_buffer_internal(target.as_mut()) };
+                }
+            }
+            Err(e) => {
+                unsafe { del_range(marker_position(start), marker_position(end)) };
+                set_marker(start, Qnil, Qnil);
+                set_marker(end, Qnil, Qnil);
+                error!("Decompression failed: {}", e);
+            }
+        }
+    }
 
-                // Put point where it was, or if the buffer has shrunk because the
-                // compressed data is bigger than the uncompressed, at
-                // point-max.
-                let charpos = min(old_pt, current_buffer.zv);
-                let bytepos = unsafe { buf_charpos_to_bytepos(current_buffer.as_mut(), charpos) };
-                current_buffer.set_pt_both(charpos, bytepos);
+    // Flush any incomplete character left over from the last chunk.
+    if let Some(ref mut state) = decoder_state {
+        if state.has_carryover() {
+            let text = state.decode(&[], true);
+            insert_at_marker(target, end, text);
+        }
+    }
+
+    set_marker(start, Qnil, Qnil);
+    set_marker(end, Qnil, Qnil);
+    unbind_to(count, Qnil);
+    decompressed_bytes
+}
+
+/// Insert TEXT into the current buffer BUFFER at the position of the
+/// marker AT.  Point moves past the text only if it was at AT.
+fn insert_at_marker(mut buffer: LispBufferRef, at: LispObject, text: LispObject) {
+    let (pt, pt_byte) = (buffer.pt, buffer.pt_byte);
+    let (pos, pos_byte) = (marker_position(at), marker_byte_position(at));
+    if pt == pos {
+        unsafe { insert1(text) };
+        return;
+    }
+
+    let (z, z_byte) = (buffer.z(), buffer.z_byte());
+    unsafe {
+        temp_set_point_both(buffer.as_mut(), pos, pos_byte);
+        insert1(text);
+    }
+    if pt < pos {
+        unsafe { temp_set_point_both(buffer.as_mut(), pt, pt_byte) };
+    } else {
+        let (chars, bytes) = (buffer.z() - z, buffer.z_byte() - z_byte);
+        unsafe { temp_set_point_both(buffer.as_mut(), pt + chars, pt_byte + bytes) };
+    }
+}
+
+/// Decodes a stream of bytes with a coding system, one chunk at a time.
+/// Bytes at the end of a chunk that don't make up a whole character are
+/// kept back and decoded with the next chunk.
+struct ChunkDecoder {
+    coding: coding_system,
+    carryover: Vec<u8>,
+}
+
+impl ChunkDecoder {
+    fn new(coding_system: LispObject) -> Self {
+        let mut coding: coding_system = unsafe { mem::zeroed() };
+        unsafe {
+            Fcheck_coding_system(coding_system);
+            setup_coding_system(coding_system, &mut coding);
+        }
+        ChunkDecoder {
+            coding,
+            carryover: Vec::new(),
+        }
+    }
+
+    fn has_carryover(&self) -> bool {
+        !self.carryover.is_empty()
+    }
+
+    /// Decode BYTES and return the text as a string.  If LAST, this is
+    /// the end of the stream, and nothing is kept back.
+    fn decode(&mut self, bytes: &[u8], last: bool) -> LispObject {
+        let mut source = mem::replace(&mut self.carryover, Vec::new());
+        source.extend_from_slice(bytes);
+        let len = source.len() as isize;
+
+        let coding = &mut self.coding;
+        let mode = coding.mode();
+        if last {
+            coding.set_mode(mode | CODING_MODE_LAST_BLOCK);
+        } else {
+            coding.set_mode(mode & !CODING_MODE_LAST_BLOCK);
+        }
+        coding.source = source.as_ptr();
+        coding.src_chars = len;
+        coding.src_bytes = len;
+        unsafe { decode_coding_object(&mut *coding, Qnil, 0, 0, len, len, Qt) };
+
+        let kept = coding.carryover_bytes as usize;
+        self.carryover.extend_from_slice(&coding.carryover[..kept]);
+        coding.carryover_bytes = 0;
+        coding.dst_object
+    }
+}
+
+/// Delete the INSERTED bytes written after the region between ISTART
+/// and IEND, after a conversion of the region failed, and put point
+/// back at OLD_PT.
+fn undo_region_conversion(
+    mut buffer: LispBufferRef,
+    istart: isize,
+    iend: isize,
+    inserted: isize,
+    old_pt: isize,
+) {
+    // Delete any converted data already inserted on error, but
+    // without calling the change hooks.
+
+    let data_orig = istart;
+    let data_start = iend;
+    let data_end = iend + inserted;
+
+    unsafe {
+        del_range_2(
+            data_start, data_start, // byte, char offsets the same
+            data_end, data_end, false,
+        );
+        update_compositions(data_start, data_start, CHECK_HEAD as i32);
+        // "Balance" the before-change-functions call, which would
+        // otherwise be left "hanging".
+        signal_after_change(data_orig, data_start - data_orig, data_start - data_orig);
+    };
+
+    // Put point where it was, or if the buffer has shrunk because the
+    // compressed data is bigger than the uncompressed, at
+    // point-max.
+    let charpos = min(old_pt, buffer.zv);
+    let bytepos = unsafe { buf_charpos_to_bytepos(buffer.as_mut(), charpos) };
+    buffer.set_pt_both(charpos, bytepos);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum CompressionFormat {
+    Raw,
+    Zlib,
+    Gzip,
+}
+
+/// Make sure the gap of BUFFER can hold at least `GAP_CHUNK_SIZE'
+/// bytes, and return it.
+fn gap_for_writing<'a>(buffer: LispBufferRef) -> &'a mut [u8] {
+    let old_gap_size = buffer.gap_size();
+    if old_gap_size < GAP_CHUNK_SIZE {
+        unsafe { make_gap(GAP_CHUNK_SIZE - old_gap_size) };
+    }
+    unsafe { slice::from_raw_parts_mut(buffer.gap_start_addr(), GAP_CHUNK_SIZE as usize) }
+}
+
+/// Insert BYTES, which must fit into `GAP_CHUNK_SIZE', at point.
+fn insert_bytes(buffer: LispBufferRef, bytes: &[u8]) {
+    gap_for_writing(buffer)[..bytes.len()].copy_from_slice(bytes);
+    let len = bytes.len() as isize;
+    unsafe { insert_from_gap(len, len, false) };
+}
+
+/// Compress a region to a zlib, gzip or raw deflate stream.
+/// Replace the text in the region by the compressed data.
+///
+/// FORMAT is a symbol specifying the kind of stream to produce: `zlib'
+/// (the default), `gzip' or `raw'.  LEVEL is the compression level, an
+/// integer between 0 (no compression) and 9 (best compression); if nil
+/// or omitted, a default trading speed for size is used.
+///
+/// On failure, return nil and leave the data in place.
+/// This function can be called only in unibyte buffers.
+#[lisp_fn(min = "2")]
+pub fn zlib_compress_region(
+    mut start: LispObject,
+    mut end: LispObject,
+    format: LispObject,
+    level: Option<EmacsInt>,
+) -> bool {
+    unsafe { validate_region(&mut start, &mut end) };
+
+    let format = if format.is_nil() || format.eq(Qzlib) {
+        CompressionFormat::Zlib
+    } else if format.eq(Qgzip) {
+        CompressionFormat::Gzip
+    } else if format.eq(Qraw) {
+        CompressionFormat::Raw
+    } else {
+        unsafe { wrong_choice(list!(Qzlib, Qgzip, Qraw), format) }
+    };
+
+    let compression = match level {
+        None => Compression::default(),
+        Some(level @ 0..=9) => Compression::new(level as u32),
+        Some(level) => args_out_of_range!(
+            LispObject::from(level),
+            LispObject::from(0),
+            LispObject::from(9)
+        ),
+    };
 
+    let mut current_buffer = ThreadState::current_buffer();
+
+    if current_buffer.multibyte_characters_enabled() {
+        error!("This function can be called only in unibyte buffers");
+    };
+
+    let istart = start.as_fixnum_or_error() as isize;
+    let iend = end.as_fixnum_or_error() as isize;
+
+    unsafe {
+        // Do the following before manipulating the gap.
+        modify_text(istart, iend);
+
+        move_gap_both(iend, iend);
+    }
+
+    // Insert the compressed data at the end of the uncompressed data.
+    let charpos = iend;
+    let bytepos = unsafe { buf_charpos_to_bytepos(current_buffer.as_mut(), iend as isize) };
+    let old_pt = current_buffer.pt;
+    current_buffer.set_pt_both(charpos, bytepos);
+
+    // The gzip format is a raw deflate stream wrapped in a header and a
+    // trailer holding a checksum, which we write ourselves.
+    let mut compressor = Compress::new(compression, format == CompressionFormat::Zlib);
+    let mut crc = Crc::new();
+    let mut compressed_bytes: isize = 0;
+
+    if format == CompressionFormat::Gzip {
+        // Magic number, deflate method, no flags, no modification time,
+        // no extra flags and unknown operating system.
+        let header = [0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+        insert_bytes(current_buffer, &header);
+        compressed_bytes += header.len() as isize;
+    }
+
+    loop {
+        // Making room in the gap can relocate the buffer text, so look
+        // up the remaining input again on each iteration.
+        let gap_writer = gap_for_writing(current_buffer);
+        let consumed = compressor.total_in() as isize;
+        let input = unsafe {
+            slice::from_raw_parts(
+                current_buffer.byte_pos_addr(istart + consumed),
+                (iend - istart - consumed) as usize,
+            )
+        };
+
+        let old_total_out = compressor.total_out();
+        match compressor.compress(input, gap_writer, FlushCompress::Finish) {
+            Ok(status) => {
+                let read = compressor.total_in() as isize - consumed;
+                crc.update(&input[..read as usize]);
+
+                let written = (compressor.total_out() - old_total_out) as isize;
+                unsafe { insert_from_gap(written, written, false) };
+                compressed_bytes += written;
+
+                if let Status::StreamEnd = status {
+                    break;
+                }
+
+                unsafe { maybe_quit() };
+            }
+
+            // Compress failed.
+            Err(_) => {
+                undo_region_conversion(current_buffer, istart, iend, compressed_bytes, old_pt);
                 return false;
             }
-        };
+        }
+    }
+
+    if format == CompressionFormat::Gzip {
+        // CRC-32 and size of the uncompressed data, both little-endian.
+        let mut trailer = [0; 8];
+        for i in 0..4 {
+            trailer[i] = (crc.sum() >> (8 * i)) as u8;
+            trailer[i + 4] = (crc.amount() >> (8 * i)) as u8;
+        }
+        insert_bytes(current_buffer, &trailer);
+        compressed_bytes += trailer.len() as isize;
     }
+
+    // Delete the uncompressed data.
+    unsafe {
+        del_range_2(
+            istart, istart, // byte, char offsets the same
+            iend, iend, false,
+        );
+        signal_after_change(istart, iend - istart, compressed_bytes);
+
+        update_compositions(istart, istart, CHECK_HEAD as i32);
+    };
+    true
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_decompress() {
+    def_lisp_sym!(Qzlib, "zlib");
+    def_lisp_sym!(Qgzip, "gzip");
+    def_lisp_sym!(Qraw, "raw");
+    def_lisp_sym!(Qzstd, "zstd");
+    def_lisp_sym!(Qxz, "xz");
+    def_lisp_sym!(Qlzma, "lzma");
+    def_lisp_sym!(Qbzip2, "bzip2");
 }
 
 include!(concat!(env!("OUT_DIR"), "/decompress_exports.rs"));
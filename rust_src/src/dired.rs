@@ -4,7 +4,8 @@ use remacs_macros::lisp_fn;
 
 #[cfg(unix)]
 use crate::dired_unix::{
-    directory_files_and_attributes_intro, directory_files_intro, file_attributes_intro, get_users,
+    directory_files_and_attributes_intro, directory_files_intro, directory_walk_intro,
+    file_attributes_intro, get_users,
 };
 #[cfg(windows)]
 use dired_windows::{file_attributes_intro, get_users};
@@ -55,6 +56,47 @@ pub fn directory_files_and_attributes(
     directory_files_and_attributes_intro(directory, full, match_re, nosort, id_format)
 }
 
+/// Return the files in DIRECTORY and, recursively, its subdirectories.
+/// The value is a list of absolute file names, depth first: the entries
+/// of each directory come in `string-lessp' order, and the files of a
+/// subdirectory come where the subdirectory does.  The remaining
+/// arguments are keyword/argument pairs:
+///
+/// :match REGEXP -- Only include the files whose name (without its
+/// directory) matches REGEXP.  Subdirectories are walked regardless.
+///
+/// :exclude REGEXP -- Leave out the files and subdirectories whose name
+/// matches REGEXP, and don't walk those subdirectories.
+///
+/// :follow-symlinks BOOL -- If BOOL is non-nil, walk the directories that
+/// symbolic links point to.  Each directory is walked at most once along
+/// any path, so that symlink loops terminate.
+///
+/// :max-depth N -- Only include the files at most N levels below
+/// DIRECTORY; 1 means just the files in DIRECTORY itself.
+///
+/// :attributes ID-FORMAT -- If non-nil, each element is of the form
+/// (FILE . ATTRIBUTES), where ATTRIBUTES is what `file-attributes' returns
+/// for FILE.  ID-FORMAT is passed on to `file-attributes', t meaning the
+/// default format.
+///
+/// :respect-gitignore BOOL -- If BOOL is non-nil, leave out the `.git'
+/// directories and the files that the `.gitignore' files in DIRECTORY and
+/// its subdirectories ignore.
+///
+/// :include-directories BOOL -- If BOOL is non-nil, include the
+/// subdirectories themselves in the result, before their contents.
+///
+/// :function FUNCTION -- Instead of returning the files, call FUNCTION
+/// with each element as it is found, and return nil.
+///
+/// usage: (directory-walk DIRECTORY &rest ARGS)
+#[lisp_fn(min = "1")]
+pub fn directory_walk(args: &mut [LispObject]) -> LispObject {
+    let (directory, keys) = args.split_at_mut(1);
+    directory_walk_intro(directory[0], keys)
+}
+
 /// Return a list of attributes of file FILENAME.
 /// Value is nil if specified file cannot be opened.
 ///
@@ -1,27 +1,31 @@
 use libc::{
-    c_char, c_long, endpwent, getgrgid, getpwent, getpwuid, group, passwd, size_t, ssize_t,
-    timespec as c_timespec,
+    c_char, c_long, endpwent, getgrgid, getpwent, getpwuid, group, passwd, ptrdiff_t, size_t,
+    ssize_t, timespec as c_timespec,
 };
 
 use std::ffi::{CStr, CString, OsStr};
 use std::fs;
 use std::io;
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::fs::MetadataExt;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::ptr::null_mut;
 use std::slice;
 
 use crate::{
+    eval::funcall,
     lisp::LispObject,
-    lists::list,
+    lists::{list, plist_get},
     remacs_sys::{
-        build_string, compile_pattern, decode_file_name, file_attributes_c_internal,
-        filemode_string, globals, re_pattern_buffer, re_search,
+        build_string, compile_pattern, decode_file_name, encode_file_name,
+        file_attributes_c_internal, filemode_string, globals, make_unibyte_string, maybe_quit,
+        re_pattern_buffer, re_search, report_file_errno,
     },
-    remacs_sys::{Fexpand_file_name, Ffind_file_name_handler},
+    remacs_sys::{Fdirectory_file_name, Fexpand_file_name, Ffind_file_name_handler, Fnreverse},
     remacs_sys::{
-        Qdirectory_files, Qdirectory_files_and_attributes, Qfile_attributes, Qfile_missing, Qnil,
-        Qt,
+        QCattributes, QCexclude, QCfollow_symlinks, QCfunction, QCinclude_directories, QCmatch,
+        QCmax_depth, QCrespect_gitignore, Qdirectory_files, Qdirectory_files_and_attributes,
+        Qdirectory_walk, Qfile_attributes, Qnil, Qt,
     },
     time::make_lisp_time,
 };
@@ -217,16 +221,23 @@ fn fattrs_from_os(
 }
 
 fn fnames_from_os(fnames: &mut Vec<String>, dname: &str, match_re: Option<LispObject>) {
-    let res = read_dir(dname, fnames, match_re);
-    if res.is_err() {
-        xsignal!(
-            Qfile_missing,
-            format!("Opening directory: {}", res.unwrap_err()).to_bstring(),
-            LispObject::from(dname)
-        );
+    if let Err(err) = read_dir(dname, fnames, match_re) {
+        report_directory_error(&err, LispObject::from(dname));
     }
 }
 
+// Signal the error ERR from opening the directory DIR, with the errno
+// it came from.
+fn report_directory_error(err: &io::Error, dir: LispObject) {
+    unsafe {
+        report_file_errno(
+            b"Opening directory\0".as_ptr() as *const c_char,
+            dir,
+            err.raw_os_error().unwrap_or(0),
+        )
+    };
+}
+
 fn read_dir(dname: &str, fnames: &mut Vec<String>, match_re: Option<LispObject>) -> io::Result<()> {
     let dir_p = Path::new(dname);
 
@@ -446,6 +457,345 @@ pub extern "C" fn directory_files_internal(
     directory_files_core(&dr, &mut dd)
 }
 
+// Directory walking, for `directory-walk'.
+
+// One rule of a .gitignore file.
+struct IgnoreRule {
+    pattern: Vec<u8>,
+    negated: bool,
+    dir_only: bool,
+    // Whether the pattern is matched against the path relative to the
+    // .gitignore file rather than against the file name alone.
+    anchored: bool,
+}
+
+impl IgnoreRule {
+    fn parse(line: &[u8]) -> Option<Self> {
+        let mut line = line;
+        if line.ends_with(b"\r") {
+            line = &line[..line.len() - 1];
+        }
+        // Trailing spaces are ignored unless they are quoted with backslash.
+        while line.ends_with(b" ") && !line.ends_with(b"\\ ") {
+            line = &line[..line.len() - 1];
+        }
+        if line.is_empty() || line[0] == b'#' {
+            return None;
+        }
+
+        let negated = line[0] == b'!';
+        if negated {
+            line = &line[1..];
+        }
+        let dir_only = line.ends_with(b"/");
+        if dir_only {
+            line = &line[..line.len() - 1];
+        }
+        let anchored = line.contains(&b'/');
+        if line.starts_with(b"/") {
+            line = &line[1..];
+        }
+        if line.is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            pattern: line.to_vec(),
+            negated,
+            dir_only,
+            anchored,
+        })
+    }
+
+    fn matches(&self, relative: &[u8], name: &[u8], is_dir: bool) -> bool {
+        if self.dir_only && !is_dir {
+            return false;
+        }
+        if self.anchored {
+            glob_match(&self.pattern, relative)
+        } else {
+            glob_match(&self.pattern, name)
+        }
+    }
+}
+
+// Match NAME against the gitignore-style glob PATTERN.  `*', `?' and
+// bracket expressions don't match `/'; `**/' matches any number of
+// leading directories and a trailing `/**' everything below.
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    match pattern.first() {
+        None => name.is_empty(),
+        Some(b'*') if pattern.get(1) == Some(&b'*') => {
+            let rest = &pattern[2..];
+            if rest.is_empty() {
+                true
+            } else if rest[0] == b'/' {
+                let rest = &rest[1..];
+                glob_match(rest, name)
+                    || name
+                        .iter()
+                        .enumerate()
+                        .any(|(i, &c)| c == b'/' && glob_match(rest, &name[i + 1..]))
+            } else {
+                glob_match(&pattern[1..], name)
+            }
+        }
+        Some(b'*') => {
+            let rest = &pattern[1..];
+            (0..=name.len())
+                .take_while(|&i| i == 0 || name[i - 1] != b'/')
+                .any(|i| glob_match(rest, &name[i..]))
+        }
+        Some(b'?') => match name.first() {
+            Some(&c) if c != b'/' => glob_match(&pattern[1..], &name[1..]),
+            _ => false,
+        },
+        Some(b'[') => match (name.first(), glob_match_class(&pattern[1..], name.first())) {
+            (Some(&c), Some((matched, rest))) => {
+                c != b'/' && matched && glob_match(rest, &name[1..])
+            }
+            // An unterminated bracket expression is an ordinary `['.
+            (_, None) => name.first() == Some(&b'[') && glob_match(&pattern[1..], &name[1..]),
+            (None, _) => false,
+        },
+        Some(b'\\') if pattern.len() > 1 => {
+            name.first() == Some(&pattern[1]) && glob_match(&pattern[2..], &name[1..])
+        }
+        Some(c) => name.first() == Some(c) && glob_match(&pattern[1..], &name[1..]),
+    }
+}
+
+// Match C against the bracket expression at the start of PATTERN, just
+// after its `['.  Return whether it matched and the rest of the pattern,
+// or None if the expression is not terminated.
+fn glob_match_class<'a>(pattern: &'a [u8], c: Option<&u8>) -> Option<(bool, &'a [u8])> {
+    let c = c.cloned().unwrap_or(0);
+    let negated = pattern.first() == Some(&b'!') || pattern.first() == Some(&b'^');
+    let mut i = if negated { 1 } else { 0 };
+    let start = i;
+    let mut matched = false;
+    while i < pattern.len() {
+        let lo = pattern[i];
+        if lo == b']' && i > start {
+            return Some((matched != negated, &pattern[i + 1..]));
+        }
+        if i + 2 < pattern.len() && pattern[i + 1] == b'-' && pattern[i + 2] != b']' {
+            matched |= lo <= c && c <= pattern[i + 2];
+            i += 3;
+        } else {
+            matched |= lo == c;
+            i += 1;
+        }
+    }
+    None
+}
+
+// The options of a `directory-walk' call, and the state of the walk.
+struct DirWalk {
+    match_re: Option<LispObject>,
+    exclude_re: Option<LispObject>,
+    // MATCH_RE and EXCLUDE_RE, compiled.
+    matcher: Option<RegEx>,
+    excluder: Option<RegEx>,
+    follow_symlinks: bool,
+    max_depth: Option<usize>,
+    id_format: Option<LispObject>,
+    respect_gitignore: bool,
+    include_directories: bool,
+    function: Option<LispObject>,
+    // The entries found so far, most recent first.
+    result: LispObject,
+    // The rules of the .gitignore files of the directories being walked.
+    ignores: Vec<(PathBuf, Vec<IgnoreRule>)>,
+    // The device and inode numbers of the directories being walked, so
+    // that following symlinks can't loop.
+    visited: Vec<(u64, u64)>,
+}
+
+impl DirWalk {
+    fn new(keys: LispObject) -> Self {
+        let option = |key| {
+            let value = plist_get(keys, key);
+            if value.is_nil() {
+                None
+            } else {
+                Some(value)
+            }
+        };
+        let id_format = option(QCattributes).map(
+            |attributes| {
+                if attributes.eq(Qt) {
+                    Qnil
+                } else {
+                    attributes
+                }
+            },
+        );
+        let match_re = option(QCmatch);
+        let exclude_re = option(QCexclude);
+        Self {
+            match_re,
+            exclude_re,
+            matcher: match_re.map(RegEx::new),
+            excluder: exclude_re.map(RegEx::new),
+            follow_symlinks: option(QCfollow_symlinks).is_some(),
+            max_depth: option(QCmax_depth).map(|depth| depth.as_natnum_or_error() as usize),
+            id_format,
+            respect_gitignore: option(QCrespect_gitignore).is_some(),
+            include_directories: option(QCinclude_directories).is_some(),
+            function: option(QCfunction),
+            result: Qnil,
+            ignores: Vec::new(),
+            visited: Vec::new(),
+        }
+    }
+
+    fn is_ignored(&self, path: &Path, name: &[u8], is_dir: bool) -> bool {
+        let mut ignored = false;
+        for (dir, rules) in &self.ignores {
+            let relative = match path.strip_prefix(dir) {
+                Ok(relative) => relative.as_os_str().as_bytes(),
+                Err(_) => continue,
+            };
+            for rule in rules {
+                if rule.matches(relative, name, is_dir) {
+                    ignored = !rule.negated;
+                }
+            }
+        }
+        ignored
+    }
+
+    fn emit(&mut self, file: LispObject) {
+        let entry = match self.id_format {
+            Some(id_format) => LispObject::cons(file, file_attributes_core(file, id_format)),
+            None => file,
+        };
+        match self.function {
+            Some(function) => {
+                call!(function, entry);
+                // FUNCTION may have evicted the patterns from the regexp
+                // cache, so compile them again.
+                self.matcher = self.match_re.map(RegEx::new);
+                self.excluder = self.exclude_re.map(RegEx::new);
+            }
+            None => self.result = LispObject::cons(entry, self.result),
+        }
+    }
+
+    fn walk(&mut self, dir: &Path, depth: usize) -> io::Result<()> {
+        unsafe { maybe_quit() };
+
+        let mut entries = fs::read_dir(dir)?
+            .filter_map(Result::ok)
+            .collect::<Vec<_>>();
+        entries.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+
+        let gitignore = if self.respect_gitignore {
+            fs::read(dir.join(".gitignore")).ok()
+        } else {
+            None
+        };
+        if let Some(ref contents) = gitignore {
+            let rules = contents
+                .split(|&c| c == b'\n')
+                .filter_map(IgnoreRule::parse);
+            self.ignores.push((dir.to_path_buf(), rules.collect()));
+        }
+
+        for entry in entries {
+            let path = entry.path();
+            let name = entry.file_name();
+            let is_symlink = entry.file_type().map_or(false, |t| t.is_symlink());
+            let metadata = if is_symlink {
+                fs::metadata(&path)
+            } else {
+                entry.metadata()
+            };
+            let is_dir = metadata.as_ref().map_or(false, |m| m.is_dir());
+
+            if self.respect_gitignore
+                && (name == ".git" || self.is_ignored(&path, name.as_bytes(), is_dir))
+            {
+                continue;
+            }
+            let decoded_name = decode_os_str(&name);
+            if let Some(ref excluder) = self.excluder {
+                if excluder.is_match(&decoded_name.to_stdstring()) {
+                    continue;
+                }
+            }
+
+            if !is_dir || self.include_directories {
+                let selected = self.matcher.as_ref().map_or(true, |matcher| {
+                    matcher.is_match(&decoded_name.to_stdstring())
+                });
+                if selected {
+                    self.emit(decode_os_str(path.as_os_str()));
+                }
+            }
+
+            let descend = is_dir
+                && (!is_symlink || self.follow_symlinks)
+                && self
+                    .max_depth
+                    .map_or(true, |max_depth| depth + 1 < max_depth);
+            if let (true, Ok(metadata)) = (descend, metadata) {
+                let key = (metadata.dev(), metadata.ino());
+                if !self.visited.contains(&key) {
+                    self.visited.push(key);
+                    // Like `directory-files-recursively', skip the
+                    // subdirectories that can't be read.
+                    let _ = self.walk(&path, depth + 1);
+                    self.visited.pop();
+                }
+            }
+        }
+
+        if gitignore.is_some() {
+            self.ignores.pop();
+        }
+        Ok(())
+    }
+}
+
+// Return the Lisp string for the file name NAME, decoded.
+fn decode_os_str(name: &OsStr) -> LispObject {
+    let bytes = name.as_bytes();
+    unsafe {
+        decode_file_name(make_unibyte_string(
+            bytes.as_ptr() as *const c_char,
+            bytes.len() as ptrdiff_t,
+        ))
+    }
+}
+
+pub fn directory_walk_intro(directory: LispObject, keys: &mut [LispObject]) -> LispObject {
+    let dnexp = unsafe { Fexpand_file_name(directory, Qnil) };
+
+    let handler = unsafe { Ffind_file_name_handler(dnexp, Qdirectory_walk) };
+    if handler.is_not_nil() {
+        let mut args = vec![handler, Qdirectory_walk, dnexp];
+        args.extend_from_slice(keys);
+        return funcall(&mut args);
+    }
+
+    let mut walk = DirWalk::new(list(keys));
+    let encoded = unsafe { encode_file_name(Fdirectory_file_name(dnexp)) };
+    let root = PathBuf::from(OsStr::from_bytes(encoded.as_string_or_error().as_slice()));
+
+    if walk.max_depth != Some(0) {
+        if let Ok(metadata) = fs::metadata(&root) {
+            walk.visited.push((metadata.dev(), metadata.ino()));
+        }
+        if let Err(err) = walk.walk(&root, 0) {
+            report_directory_error(&err, dnexp);
+        }
+    }
+
+    unsafe { Fnreverse(walk.result) }
+}
+
 struct RegEx {
     recomp: *mut re_pattern_buffer,
 }
@@ -188,6 +188,13 @@ pub fn goto_char(position: LispObject) -> LispObject {
         let pos = clip_to_bounds(cur_buf.begv, num, cur_buf.zv);
         let bytepos = unsafe { buf_charpos_to_bytepos(cur_buf.as_mut(), pos) };
         unsafe { set_point_both(pos, bytepos) };
+    } else if position.is_bignum() {
+        let cur_buf = ThreadState::current_buffer();
+        args_out_of_range!(
+            position,
+            LispObject::from(cur_buf.begv),
+            LispObject::from(cur_buf.zv)
+        )
     } else {
         wrong_type!(Qinteger_or_marker_p, position)
     };
@@ -5,9 +5,14 @@ use libc;
 
 use std::mem;
 
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{One, Signed, Zero};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
+    bignum::{self, check_bignum_bits},
     libm,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject},
@@ -66,10 +71,15 @@ impl LispObject {
     */
 
     pub fn any_to_float_or_error(self) -> EmacsDouble {
-        self.as_float().unwrap_or_else(|| {
-            self.as_fixnum()
-                .unwrap_or_else(|| wrong_type!(Qnumberp, self)) as EmacsDouble
-        })
+        if let Some(f) = self.as_float() {
+            f
+        } else if let Some(n) = self.as_fixnum() {
+            n as EmacsDouble
+        } else if let Some(b) = self.as_bignum() {
+            b.to_float()
+        } else {
+            wrong_type!(Qnumberp, self)
+        }
     }
 }
 
@@ -140,6 +150,7 @@ pub fn float_arith_driver(
         let next = match val.as_number_coerce_marker_or_error() {
             LispNumber::Float(f) => f,
             LispNumber::Fixnum(d) => d as f64,
+            LispNumber::Bignum(b) => b.to_float(),
         };
         match code {
             ArithOp::Add => accum += next,
@@ -244,6 +255,8 @@ pub fn float(arg: LispObject) -> LispObject {
         arg
     } else if let Some(n) = arg.as_fixnum() {
         LispObject::from_float(n as EmacsDouble)
+    } else if let Some(b) = arg.as_bignum() {
+        LispObject::from_float(b.to_float())
     } else {
         wrong_type!(Qnumberp, arg);
     }
@@ -288,9 +301,9 @@ pub fn ldexp(significand: EmacsDouble, exponent: EmacsInt) -> EmacsDouble {
 /// Return the exponential ARG1 ** ARG2.
 #[lisp_fn]
 pub fn expt(arg1: LispObject, arg2: LispObject) -> LispObject {
-    if let (Some(x), Some(y)) = (arg1.as_fixnum(), arg2.as_fixnum()) {
-        if y >= 0 && y <= EmacsInt::from(u32::max_value()) {
-            return LispObject::from(x.pow(y as u32));
+    if let (Some(x), Some(y)) = (arg1.as_bigint(), arg2.as_fixnum()) {
+        if y >= 0 {
+            return LispObject::from(bigint_pow(x, y));
         }
     }
     let b = arg1.any_to_float_or_error();
@@ -298,6 +311,23 @@ pub fn expt(arg1: LispObject, arg2: LispObject) -> LispObject {
     LispObject::from_float(b.powf(e))
 }
 
+/// Return X raised to the non-negative power Y, signaling an
+/// `overflow-error' if the result would be too large.
+fn bigint_pow(x: BigInt, y: EmacsInt) -> BigInt {
+    // Powers of -1, 0 and 1 stay small however large Y is.
+    if x.abs() <= BigInt::one() {
+        return if y == 0 {
+            BigInt::one()
+        } else if x.is_negative() && y.is_even() {
+            -x
+        } else {
+            x
+        };
+    }
+    check_bignum_bits((x.bits() as EmacsInt - 1).saturating_mul(y) as usize);
+    num_traits::pow(x, y as usize)
+}
+
 /// Returns largest integer <= the base 2 log of the magnitude of ARG.
 /// This is the same as the exponent of a float.
 #[lisp_fn]
@@ -318,6 +348,8 @@ pub fn logb(arg: LispObject) -> EmacsInt {
         } else {
             MOST_POSITIVE_FIXNUM
         }
+    } else if let Some(b) = arg.as_bignum() {
+        b.value().bits() as EmacsInt - 1
     } else {
         wrong_type!(Qnumberp, arg)
     }
@@ -334,16 +366,23 @@ pub fn fround(arg: LispObject) -> EmacsDouble {
 /// This rounds the value towards +inf.
 /// With optional DIVISOR, return the smallest integer no less than ARG/DIVISOR.
 #[lisp_fn(min = "1")]
-pub fn ceiling(arg: LispObject, divisor: LispObject) -> EmacsInt {
-    rounding_driver(arg, divisor, |x| x.ceil(), ceiling2, "ceiling")
+pub fn ceiling(arg: LispObject, divisor: LispObject) -> LispObject {
+    rounding_driver(
+        arg,
+        divisor,
+        |x| x.ceil(),
+        ceiling2,
+        bigint_ceiling2,
+        "ceiling",
+    )
 }
 
 /// Return the largest integer no greater than ARG.
 /// This rounds the value towards -inf.
 /// With optional DIVISOR, return the largest integer no greater than ARG/DIVISOR.
 #[lisp_fn(min = "1")]
-pub fn floor(arg: LispObject, divisor: LispObject) -> EmacsInt {
-    rounding_driver(arg, divisor, |x| x.floor(), floor2, "floor")
+pub fn floor(arg: LispObject, divisor: LispObject) -> LispObject {
+    rounding_driver(arg, divisor, |x| x.floor(), floor2, bigint_floor2, "floor")
 }
 
 /// Return the nearest integer to ARG.
@@ -354,16 +393,23 @@ pub fn floor(arg: LispObject, divisor: LispObject) -> EmacsInt {
 /// your machine.  For example, (round 2.5) can return 3 on some
 /// systems, but 2 on others.
 #[lisp_fn(min = "1")]
-pub fn round(arg: LispObject, divisor: LispObject) -> EmacsInt {
-    rounding_driver(arg, divisor, libm::rint, round2, "round")
+pub fn round(arg: LispObject, divisor: LispObject) -> LispObject {
+    rounding_driver(arg, divisor, libm::rint, round2, bigint_round2, "round")
 }
 
 /// Truncate a floating point number to an int.
 /// Rounds ARG toward zero.
 /// With optional DIVISOR, truncate ARG/DIVISOR.
 #[lisp_fn(min = "1")]
-pub fn truncate(arg: LispObject, divisor: LispObject) -> EmacsInt {
-    rounding_driver(arg, divisor, |x| x.trunc(), truncate2, "truncate")
+pub fn truncate(arg: LispObject, divisor: LispObject) -> LispObject {
+    rounding_driver(
+        arg,
+        divisor,
+        |x| x.trunc(),
+        truncate2,
+        bigint_truncate2,
+        "truncate",
+    )
 }
 
 fn rounding_driver<F>(
@@ -371,15 +417,16 @@ fn rounding_driver<F>(
     divisor: LispObject,
     double_round: F,
     int_round2: fn(EmacsInt, EmacsInt) -> EmacsInt,
+    bigint_round2: fn(&BigInt, &BigInt) -> BigInt,
     name: &str,
-) -> EmacsInt
+) -> LispObject
 where
     F: Fn(f64) -> f64,
 {
     let d;
     if divisor.is_nil() {
-        if arg.is_fixnum() {
-            return arg.as_fixnum().unwrap();
+        if arg.is_integer() {
+            return arg;
         } else if let Some(f) = arg.as_float() {
             d = f;
         } else {
@@ -390,26 +437,25 @@ where
             if div == 0 {
                 xsignal!(Qarith_error);
             }
-            return int_round2(arg, div);
+            return LispObject::from_integer(int_round2(arg, div));
+        }
+        if let (Some(arg), Some(div)) = (arg.as_bigint(), divisor.as_bigint()) {
+            if div.is_zero() {
+                xsignal!(Qarith_error);
+            }
+            return LispObject::from(bigint_round2(&arg, &div));
         }
         let arg = arg.any_to_float_or_error();
         let div = divisor.any_to_float_or_error();
         d = arg / div;
     }
 
-    // Round, coarsely test for fixnum overflow before converting to
-    // EmacsInt (to avoid undefined behavior), and then exactly test
-    // for overflow after converting (as FIXNUM_OVERFLOW_P is inaccurate
-    // on floats).
-    let dr = double_round(d);
-    if dr.abs() < (2 * (MOST_POSITIVE_FIXNUM + 1)) as f64 {
-        let ir = dr as EmacsInt;
-        if !LispObject::fixnum_overflow(ir) {
-            return ir;
-        }
+    // Infinities and NaNs have no integer value; everything else is
+    // converted exactly, as a bignum if need be.
+    match bignum::float_to_bigint(double_round(d)) {
+        Some(n) => LispObject::from(n),
+        None => xsignal!(Qrange_error, LispObject::from(name), arg),
     }
-
-    xsignal!(Qrange_error, LispObject::from(name), arg)
 }
 
 fn ceiling2(i1: EmacsInt, i2: EmacsInt) -> EmacsInt {
@@ -442,6 +488,37 @@ fn round2(i1: EmacsInt, i2: EmacsInt) -> EmacsInt {
     }
 }
 
+fn bigint_ceiling2(i1: &BigInt, i2: &BigInt) -> BigInt {
+    -(-i1).div_floor(i2)
+}
+
+fn bigint_floor2(i1: &BigInt, i2: &BigInt) -> BigInt {
+    i1.div_floor(i2)
+}
+
+fn bigint_truncate2(i1: &BigInt, i2: &BigInt) -> BigInt {
+    i1 / i2
+}
+
+fn bigint_round2(i1: &BigInt, i2: &BigInt) -> BigInt {
+    // See `round2'.
+    let (q, r) = i1.div_rem(i2);
+    let abs_r = r.abs();
+    let abs_r1 = i2.abs() - &abs_r;
+    let odd = if q.is_odd() {
+        BigInt::one()
+    } else {
+        BigInt::zero()
+    };
+    if abs_r + odd <= abs_r1 {
+        q
+    } else if i2.is_negative() != r.is_negative() {
+        q - BigInt::one()
+    } else {
+        q + BigInt::one()
+    }
+}
+
 // Since these are generated via a macro the build cannot hook them into the
 // system automatically. Do not add more items here unless they are also generated
 // with something like simple_float_op.
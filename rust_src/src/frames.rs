@@ -176,7 +176,7 @@ pub fn selected_frame() -> LispFrameRef {
 ///  `w32' for an Emacs frame that is a window on MS-Windows display,
 ///  `ns' for an Emacs frame on a GNUstep or Macintosh Cocoa display,
 /// See also `frame-live-p'.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn framep(frame: Option<LispFrameRef>) -> LispObject {
     frame.map_or(Qnil, framep_1)
 }
@@ -186,7 +186,7 @@ pub fn framep(frame: Option<LispFrameRef>) -> LispObject {
 /// frame, the return value indicates what sort of terminal device it is
 /// displayed on.  See the documentation of `framep' for possible
 /// return values.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn frame_live_p(frame: Option<LispFrameRef>) -> LispObject {
     frame.map_or(Qnil, |f| if f.is_live() { framep_1(f) } else { Qnil })
 }
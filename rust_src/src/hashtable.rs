@@ -22,6 +22,11 @@ use crate::{
 
 pub type LispHashTableRef = ExternalPtr<Lisp_Hash_Table>;
 
+// These mirror the static constants of the same name in lisp.h, which
+// bindgen can't see.
+pub const DEFAULT_REHASH_THRESHOLD: f32 = 0.8125;
+pub const DEFAULT_REHASH_SIZE: f32 = 1.5 - 1.0;
+
 #[derive(Clone, Copy, PartialEq, PartialOrd, Eq, Ord, Debug)]
 pub enum HashLookupResult {
     Missing(EmacsUint),
@@ -95,6 +100,10 @@ impl LispHashTableRef {
         aref(self.key_and_value, (2 * idx) as EmacsInt)
     }
 
+    pub fn set_hash_key(self, idx: isize, key: LispObject) {
+        unsafe { gc_aset(self.key_and_value, 2 * idx, key) };
+    }
+
     pub fn get_hash_hash(self, idx: isize) -> LispObject {
         aref(self.hash, idx as EmacsInt)
     }
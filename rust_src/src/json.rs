@@ -0,0 +1,999 @@
+//! JSON parsing and serialization.
+
+use std::collections::{HashMap, HashSet};
+
+use libc::{c_char, ptrdiff_t};
+
+use remacs_macros::lisp_fn;
+
+use crate::{
+    buffer_text::BufferText,
+    hashtable::{
+        HashLookupResult::{Found, Missing},
+        LispHashTableRef,
+    },
+    lisp::defsubr,
+    lisp::LispObject,
+    lists::{get, list, put},
+    marker::buf_bytepos_to_charpos,
+    obarray::lisp_intern,
+    remacs_sys::{
+        code_convert_string, globals, insert1, make_pure_c_string, make_specified_string,
+        pure_cons, set_point_both, wrong_choice, EmacsInt,
+    },
+    remacs_sys::{Fmake_hash_table, Fmake_vector},
+    remacs_sys::{
+        QCarray_type, QCfalse, QCfalse_object, QCnull, QCnull_object, QCobject_type, QCsize,
+        QCtest, Qalist, Qarray, Qequal, Qerror, Qerror_conditions, Qerror_message, Qhash_table,
+        Qjson_end_of_file, Qjson_error, Qjson_object_too_deep, Qjson_out_of_memory,
+        Qjson_parse_error, Qjson_parse_string, Qjson_serialize, Qjson_trailing_content,
+        Qjson_value_p, Qlist, Qnil, Qplist, Qplistp, Qpure, Qside_effect_free,
+        Qstring_without_embedded_nulls_p, Qstringp, Qt, Qutf_8_string_p, Qutf_8_unix,
+    },
+    threads::ThreadState,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+enum ObjectType {
+    HashTable,
+    Alist,
+    Plist,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ArrayType {
+    Array,
+    List,
+}
+
+/// The keyword arguments accepted by the parsing and serialization
+/// functions.
+#[derive(Clone, Copy)]
+struct JsonConfiguration {
+    object_type: ObjectType,
+    array_type: ArrayType,
+    null_object: LispObject,
+    false_object: LispObject,
+}
+
+impl JsonConfiguration {
+    /// Parse the keyword arguments in ARGS.  Only `:null-object' and
+    /// `:false-object' are accepted unless PARSE_OBJECT_TYPES is true.
+    fn from_args(args: &[LispObject], parse_object_types: bool) -> Self {
+        let mut conf = JsonConfiguration {
+            object_type: ObjectType::HashTable,
+            array_type: ArrayType::Array,
+            null_object: QCnull,
+            false_object: QCfalse,
+        };
+
+        if args.len() % 2 != 0 {
+            wrong_type!(Qplistp, list(args));
+        }
+
+        // Start from the back so keyword values appearing first take
+        // precedence.
+        for pair in args.chunks(2).rev() {
+            let (key, value) = (pair[0], pair[1]);
+            if parse_object_types && key.eq(QCobject_type) {
+                conf.object_type = if value.eq(Qhash_table) {
+                    ObjectType::HashTable
+                } else if value.eq(Qalist) {
+                    ObjectType::Alist
+                } else if value.eq(Qplist) {
+                    ObjectType::Plist
+                } else {
+                    unsafe { wrong_choice(list!(Qhash_table, Qalist, Qplist), value) }
+                };
+            } else if parse_object_types && key.eq(QCarray_type) {
+                conf.array_type = if value.eq(Qarray) {
+                    ArrayType::Array
+                } else if value.eq(Qlist) {
+                    ArrayType::List
+                } else {
+                    unsafe { wrong_choice(list!(Qarray, Qlist), value) }
+                };
+            } else if key.eq(QCnull_object) {
+                conf.null_object = value;
+            } else if key.eq(QCfalse_object) {
+                conf.false_object = value;
+            } else if parse_object_types {
+                unsafe {
+                    wrong_choice(
+                        list!(QCobject_type, QCarray_type, QCnull_object, QCfalse_object),
+                        value,
+                    )
+                }
+            } else {
+                unsafe { wrong_choice(list!(QCnull_object, QCfalse_object), value) }
+            }
+        }
+
+        conf
+    }
+}
+
+/// How many more nested arrays and objects we may descend into before
+/// signaling `json-object-too-deep'.
+fn remaining_depth() -> EmacsInt {
+    let depth = ThreadState::current_thread().m_lisp_eval_depth;
+    let max_depth = unsafe { globals.max_lisp_eval_depth };
+    max_depth - depth
+}
+
+/// Create a multibyte Lisp string from the valid UTF-8 sequence
+/// BYTES.  UTF-8 is a subset of the internal Emacs encoding, so no
+/// decoding is necessary.
+fn json_make_string(bytes: &[u8]) -> LispObject {
+    let nchars = bytes.iter().filter(|&&b| b & 0xC0 != 0x80).count();
+    unsafe {
+        make_specified_string(
+            bytes.as_ptr() as *const c_char,
+            nchars as ptrdiff_t,
+            bytes.len() as ptrdiff_t,
+            true,
+        )
+    }
+}
+
+/// Return a unibyte string containing the UTF-8 representation of
+/// STRING.  If STRING does not represent a sequence of Unicode scalar
+/// values the result is not valid UTF-8; callers have to check.
+fn json_encode(string: LispObject) -> LispObject {
+    unsafe { code_convert_string(string, Qutf_8_unix, Qt, true, true, true) }
+}
+
+/// Accumulates the compact JSON text of a Lisp object.
+struct Serializer {
+    conf: JsonConfiguration,
+    out: Vec<u8>,
+    depth: EmacsInt,
+    max_depth: EmacsInt,
+}
+
+impl Serializer {
+    fn new(conf: JsonConfiguration) -> Self {
+        Self {
+            conf,
+            out: Vec::new(),
+            depth: 0,
+            max_depth: remaining_depth(),
+        }
+    }
+
+    fn value(&mut self, object: LispObject) {
+        if object.eq(self.conf.null_object) {
+            self.out.extend_from_slice(b"null");
+        } else if object.eq(self.conf.false_object) {
+            self.out.extend_from_slice(b"false");
+        } else if object.is_t() {
+            self.out.extend_from_slice(b"true");
+        } else if let Some(n) = object.as_fixnum() {
+            self.out.extend_from_slice(n.to_string().as_bytes());
+        } else if let Some(f) = object.as_float() {
+            if !f.is_finite() {
+                wrong_type!(Qjson_value_p, object);
+            }
+            let mut repr = f.to_string();
+            if !repr.contains(|c| c == '.' || c == 'e' || c == 'E') {
+                repr.push_str(".0");
+            }
+            self.out.extend_from_slice(repr.as_bytes());
+        } else if object.is_string() {
+            self.string(object);
+        } else {
+            self.compound(object);
+        }
+    }
+
+    /// Serialize a vector, hash table, alist or plist.
+    fn compound(&mut self, object: LispObject) {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            xsignal!(Qjson_object_too_deep);
+        }
+
+        if let Some(vector) = object.as_vector() {
+            self.out.push(b'[');
+            for (i, elt) in vector.as_slice().iter().enumerate() {
+                if i > 0 {
+                    self.out.push(b',');
+                }
+                self.value(*elt);
+            }
+            self.out.push(b']');
+        } else if object.is_hash_table() {
+            let table = object.as_hash_table_or_error();
+            let mut seen = HashSet::new();
+            self.out.push(b'{');
+            for (key, value) in table.iter() {
+                if !key.is_string() {
+                    wrong_type!(Qstringp, key);
+                }
+                // Duplicate keys are possible if the hash table test
+                // is not `equal'.
+                if !self.member(key, &mut seen) {
+                    wrong_type!(Qjson_value_p, object);
+                }
+                self.value(value);
+            }
+            self.out.push(b'}');
+        } else if object.is_nil() {
+            self.out.extend_from_slice(b"{}");
+        } else if let Some(cons) = object.as_cons() {
+            let mut seen = HashSet::new();
+            self.out.push(b'{');
+            if cons.car().is_cons() {
+                // An alist.  If a key is duplicate, the first instance
+                // is used.
+                for pair in object.iter_cars() {
+                    let (key, value) = pair.as_cons_or_error().as_tuple();
+                    let name = key.as_symbol_or_error().symbol_name();
+                    if self.member(name, &mut seen) {
+                        self.value(value);
+                    }
+                }
+            } else {
+                // A plist.  Leading colons are stripped from keyword
+                // keys.
+                let mut tail = object.iter_tails_plist();
+                while let Some(key_cell) = tail.next() {
+                    let value_cell = match tail.next() {
+                        Some(cell) => cell,
+                        None => wrong_type!(Qplistp, object),
+                    };
+                    let mut name = key_cell.car().as_symbol_or_error().symbol_name();
+                    let name_ref = name.as_string_or_error();
+                    if name_ref.as_slice().first() == Some(&b':') {
+                        name = json_make_string(&name_ref.as_slice()[1..]);
+                    }
+                    if self.member(name, &mut seen) {
+                        self.value(value_cell.car());
+                    }
+                }
+            }
+            self.out.push(b'}');
+        } else {
+            wrong_type!(Qjson_value_p, object);
+        }
+
+        self.depth -= 1;
+    }
+
+    /// Write KEY followed by a colon, preceded by a comma unless it is
+    /// the first member of the current object.  Return false, writing
+    /// nothing, if KEY has already been written to this object.
+    fn member(&mut self, key: LispObject, seen: &mut HashSet<Vec<u8>>) -> bool {
+        let bytes = json_encode(key).as_string_or_error().as_slice().to_vec();
+        if seen.contains(&bytes) {
+            return false;
+        }
+        if !seen.is_empty() {
+            self.out.push(b',');
+        }
+        self.string(key);
+        self.out.push(b':');
+        seen.insert(bytes);
+        true
+    }
+
+    fn string(&mut self, string: LispObject) {
+        let encoded = json_encode(string).as_string_or_error();
+        let bytes = encoded.as_slice();
+        if ::std::str::from_utf8(bytes).is_err() {
+            wrong_type!(Qutf_8_string_p, string);
+        }
+
+        static HEX: &[u8; 16] = b"0123456789ABCDEF";
+        self.out.push(b'"');
+        for &b in bytes {
+            match b {
+                b'"' => self.out.extend_from_slice(b"\\\""),
+                b'\\' => self.out.extend_from_slice(b"\\\\"),
+                b'\x08' => self.out.extend_from_slice(b"\\b"),
+                b'\x0C' => self.out.extend_from_slice(b"\\f"),
+                b'\n' => self.out.extend_from_slice(b"\\n"),
+                b'\r' => self.out.extend_from_slice(b"\\r"),
+                b'\t' => self.out.extend_from_slice(b"\\t"),
+                0..=0x1F => {
+                    self.out.extend_from_slice(b"\\u00");
+                    self.out.push(HEX[(b >> 4) as usize]);
+                    self.out.push(HEX[(b & 0xF) as usize]);
+                }
+                _ => self.out.push(b),
+            }
+        }
+        self.out.push(b'"');
+    }
+}
+
+fn serialize(object: LispObject, conf: JsonConfiguration) -> Vec<u8> {
+    let mut serializer = Serializer::new(conf);
+    serializer.value(object);
+    serializer.out
+}
+
+/// Return the JSON representation of OBJECT as a string.
+///
+/// OBJECT must be t, a number, string, vector, hashtable, alist, plist,
+/// or the Lisp equivalents to the JSON null and false values, and its
+/// elements must recursively consist of the same kinds of values.  t
+/// will be converted to the JSON true value.  Vectors will be converted
+/// to JSON arrays, whereas hashtables, alists and plists are converted
+/// to JSON objects.  Hashtable keys must be strings and must be unique
+/// within each object.  Alist and plist keys must be symbols; if a key
+/// is duplicate, the first instance is used.  A leading colon in plist
+/// keys is elided.
+///
+/// The Lisp equivalents to the JSON null and false values are
+/// configurable in the arguments ARGS, a list of keyword/argument pairs:
+///
+/// The keyword argument `:null-object' specifies which object to use
+/// to represent a JSON null value.  It defaults to `:null'.
+///
+/// The keyword argument `:false-object' specifies which object to use
+/// to represent a JSON false value.  It defaults to `:false'.
+///
+/// If you specify the same value for `:null-object' and `:false-object',
+/// a potentially ambiguous situation, the JSON output will not contain
+/// any JSON false values.
+/// usage: (json-serialize OBJECT &rest ARGS)
+#[lisp_fn(min = "1")]
+pub fn json_serialize(args: &[LispObject]) -> LispObject {
+    let conf = JsonConfiguration::from_args(&args[1..], false);
+    json_make_string(&serialize(args[0], conf))
+}
+
+/// Insert the JSON representation of OBJECT before point.
+/// This is the same as (insert (json-serialize OBJECT)), but potentially
+/// faster.  See the function `json-serialize' for allowed values of
+/// OBJECT.
+/// usage: (json-insert OBJECT &rest ARGS)
+#[lisp_fn(min = "1")]
+pub fn json_insert(args: &[LispObject]) {
+    let conf = JsonConfiguration::from_args(&args[1..], false);
+    let string = json_make_string(&serialize(args[0], conf));
+    unsafe { insert1(string) };
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum ParseErrorKind {
+    /// The input ended in the middle of a value.
+    EndOfFile,
+    /// A complete value was followed by something other than
+    /// whitespace.
+    TrailingContent,
+    /// Anything else.
+    Syntax,
+}
+
+struct ParseError {
+    kind: ParseErrorKind,
+    message: &'static str,
+    position: usize,
+}
+
+type ParseResult<T> = Result<T, ParseError>;
+
+/// A recursive descent JSON parser over the two halves of a byte
+/// sequence, so that buffer text can be read straight from around the
+/// gap.
+struct Parser<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+    pos: usize,
+    conf: JsonConfiguration,
+    depth: EmacsInt,
+    max_depth: EmacsInt,
+    scratch: Vec<u8>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(first: &'a [u8], second: &'a [u8], conf: JsonConfiguration) -> Self {
+        Self {
+            first,
+            second,
+            pos: 0,
+            conf,
+            depth: 0,
+            max_depth: remaining_depth(),
+            scratch: Vec::new(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    fn byte_at(&self, pos: usize) -> u8 {
+        if pos < self.first.len() {
+            self.first[pos]
+        } else {
+            self.second[pos - self.first.len()]
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        if self.pos < self.len() {
+            Some(self.byte_at(self.pos))
+        } else {
+            None
+        }
+    }
+
+    fn next_byte(&mut self) -> ParseResult<u8> {
+        match self.peek() {
+            Some(b) => {
+                self.pos += 1;
+                Ok(b)
+            }
+            None => Err(self.eof()),
+        }
+    }
+
+    fn error(&self, message: &'static str) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::Syntax,
+            message,
+            position: self.pos,
+        }
+    }
+
+    fn eof(&self) -> ParseError {
+        ParseError {
+            kind: ParseErrorKind::EndOfFile,
+            message: "unexpected end of input",
+            position: self.pos,
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') = self.peek() {
+            self.pos += 1;
+        }
+    }
+
+    /// Parse a single value, surrounded by optional whitespace.  Input
+    /// following the value is left unread.
+    fn parse(&mut self) -> ParseResult<LispObject> {
+        self.skip_whitespace();
+        self.value()
+    }
+
+    /// Parse a value and make sure nothing but whitespace follows.
+    fn parse_all(&mut self) -> ParseResult<LispObject> {
+        let value = self.parse()?;
+        self.skip_whitespace();
+        if self.peek().is_some() {
+            return Err(ParseError {
+                kind: ParseErrorKind::TrailingContent,
+                message: "end of file expected",
+                position: self.pos,
+            });
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self) -> ParseResult<LispObject> {
+        match self.peek() {
+            None => Err(self.eof()),
+            Some(b'{') => self.object(),
+            Some(b'[') => self.array(),
+            Some(b'"') => {
+                self.string()?;
+                Ok(json_make_string(&self.scratch))
+            }
+            Some(b'-') | Some(b'0'..=b'9') => self.number(),
+            Some(b't') => self.literal(b"true", Qt),
+            Some(b'f') => {
+                let false_object = self.conf.false_object;
+                self.literal(b"false", false_object)
+            }
+            Some(b'n') => {
+                let null_object = self.conf.null_object;
+                self.literal(b"null", null_object)
+            }
+            Some(_) => Err(self.error("invalid token")),
+        }
+    }
+
+    fn literal(&mut self, text: &[u8], value: LispObject) -> ParseResult<LispObject> {
+        for &expected in text {
+            if self.next_byte()? != expected {
+                self.pos -= 1;
+                return Err(self.error("invalid token"));
+            }
+        }
+        Ok(value)
+    }
+
+    fn enter(&mut self) {
+        self.depth += 1;
+        if self.depth > self.max_depth {
+            xsignal!(Qjson_object_too_deep);
+        }
+    }
+
+    fn array(&mut self) -> ParseResult<LispObject> {
+        self.enter();
+        self.pos += 1;
+        let mut elements = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                elements.push(self.value()?);
+                self.skip_whitespace();
+                match self.next_byte()? {
+                    b',' => continue,
+                    b']' => break,
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error("']' expected"));
+                    }
+                }
+            }
+        }
+
+        self.depth -= 1;
+        Ok(match self.conf.array_type {
+            ArrayType::List => list(&elements),
+            ArrayType::Array => {
+                let result = unsafe { Fmake_vector(LispObject::from(elements.len()), Qnil) };
+                let mut vector = result.as_vector_or_error();
+                for (i, elt) in elements.into_iter().enumerate() {
+                    vector.set(i, elt);
+                }
+                result
+            }
+        })
+    }
+
+    fn object(&mut self) -> ParseResult<LispObject> {
+        self.enter();
+        self.pos += 1;
+        // Members in the order of their first appearance.  A repeated
+        // key replaces the earlier value.
+        let mut members: Vec<(LispObject, LispObject)> = Vec::new();
+        let mut index: HashMap<Vec<u8>, usize> = HashMap::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_whitespace();
+                match self.peek() {
+                    Some(b'"') => self.string()?,
+                    Some(_) => return Err(self.error("string or '}' expected")),
+                    None => return Err(self.eof()),
+                }
+                let key_bytes = self.scratch.clone();
+                self.skip_whitespace();
+                if self.next_byte()? != b':' {
+                    self.pos -= 1;
+                    return Err(self.error("':' expected"));
+                }
+                self.skip_whitespace();
+                let value = self.value()?;
+
+                if let Some(&i) = index.get(&key_bytes) {
+                    members[i].1 = value;
+                } else {
+                    let key = self.object_key(&key_bytes);
+                    index.insert(key_bytes, members.len());
+                    members.push((key, value));
+                }
+
+                self.skip_whitespace();
+                match self.next_byte()? {
+                    b',' => continue,
+                    b'}' => break,
+                    _ => {
+                        self.pos -= 1;
+                        return Err(self.error("'}' expected"));
+                    }
+                }
+            }
+        }
+
+        self.depth -= 1;
+        Ok(match self.conf.object_type {
+            ObjectType::HashTable => {
+                let table = callN_raw!(
+                    Fmake_hash_table,
+                    QCtest,
+                    Qequal,
+                    QCsize,
+                    LispObject::from(members.len())
+                );
+                let hash_table = LispHashTableRef::from(table);
+                for (key, value) in members {
+                    match hash_table.lookup(key) {
+                        Found(idx) => hash_table.set_hash_value(idx, value),
+                        Missing(hash) => {
+                            hash_table.put(key, value, hash);
+                        }
+                    }
+                }
+                table
+            }
+            ObjectType::Alist => members.into_iter().rev().fold(Qnil, |tail, (key, value)| {
+                LispObject::cons(LispObject::cons(key, value), tail)
+            }),
+            ObjectType::Plist => members.into_iter().rev().fold(Qnil, |tail, (key, value)| {
+                LispObject::cons(key, LispObject::cons(value, tail))
+            }),
+        })
+    }
+
+    /// Turn the UTF-8 object key KEY into a string, symbol or keyword,
+    /// depending on the configured object type.
+    fn object_key(&self, key: &[u8]) -> LispObject {
+        match self.conf.object_type {
+            ObjectType::HashTable => json_make_string(key),
+            ObjectType::Alist => lisp_intern(json_make_string(key), Qnil),
+            ObjectType::Plist => {
+                let mut name = Vec::with_capacity(key.len() + 1);
+                name.push(b':');
+                name.extend_from_slice(key);
+                lisp_intern(json_make_string(&name), Qnil)
+            }
+        }
+    }
+
+    /// Read a string literal into `self.scratch' as UTF-8.
+    fn string(&mut self) -> ParseResult<()> {
+        let start = self.pos;
+        self.pos += 1;
+        self.scratch.clear();
+
+        loop {
+            match self.next_byte()? {
+                b'"' => break,
+                b'\\' => {
+                    let c = match self.next_byte()? {
+                        b'"' => u32::from(b'"'),
+                        b'\\' => u32::from(b'\\'),
+                        b'/' => u32::from(b'/'),
+                        b'b' => 0x08,
+                        b'f' => 0x0C,
+                        b'n' => u32::from(b'\n'),
+                        b'r' => u32::from(b'\r'),
+                        b't' => u32::from(b'\t'),
+                        b'u' => self.unicode_escape()?,
+                        _ => {
+                            self.pos -= 1;
+                            return Err(self.error("invalid escape"));
+                        }
+                    };
+                    let mut buf = [0; 4];
+                    let ch = ::std::char::from_u32(c).unwrap();
+                    self.scratch
+                        .extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
+                }
+                0..=0x1F => {
+                    self.pos -= 1;
+                    return Err(self.error("control character in string"));
+                }
+                b => self.scratch.push(b),
+            }
+        }
+
+        if ::std::str::from_utf8(&self.scratch).is_err() {
+            return Err(ParseError {
+                kind: ParseErrorKind::Syntax,
+                message: "invalid UTF-8 in string",
+                position: start,
+            });
+        }
+        Ok(())
+    }
+
+    fn hex4(&mut self) -> ParseResult<u32> {
+        let mut value = 0;
+        for _ in 0..4 {
+            let digit = match self.next_byte()? {
+                b @ b'0'..=b'9' => b - b'0',
+                b @ b'a'..=b'f' => b - b'a' + 10,
+                b @ b'A'..=b'F' => b - b'A' + 10,
+                _ => {
+                    self.pos -= 1;
+                    return Err(self.error("invalid \\u escape"));
+                }
+            };
+            value = value * 16 + u32::from(digit);
+        }
+        Ok(value)
+    }
+
+    /// Decode the four hex digits after `\u', combining surrogate
+    /// pairs.  Lone surrogates are an error.
+    fn unicode_escape(&mut self) -> ParseResult<u32> {
+        let high = self.hex4()?;
+        match high {
+            0xD800..=0xDBFF => {
+                if self.next_byte()? != b'\\' || self.next_byte()? != b'u' {
+                    return Err(self.error("invalid Unicode surrogate pair"));
+                }
+                let low = self.hex4()?;
+                if low < 0xDC00 || low > 0xDFFF {
+                    return Err(self.error("invalid Unicode surrogate pair"));
+                }
+                Ok(0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00))
+            }
+            0xDC00..=0xDFFF => Err(self.error("invalid Unicode surrogate pair")),
+            c => Ok(c),
+        }
+    }
+
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.pos += 1;
+        }
+        self.pos - start
+    }
+
+    fn number(&mut self) -> ParseResult<LispObject> {
+        let start = self.pos;
+        let mut integral = true;
+
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.peek() {
+            Some(b'0') => self.pos += 1,
+            Some(b'1'..=b'9') => {
+                self.digits();
+            }
+            Some(_) => return Err(self.error("invalid number")),
+            None => return Err(self.eof()),
+        }
+        if self.peek() == Some(b'.') {
+            integral = false;
+            self.pos += 1;
+            if self.digits() == 0 {
+                return Err(self
+                    .peek()
+                    .map_or_else(|| self.eof(), |_| self.error("invalid number")));
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            integral = false;
+            self.pos += 1;
+            if let Some(b'+') | Some(b'-') = self.peek() {
+                self.pos += 1;
+            }
+            if self.digits() == 0 {
+                return Err(self
+                    .peek()
+                    .map_or_else(|| self.eof(), |_| self.error("invalid number")));
+            }
+        }
+
+        let text: String = (start..self.pos)
+            .map(|i| char::from(self.byte_at(i)))
+            .collect();
+
+        // Integers that don't fit into a fixnum are returned as
+        // floating-point numbers.  This loses precision, but such
+        // integers are nonportable anyway because many JSON
+        // implementations only use 64-bit floating-point numbers.
+        if integral {
+            if let Ok(n) = text.parse::<EmacsInt>() {
+                if !LispObject::fixnum_overflow(n) {
+                    return Ok(LispObject::from(n));
+                }
+            }
+        }
+        match text.parse::<f64>() {
+            Ok(f) => Ok(LispObject::from_float(f)),
+            Err(_) => Err(ParseError {
+                kind: ParseErrorKind::Syntax,
+                message: "invalid number",
+                position: start,
+            }),
+        }
+    }
+
+    /// Signal the Lisp error corresponding to ERROR.  SOURCE describes
+    /// the input, like Jansson used to.
+    fn signal(&self, error: &ParseError, source: &str) -> ! {
+        let symbol = match error.kind {
+            ParseErrorKind::EndOfFile => Qjson_end_of_file,
+            ParseErrorKind::TrailingContent => Qjson_trailing_content,
+            ParseErrorKind::Syntax => Qjson_parse_error,
+        };
+
+        let mut line = 1;
+        let mut column = 0;
+        for i in 0..error.position.min(self.len()) {
+            if self.byte_at(i) == b'\n' {
+                line += 1;
+                column = 0;
+            } else {
+                column += 1;
+            }
+        }
+
+        xsignal!(
+            symbol,
+            LispObject::from(error.message),
+            LispObject::from(source),
+            LispObject::from(line),
+            LispObject::from(column),
+            LispObject::from(error.position)
+        );
+    }
+}
+
+/// Parse the JSON STRING into a Lisp object.
+///
+/// This is essentially the reverse operation of `json-serialize', which
+/// see.  The returned object will be the JSON null value, the JSON false
+/// value, t, a number, a string, a vector, a list, a hashtable, an alist,
+/// or a plist.  Its elements will be further objects of these types.  If
+/// there are duplicate keys in an object, all but the last one are
+/// ignored.  If STRING doesn't contain a valid JSON object, this function
+/// signals an error of type `json-parse-error'.
+///
+/// The arguments ARGS are a list of keyword/argument pairs:
+///
+/// The keyword argument `:object-type' specifies which Lisp type is used
+/// to represent objects; it can be `hash-table', `alist' or `plist'.  It
+/// defaults to `hash-table'.
+///
+/// The keyword argument `:array-type' specifies which Lisp type is used
+/// to represent arrays; it can be `array' (the default) or `list'.
+///
+/// The keyword argument `:null-object' specifies which object to use
+/// to represent a JSON null value.  It defaults to `:null'.
+///
+/// The keyword argument `:false-object' specifies which object to use to
+/// represent a JSON false value.  It defaults to `:false'.
+/// usage: (json-parse-string STRING &rest ARGS)
+#[lisp_fn(min = "1")]
+pub fn json_parse_string(args: &[LispObject]) -> LispObject {
+    let encoded = json_encode(args[0]).as_string_or_error();
+    let bytes = encoded.as_slice();
+    if bytes.contains(&0) {
+        wrong_type!(Qstring_without_embedded_nulls_p, encoded.as_lisp_obj());
+    }
+    let conf = JsonConfiguration::from_args(&args[1..], true);
+
+    let mut parser = Parser::new(bytes, &[], conf);
+    match parser.parse_all() {
+        Ok(value) => value,
+        Err(error) => parser.signal(&error, "<string>"),
+    }
+}
+
+/// Read JSON object from current buffer starting at point.
+///
+/// Move point after the end of the object if parsing was successful.
+/// On error, don't move point.
+///
+/// The returned object will be a vector, list, hashtable, alist, or
+/// plist.  Its elements will be the JSON null value, the JSON false
+/// value, t, numbers, strings, or further vectors, lists, hashtables,
+/// alists, or plists.  If there are duplicate keys in an object, all
+/// but the last one are ignored.
+///
+/// If the current buffer doesn't contain a valid JSON object, the
+/// function signals an error of type `json-parse-error'.
+///
+/// The arguments ARGS are a list of keyword/argument pairs, see
+/// `json-parse-string' for their meaning.
+/// usage: (json-parse-buffer &rest args)
+#[lisp_fn(min = "0")]
+pub fn json_parse_buffer(args: &[LispObject]) -> LispObject {
+    let conf = JsonConfiguration::from_args(args, true);
+    let mut buffer = ThreadState::current_buffer();
+
+    let point = buffer.pt_byte;
+    let text = BufferText::region(buffer, point, buffer.zv_byte);
+    let (first, second) = text.as_slices();
+    let mut parser = Parser::new(first, second, conf);
+
+    match parser.parse() {
+        Ok(value) => {
+            // Adjust point by how much we just read.
+            let end = point + parser.pos as ptrdiff_t;
+            unsafe { set_point_both(buf_bytepos_to_charpos(buffer.as_mut(), end), end) };
+            value
+        }
+        Err(error) => parser.signal(&error, "<buffer>"),
+    }
+}
+
+/// Simplified version of `define-error' that works with pure objects.
+fn define_error(name: LispObject, message: &str, parent: LispObject) {
+    let name_sym = name.as_symbol_or_error();
+    let parent_conditions = get(parent.as_symbol_or_error(), Qerror_conditions);
+    debug_assert!(parent_conditions.is_cons());
+    unsafe {
+        put(
+            name_sym,
+            Qerror_conditions,
+            pure_cons(name, parent_conditions),
+        );
+        put(
+            name_sym,
+            Qerror_message,
+            make_pure_c_string(
+                message.as_ptr() as *const c_char,
+                message.len() as ptrdiff_t,
+            ),
+        );
+    }
+}
+
+#[no_mangle]
+pub extern "C" fn syms_of_json() {
+    def_lisp_sym!(QCnull, ":null");
+    def_lisp_sym!(QCfalse, ":false");
+
+    def_lisp_sym!(
+        Qstring_without_embedded_nulls_p,
+        "string-without-embedded-nulls-p"
+    );
+    def_lisp_sym!(Qjson_value_p, "json-value-p");
+    def_lisp_sym!(Qutf_8_string_p, "utf-8-string-p");
+
+    def_lisp_sym!(Qjson_error, "json-error");
+    def_lisp_sym!(Qjson_out_of_memory, "json-out-of-memory");
+    def_lisp_sym!(Qjson_parse_error, "json-parse-error");
+    def_lisp_sym!(Qjson_end_of_file, "json-end-of-file");
+    def_lisp_sym!(Qjson_trailing_content, "json-trailing-content");
+    def_lisp_sym!(Qjson_object_too_deep, "json-object-too-deep");
+    define_error(Qjson_error, "generic JSON error", Qerror);
+    define_error(
+        Qjson_out_of_memory,
+        "not enough memory for creating JSON object",
+        Qjson_error,
+    );
+    define_error(
+        Qjson_parse_error,
+        "could not parse JSON stream",
+        Qjson_error,
+    );
+    define_error(Qjson_end_of_file, "end of JSON stream", Qjson_parse_error);
+    define_error(
+        Qjson_trailing_content,
+        "trailing content after JSON stream",
+        Qjson_parse_error,
+    );
+    define_error(
+        Qjson_object_too_deep,
+        "object cyclic or Lisp evaluation too deep",
+        Qjson_error,
+    );
+
+    def_lisp_sym!(Qpure, "pure");
+    def_lisp_sym!(Qside_effect_free, "side-effect-free");
+
+    def_lisp_sym!(Qjson_serialize, "json-serialize");
+    def_lisp_sym!(Qjson_parse_string, "json-parse-string");
+    put(Qjson_serialize.as_symbol_or_error(), Qpure, Qt);
+    put(Qjson_serialize.as_symbol_or_error(), Qside_effect_free, Qt);
+    put(Qjson_parse_string.as_symbol_or_error(), Qpure, Qt);
+    put(
+        Qjson_parse_string.as_symbol_or_error(),
+        Qside_effect_free,
+        Qt,
+    );
+
+    def_lisp_sym!(QCobject_type, ":object-type");
+    def_lisp_sym!(QCarray_type, ":array-type");
+    def_lisp_sym!(QCnull_object, ":null-object");
+    def_lisp_sym!(QCfalse_object, ":false-object");
+    def_lisp_sym!(Qalist, "alist");
+    def_lisp_sym!(Qplist, "plist");
+    def_lisp_sym!(Qarray, "array");
+}
+
+include!(concat!(env!("OUT_DIR"), "/json_exports.rs"));
@@ -26,14 +26,22 @@ extern crate if_chain;
 extern crate lazy_static;
 
 extern crate base64 as base64_crate;
+extern crate blake2;
+extern crate bzip2;
 extern crate libc;
 extern crate md5;
+extern crate num_bigint;
+extern crate num_integer;
+extern crate num_traits;
 extern crate rand;
 extern crate sha1;
 extern crate sha2;
+extern crate sha3;
 
 extern crate field_offset;
 extern crate flate2;
+extern crate xz2;
+extern crate zstd;
 
 extern crate core;
 
@@ -59,8 +67,11 @@ mod frames;
 mod vector_macros;
 mod str2sig;
 
+mod aho_corasick;
 mod alloc;
 mod base64;
+mod bignum;
+mod buffer_text;
 mod buffers;
 mod bytecode;
 mod callint;
@@ -92,6 +103,7 @@ mod fonts;
 mod hashtable;
 mod indent;
 mod interactive;
+mod json;
 mod keyboard;
 mod keymap;
 mod libm;
@@ -106,6 +118,7 @@ mod obarray;
 mod objects;
 mod process;
 mod profiler;
+mod regex;
 #[allow(clippy::all)]
 mod remacs_sys;
 mod search;
@@ -523,7 +523,7 @@ impl LispObject {
     }
 
     pub fn eql(self, other: LispObject) -> bool {
-        if self.is_float() {
+        if self.is_float() || self.is_bignum() {
             self.equal_no_quit(other)
         } else {
             self.eq(other)
@@ -524,7 +524,7 @@ pub fn memq(elt: LispObject, list: LispObject) -> LispObject {
 /// The value is actually the tail of LIST whose car is ELT.
 #[lisp_fn]
 pub fn memql(elt: LispObject, list: LispObject) -> LispObject {
-    if !elt.is_float() {
+    if !elt.is_float() && !elt.is_bignum() {
         return memq(elt, list);
     }
     lookup_member(elt, list, LispObject::eql)
@@ -607,13 +607,169 @@ impl LispBufferRef {
 
 // There are several places in the buffer where we know
 // the correspondence: BEG, BEGV, PT, GPT, ZV and Z,
-// and everywhere there is a marker.  So we find the one of these places
-// that is closest to the specified position, and scan from there.
+// the position cached by the last conversion, and everywhere there is
+// a marker.  If none of these is close to the specified position, we
+// ask the buffer's checkpoint index, which finds a nearby known place
+// by binary search.
+
+/// Number of characters between the checkpoints that a
+/// `CharposIndex` records as it scans.
+const CHECKPOINT_INTERVAL: isize = 4096;
+
+/// A conversion target for `CharposIndex::find`.
+#[derive(Clone, Copy)]
+enum Position {
+    Char(isize),
+    Byte(isize),
+}
+
+impl Position {
+    fn key(self, (charpos, bytepos): (isize, isize)) -> isize {
+        match self {
+            Position::Char(_) => charpos,
+            Position::Byte(_) => bytepos,
+        }
+    }
+
+    fn value(self) -> isize {
+        match self {
+            Position::Char(pos) | Position::Byte(pos) => pos,
+        }
+    }
+}
+
+/// Known pairs of character and byte positions in a buffer's text,
+/// kept in increasing order.  Checkpoints are recorded every
+/// `CHECKPOINT_INTERVAL` characters along each scan, and insertions
+/// and deletions shift or drop them in place, so the index survives
+/// editing.  It lives in the `struct buffer_text`, and so is shared by
+/// indirect buffers.
+struct CharposIndex {
+    points: Vec<(isize, isize)>,
+    /// The size of the text the checkpoints describe.
+    z: isize,
+    z_byte: isize,
+}
+
+impl CharposIndex {
+    fn new(buffer: LispBufferRef) -> Self {
+        CharposIndex {
+            points: vec![(buffer.beg(), buffer.beg_byte())],
+            z: buffer.z(),
+            z_byte: buffer.z_byte(),
+        }
+    }
+
+    /// Return the character and byte positions of TARGET in BUFFER.  A
+    /// byte position inside a character yields one of its ends.
+    fn find(&mut self, buffer: LispBufferRef, target: Position) -> (isize, isize) {
+        let goal = target.value();
+        // The last checkpoint at or before the target; the first one
+        // is at BEG, so there always is one.
+        let i = match self.points.binary_search_by_key(&goal, |&p| target.key(p)) {
+            Ok(i) => return self.points[i],
+            Err(i) => i - 1,
+        };
+        let (charpos, bytepos) = self.points[i];
+
+        if let Some(&(above, above_byte)) = self.points.get(i + 1) {
+            // All single-byte characters in between: interpolate.
+            if above - charpos == above_byte - bytepos {
+                let offset = goal - target.key((charpos, bytepos));
+                return (charpos + offset, bytepos + offset);
+            }
+            // Scan back from the next checkpoint if it is nearer, as
+            // long as that doesn't leave a long stretch unrecorded.
+            if above - charpos <= 2 * CHECKPOINT_INTERVAL
+                && target.key((above, above_byte)) - goal < goal - target.key((charpos, bytepos))
+            {
+                let (mut c, mut b) = (above, above_byte);
+                while target.key((c, b)) > goal {
+                    c -= 1;
+                    b = buffer.dec_pos(b);
+                }
+                return (c, b);
+            }
+        }
+
+        let (mut c, mut b) = (charpos, bytepos);
+        let mut recorded = Vec::new();
+        while target.key((c, b)) < goal {
+            c += 1;
+            b = buffer.inc_pos(b);
+            if c - charpos == CHECKPOINT_INTERVAL * (recorded.len() as isize + 1) {
+                recorded.push((c, b));
+            }
+        }
+        self.points.splice(i + 1..i + 1, recorded);
+        (c, b)
+    }
+
+    /// Account for the replacement of the text from FROM to FROM +
+    /// OLD_CHARS by NEW_CHARS characters.  Checkpoints before the change
+    /// stay, those after it move, and those inside it are dropped.
+    fn adjust(
+        &mut self,
+        from: isize,
+        old_chars: isize,
+        old_bytes: isize,
+        new_chars: isize,
+        new_bytes: isize,
+    ) {
+        let first = match self.points.binary_search_by_key(&from, |p| p.0) {
+            Ok(i) => i + 1,
+            Err(i) => i,
+        };
+        let end = from + old_chars;
+        let last = match self.points.binary_search_by_key(&end, |p| p.0) {
+            Ok(i) | Err(i) => i.max(first),
+        };
+        self.points.drain(first..last);
+        for point in &mut self.points[first..] {
+            point.0 += new_chars - old_chars;
+            point.1 += new_bytes - old_bytes;
+        }
+        // A deletion can bring a checkpoint down onto the one at FROM.
+        self.points.dedup_by_key(|p| p.0);
+        self.z += new_chars - old_chars;
+        self.z_byte += new_bytes - old_bytes;
+    }
+}
+
+/// Return the checkpoint index of BUFFER's text, creating it if need
+/// be.  An index that no longer matches the size of the text is
+/// discarded, in case the text changed behind our back.
+unsafe fn charpos_index<'a>(buffer: LispBufferRef) -> &'a mut CharposIndex {
+    let text = &mut *buffer.text;
+    let stale = !text.charpos_index.is_null() && {
+        let index = &*(text.charpos_index as *const CharposIndex);
+        index.z != text.z || index.z_byte != text.z_byte
+    };
+    if stale {
+        drop(Box::from_raw(text.charpos_index as *mut CharposIndex));
+        text.charpos_index = ptr::null_mut();
+    }
+    if text.charpos_index.is_null() {
+        text.charpos_index = Box::into_raw(Box::new(CharposIndex::new(buffer))) as *mut c_void;
+    }
+    &mut *(text.charpos_index as *mut CharposIndex)
+}
+
+/// Remember CHARPOS and BYTEPOS as the last conversion done in BUFFER.
+fn cache_position(mut buffer: LispBufferRef, charpos: isize, bytepos: isize) {
+    if MARKER_DEBUG {
+        byte_char_debug_check(buffer, charpos, bytepos);
+    }
+    buffer.is_cached = true;
+    buffer.cached_modiff = buffer.modifications();
+    buffer.cached_charpos = charpos;
+    buffer.cached_bytepos = bytepos;
+}
 
 /// Return the byte position corresponding to CHARPOS in B.
 #[no_mangle]
 pub unsafe extern "C" fn buf_charpos_to_bytepos(b: *mut Lisp_Buffer, charpos: isize) -> isize {
-    let mut buffer_ref = LispBufferRef::from_ptr(b as *mut c_void).unwrap();
+    let buffer_ref = LispBufferRef::from_ptr(b as *mut c_void).unwrap();
 
     assert!(buffer_ref.beg() <= charpos && charpos <= buffer_ref.z());
 
@@ -685,55 +841,36 @@ pub unsafe extern "C" fn buf_charpos_to_bytepos(b: *mut Lisp_Buffer, charpos: is
         }
     }
 
-    if charpos - best_below < best_above - charpos {
-        let record = charpos - best_below > 5000;
+    // If the known places are far apart, the checkpoint index
+    // finds one nearby.
+    if best_above - best_below > CHECKPOINT_INTERVAL {
+        let (_, bytepos) = charpos_index(buffer_ref).find(buffer_ref, Position::Char(charpos));
+        cache_position(buffer_ref, charpos, bytepos);
+        return bytepos;
+    }
 
+    // Otherwise scan the intervening chars directly
+    // from whichever known place is closer.
+    if charpos - best_below < best_above - charpos {
         while best_below != charpos {
             best_below += 1;
             best_below_byte = buffer_ref.inc_pos(best_below_byte);
         }
-        if record {
-            build_marker(b, best_below, best_below_byte);
-        }
-        if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
-        }
-
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_below;
-        buffer_ref.cached_bytepos = best_below_byte;
-
+        cache_position(buffer_ref, best_below, best_below_byte);
         best_below_byte
     } else {
-        let record = best_above - charpos > 5000;
-
         while best_above != charpos {
             best_above -= 1;
             best_above_byte = buffer_ref.dec_pos(best_above_byte);
         }
-
-        if record {
-            build_marker(b, best_above, best_above_byte);
-        }
-        if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
-        }
-
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_above;
-        buffer_ref.cached_bytepos = best_above_byte;
-
+        cache_position(buffer_ref, best_above, best_above_byte);
         best_above_byte
     }
 }
 
 #[no_mangle]
 pub unsafe extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: isize) -> isize {
-    let mut buffer_ref = LispBufferRef::from_ptr(b as *mut c_void).unwrap();
+    let buffer_ref = LispBufferRef::from_ptr(b as *mut c_void).unwrap();
 
     assert!(buffer_ref.beg_byte() <= bytepos && bytepos <= buffer_ref.z_byte());
 
@@ -796,72 +933,69 @@ pub unsafe extern "C" fn buf_bytepos_to_charpos(b: *mut Lisp_Buffer, bytepos: is
         }
     }
 
+    // If the known places are far apart, the checkpoint index
+    // finds one nearby.
+    if best_above - best_below > CHECKPOINT_INTERVAL {
+        let (charpos, found_byte) =
+            charpos_index(buffer_ref).find(buffer_ref, Position::Byte(bytepos));
+        cache_position(buffer_ref, charpos, found_byte);
+        return charpos;
+    }
+
     // We get here if we did not exactly hit one of the known places.
     // We have one known above and one known below.
     // Scan, counting characters, from whichever one is closer.
 
     if bytepos - best_below_byte < best_above_byte - bytepos {
-        let record = bytepos - best_below_byte > 5000;
-
         while best_below_byte < bytepos {
             best_below += 1;
             best_below_byte = buffer_ref.inc_pos(best_below_byte);
         }
-
-        // If this position is quite far from the nearest known position,
-        // cache the correspondence by creating a marker here.
-        // It will last until the next GC.
-        // But don't do it if BUF_MARKERS is nil;
-        // that is a signal from Fset_buffer_multibyte.
-        if record && buffer_ref.markers().is_some() {
-            build_marker(b, best_below, best_below_byte);
-        }
-        if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
-        }
-
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_below;
-        buffer_ref.cached_bytepos = best_below_byte;
-
+        cache_position(buffer_ref, best_below, best_below_byte);
         best_below
     } else {
-        let record = best_above_byte - bytepos > 5000;
-
         while best_above_byte > bytepos {
             best_above -= 1;
             best_above_byte = buffer_ref.dec_pos(best_above_byte);
         }
-
-        // If this position is quite far from the nearest known position,
-        // cache the correspondence by creating a marker here.
-        // It will last until the next GC.
-        // But don't do it if BUF_MARKERS is nil;
-        // that is a signal from Fset_buffer_multibyte.
-        if record && buffer_ref.markers().is_some() {
-            build_marker(b, best_below, best_below_byte);
-        }
-        if MARKER_DEBUG {
-            byte_char_debug_check(buffer_ref, best_below, best_below_byte);
-        }
-
-        buffer_ref.is_cached = true;
-        buffer_ref.cached_modiff = buffer_ref.modifications();
-
-        buffer_ref.cached_charpos = best_above;
-        buffer_ref.cached_bytepos = best_above_byte;
-
+        cache_position(buffer_ref, best_above, best_above_byte);
         best_above
     }
 }
 
+/// Update the checkpoint index of B's text for the replacement of
+/// OLD_CHARS characters (OLD_BYTES bytes) at FROM by NEW_CHARS
+/// characters (NEW_BYTES bytes).  An insertion has no old text, and a
+/// deletion no new text.
+#[no_mangle]
+pub unsafe extern "C" fn adjust_charpos_index(
+    b: *mut Lisp_Buffer,
+    from: isize,
+    old_chars: isize,
+    old_bytes: isize,
+    new_chars: isize,
+    new_bytes: isize,
+) {
+    let buffer_ref = LispBufferRef::from_ptr(b as *mut c_void).unwrap();
+    let index = (*buffer_ref.text).charpos_index as *mut CharposIndex;
+    if let Some(index) = index.as_mut() {
+        index.adjust(from, old_chars, old_bytes, new_chars, new_bytes);
+    }
+}
+
+/// Forget the positions cached for B, including its checkpoint index.
 #[no_mangle]
 pub extern "C" fn clear_charpos_cache(b: *mut Lisp_Buffer) {
     let mut buf_ref = LispBufferRef::from_ptr(b as *mut c_void)
         .unwrap_or_else(|| panic!("Invalid buffer reference."));
     buf_ref.is_cached = false;
+    unsafe {
+        let text = &mut *buf_ref.text;
+        if !text.charpos_index.is_null() {
+            drop(Box::from_raw(text.charpos_index as *mut CharposIndex));
+            text.charpos_index = ptr::null_mut();
+        }
+    }
 }
 
 // Debugging
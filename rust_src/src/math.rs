@@ -1,10 +1,17 @@
 //! Functions doing math on numbers.
 #![allow(clippy::float_cmp)]
 
-use crate::remacs_sys::{EmacsInt, Qarith_error, Qnumberp};
+use std::cmp::Ordering;
+
+use num_bigint::BigInt;
+use num_integer::Integer;
+use num_traits::{Signed, Zero};
+
+use crate::remacs_sys::{EmacsInt, Qarith_error, Qinteger_or_marker_p, Qnumberp};
 use remacs_macros::lisp_fn;
 
 use crate::{
+    bignum::{self, bigint_shift},
     floatfns,
     lisp::{defsubr, LispObject},
     numbers::LispNumber,
@@ -31,18 +38,29 @@ pub fn lisp_mod(x: LispNumber, y: LispNumber) -> LispObject {
 
             LispObject::from(i1)
         }
-        (LispNumber::Fixnum(i1), LispNumber::Float(f2)) => {
-            LispObject::from(floatfns::fmod_float(i1 as f64, f2))
+        (LispNumber::Float(_), _) | (_, LispNumber::Float(_)) => {
+            LispObject::from(floatfns::fmod_float(x.to_float(), y.to_float()))
         }
-        (LispNumber::Float(f1), LispNumber::Fixnum(i2)) => {
-            LispObject::from(floatfns::fmod_float(f1, i2 as f64))
-        }
-        (LispNumber::Float(f1), LispNumber::Float(f2)) => {
-            LispObject::from(floatfns::fmod_float(f1, f2))
+        _ => {
+            let (i1, i2) = (integer_value(x), integer_value(y));
+            if i2.is_zero() {
+                xsignal!(Qarith_error);
+            }
+            // `mod_floor' gives the remainder the sign of the divisor.
+            LispObject::from(i1.mod_floor(&i2))
         }
     }
 }
 
+/// Return the value of the integer N, which must not be a float.
+fn integer_value(n: LispNumber) -> BigInt {
+    match n {
+        LispNumber::Fixnum(v) => BigInt::from(v),
+        LispNumber::Bignum(v) => v.value().clone(),
+        LispNumber::Float(v) => wrong_type!(Qinteger_or_marker_p, LispObject::from_float(v)),
+    }
+}
+
 #[repr(C)]
 #[derive(Clone, Copy)]
 pub enum ArithOp {
@@ -61,7 +79,9 @@ pub enum ArithOp {
 /// Given an array of `LispObject`, reduce over them according to the
 /// arithmetic operation specified.
 ///
-/// Modifies the array in place.
+/// The computation is done with fixnums as long as possible; it
+/// switches to bignums on overflow or when it meets a bignum argument,
+/// and to floats when it meets a float argument.
 fn arith_driver(code: ArithOp, args: &[LispObject]) -> LispObject {
     let mut accum: EmacsInt = match code {
         ArithOp::Add | ArithOp::Sub | ArithOp::Logior | ArithOp::Logxor => 0,
@@ -69,86 +89,111 @@ fn arith_driver(code: ArithOp, args: &[LispObject]) -> LispObject {
         _ => 1,
     };
 
-    // TODO: use better variable names rather than just copying the C.
-    let mut overflow = false;
-    let mut ok_accum = accum;
-    let mut ok_args = 0;
-
     for (argnum, &val) in args.iter().enumerate() {
-        if !overflow {
-            ok_args = argnum;
-            ok_accum = accum;
+        let next = match val.as_number_coerce_marker_or_error() {
+            LispNumber::Float(_) => {
+                return LispObject::from(floatfns::float_arith_driver(
+                    accum as f64,
+                    argnum,
+                    code,
+                    args,
+                ));
+            }
+            LispNumber::Bignum(_) => {
+                return bignum_arith_driver(BigInt::from(accum), argnum, code, args);
+            }
+            LispNumber::Fixnum(next) => next,
+        };
+
+        let result = match code {
+            ArithOp::Add => accum.checked_add(next),
+            ArithOp::Sub => {
+                if argnum == 0 {
+                    // Calling - with one argument negates it.
+                    Some(if args.len() == 1 { -next } else { next })
+                } else {
+                    accum.checked_sub(next)
+                }
+            }
+            ArithOp::Mult => accum.checked_mul(next),
+            ArithOp::Div => {
+                // If we have multiple arguments, we divide the first
+                // argument by all the others.
+                if args.len() > 1 && argnum == 0 {
+                    Some(next)
+                } else {
+                    if next == 0 {
+                        xsignal!(Qarith_error);
+                    }
+                    accum.checked_div(next)
+                }
+            }
+            ArithOp::Logand => Some(accum & next),
+            ArithOp::Logior => Some(accum | next),
+            ArithOp::Logxor => Some(accum ^ next),
+        };
+
+        match result {
+            Some(result) => accum = result,
+            None => return bignum_arith_driver(BigInt::from(accum), argnum, code, args),
         }
+    }
 
-        match val.as_number_coerce_marker_or_error() {
+    LispObject::from_integer(accum)
+}
+
+/// Continue the computation of `arith_driver' with bignums, from the
+/// argument ARGSTART on, with ACCUM the result so far.
+fn bignum_arith_driver(
+    mut accum: BigInt,
+    argstart: usize,
+    code: ArithOp,
+    args: &[LispObject],
+) -> LispObject {
+    for (i, &val) in args[argstart..].iter().enumerate() {
+        let argnum = argstart + i;
+        let next = match val.as_number_coerce_marker_or_error() {
             LispNumber::Float(_) => {
                 return LispObject::from(floatfns::float_arith_driver(
-                    ok_accum as f64,
-                    ok_args,
+                    bignum::bigint_to_float(&accum),
+                    argnum,
                     code,
                     args,
                 ));
             }
-            LispNumber::Fixnum(next) => {
-                match code {
-                    ArithOp::Add => {
-                        if accum.checked_add(next).is_none() {
-                            overflow = true;
-                        }
-                        accum = accum.wrapping_add(next);
-                    }
-                    ArithOp::Sub => {
-                        if argnum == 0 {
-                            if args.len() == 1 {
-                                // Calling - with one argument negates it.
-                                accum = -next;
-                            } else {
-                                accum = next;
-                            }
-                        } else {
-                            if accum.checked_sub(next).is_none() {
-                                overflow = true;
-                            }
-                            accum = accum.wrapping_sub(next);
-                        }
-                    }
-                    ArithOp::Mult => {
-                        if accum.checked_mul(next).is_none() {
-                            overflow = true;
-                        }
-                        accum = accum.wrapping_mul(next);
-                    }
-                    ArithOp::Div => {
-                        // If we have multiple arguments, we divide the first
-                        // argument by all the others.
-                        if args.len() > 1 && argnum == 0 {
-                            accum = next;
-                        } else {
-                            if next == 0 {
-                                xsignal!(Qarith_error);
-                            }
-                            if accum.checked_div(next).is_none() {
-                                overflow = true;
-                            } else {
-                                accum = accum.wrapping_div(next);
-                            }
-                        }
-                    }
-                    ArithOp::Logand => {
-                        accum &= next;
-                    }
-                    ArithOp::Logior => {
-                        accum |= next;
-                    }
-                    ArithOp::Logxor => {
-                        accum ^= next;
+            LispNumber::Bignum(b) => b.value().clone(),
+            LispNumber::Fixnum(n) => BigInt::from(n),
+        };
+
+        accum = match code {
+            ArithOp::Add => accum + next,
+            ArithOp::Sub => {
+                if argnum > 0 {
+                    accum - next
+                } else if args.len() == 1 {
+                    -next
+                } else {
+                    next
+                }
+            }
+            ArithOp::Mult => accum * next,
+            ArithOp::Div => {
+                if args.len() > 1 && argnum == 0 {
+                    next
+                } else {
+                    if next.is_zero() {
+                        xsignal!(Qarith_error);
                     }
+                    accum / next
                 }
             }
-        }
+            ArithOp::Logand => accum & next,
+            ArithOp::Logior => accum | next,
+            ArithOp::Logxor => accum ^ next,
+        };
     }
 
-    LispObject::from_fixnum_truncated(accum)
+    LispObject::from(accum)
 }
 
 /// Return sum of any number of arguments, which are numbers or markers.
@@ -254,7 +299,9 @@ pub fn abs(arg: LispObject) -> LispObject {
     if let Some(f) = arg.as_float() {
         LispObject::from_float(f.abs())
     } else if let Some(n) = arg.as_fixnum() {
-        LispObject::from(n.abs())
+        LispObject::from_integer(n.abs())
+    } else if let Some(b) = arg.as_bignum() {
+        LispObject::from(b.value().abs())
     } else {
         wrong_type!(Qnumberp, arg);
     }
@@ -279,10 +326,43 @@ pub fn arithcompare(obj1: LispObject, obj2: LispObject, comparison: ArithCompari
     // ties if the floating-point comparison is either not done or reports
     // equality.
 
-    let (i1, i2, f1, f2) = match (
+    let (n1, n2) = (
         obj1.as_number_coerce_marker_or_error(),
         obj2.as_number_coerce_marker_or_error(),
-    ) {
+    );
+
+    // Bignums are compared exactly, against integers and floats alike.
+    let bignum_order = match (n1, n2) {
+        (LispNumber::Bignum(b), LispNumber::Float(f)) => {
+            Some(bignum::compare_bigint_float(b.value(), f))
+        }
+        (LispNumber::Float(f), LispNumber::Bignum(b)) => {
+            Some(bignum::compare_bigint_float(b.value(), f).map(Ordering::reverse))
+        }
+        (LispNumber::Bignum(_), _) | (_, LispNumber::Bignum(_)) => {
+            Some(Some(integer_value(n1).cmp(&integer_value(n2))))
+        }
+        _ => None,
+    };
+    if let Some(order) = bignum_order {
+        return match order {
+            // A NaN is unordered with respect to everything.
+            None => match comparison {
+                ArithComparison::Notequal => true,
+                _ => false,
+            },
+            Some(order) => match comparison {
+                ArithComparison::Equal => order == Ordering::Equal,
+                ArithComparison::Notequal => order != Ordering::Equal,
+                ArithComparison::Less => order == Ordering::Less,
+                ArithComparison::LessOrEqual => order != Ordering::Greater,
+                ArithComparison::Grtr => order == Ordering::Greater,
+                ArithComparison::GrtrOrEqual => order != Ordering::Less,
+            },
+        };
+    }
+
+    let (i1, i2, f1, f2) = match (n1, n2) {
         (LispNumber::Fixnum(n1), LispNumber::Fixnum(n2)) => (n1, n2, 0., 0.),
         (LispNumber::Fixnum(n1), LispNumber::Float(n2)) => {
             // Compare an integer NUM1 to a float NUM2.  This is the
@@ -301,6 +381,7 @@ pub fn arithcompare(obj1: LispObject, obj2: LispObject, comparison: ArithCompari
             (n2 as f64 as EmacsInt, n2, n1, n2 as f64)
         }
         (LispNumber::Float(n1), LispNumber::Float(n2)) => (0, 0, n1, n2),
+        _ => unreachable!(),
     };
     let fneq = f1 != f2;
 
@@ -387,41 +468,67 @@ pub fn neq(num1: LispObject, num2: LispObject) -> bool {
 /// Return remainder of X divided by Y.
 /// Both must be integers or markers.
 #[lisp_fn(name = "%")]
-pub fn rem(x: LispNumber, y: LispNumber) -> EmacsInt {
-    let x = x.to_fixnum();
-    let y = y.to_fixnum();
+pub fn rem(x: LispNumber, y: LispNumber) -> LispObject {
+    if let (LispNumber::Fixnum(x), LispNumber::Fixnum(y)) = (x, y) {
+        if y == 0 {
+            xsignal!(Qarith_error);
+        }
+        return LispObject::from(x % y);
+    }
 
-    if y == 0 {
+    let (x, y) = (integer_value(x), integer_value(y));
+    if y.is_zero() {
         xsignal!(Qarith_error);
     }
-
-    x % y
+    LispObject::from(x % y)
 }
 
 /// Return NUMBER plus one.  NUMBER may be a number or a marker.
 /// Markers are converted to integers.
 #[lisp_fn(name = "1+")]
-pub fn add1(number: LispNumber) -> LispNumber {
+pub fn add1(number: LispNumber) -> LispObject {
     match number {
-        LispNumber::Fixnum(num) => LispNumber::Fixnum(num + 1),
-        LispNumber::Float(num) => LispNumber::Float(num + 1.0),
+        LispNumber::Fixnum(num) => LispObject::from_integer(num + 1),
+        LispNumber::Bignum(num) => LispObject::from(num.value() + BigInt::from(1)),
+        LispNumber::Float(num) => LispObject::from_float(num + 1.0),
     }
 }
 
 /// Return NUMBER minus one.  NUMBER may be a number or a marker.
 /// Markers are converted to integers.
 #[lisp_fn(name = "1-")]
-pub fn sub1(number: LispNumber) -> LispNumber {
+pub fn sub1(number: LispNumber) -> LispObject {
     match number {
-        LispNumber::Fixnum(num) => LispNumber::Fixnum(num - 1),
-        LispNumber::Float(num) => LispNumber::Float(num - 1.0),
+        LispNumber::Fixnum(num) => LispObject::from_integer(num - 1),
+        LispNumber::Bignum(num) => LispObject::from(num.value() - BigInt::from(1)),
+        LispNumber::Float(num) => LispObject::from_float(num - 1.0),
     }
 }
 
 /// Return the bitwise complement of NUMBER.  NUMBER must be an integer.
 #[lisp_fn]
-pub fn lognot(number: EmacsInt) -> EmacsInt {
-    !number
+pub fn lognot(number: LispObject) -> LispObject {
+    if let Some(n) = number.as_fixnum() {
+        LispObject::from(!n)
+    } else {
+        LispObject::from(-number.as_bigint_or_error() - BigInt::from(1))
+    }
+}
+
+/// Return VALUE with its bits shifted left by COUNT.
+/// If COUNT is negative, shifting is actually to the right.
+/// In this case, the sign bit is duplicated.
+#[lisp_fn]
+pub fn ash(value: LispObject, count: EmacsInt) -> LispObject {
+    if let Some(n) = value.as_fixnum() {
+        // Shift fixnums directly as long as no bits are lost.
+        if count <= 0 {
+            return LispObject::from(n >> count.checked_neg().map_or(63, |c| c.min(63)));
+        } else if count < 63 && (n << count) >> count == n {
+            return LispObject::from_integer(n << count);
+        }
+    }
+    LispObject::from(bigint_shift(&value.as_bigint_or_error(), count))
 }
 
 include!(concat!(env!("OUT_DIR"), "/math_exports.rs"));
@@ -40,6 +40,7 @@ use libc::{c_char, c_int, c_uchar, c_uint, c_void, memset, ptrdiff_t, size_t};
 
 use crate::{
     lisp::{ExternalPtr, LispObject},
+    numbers::MOST_POSITIVE_FIXNUM,
     remacs_sys::Qstringp,
     remacs_sys::{char_bits, equal_kind, EmacsDouble, EmacsInt, Lisp_String, Lisp_Type},
     remacs_sys::{compare_string_intervals, empty_unibyte_string},
@@ -238,7 +239,6 @@ impl LispStringRef {
         }
     }
 
-    #[allow(dead_code)]
     pub fn chars(&self) -> LispStringRefCharIterator {
         LispStringRefCharIterator(self.char_indices())
     }
@@ -300,6 +300,14 @@ pub fn is_single_byte_char(c: Codepoint) -> bool {
     c < 0x100
 }
 
+/// The largest number of bytes a string may have.
+///
+/// Same as the `STRING_BYTES_BOUND` macro.
+pub fn string_bytes_bound() -> EmacsInt {
+    let pointer_bound = (isize::max_value() as EmacsInt).saturating_sub(1);
+    MOST_POSITIVE_FIXNUM.min(pointer_bound)
+}
+
 fn string_overflow() -> ! {
     error!("Maximum string size exceeded")
 }
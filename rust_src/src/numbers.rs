@@ -1,15 +1,23 @@
 //! Functions operating on numbers.
 
+use num_bigint::{BigInt, Sign};
+use num_traits::Signed;
 use rand::{Rng, SeedableRng, StdRng};
+use std::ptr;
 use std::sync::Mutex;
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    bignum::LispBignumRef,
     lisp::defsubr,
-    lisp::LispObject,
+    lisp::{ExternalPtr, LispObject},
+    multibyte::string_bytes_bound,
+    remacs_sys::{make_uninit_string, pvec_type, vectorlike_header, Lisp_Vector},
     remacs_sys::{EmacsInt, EmacsUint, Lisp_Bits, Lisp_Type, EMACS_INT_MAX, INTMASK, USE_LSB_TAG},
-    remacs_sys::{Qinteger_or_marker_p, Qintegerp, Qnumber_or_marker_p, Qwholenump},
+    remacs_sys::{
+        Qinteger_or_marker_p, Qintegerp, Qnumber_or_marker_p, Qrandom_state_p, Qwholenump,
+    },
 };
 
 lazy_static! {
@@ -127,15 +135,15 @@ impl LispObject {
         }
     }
 
-    /// TODO: Bignum support? (Current Emacs doesn't have it)
     pub fn is_integer(self) -> bool {
-        self.is_fixnum()
+        self.is_fixnum() || self.is_bignum()
     }
 }
 
 #[derive(Clone, Copy)]
 pub enum LispNumber {
     Fixnum(EmacsInt),
+    Bignum(LispBignumRef),
     Float(f64),
 }
 
@@ -152,12 +160,28 @@ impl IsLispNatnum for EmacsInt {
 }
 
 impl LispNumber {
+    /// Convert to a fixnum, truncating floats.  This is used for buffer
+    /// positions, which a bignum can never be, so bignums signal
+    /// `args-out-of-range'.
     pub fn to_fixnum(&self) -> EmacsInt {
         match *self {
             LispNumber::Fixnum(v) => v,
+            LispNumber::Bignum(v) => args_out_of_range!(
+                LispObject::from(v),
+                LispObject::from(MOST_NEGATIVE_FIXNUM),
+                LispObject::from(MOST_POSITIVE_FIXNUM)
+            ),
             LispNumber::Float(v) => v as EmacsInt,
         }
     }
+
+    pub fn to_float(&self) -> f64 {
+        match *self {
+            LispNumber::Fixnum(v) => v as f64,
+            LispNumber::Bignum(v) => v.to_float(),
+            LispNumber::Float(v) => v,
+        }
+    }
 }
 
 impl From<EmacsInt> for LispNumber {
@@ -182,6 +206,7 @@ impl From<LispNumber> for LispObject {
     fn from(n: LispNumber) -> LispObject {
         match n {
             LispNumber::Fixnum(v) => v.into(),
+            LispNumber::Bignum(v) => v.into(),
             LispNumber::Float(v) => LispObject::from_float(v),
         }
     }
@@ -189,7 +214,7 @@ impl From<LispNumber> for LispObject {
 
 impl LispObject {
     pub fn is_number(self) -> bool {
-        self.is_fixnum() || self.is_float()
+        self.is_integer() || self.is_float()
     }
 
     /*
@@ -207,6 +232,8 @@ impl LispObject {
     pub fn as_number_coerce_marker(self) -> Option<LispNumber> {
         if let Some(n) = self.as_fixnum() {
             Some(LispNumber::Fixnum(n))
+        } else if let Some(b) = self.as_bignum() {
+            Some(LispNumber::Bignum(b))
         } else if let Some(f) = self.as_float() {
             Some(LispNumber::Float(f))
         } else if let Some(m) = self.as_marker() {
@@ -244,6 +271,9 @@ pub fn integer_or_marker_p(object: LispObject) -> bool {
 #[lisp_fn]
 pub fn natnump(object: LispObject) -> bool {
     object.is_natnum()
+        || object
+            .as_bignum()
+            .map_or(false, |b| b.value().is_positive())
 }
 
 /// Return t if OBJECT is a number (floating point or integer).
@@ -258,43 +288,216 @@ pub fn number_or_marker_p(object: LispObject) -> bool {
     object.is_number() || object.is_marker()
 }
 
+/// A random number generator, as returned by `make-random-state'.
+#[repr(C)]
+pub struct Lisp_Random_State {
+    header: vectorlike_header,
+    /// The generator, owned by this object and freed by
+    /// `finalize_random_state'.
+    rng: *mut StdRng,
+}
+
+pub type LispRandomStateRef = ExternalPtr<Lisp_Random_State>;
+
+impl LispRandomStateRef {
+    fn allocate(rng: StdRng) -> Self {
+        let ptr = allocate_pseudovector!(Lisp_Random_State, rng, pvec_type::PVEC_RANDOM_STATE);
+        let mut state = LispRandomStateRef::new(ptr);
+        state.rng = Box::into_raw(Box::new(rng));
+        state
+    }
+
+    fn rng(&mut self) -> &mut StdRng {
+        unsafe { &mut *self.rng }
+    }
+}
+
+impl LispObject {
+    pub fn is_random_state(self) -> bool {
+        self.as_vectorlike()
+            .map_or(false, |v| v.is_pseudovector(pvec_type::PVEC_RANDOM_STATE))
+    }
+
+    pub fn as_random_state_or_error(self) -> LispRandomStateRef {
+        if self.is_random_state() {
+            LispRandomStateRef::new(self.get_untaggedptr() as *mut Lisp_Random_State)
+        } else {
+            wrong_type!(Qrandom_state_p, self);
+        }
+    }
+}
+
+impl From<LispObject> for LispRandomStateRef {
+    fn from(o: LispObject) -> Self {
+        o.as_random_state_or_error()
+    }
+}
+
+impl From<LispObject> for Option<LispRandomStateRef> {
+    fn from(o: LispObject) -> Self {
+        if o.is_nil() {
+            None
+        } else {
+            Some(o.as_random_state_or_error())
+        }
+    }
+}
+
+impl From<LispRandomStateRef> for LispObject {
+    fn from(state: LispRandomStateRef) -> Self {
+        LispObject::tag_ptr(state, Lisp_Type::Lisp_Vectorlike)
+    }
+}
+
+/// Free the generator of a random state being garbage collected.
+#[no_mangle]
+pub extern "C" fn finalize_random_state(vector: *mut Lisp_Vector) {
+    let mut state = LispRandomStateRef::new(vector as *mut Lisp_Random_State);
+    if !state.rng.is_null() {
+        unsafe { Box::from_raw(state.rng) };
+        state.rng = ptr::null_mut();
+    }
+}
+
+/// Run F on the generator of STATE, or on the global generator used by
+/// `random' if STATE is `None`.
+fn with_rng<F, R>(state: Option<LispRandomStateRef>, f: F) -> R
+where
+    F: FnOnce(&mut StdRng) -> R,
+{
+    match state {
+        Some(mut state) => f(state.rng()),
+        None => f(&mut RNG.lock().unwrap()),
+    }
+}
+
+/// Return a generator seeded from SEED, which is a string or an
+/// integer, or `None` if SEED is neither.
+fn seeded_rng(seed: LispObject) -> Option<StdRng> {
+    let values: Vec<usize> = if let Some(s) = seed.as_string() {
+        s.as_slice().iter().map(|&x| x as usize).collect()
+    } else if let Some(n) = seed.as_bigint() {
+        let (sign, bytes) = n.to_bytes_le();
+        bytes
+            .iter()
+            .map(|&x| x as usize)
+            .chain(Some(sign as usize))
+            .collect()
+    } else {
+        return None;
+    };
+    Some(StdRng::from_seed(&values[..]))
+}
+
 /// Return a pseudo-random number.
 /// All integers representable in Lisp, i.e. between `most-negative-fixnum'
 /// and `most-positive-fixnum', inclusive, are equally likely.
 ///
 /// With positive integer LIMIT, return random number in interval [0,LIMIT).
+/// LIMIT may be a bignum.
 /// With argument t, set the random number seed from the system's entropy
 /// pool if available, otherwise from less-random volatile data such as the time.
 /// With a string argument, set the seed based on the string's contents.
 /// Other values of LIMIT are ignored.
 ///
+/// If STATE is a random state made by `make-random-state', draw from
+/// it, and reseed it rather than the global generator, instead.
+///
 /// See Info node `(elisp)Random Numbers' for more details.
 // NOTE(db48x): does not return an EmacsInt, because it relies on the
 // truncating behavior of from_fixnum_truncated.
 #[lisp_fn(min = "0")]
-pub fn random(limit: LispObject) -> LispObject {
-    let mut rng = RNG.lock().unwrap();
-    if limit.is_t() {
-        *rng = StdRng::new().unwrap();
-    } else if let Some(s) = limit.as_string() {
-        let values: Vec<usize> = s.as_slice().iter().map(|&x| x as usize).collect();
-        rng.reseed(&values);
-    }
-
-    if let Some(limit) = limit.as_fixnum() {
-        // Return the remainder, except reject the rare case where
-        // get_random returns a number so close to INTMASK that the
-        // remainder isn't random.
-        loop {
-            let val: EmacsInt = rng.gen();
-            let remainder = val.abs() % limit;
-            if val - remainder <= INTMASK - limit + 1 {
-                return LispObject::from(remainder);
+pub fn random(limit: LispObject, state: Option<LispRandomStateRef>) -> LispObject {
+    with_rng(state, |rng| {
+        if limit.is_t() {
+            *rng = StdRng::new().unwrap();
+        } else if limit.is_string() {
+            *rng = seeded_rng(limit).unwrap();
+        }
+
+        if let Some(limit) = limit.as_fixnum().filter(|&limit| limit > 0) {
+            // Return the remainder, except reject the rare case where
+            // get_random returns a number so close to INTMASK that the
+            // remainder isn't random.
+            loop {
+                let val: EmacsInt = rng.gen();
+                let remainder = val.abs() % limit;
+                if val - remainder <= INTMASK - limit + 1 {
+                    return LispObject::from(remainder);
+                }
             }
+        } else if let Some(limit) = limit.as_bignum().filter(|b| b.value().is_positive()) {
+            // Draw 64 more random bits than LIMIT has, so that the bias of
+            // the remainder is negligible.
+            let limit = limit.value();
+            let nbytes = (limit.bits() + 64 + 7) / 8;
+            let bytes: Vec<u8> = (0..nbytes).map(|_| rng.gen()).collect();
+            LispObject::from(BigInt::from_bytes_le(Sign::Plus, &bytes) % limit)
+        } else {
+            LispObject::from_fixnum_truncated(rng.gen())
         }
+    })
+}
+
+/// Return a new random state, for use as the STATE argument of `random'.
+/// If SEED is a string or an integer, the state is seeded from it, so
+/// that two states made from the same seed produce the same numbers;
+/// a string seed gives the same numbers as `random' reseeded with it.
+/// If SEED is nil or t, the state is seeded from the system's entropy
+/// pool.
+#[lisp_fn(min = "0")]
+pub fn make_random_state(seed: LispObject) -> LispRandomStateRef {
+    let rng = if seed.is_nil() || seed.is_t() {
+        StdRng::new().unwrap()
     } else {
-        LispObject::from_fixnum_truncated(rng.gen())
+        seeded_rng(seed).unwrap_or_else(|| wrong_type!(Qintegerp, seed))
+    };
+    LispRandomStateRef::allocate(rng)
+}
+
+/// Return a copy of the random state STATE.
+/// The copy produces the same numbers as STATE from this point on, but
+/// drawing from one doesn't advance the other.  If STATE is nil, copy
+/// the global generator used by `random'.
+#[lisp_fn(min = "0")]
+pub fn random_state_copy(state: Option<LispRandomStateRef>) -> LispRandomStateRef {
+    LispRandomStateRef::allocate(with_rng(state, |rng| rng.clone()))
+}
+
+/// Return t if OBJECT is a random state.
+#[lisp_fn]
+pub fn random_state_p(object: LispObject) -> bool {
+    object.is_random_state()
+}
+
+/// Return a pseudo-random float in the interval [0.0,1.0).
+/// Draw from the random state STATE if it is non-nil, and from the
+/// global generator used by `random' otherwise.
+#[lisp_fn(min = "0")]
+pub fn random_float(state: Option<LispRandomStateRef>) -> f64 {
+    with_rng(state, |rng| rng.gen())
+}
+
+/// Return a unibyte string of N pseudo-random bytes.
+/// Draw from the random state STATE if it is non-nil, and from the
+/// global generator used by `random' otherwise.
+#[lisp_fn(min = "1")]
+pub fn random_bytes(n: EmacsUint, state: Option<LispRandomStateRef>) -> LispObject {
+    let n = n as EmacsInt;
+    if n > string_bytes_bound() {
+        args_out_of_range!(
+            LispObject::from(n),
+            LispObject::from(0),
+            LispObject::from(string_bytes_bound())
+        );
     }
+    // Fill the string in place, so that a failure to allocate it
+    // signals `memory-full' rather than aborting.
+    let string = unsafe { make_uninit_string(n) };
+    with_rng(state, |rng| {
+        rng.fill_bytes(string.as_string_or_error().as_mut_slice())
+    });
+    string
 }
 
 include!(concat!(env!("OUT_DIR"), "/numbers_exports.rs"));
@@ -1,25 +1,55 @@
 //! Functions operating on process.
-use libc;
+use std::ffi::{OsStr, OsString};
+use std::mem;
+use std::os::unix::ffi::{OsStrExt, OsStringExt};
+use std::os::unix::io::FromRawFd;
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
 
+use libc::{self, c_char, c_int, c_uint, pid_t, ptrdiff_t, timespec};
+
+use remacs_lib::current_timespec;
 use remacs_macros::lisp_fn;
 
 use crate::{
     buffers::{current_buffer, get_buffer, LispBufferOrName, LispBufferRef},
+    eval::unbind_to,
+    frames::selected_frame,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject},
-    lists::{assoc, car, cdr, plist_put},
+    lists::{assoc, car, cdr, list, plist_get, plist_put},
+    marker::set_marker_both,
     multibyte::LispStringRef,
     remacs_sys::{
-        add_process_read_fd, current_thread, delete_read_fd, emacs_get_tty_pgrp,
-        get_process as cget_process, send_process, setup_process_coding_systems, update_status,
-        Fmapcar, STRING_BYTES,
+        add_process_read_fd, allocate_pty, block_child_signal, build_string, chan_process,
+        child_setup_tty, code_convert_string_norecord, complement_process_encoding_system, concat2,
+        create_pty, current_thread, delete_read_fd, egetenv_internal, emacs_close,
+        emacs_get_tty_pgrp, emacs_open, emacs_pipe, empty_unibyte_string, encode_current_directory,
+        encode_file_name, get_process as cget_process, globals,
+        make_process as make_process_object, openp, record_unwind_protect, remove_process,
+        remove_slash_colon, report_file_errno, report_file_error, restore_nofile_limit,
+        send_process, setup_process_coding_systems, sigset_t, timespec_sub, unblock_child_signal,
+        update_status, Fcopy_sequence, Fexpand_file_name, Ffile_directory_p,
+        Ffind_operation_coding_system, Fframe_parameter, Fget_buffer_create, Fgetenv_internal,
+        Fmake_pipe_process, Fmapcar, Fnreverse, STRING_BYTES,
+    },
+    remacs_sys::{
+        process_open_fd::{
+            READ_FROM_SUBPROCESS, SUBPROCESS_STDIN, SUBPROCESS_STDOUT, WRITE_TO_SUBPROCESS,
+        },
+        pty_name_size::PTY_NAME_SIZE,
     },
     remacs_sys::{pvec_type, EmacsInt, Lisp_Process, Lisp_Type, Vprocess_alist},
     remacs_sys::{
-        QCbuffer, QCfilter, QCsentinel, Qcdr, Qclosed, Qexit, Qinternal_default_process_filter,
-        Qinternal_default_process_sentinel, Qlisten, Qlistp, Qnetwork, Qnil, Qopen, Qpipe,
-        Qprocessp, Qreal, Qrun, Qserial, Qstop, Qt,
+        QCbuffer, QCcoding, QCcommand, QCconnection_type, QCcore_dumped, QCexit_code, QCfilter,
+        QCname, QCnoquery, QCsentinel, QCsignal, QCsignal_name, QCsignaled, QCstderr, QCstop, Qcdr,
+        Qclosed, Qdisplay, Qetime, Qexit, Qinternal_default_process_filter,
+        Qinternal_default_process_sentinel, Qlisten, Qlistp, Qmaxrss, Qnetwork, Qnil, Qopen, Qpipe,
+        Qprocessp, Qpty, Qreal, Qrun, Qserial, Qsignal, Qstart_process, Qstime, Qstop, Qt, Qutime,
     },
+    str2sig::sig2str,
+    threads::c_specpdl_index,
+    time::make_lisp_time,
 };
 
 pub type LispProcessRef = ExternalPtr<Lisp_Process>;
@@ -170,7 +200,7 @@ pub fn process_id(process: LispProcessRef) -> Option<EmacsInt> {
 /// BUFFER may be a buffer or the name of one.
 /// Return nil if all processes associated with BUFFER have been
 /// deleted or killed.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn get_buffer_process(buffer_or_name: Option<LispBufferOrName>) -> LispObject {
     get_buffer_process_internal(buffer_or_name.and_then(|b| b.into()))
 }
@@ -496,6 +526,106 @@ pub fn process_exit_status(mut process: LispProcessRef) -> LispObject {
         .map_or_else(|| LispObject::from(0), |cons| car(cons.cdr()))
 }
 
+fn timeval_to_timespec(tv: libc::timeval) -> timespec {
+    timespec {
+        tv_sec: tv.tv_sec,
+        tv_nsec: tv.tv_usec * 1000,
+    }
+}
+
+/// Return the resources used by PROCESS, once it has terminated.
+/// The value is an alist of the following attributes, in the same
+/// format as `process-attributes':
+///
+///  utime  -- user time used by the process, in (current-time) format
+///  stime  -- system time used by the process, in (current-time) format
+///  maxrss -- maximum resident set size of the process, in KB
+///  etime  -- wall clock time elapsed between the start of the process
+///            and its termination, in (current-time) format
+///
+/// Return nil if PROCESS is not a subprocess or has not terminated yet.
+#[lisp_fn]
+pub fn process_resource_usage(mut process: LispProcessRef) -> LispObject {
+    if process.raw_status_new() {
+        unsafe { update_status(process.as_mut()) };
+    }
+    if !process.ptype().eq(Qreal) || process.alive() || process.end_time.tv_sec == 0 {
+        return Qnil;
+    }
+    let usage = process.rusage;
+    let elapsed = unsafe { timespec_sub(process.end_time, process.start_time) };
+    list(&[
+        LispObject::cons(Qutime, make_lisp_time(timeval_to_timespec(usage.ru_utime))),
+        LispObject::cons(Qstime, make_lisp_time(timeval_to_timespec(usage.ru_stime))),
+        LispObject::cons(Qmaxrss, EmacsInt::from(usage.ru_maxrss).into()),
+        LispObject::cons(Qetime, make_lisp_time(elapsed)),
+    ])
+}
+
+/// Return details about how PROCESS terminated, as a plist.
+/// The plist has the following properties:
+///
+///  :exit-code   -- the exit status of the process, or nil if it was
+///                  killed by a signal
+///  :signaled    -- non-nil if the process was killed by a signal
+///  :signal      -- the number of that signal, or nil
+///  :signal-name -- the name of that signal without the "SIG" prefix,
+///                  such as "KILL", or nil if it is not known
+///  :core-dumped -- non-nil if the process dumped core
+///
+/// Return nil if PROCESS is not a subprocess or has neither exited nor
+/// been killed by a signal.
+#[lisp_fn]
+pub fn process_exit_details(mut process: LispProcessRef) -> LispObject {
+    if !process.ptype().eq(Qreal) {
+        return Qnil;
+    }
+    if process.raw_status_new() {
+        unsafe { update_status(process.as_mut()) };
+    }
+    // The status of a terminated process is (exit CODE . CORE-DUMPED)
+    // or (signal SIGNUM . CORE-DUMPED); see `status_convert'.
+    let (symbol, detail) = match process.status.as_cons() {
+        Some(status) => (status.car(), status.cdr()),
+        None => return Qnil,
+    };
+    let (code, core_dumped) = match detail.as_cons() {
+        Some(detail) => (detail.car(), detail.cdr()),
+        None => return Qnil,
+    };
+    if symbol.eq(Qexit) {
+        list(&[
+            QCexit_code,
+            code,
+            QCsignaled,
+            Qnil,
+            QCsignal,
+            Qnil,
+            QCsignal_name,
+            Qnil,
+            QCcore_dumped,
+            core_dumped,
+        ])
+    } else if symbol.eq(Qsignal) {
+        let name = sig2str(code.as_fixnum_or_error() as c_int)
+            .map_or(Qnil, |name| LispObject::from(name.as_str()));
+        list(&[
+            QCexit_code,
+            Qnil,
+            QCsignaled,
+            Qt,
+            QCsignal,
+            code,
+            QCsignal_name,
+            name,
+            QCcore_dumped,
+            core_dumped,
+        ])
+    } else {
+        Qnil
+    }
+}
+
 /// Return non-nil if PROCESS has given the terminal to a
 /// child.  If the operating system does not make it possible to find out,
 /// return t.  If we can find out, return the numeric ID of the foreground
@@ -531,4 +661,577 @@ pub fn process_running_child_p(mut process: LispObject) -> LispObject {
     }
 }
 
+// Starting asynchronous inferior processes.
+
+/// If PROC doesn't have its pid set, then an error was signaled and
+/// the process wasn't started successfully, so remove it.
+extern "C" fn start_process_unwind(proc: LispObject) {
+    let pid = proc.as_process_or_error().pid;
+    if pid <= 0 && pid != -2 {
+        unsafe { remove_process(proc) };
+    }
+}
+
+/// If FD is nonnegative, close it, and mark it as closed.
+fn close_process_fd(fd: &mut c_int) {
+    if *fd >= 0 {
+        let old = mem::replace(fd, -1);
+        unsafe { emacs_close(old) };
+    }
+}
+
+fn adaptive_read_buffering() -> c_uint {
+    let buffering = unsafe { globals.Vprocess_adaptive_read_buffering };
+    if buffering.is_nil() {
+        0
+    } else if buffering.eq(Qt) {
+        1
+    } else {
+        2
+    }
+}
+
+/// Decide the coding systems for communicating with a new process.
+/// CODING is its `:coding' argument.  OPERATION is the operation and
+/// its arguments to pass to `find-operation-coding-system' if CODING
+/// and `coding-system-for-read' or `coding-system-for-write' don't
+/// decide; if it is empty, use `default-process-coding-system'.
+/// Return (DECODING . ENCODING).
+///
+/// Here we don't setup the structure coding_system nor pay attention
+/// to unibyte mode.  They are done in `create_process'.
+pub(crate) fn decide_process_coding_systems(
+    coding: LispObject,
+    operation: &mut [LispObject],
+) -> (LispObject, LispObject) {
+    let mut find_operation_coding_system = || {
+        if operation.is_empty() {
+            return Qt;
+        }
+        unsafe {
+            Ffind_operation_coding_system(operation.len() as ptrdiff_t, operation.as_mut_ptr())
+        }
+    };
+    let default_process_coding_system = unsafe { globals.Vdefault_process_coding_system };
+
+    // t denotes we have not yet called `find-operation-coding-system'.
+    let mut coding_systems = Qt;
+
+    let mut decoding = if coding.is_not_nil() {
+        coding.as_cons().map_or(coding, |c| c.car())
+    } else {
+        unsafe { globals.Vcoding_system_for_read }
+    };
+    if decoding.is_nil() {
+        coding_systems = find_operation_coding_system();
+        if let Some(c) = coding_systems.as_cons() {
+            decoding = c.car();
+        } else if let Some(c) = default_process_coding_system.as_cons() {
+            decoding = c.car();
+        }
+    }
+
+    let mut encoding = if coding.is_not_nil() {
+        coding.as_cons().map_or(coding, |c| c.cdr())
+    } else {
+        unsafe { globals.Vcoding_system_for_write }
+    };
+    if encoding.is_nil() {
+        if coding_systems.eq(Qt) {
+            coding_systems = find_operation_coding_system();
+        }
+        if let Some(c) = coding_systems.as_cons() {
+            encoding = c.cdr();
+        } else if let Some(c) = default_process_coding_system.as_cons() {
+            encoding = c.cdr();
+        }
+    }
+
+    // Note: At this moment, the above coding system may leave
+    // text-conversion or eol-conversion unspecified.  They will be
+    // decided after we read output from the process and decode it by
+    // some coding system, or just before we actually send a text to
+    // the process.
+    (decoding, encoding)
+}
+
+/// Return the file name PROGRAM should be run from.
+/// If PROGRAM is not absolute, search `exec-path' for it.
+pub(crate) fn find_program(program: LispStringRef) -> LispObject {
+    let file = if program.as_slice().first() != Some(&b'/') {
+        let mut found = Qnil;
+        unsafe {
+            openp(
+                globals.Vexec_path,
+                program.into(),
+                globals.Vexec_suffixes,
+                &mut found,
+                LispObject::from_fixnum(EmacsInt::from(libc::X_OK)),
+                false,
+            )
+        };
+        if found.is_nil() {
+            unsafe {
+                report_file_error(
+                    b"Searching for program\0".as_ptr() as *const c_char,
+                    program.into(),
+                )
+            };
+        }
+        unsafe { Fexpand_file_name(found, Qnil) }
+    } else {
+        if unsafe { Ffile_directory_p(program.into()) }.is_not_nil() {
+            error!("Specified program for new process is a directory");
+        }
+        program.into()
+    };
+
+    // Remove "/:" from the name.
+    unsafe { remove_slash_colon(file) }
+}
+
+/// Add the "NAME=VALUE" STRING to ENV unless ENV already binds NAME.
+/// When a variable has multiple definitions, we keep the definition
+/// that comes first.  A lone variable name is kept as a placeholder that
+/// hides later definitions; `child_environment' removes it at the end.
+fn add_env(env: &mut Vec<Vec<u8>>, string: Vec<u8>) {
+    if let Some(pos) = string.iter().position(|&c| c == b'=') {
+        let name = &string[..pos];
+        if env
+            .iter()
+            .any(|entry| entry.split(|&c| c == b'=').next() == Some(name))
+        {
+            return;
+        }
+    }
+    env.push(string);
+}
+
+/// Compute the environment of a subprocess running in CURRENT_DIR from
+/// `process-environment', the way `child_setup' in callproc.c does.
+/// The "NAME=VALUE" strings in OVERRIDES take precedence over
+/// everything else.
+pub(crate) fn child_environment(
+    current_dir: LispStringRef,
+    overrides: Vec<Vec<u8>>,
+) -> Vec<(OsString, OsString)> {
+    let process_environment: Vec<LispStringRef> = unsafe { globals.Vprocess_environment }
+        .iter_cars_safe()
+        .take_while(|elt| elt.is_string())
+        .map(LispStringRef::from)
+        .collect();
+    let mut env = Vec::with_capacity(overrides.len() + process_environment.len() + 2);
+    for var in overrides {
+        add_env(&mut env, var);
+    }
+
+    // If we have a PWD envvar, pass one down, but with corrected value.
+    if !unsafe { egetenv_internal(b"PWD\0".as_ptr() as *const c_char, 3) }.is_null() {
+        let mut dir = current_dir.as_slice();
+        // Strip trailing slashes for PWD, but leave "/" and "//" alone.
+        while dir.len() > 2 && dir[dir.len() - 1] == b'/' {
+            dir = &dir[..dir.len() - 1];
+        }
+        add_env(&mut env, [&b"PWD="[..], dir].concat());
+    }
+
+    let has_display = process_environment.iter().any(|var| {
+        let var = var.as_slice();
+        var.starts_with(b"DISPLAY") && (var.len() == 7 || var[7] == b'=')
+    });
+    // If not provided yet, use the frame's DISPLAY.
+    if !has_display {
+        let mut display = unsafe { Fframe_parameter(selected_frame().into(), Qdisplay) };
+        let initial_environment = unsafe { globals.Vinitial_environment };
+        if !display.is_string() && initial_environment.is_cons() {
+            // If still not found, Look for DISPLAY in Vinitial_environment.
+            display = unsafe { Fgetenv_internal("DISPLAY".into(), initial_environment) };
+        }
+        if let Some(display) = display.as_string() {
+            add_env(&mut env, [&b"DISPLAY="[..], display.as_slice()].concat());
+        }
+    }
+
+    // Overrides.
+    for var in process_environment {
+        add_env(&mut env, var.as_slice().to_vec());
+    }
+
+    // Remove variable names without values.
+    env.into_iter()
+        .filter_map(|mut var| {
+            let pos = var.iter().position(|&c| c == b'=')?;
+            let value = var.split_off(pos + 1);
+            var.pop();
+            Some((OsString::from_vec(var), OsString::from_vec(value)))
+        })
+        .collect()
+}
+
+/// Return a `Stdio' for the child that refers to FD.  FD itself stays
+/// open; the duplicate is closed when the `Command' is dropped.
+fn child_stdio(fd: c_int) -> Stdio {
+    let dup = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+    if dup < 0 {
+        unsafe {
+            report_file_error(
+                b"Duplicating file descriptor\0".as_ptr() as *const c_char,
+                Qnil,
+            )
+        };
+    }
+    unsafe { Stdio::from_raw_fd(dup) }
+}
+
+/// Start the subprocess of PROCESS, running ARGV (a list of encoded
+/// strings, the program's file name first) in CURRENT_DIR.  Set up the
+/// channels Emacs uses to talk with it.
+fn create_process(process: LispObject, argv: LispObject, current_dir: LispStringRef) {
+    let mut p = process.as_process_or_error();
+    let mut pty_name = [0 as c_char; PTY_NAME_SIZE as usize];
+    let mut lisp_pty_name = Qnil;
+
+    let pty_fd = if p.pty_flag() {
+        unsafe { allocate_pty(pty_name.as_mut_ptr()) }
+    } else {
+        -1
+    };
+    let pty_flag = pty_fd >= 0;
+
+    let (inchannel, outchannel, forkin, forkout, mut forkerr);
+    if pty_flag {
+        p.open_fd[READ_FROM_SUBPROCESS as usize] = pty_fd;
+        // Don't let this terminal become our controlling terminal
+        // (in case we don't have one).
+        let fd = unsafe { emacs_open(pty_name.as_ptr(), libc::O_RDWR | libc::O_NOCTTY, 0) };
+        if fd < 0 {
+            unsafe { report_file_error(b"Opening pty\0".as_ptr() as *const c_char, Qnil) };
+        }
+        p.open_fd[SUBPROCESS_STDIN as usize] = fd;
+        inchannel = pty_fd;
+        outchannel = pty_fd;
+        forkin = fd;
+        forkout = fd;
+        forkerr = -1;
+        lisp_pty_name = unsafe { build_string(pty_name.as_ptr()) };
+    } else {
+        unsafe {
+            let open_fd = p.open_fd.as_mut_ptr();
+            if emacs_pipe(open_fd.add(SUBPROCESS_STDIN as usize)) != 0
+                || emacs_pipe(open_fd.add(READ_FROM_SUBPROCESS as usize)) != 0
+            {
+                report_file_error(b"Creating pipe\0".as_ptr() as *const c_char, Qnil);
+            }
+        }
+        forkin = p.open_fd[SUBPROCESS_STDIN as usize];
+        outchannel = p.open_fd[WRITE_TO_SUBPROCESS as usize];
+        inchannel = p.open_fd[READ_FROM_SUBPROCESS as usize];
+        forkout = p.open_fd[SUBPROCESS_STDOUT as usize];
+        forkerr = -1;
+
+        if let Some(mut pp) = p.stderrproc.as_process() {
+            forkerr = pp.open_fd[SUBPROCESS_STDOUT as usize];
+
+            // Close unnecessary file descriptors.
+            close_process_fd(&mut pp.open_fd[WRITE_TO_SUBPROCESS as usize]);
+            close_process_fd(&mut pp.open_fd[SUBPROCESS_STDIN as usize]);
+        }
+    }
+    if forkerr < 0 {
+        forkerr = forkout;
+    }
+
+    unsafe {
+        libc::fcntl(inchannel, libc::F_SETFL, libc::O_NONBLOCK);
+        libc::fcntl(outchannel, libc::F_SETFL, libc::O_NONBLOCK);
+
+        // Record this as an active process, with its channels.
+        chan_process[inchannel as usize] = process;
+    }
+    p.infd = inchannel;
+    p.outfd = outchannel;
+
+    p.set_pty_flag(pty_flag);
+    p.status = Qrun;
+
+    if !p.command.eq(Qt) {
+        unsafe { add_process_read_fd(inchannel) };
+    }
+
+    // This may signal an error.
+    unsafe { setup_process_coding_systems(process) };
+
+    // The child can't call back into Emacs, so set up the tty here
+    // rather than after forking.
+    if pty_flag {
+        unsafe { child_setup_tty(forkout) };
+    }
+
+    let mut args = argv
+        .iter_cars_safe()
+        .map(|arg| OsStr::from_bytes(arg.as_string_or_error().as_slice()).to_os_string());
+    let mut command = Command::new(args.next().unwrap());
+    command
+        .args(args)
+        .current_dir(OsStr::from_bytes(current_dir.as_slice()))
+        .env_clear()
+        .envs(child_environment(current_dir, Vec::new()))
+        .stdin(child_stdio(forkin))
+        .stdout(child_stdio(forkout))
+        .stderr(child_stdio(forkerr));
+    unsafe {
+        command.before_exec(move || {
+            // Make the pty be the controlling terminal of the process.
+            if pty_flag {
+                // First, disconnect its current controlling terminal.
+                libc::setsid();
+                // We ignore the return value because faith@cs.unc.edu
+                // says that is necessary on Linux.
+                libc::ioctl(libc::STDIN_FILENO, libc::TIOCSCTTY, 0);
+            }
+
+            // `Command' already restores SIGPIPE and the signal mask.
+            libc::signal(libc::SIGINT, libc::SIG_DFL);
+            libc::signal(libc::SIGQUIT, libc::SIG_DFL);
+            libc::signal(libc::SIGPROF, libc::SIG_DFL);
+
+            restore_nofile_limit();
+
+            libc::setpgid(0, 0);
+            libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpid());
+            Ok(())
+        })
+    };
+
+    // Block SIGCHLD until the pid is recorded, so that the child can't
+    // be reaped before we know it is ours.
+    let mut oldset: sigset_t = unsafe { mem::zeroed() };
+    unsafe { block_child_signal(&mut oldset) };
+    let spawned = command.spawn();
+    if let Ok(ref child) = spawned {
+        p.pid = child.id() as pid_t;
+        p.set_alive(true);
+        p.start_time = current_timespec();
+    }
+    // Stop blocking in the parent.
+    unsafe { unblock_child_signal(&oldset) };
+    drop(command);
+
+    if let Err(err) = spawned {
+        unsafe {
+            report_file_errno(
+                b"Spawning child process\0".as_ptr() as *const c_char,
+                Qnil,
+                err.raw_os_error().unwrap_or(0),
+            )
+        };
+    }
+
+    // Close the pipe ends that the child uses, or the child's pty.
+    close_process_fd(&mut p.open_fd[SUBPROCESS_STDIN as usize]);
+    close_process_fd(&mut p.open_fd[SUBPROCESS_STDOUT as usize]);
+
+    p.tty_name = lisp_pty_name;
+
+    if let Some(mut pp) = p.stderrproc.as_process() {
+        close_process_fd(&mut pp.open_fd[SUBPROCESS_STDOUT as usize]);
+    }
+}
+
+/// Start a program in a subprocess.  Return the process object for it.
+///
+/// This is similar to `start-process', but arguments are specified as
+/// keyword/argument pairs.  The following arguments are defined:
+///
+/// :name NAME -- NAME is name for process.  It is modified if necessary
+/// to make it unique.
+///
+/// :buffer BUFFER -- BUFFER is the buffer (or buffer-name) to associate
+/// with the process.  Process output goes at end of that buffer, unless
+/// you specify a filter function to handle the output.  BUFFER may be
+/// also nil, meaning that this process is not associated with any buffer.
+///
+/// :command COMMAND -- COMMAND is a list starting with the program file
+/// name, followed by strings to give to the program as arguments.
+///
+/// :coding CODING -- If CODING is a symbol, it specifies the coding
+/// system used for both reading and writing for this process.  If CODING
+/// is a cons (DECODING . ENCODING), DECODING is used for reading, and
+/// ENCODING is used for writing.
+///
+/// :noquery BOOL -- When exiting Emacs, query the user if BOOL is nil and
+/// the process is running.  If BOOL is not given, query before exiting.
+///
+/// :stop BOOL -- Start process in the `stopped' state if BOOL non-nil.
+/// In the stopped state, a process does not accept incoming data, but you
+/// can send outgoing data.  The stopped state is cleared by
+/// `continue-process' and set by `stop-process'.
+///
+/// :connection-type TYPE -- TYPE is control type of device used to
+/// communicate with subprocesses.  Values are `pipe' to use a pipe, `pty'
+/// to use a pty, or nil to use the default specified through
+/// `process-connection-type'.
+///
+/// :filter FILTER -- Install FILTER as the process filter.
+///
+/// :sentinel SENTINEL -- Install SENTINEL as the process sentinel.
+///
+/// :stderr STDERR -- STDERR is either a buffer or a pipe process attached
+/// to the standard error of subprocess.  Specifying this implies
+/// `:connection-type' is set to `pipe'.
+///
+/// usage: (make-process &rest ARGS)
+#[lisp_fn]
+pub fn make_process(args: &mut [LispObject]) -> LispObject {
+    if args.is_empty() {
+        return Qnil;
+    }
+
+    let count = c_specpdl_index();
+
+    // Save arguments for process-contact and clone-process.
+    let contact = list(args);
+
+    let mut buffer = plist_get(contact, QCbuffer);
+    if buffer.is_not_nil() {
+        buffer = unsafe { Fget_buffer_create(buffer) };
+    }
+
+    // Make sure that the child will be able to chdir to the current
+    // buffer's current directory, or its unhandled equivalent.  We
+    // check this before forking, since the child can't signal an
+    // error.
+    let current_dir = unsafe { encode_current_directory() };
+
+    let name = plist_get(contact, QCname);
+    name.as_string_or_error();
+
+    let command = plist_get(contact, QCcommand);
+    let program = command.as_cons().map_or(Qnil, |c| c.car());
+    if program.is_not_nil() {
+        program.as_string_or_error();
+    }
+
+    let query_on_exit = plist_get(contact, QCnoquery).is_nil();
+
+    let xstderr = plist_get(contact, QCstderr);
+    let stderrproc = if let Some(p) = xstderr.as_process() {
+        if !p.ptype().eq(Qpipe) {
+            error!("Process is not a pipe process");
+        }
+        xstderr
+    } else if xstderr.is_not_nil() {
+        program.as_string_or_error();
+        let mut pipe_args = [
+            QCname,
+            unsafe { concat2(name, " stderr".into()) },
+            QCbuffer,
+            unsafe { Fget_buffer_create(xstderr) },
+            QCnoquery,
+            if query_on_exit { Qnil } else { Qt },
+        ];
+        unsafe { Fmake_pipe_process(pipe_args.len() as ptrdiff_t, pipe_args.as_mut_ptr()) }
+    } else {
+        Qnil
+    };
+
+    let proc = unsafe { make_process_object(name) };
+    unsafe { record_unwind_protect(Some(start_process_unwind), proc) };
+    let mut p = proc.as_process_or_error();
+
+    p.set_childp(Qt);
+    debug_assert!(p.plist.is_nil());
+    p.type_ = Qreal;
+    p.set_buffer(buffer);
+    pset_sentinel(p, plist_get(contact, QCsentinel));
+    pset_filter(p, plist_get(contact, QCfilter));
+    p.command = unsafe { Fcopy_sequence(command) };
+
+    if !query_on_exit {
+        p.set_kill_without_query(true);
+    }
+    if plist_get(contact, QCstop).is_not_nil() {
+        p.command = Qt;
+    }
+
+    let connection_type = plist_get(contact, QCconnection_type);
+    if connection_type.eq(Qpty) {
+        p.set_pty_flag(true);
+    } else if connection_type.eq(Qpipe) {
+        p.set_pty_flag(false);
+    } else if connection_type.is_nil() {
+        p.set_pty_flag(unsafe { globals.Vprocess_connection_type }.is_not_nil());
+    } else {
+        unsafe {
+            report_file_error(
+                b"Unknown connection type\0".as_ptr() as *const c_char,
+                connection_type,
+            )
+        };
+    }
+
+    if stderrproc.is_not_nil() {
+        p.stderrproc = stderrproc;
+        p.set_pty_flag(false);
+    }
+
+    p.set_adaptive_read_buffering(adaptive_read_buffering());
+
+    // Make the process marker point into the process buffer (if any).
+    if let Some(b) = buffer.as_buffer() {
+        set_marker_both(p.mark, buffer, b.zv, b.zv_byte);
+    }
+
+    let mut operation = Vec::new();
+    if program.is_not_nil() {
+        operation.extend_from_slice(&[Qstart_process, name, buffer]);
+        operation.extend(command.iter_cars_safe());
+    }
+    let (decoding, encoding) =
+        decide_process_coding_systems(plist_get(contact, QCcoding), &mut operation);
+    p.decode_coding_system = decoding;
+    p.encode_coding_system = encoding;
+
+    p.decoding_buf = unsafe { empty_unibyte_string };
+    debug_assert!(p.decoding_carryover == 0);
+    p.encoding_buf = unsafe { empty_unibyte_string };
+
+    p.set_inherit_coding_system_flag(
+        buffer.is_not_nil() && unsafe { globals.inherit_process_coding_system },
+    );
+
+    if let Some(program) = program.as_string() {
+        let file = find_program(program);
+
+        // Encode the file name; that's what the child will run.  The
+        // arguments are encoded by the coding system used for sending
+        // data to the process.  We don't support using different coding
+        // systems for encoding arguments and for encoding data sent to
+        // the process.
+        let mut arg_encoding = Qnil;
+        let mut argv = list!(unsafe { encode_file_name(file) });
+        for arg in command.as_cons_or_error().cdr().iter_cars_safe() {
+            let mut arg = arg;
+            if arg.as_string_or_error().is_multibyte() {
+                if arg_encoding.is_nil() {
+                    arg_encoding =
+                        unsafe { complement_process_encoding_system(p.encode_coding_system) };
+                }
+                arg = unsafe { code_convert_string_norecord(arg, arg_encoding, true) };
+            }
+            argv = LispObject::cons(arg, argv);
+        }
+
+        create_process(
+            proc,
+            unsafe { Fnreverse(argv) },
+            current_dir.as_string_or_error(),
+        );
+    } else {
+        unsafe { create_pty(proc) };
+    }
+
+    unbind_to(count, proc)
+}
+
 include!(concat!(env!("OUT_DIR"), "/process_exports.rs"));
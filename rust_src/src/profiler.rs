@@ -1,14 +1,792 @@
 //! Profiler implementation.
-use std::ffi::CString;
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::mem;
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::{write::GzEncoder, Compression};
+use libc::{self, c_int};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    alloc::memory_use_counts,
+    hashtable::{
+        hash_table_count, puthash,
+        HashLookupResult::{Found, Missing},
+        LispHashTableRef, DEFAULT_REHASH_SIZE, DEFAULT_REHASH_THRESHOLD,
+    },
     lisp::{defsubr, LispObject},
-    remacs_sys::{error, globals, Qnil},
-    remacs_sys::{make_log, memory_log, profiler_memory_running},
+    lists::plist_get,
+    math::{eqlsign, minus},
+    multibyte::LispStringRef,
+    numbers::MOST_POSITIVE_FIXNUM,
+    remacs_sys::{
+        backtrace_top_function, deliver_process_signal, emacs_sigaction_init, get_backtrace,
+        hashtest_profiler, make_hash_table, Fcopy_sequence, Fmake_vector,
+    },
+    remacs_sys::{
+        encode_file_name, report_file_errno, sxhash, wrong_choice, Fexpand_file_name,
+        Fprin1_to_string,
+    },
+    remacs_sys::{globals, profiler_memory_running, EmacsInt, QAutomatic_GC, Qnil},
+    remacs_sys::{itimerval, setitimer, sigaction},
+    remacs_sys::{
+        QCcounts, QCelapsed, QClog, QCtime, Qclosure, Qcollapsed, Qcpu, Qlambda, Qmemory, Qpprof,
+        Qt,
+    },
+};
+
+// POSIX timers are not available everywhere; macOS for one lacks them,
+// and only has `setitimer'.
+#[cfg(target_os = "linux")]
+use crate::remacs_sys::{
+    itimerspec, sigevent, timer_create, timer_getoverrun, timer_settime, timer_t,
 };
 
+/// Return A + B, but return the maximum fixnum if the result would
+/// overflow.  Assume A and B are nonnegative and in fixnum range.
+fn saturated_add(a: EmacsInt, b: EmacsInt) -> EmacsInt {
+    (a + b).min(MOST_POSITIVE_FIXNUM)
+}
+
+// Logs.
+
+/// Make a new log for `record_backtrace'.
+///
+/// We use a standard Elisp hash-table object, but we use it in a
+/// special way.  This is OK as long as the object is not exposed to
+/// Elisp, i.e. until it is returned by *-profiler-log, after which it
+/// can't be used any more.
+fn make_log() -> LispObject {
+    let (heap_size, max_stack_depth) =
+        unsafe { (globals.profiler_log_size, globals.profiler_max_stack_depth) };
+    let log = make_profiler_table(heap_size);
+    let table = log.as_hash_table_or_error();
+
+    // What is special about our hash-tables is that the keys are
+    // pre-filled with the vectors we'll put in them.
+    for i in (0..table.size() as isize).rev() {
+        table.set_hash_key(i, unsafe { Fmake_vector(max_stack_depth.into(), Qnil) });
+    }
+
+    log
+}
+
+/// Make an empty hash-table of SIZE entries which compares backtraces
+/// like profiler logs do.
+fn make_profiler_table(size: EmacsInt) -> LispObject {
+    unsafe {
+        make_hash_table(
+            hashtest_profiler,
+            size,
+            DEFAULT_REHASH_SIZE,
+            DEFAULT_REHASH_THRESHOLD,
+            Qnil,
+            false,
+        )
+    }
+}
+
+/// Return an approximation of the median of the counts in the SIZE
+/// entries of LOG starting at START.
+fn approximate_median(log: LispHashTableRef, start: isize, size: isize) -> EmacsInt {
+    debug_assert!(size > 0);
+    let count = |i| log.get_hash_value(i).as_fixnum_or_error();
+
+    if size < 2 {
+        count(start)
+    } else if size < 3 {
+        // Not an actual median, but better for our application than
+        // choosing either of the two numbers.
+        (count(start) + count(start + 1)) / 2
+    } else {
+        let newsize = size / 3;
+        let start2 = start + newsize;
+        let i1 = approximate_median(log, start, newsize);
+        let i2 = approximate_median(log, start2, newsize);
+        let i3 = approximate_median(log, start2 + newsize, size - 2 * newsize);
+        if i1 < i2 {
+            if i2 < i3 {
+                i2
+            } else if i1 < i3 {
+                i3
+            } else {
+                i1
+            }
+        } else if i1 < i3 {
+            i1
+        } else if i2 < i3 {
+            i3
+        } else {
+            i2
+        }
+    }
+}
+
+/// Evict the least used half of LOG.
+///
+/// When the table is full, we have to evict someone.  The easiest and
+/// most efficient is to evict the value we're about to add (i.e. once
+/// the table is full, stop sampling).
+///
+/// We could also pick the element with the lowest count and evict it,
+/// but finding it is O(N) and for that amount of work we get very
+/// little in return: for the next sample, this latest sample will have
+/// count==1 and will hence be a prime candidate for eviction :-(
+///
+/// So instead, we take O(N) time to eliminate more or less half of the
+/// entries (the half with the lowest counts).  So we get an amortized
+/// cost of O(1) and we get O(N) time for a new entry to grow larger
+/// than the other least counts before a new round of eviction.
+fn evict_lower_half(log: LispHashTableRef) {
+    let size = log.size() as isize;
+    let median = approximate_median(log, 0, size);
+
+    for i in 0..size {
+        // Evict not only values smaller but also values equal to the
+        // median, so as to make sure we evict something no matter what.
+        if log.get_hash_value(i).as_fixnum_or_error() <= median {
+            let key = log.get_hash_key(i);
+            log.remove(key);
+            debug_assert!(log.next_free == i);
+
+            let mut backtrace = key.as_vector_or_error();
+            for j in 0..backtrace.len() {
+                backtrace.set(j, Qnil);
+            }
+
+            log.set_hash_key(i, key);
+        }
+    }
+}
+
+/// Record the current backtrace in LOG.  COUNT is the weight of this
+/// current backtrace: interrupt counts for CPU, and the allocation size
+/// for memory.
+fn record_backtrace(log: LispHashTableRef, count: EmacsInt) {
+    if log.next_free < 0 {
+        // FIXME: transfer the evicted counts to a special entry rather
+        // than dropping them on the floor.
+        evict_lower_half(log);
+    }
+    let index = log.next_free;
+
+    // Get a "working memory" vector.
+    let backtrace = log.get_hash_key(index);
+    unsafe { get_backtrace(backtrace) };
+
+    // We basically do a `gethash+puthash' here, except that we have to
+    // be careful to avoid memory allocation since we're in a signal
+    // handler, and we optimize the code to try and avoid computing the
+    // hash+lookup twice.  See `puthash' for reference.
+    match log.lookup(backtrace) {
+        Found(j) => {
+            let old_count = log.get_hash_value(j).as_fixnum_or_error();
+            log.set_hash_value(j, saturated_add(old_count, count).into());
+        }
+        Missing(hash) => {
+            // BEWARE!  hash_put in general can allocate memory.  But
+            // currently it only does that if log.next_free is -1.
+            debug_assert!(log.next_free >= 0);
+            let j = log.put(backtrace, count.into(), hash);
+            // Let's make sure we've put `backtrace' right where it
+            // already was to start with.
+            debug_assert!(index == j);
+        }
+    }
+}
+
+/// A profiler log, as returned by `profiler-cpu-log' and
+/// `profiler-memory-log', in a form that is convenient to walk from
+/// Rust.
+pub struct ProfilerLog {
+    pub samples: Vec<ProfilerSample>,
+}
+
+/// A backtrace recorded by the profiler, and its count.
+pub struct ProfilerSample {
+    /// The functions on the call-stack, innermost first.
+    pub frames: Vec<LispObject>,
+    pub count: EmacsInt,
+}
+
+impl ProfilerLog {
+    /// The sum of the counts of all samples.
+    pub fn total(&self) -> EmacsInt {
+        self.samples
+            .iter()
+            .fold(0, |total, sample| saturated_add(total, sample.count))
+    }
+}
+
+impl From<LispHashTableRef> for ProfilerLog {
+    fn from(log: LispHashTableRef) -> Self {
+        let samples = log
+            .iter()
+            .filter_map(|(backtrace, count)| {
+                let count = count.as_fixnum()?;
+                // Backtraces shorter than `profiler-max-stack-depth' are
+                // padded with nil.
+                let frames = backtrace
+                    .as_vector_or_error()
+                    .as_slice()
+                    .iter()
+                    .cloned()
+                    .take_while(|frame| frame.is_not_nil())
+                    .collect();
+                Some(ProfilerSample { frames, count })
+            })
+            .collect();
+        Self { samples }
+    }
+}
+
+impl From<LispObject> for ProfilerLog {
+    fn from(o: LispObject) -> Self {
+        o.as_hash_table_or_error().into()
+    }
+}
+
+// Exporting logs.
+
+/// Return a human readable name for the profiler frame FRAME, in the
+/// same style as `profiler-format-entry'.
+fn frame_name(frame: LispObject) -> String {
+    let kind = if frame.as_cons().map_or(false, |cons| {
+        cons.car().eq(Qclosure) || cons.car().eq(Qlambda)
+    }) {
+        "lambda"
+    } else if frame.is_byte_code_function() {
+        "compiled"
+    } else if frame.is_subr() || frame.is_symbol() || frame.is_string() {
+        return unsafe { Fprin1_to_string(frame, Qt) }
+            .as_string_or_error()
+            .to_string();
+    } else {
+        "unknown"
+    };
+    format!("#<{} {:#x}>", kind, unsafe { sxhash(frame, 0) })
+}
+
+/// Write LOG in the "collapsed stack" format understood by
+/// flamegraph.pl and inferno: one line per backtrace, outermost frame
+/// first, frames separated by semicolons and followed by the count.
+fn write_collapsed(log: &ProfilerLog, out: &mut dyn Write) -> io::Result<()> {
+    // Semicolons separate frames, and the count is whatever follows
+    // the last space, so keep frame names on a single line and free of
+    // separators.
+    let sanitize = |name: String| {
+        name.chars()
+            .map(|c| if c == ';' || c.is_control() { ' ' } else { c })
+            .collect::<String>()
+    };
+
+    let mut lines: Vec<String> = log
+        .samples
+        .iter()
+        .filter(|sample| !sample.frames.is_empty() && sample.count > 0)
+        .map(|sample| {
+            let stack: Vec<String> = sample
+                .frames
+                .iter()
+                .rev()
+                .map(|&frame| sanitize(frame_name(frame)))
+                .collect();
+            format!("{} {}", stack.join(";"), sample.count)
+        })
+        .collect();
+    lines.sort();
+
+    for line in lines {
+        writeln!(out, "{}", line)?;
+    }
+    Ok(())
+}
+
+/// A minimal protocol buffer encoder, just enough to write the
+/// messages of profile.proto.
+#[derive(Default)]
+struct ProtoWriter {
+    buf: Vec<u8>,
+}
+
+impl ProtoWriter {
+    const VARINT: u64 = 0;
+    const LENGTH_DELIMITED: u64 = 2;
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.buf.push((value as u8 & 0x7f) | 0x80);
+            value >>= 7;
+        }
+        self.buf.push(value as u8);
+    }
+
+    fn key(&mut self, field: u64, wire_type: u64) {
+        self.varint(field << 3 | wire_type);
+    }
+
+    fn int(&mut self, field: u64, value: u64) {
+        self.key(field, Self::VARINT);
+        self.varint(value);
+    }
+
+    fn bytes(&mut self, field: u64, bytes: &[u8]) {
+        self.key(field, Self::LENGTH_DELIMITED);
+        self.varint(bytes.len() as u64);
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn packed(&mut self, field: u64, values: &[u64]) {
+        let mut packed = ProtoWriter::default();
+        for &value in values {
+            packed.varint(value);
+        }
+        self.bytes(field, &packed.buf);
+    }
+
+    fn message(&mut self, field: u64, build: impl FnOnce(&mut ProtoWriter)) {
+        let mut message = ProtoWriter::default();
+        build(&mut message);
+        self.bytes(field, &message.buf);
+    }
+}
+
+/// What the counts of a profiler log measure.
+#[derive(Clone, Copy)]
+enum LogKind {
+    /// Samples taken by the CPU profiler.
+    Cpu,
+    /// Bytes allocated, as recorded by the memory profiler.
+    Memory,
+}
+
+impl LogKind {
+    fn from_symbol(kind: LispObject) -> Self {
+        if kind.is_nil() || kind.eq(Qcpu) {
+            LogKind::Cpu
+        } else if kind.eq(Qmemory) {
+            LogKind::Memory
+        } else {
+            unsafe { wrong_choice(list!(Qcpu, Qmemory), kind) }
+        }
+    }
+
+    /// The type and unit of the sample values in pprof terms.
+    fn sample_type(self) -> (&'static str, &'static str) {
+        match self {
+            LogKind::Cpu => ("samples", "count"),
+            LogKind::Memory => ("space", "bytes"),
+        }
+    }
+}
+
+/// Write LOG, whose counts measure KIND, as a gzip-compressed
+/// profile.proto message, as read by `pprof' and most other profile
+/// viewers.
+fn write_pprof(log: &ProfilerLog, kind: LogKind, out: &mut dyn Write) -> io::Result<()> {
+    // Field numbers from profile.proto.
+    const PROFILE_SAMPLE_TYPE: u64 = 1;
+    const PROFILE_SAMPLE: u64 = 2;
+    const PROFILE_LOCATION: u64 = 4;
+    const PROFILE_FUNCTION: u64 = 5;
+    const PROFILE_STRING_TABLE: u64 = 6;
+    const VALUE_TYPE_TYPE: u64 = 1;
+    const VALUE_TYPE_UNIT: u64 = 2;
+    const SAMPLE_LOCATION_ID: u64 = 1;
+    const SAMPLE_VALUE: u64 = 2;
+    const LOCATION_ID: u64 = 1;
+    const LOCATION_LINE: u64 = 4;
+    const LINE_FUNCTION_ID: u64 = 1;
+    const FUNCTION_ID: u64 = 1;
+    const FUNCTION_NAME: u64 = 2;
+
+    // The first entry of the string table must be the empty string.
+    let mut strings: Vec<String> = vec![String::new()];
+    let mut string_ids: HashMap<String, u64> = HashMap::new();
+    let mut intern = |s: String| -> u64 {
+        let next_id = strings.len() as u64;
+        *string_ids.entry(s.clone()).or_insert_with(|| {
+            strings.push(s);
+            next_id
+        })
+    };
+
+    let (sample_type, sample_unit) = kind.sample_type();
+    let type_id = intern(sample_type.to_string());
+    let unit_id = intern(sample_unit.to_string());
+
+    // Every distinct frame gets a function and a location with the
+    // same id; ids must be nonzero.
+    let mut functions: Vec<u64> = Vec::new();
+    let mut function_ids: HashMap<String, u64> = HashMap::new();
+
+    let mut profile = ProtoWriter::default();
+    profile.message(PROFILE_SAMPLE_TYPE, |value_type| {
+        value_type.int(VALUE_TYPE_TYPE, type_id);
+        value_type.int(VALUE_TYPE_UNIT, unit_id);
+    });
+
+    for sample in &log.samples {
+        if sample.frames.is_empty() || sample.count <= 0 {
+            continue;
+        }
+
+        // Locations are listed innermost first, like our frames.
+        let locations: Vec<u64> = sample
+            .frames
+            .iter()
+            .map(|&frame| {
+                let name = frame_name(frame);
+                let next_id = functions.len() as u64 + 1;
+                *function_ids.entry(name.clone()).or_insert_with(|| {
+                    functions.push(intern(name));
+                    next_id
+                })
+            })
+            .collect();
+
+        profile.message(PROFILE_SAMPLE, |message| {
+            message.packed(SAMPLE_LOCATION_ID, &locations);
+            message.packed(SAMPLE_VALUE, &[sample.count as u64]);
+        });
+    }
+
+    for (i, &name_id) in functions.iter().enumerate() {
+        let id = i as u64 + 1;
+        profile.message(PROFILE_LOCATION, |location| {
+            location.int(LOCATION_ID, id);
+            location.message(LOCATION_LINE, |line| line.int(LINE_FUNCTION_ID, id));
+        });
+        profile.message(PROFILE_FUNCTION, |function| {
+            function.int(FUNCTION_ID, id);
+            function.int(FUNCTION_NAME, name_id);
+        });
+    }
+
+    for string in &strings {
+        profile.bytes(PROFILE_STRING_TABLE, string.as_bytes());
+    }
+
+    let mut encoder = GzEncoder::new(out, Compression::default());
+    encoder.write_all(&profile.buf)?;
+    encoder.finish()?;
+    Ok(())
+}
+
+/// Write the profiler log LOG to FILE in FORMAT.
+/// LOG is a log as returned by `profiler-cpu-log' or `profiler-memory-log'.
+/// FORMAT is either `collapsed', for the collapsed stack text read by
+/// flamegraph.pl and inferno, or `pprof', for a gzip-compressed profile
+/// protocol buffer as read by pprof.
+/// KIND says what LOG is, `cpu' (the default) or `memory'.  pprof uses
+/// it to label the counts as samples or as allocated bytes.
+/// Return nil.
+#[lisp_fn(min = "3")]
+pub fn profiler_export_log(
+    log: LispObject,
+    file: LispStringRef,
+    format: LispObject,
+    kind: LispObject,
+) {
+    let pprof = if format.eq(Qcollapsed) {
+        false
+    } else if format.eq(Qpprof) {
+        true
+    } else {
+        unsafe { wrong_choice(list!(Qcollapsed, Qpprof), format) }
+    };
+    let kind = LogKind::from_symbol(kind);
+
+    let log = ProfilerLog::from(log);
+
+    let filename = unsafe { Fexpand_file_name(file.into(), Qnil) };
+    let encoded = unsafe { encode_file_name(filename) }.as_string_or_error();
+    let path = Path::new(OsStr::from_bytes(encoded.as_slice()));
+
+    let result = File::create(path).and_then(|file| {
+        let mut out = BufWriter::new(file);
+        if pprof {
+            write_pprof(&log, kind, &mut out)?;
+        } else {
+            write_collapsed(&log, &mut out)?;
+        }
+        out.flush()
+    });
+
+    if let Err(err) = result {
+        unsafe {
+            report_file_errno(
+                b"Writing profiler log\0".as_ptr() as *const libc::c_char,
+                filename,
+                err.raw_os_error().unwrap_or(0),
+            )
+        };
+    }
+}
+
+// Sampling profiler.
+
+#[derive(Clone, Copy, PartialEq)]
+enum CpuProfilerState {
+    NotRunning,
+    #[cfg(target_os = "linux")]
+    TimerSettimeRunning,
+    SetitimerRunning,
+}
+
+/// Status of sampling profiler.
+static mut CPU_PROFILER_STATE: CpuProfilerState = CpuProfilerState::NotRunning;
+
+/// The profiler timer, if POSIX timers are available and it could be
+/// created.
+#[cfg(target_os = "linux")]
+static mut PROFILER_TIMER: Option<timer_t> = None;
+
+/// Separate counter for the time spent in the GC.
+static mut CPU_GC_COUNT: EmacsInt = 0;
+
+/// Hash-table log of CPU profiler.
+declare_GC_protected_static!(cpu_log, Qnil);
+
+extern "C" fn handle_profiler_signal(_signal: c_int) {
+    if unsafe { backtrace_top_function() }.eq(QAutomatic_GC) {
+        // Special case the time-count inside GC because the hash-table
+        // code is not prepared to be used while the GC is running.
+        // More specifically it uses ASIZE at many places where it does
+        // not expect the ARRAY_MARK_FLAG to be set.  We could try and
+        // harden the hash-table code, but it doesn't seem worth the
+        // effort.
+        unsafe { CPU_GC_COUNT = saturated_add(CPU_GC_COUNT, 1) };
+    } else {
+        let count = 1 + timer_overruns();
+        let log = unsafe { cpu_log };
+        debug_assert!(log.is_hash_table());
+        record_backtrace(log.as_hash_table_or_error(), count);
+    }
+}
+
+extern "C" fn deliver_profiler_signal(signal: c_int) {
+    unsafe { deliver_process_signal(signal, Some(handle_profiler_signal)) };
+}
+
+/// Return the number of signals the profiler timer failed to deliver
+/// since the last one.
+#[cfg(target_os = "linux")]
+fn timer_overruns() -> EmacsInt {
+    match unsafe { PROFILER_TIMER } {
+        Some(timer) => {
+            let overruns = unsafe { timer_getoverrun(timer) };
+            debug_assert!(overruns >= 0);
+            EmacsInt::from(overruns)
+        }
+        None => 0,
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn timer_overruns() -> EmacsInt {
+    0
+}
+
+/// Create the POSIX timer used for sampling, trying system clocks in
+/// decreasing order of desirability.
+#[cfg(target_os = "linux")]
+fn create_profiler_timer() -> Option<timer_t> {
+    let system_clocks = [
+        libc::CLOCK_THREAD_CPUTIME_ID,
+        libc::CLOCK_PROCESS_CPUTIME_ID,
+        libc::CLOCK_MONOTONIC,
+        libc::CLOCK_REALTIME,
+    ];
+
+    let mut timer: timer_t = ptr::null_mut();
+    let mut sigev: sigevent = unsafe { mem::zeroed() };
+    sigev.sigev_value.sival_ptr = &mut timer as *mut timer_t as *mut libc::c_void;
+    sigev.sigev_signo = libc::SIGPROF;
+    sigev.sigev_notify = libc::SIGEV_SIGNAL;
+
+    system_clocks
+        .iter()
+        .find(|&&clock| unsafe { timer_create(clock as _, &mut sigev, &mut timer) } == 0)
+        .map(|_| timer)
+}
+
+/// Start the POSIX profiler timer with INTERVAL, creating it first if
+/// need be.  Return whether that worked.
+#[cfg(target_os = "linux")]
+fn start_profiler_timer(interval: libc::timespec) -> bool {
+    unsafe {
+        if PROFILER_TIMER.is_none() {
+            PROFILER_TIMER = create_profiler_timer();
+        }
+        match PROFILER_TIMER {
+            Some(timer) => {
+                let ispec = itimerspec {
+                    it_interval: interval,
+                    it_value: interval,
+                };
+                timer_settime(timer, 0, &ispec, ptr::null_mut()) == 0
+            }
+            None => false,
+        }
+    }
+}
+
+/// Start sampling every SAMPLING_INTERVAL nanoseconds, with a POSIX
+/// timer where there are any and with `setitimer' otherwise.
+fn setup_cpu_timer(sampling_interval: EmacsInt) -> CpuProfilerState {
+    const BILLION: EmacsInt = 1_000_000_000;
+    let interval = libc::timespec {
+        tv_sec: (sampling_interval / BILLION) as libc::time_t,
+        tv_nsec: (sampling_interval % BILLION) as libc::c_long,
+    };
+
+    unsafe {
+        let mut action: sigaction = mem::zeroed();
+        emacs_sigaction_init(&mut action, Some(deliver_profiler_signal));
+        sigaction(libc::SIGPROF, &action, ptr::null_mut());
+
+        #[cfg(target_os = "linux")]
+        {
+            if start_profiler_timer(interval) {
+                return CpuProfilerState::TimerSettimeRunning;
+            }
+        }
+
+        let tv = libc::timeval {
+            tv_sec: interval.tv_sec,
+            tv_usec: (interval.tv_nsec / 1000) as libc::suseconds_t,
+        };
+        let timer = itimerval {
+            it_interval: tv,
+            it_value: tv,
+        };
+        if setitimer(libc::ITIMER_PROF as _, &timer, ptr::null_mut()) == 0 {
+            return CpuProfilerState::SetitimerRunning;
+        }
+    }
+
+    CpuProfilerState::NotRunning
+}
+
+/// Start or restart the cpu profiler.
+/// It takes call-stack samples each SAMPLING-INTERVAL nanoseconds, approximately.
+/// See also `profiler-log-size' and `profiler-max-stack-depth'.
+#[lisp_fn]
+pub fn profiler_cpu_start(sampling_interval: LispObject) -> bool {
+    unsafe {
+        if CPU_PROFILER_STATE != CpuProfilerState::NotRunning {
+            error!("CPU profiler is already running");
+        }
+
+        if cpu_log.is_nil() {
+            CPU_GC_COUNT = 0;
+            cpu_log = make_log();
+        }
+    }
+
+    let sampling_interval = match sampling_interval.as_fixnum() {
+        Some(n) if n >= 1 => n,
+        _ => error!("Invalid sampling interval"),
+    };
+
+    let state = setup_cpu_timer(sampling_interval);
+    unsafe { CPU_PROFILER_STATE = state };
+    if state == CpuProfilerState::NotRunning {
+        error!("Unable to start profiler timer");
+    }
+
+    true
+}
+
+/// Stop the cpu profiler.  The profiler log is not affected.
+/// Return non-nil if the profiler was running.
+#[lisp_fn]
+pub fn profiler_cpu_stop() -> bool {
+    unsafe {
+        match CPU_PROFILER_STATE {
+            CpuProfilerState::NotRunning => return false,
+            #[cfg(target_os = "linux")]
+            CpuProfilerState::TimerSettimeRunning => {
+                let disable: itimerspec = mem::zeroed();
+                if let Some(timer) = PROFILER_TIMER {
+                    timer_settime(timer, 0, &disable, ptr::null_mut());
+                }
+            }
+            CpuProfilerState::SetitimerRunning => {
+                let disable: itimerval = mem::zeroed();
+                setitimer(libc::ITIMER_PROF as _, &disable, ptr::null_mut());
+            }
+        }
+
+        libc::signal(libc::SIGPROF, libc::SIG_IGN);
+        CPU_PROFILER_STATE = CpuProfilerState::NotRunning;
+    }
+
+    true
+}
+
+/// Return non-nil if cpu profiler is running.
+#[lisp_fn]
+pub fn profiler_cpu_running_p() -> bool {
+    unsafe { CPU_PROFILER_STATE != CpuProfilerState::NotRunning }
+}
+
+/// Return the current cpu profiler log.
+/// The log is a hash-table mapping backtraces to counters which represent
+/// the amount of time spent at those points.  Every backtrace is a vector
+/// of functions, where the last few elements may be nil.
+/// Before returning, a new log is allocated for future samples.
+#[lisp_fn]
+pub fn profiler_cpu_log() -> LispObject {
+    unsafe {
+        let result = cpu_log;
+        if result.is_nil() {
+            return Qnil;
+        }
+
+        // Here we're making the log visible to Elisp, so it's not safe
+        // any more for our use afterwards since we can't rely on its
+        // special pre-allocated keys anymore.  So we have to allocate a
+        // new one.
+        cpu_log = if profiler_cpu_running_p() {
+            make_log()
+        } else {
+            Qnil
+        };
+
+        puthash(
+            Fmake_vector(1.into(), QAutomatic_GC),
+            CPU_GC_COUNT.into(),
+            result.as_hash_table_or_error(),
+        );
+        CPU_GC_COUNT = 0;
+
+        result
+    }
+}
+
+// Memory profiler.
+
+declare_GC_protected_static!(memory_log, Qnil);
+
+/// Record that the current backtrace allocated SIZE bytes.
+#[no_mangle]
+pub extern "C" fn malloc_probe(size: libc::size_t) {
+    let log = unsafe { memory_log };
+    debug_assert!(log.is_hash_table());
+    let size = size.min(MOST_POSITIVE_FIXNUM as libc::size_t) as EmacsInt;
+    record_backtrace(log.as_hash_table_or_error(), size);
+}
+
 /// Return non-nil if memory profiler is running.
 #[lisp_fn]
 pub fn profiler_memory_running_p() -> bool {
@@ -23,12 +801,11 @@ pub fn profiler_memory_running_p() -> bool {
 pub fn profiler_memory_start() -> bool {
     unsafe {
         if profiler_memory_running {
-            let error_str = CString::new("Memory profiler is already running").unwrap();
-            error(error_str.as_ptr() as *const u8);
+            error!("Memory profiler is already running");
         }
 
         if memory_log.is_nil() {
-            memory_log = make_log(globals.profiler_log_size, globals.profiler_max_stack_depth)
+            memory_log = make_log();
         }
 
         profiler_memory_running = true;
@@ -66,7 +843,7 @@ pub fn profiler_memory_log() -> LispObject {
         // for our use afterwards since we can't rely on its special
         // pre-allocated keys anymore. So we have to allocate a new one.
         memory_log = if profiler_memory_running {
-            make_log(globals.profiler_log_size, globals.profiler_max_stack_depth)
+            make_log()
         } else {
             Qnil
         };
@@ -75,4 +852,115 @@ pub fn profiler_memory_log() -> LispObject {
     }
 }
 
+// Memory snapshots.
+
+/// Return a copy of the profiler log LOG that doesn't share its
+/// backtrace vectors, so that it isn't affected when the profiler
+/// records into LOG again.
+fn copy_log(log: LispHashTableRef) -> LispObject {
+    let entries: Vec<(LispObject, LispObject)> = log.iter().collect();
+    let copy = make_profiler_table(entries.len().max(1) as EmacsInt);
+    let table = copy.as_hash_table_or_error();
+    for (backtrace, count) in entries {
+        puthash(unsafe { Fcopy_sequence(backtrace) }, count, table);
+    }
+    copy
+}
+
+/// Return the current time, as a float number of seconds since the epoch.
+fn current_time() -> f64 {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    now.as_secs() as f64 + f64::from(now.subsec_nanos()) / 1e9
+}
+
+/// Return the LOG, COUNTS and TIME recorded in the memory snapshot SNAPSHOT.
+fn snapshot_parts(snapshot: LispObject) -> (LispHashTableRef, LispObject, f64) {
+    let log = plist_get(snapshot, QClog).as_hash_table_or_error();
+    let counts = plist_get(snapshot, QCcounts);
+    let time = plist_get(snapshot, QCtime).any_to_float_or_error();
+    (log, counts, time)
+}
+
+/// Return a snapshot of the memory profiler log and of the allocation counts.
+/// The value is a plist (:log LOG :counts COUNTS :time TIME).  LOG is a
+/// copy of the memory profiler log as `profiler-memory-log' would
+/// return it, empty if the memory profiler isn't running.  Unlike
+/// `profiler-memory-log', taking a snapshot leaves the profiler log
+/// untouched, so the profiler keeps accumulating into it.  COUNTS is
+/// the value of `memory-use-counts', and TIME the time the snapshot was
+/// taken, as a float number of seconds.
+///
+/// Use `profiler-memory-diff' to compare two snapshots.
+#[lisp_fn]
+pub fn profiler_memory_snapshot() -> LispObject {
+    let counts = LispObject::from(memory_use_counts());
+    let time = current_time();
+
+    unsafe {
+        // Don't let the allocations made while copying be recorded into
+        // the log being copied.
+        let running = profiler_memory_running;
+        profiler_memory_running = false;
+        let log = if memory_log.is_hash_table() {
+            copy_log(memory_log.as_hash_table_or_error())
+        } else {
+            make_profiler_table(1)
+        };
+        profiler_memory_running = running;
+
+        list!(QClog, log, QCcounts, counts, QCtime, LispObject::from(time))
+    }
+}
+
+/// Return the difference between the memory snapshots SNAP-A and SNAP-B.
+/// Both are values returned by `profiler-memory-snapshot', SNAP-A
+/// usually being the earlier one.  The value is a plist (:log LOG
+/// :counts COUNTS :elapsed ELAPSED).  LOG maps each backtrace whose
+/// allocations changed between the snapshots to the amount allocated
+/// at that point in SNAP-B minus that in SNAP-A; it can be passed to
+/// the functions that accept a memory profiler log.  COUNTS are the
+/// differences between the `memory-use-counts' of the snapshots, and
+/// ELAPSED the number of seconds between them.
+#[lisp_fn]
+pub fn profiler_memory_diff(snap_a: LispObject, snap_b: LispObject) -> LispObject {
+    let (log_a, counts_a, time_a) = snapshot_parts(snap_a);
+    let (log_b, counts_b, time_b) = snapshot_parts(snap_b);
+
+    let size = hash_table_count(log_a).max(hash_table_count(log_b)).max(1);
+    let diff = make_profiler_table(size);
+    let table = diff.as_hash_table_or_error();
+    for (backtrace, count) in log_b.iter() {
+        let old_count = match log_a.lookup(backtrace) {
+            Found(i) => log_a.get_hash_value(i),
+            Missing(_) => LispObject::from(0),
+        };
+        let delta = minus(&[count, old_count]);
+        if !eqlsign(&[delta, LispObject::from(0)]) {
+            puthash(backtrace, delta, table);
+        }
+    }
+    for (backtrace, count) in log_a.iter() {
+        if let Missing(_) = log_b.lookup(backtrace) {
+            puthash(backtrace, minus(&[count]), table);
+        }
+    }
+
+    let counts: Vec<LispObject> = counts_b
+        .iter_cars()
+        .zip(counts_a.iter_cars())
+        .map(|(b, a)| minus(&[b, a]))
+        .collect();
+
+    list!(
+        QClog,
+        diff,
+        QCcounts,
+        LispObject::from(counts),
+        QCelapsed,
+        LispObject::from(time_b - time_a)
+    )
+}
+
 include!(concat!(env!("OUT_DIR"), "/profiler_exports.rs"));
@@ -0,0 +1,2015 @@
+//! Regular expression matching.
+//!
+//! A backtracking matcher for Emacs regexp syntax.  Patterns compile to
+//! the same instruction sequence `regex.c` builds, so alternatives,
+//! repetitions and groups agree with the C engine, but the program runs
+//! directly over the two halves of the buffer text on either side of the
+//! gap.  Case folding and syntax lookups happen at match time, so one
+//! compiled program serves every buffer.
+
+use std::rc::Rc;
+
+use libc::c_int;
+
+use crate::{
+    buffer_text::BufferText,
+    chartable::LispCharTableRef,
+    lisp::LispObject,
+    multibyte::{
+        char_byte8_p, is_ascii, is_single_byte_char, multibyte_char_at, multibyte_length_by_head,
+        unibyte_to_char, Codepoint, LispStringRef, MAX_CHAR, MAX_MULTIBYTE_LENGTH,
+    },
+    remacs_sys::{
+        alphabeticp, alphanumericp, blankp, emacs_re_max_failures, gl_state, graphicp, lowercasep,
+        maybe_quit, printablep, syntax_property, syntax_spec_code, syntaxcode, uppercasep,
+        word_boundary_p, CHAR_HAS_CATEGORY, SETUP_SYNTAX_TABLE_FOR_OBJECT,
+        SYNTAX_TABLE_BYTE_TO_CHAR, UPDATE_SYNTAX_TABLE_BACKWARD, UPDATE_SYNTAX_TABLE_FAST,
+        UPDATE_SYNTAX_TABLE_FORWARD_FAST,
+    },
+    remacs_sys::{downcase, globals, upcase, EmacsInt},
+    remacs_sys::{Qinvalid_regexp, Qnil},
+    threads::ThreadState,
+};
+
+/// Largest register number that records its match positions.
+const MAX_REGNUM: usize = 255;
+
+/// Largest count allowed inside `\{...\}`.
+const RE_DUP_MAX: i32 = 0xFFFF;
+
+/// Number of compiled patterns kept for reuse.
+const REGEXP_CACHE_SIZE: usize = 20;
+
+/// Approximate number of stack slots a failure point costs in `regex.c`.
+const TYPICAL_FAILURE_SIZE: usize = 20;
+
+const REG_BADPAT: &str = "Invalid regular expression";
+const REG_ECTYPE: &str = "Invalid character class name";
+const REG_EESCAPE: &str = "Trailing backslash";
+const REG_ESUBREG: &str = "Invalid back reference";
+const REG_EBRACK: &str = "Unmatched [ or [^";
+const REG_EPAREN: &str = "Unmatched ( or \\(";
+const REG_EBRACE: &str = "Unmatched \\{";
+const REG_BADBR: &str = "Invalid content of \\{\\}";
+const REG_EEND: &str = "Premature end of regular expression";
+const REG_ESIZEBR: &str = "Invalid content of \\{\\}, repetitions too big";
+const REG_ERPAREN: &str = "Unmatched ) or \\)";
+
+/// Character classes usable inside brackets, as in `[[:alpha:]]`.
+#[derive(Clone, Copy, PartialEq)]
+enum CharClass {
+    Alnum,
+    Alpha,
+    Ascii,
+    Blank,
+    Cntrl,
+    Digit,
+    Graph,
+    Lower,
+    Multibyte,
+    Nonascii,
+    Print,
+    Punct,
+    Space,
+    Unibyte,
+    Upper,
+    Word,
+    Xdigit,
+}
+
+impl CharClass {
+    fn from_name(name: &[u8]) -> Option<Self> {
+        let class = match name {
+            b"alnum" => CharClass::Alnum,
+            b"alpha" => CharClass::Alpha,
+            b"ascii" => CharClass::Ascii,
+            b"blank" => CharClass::Blank,
+            b"cntrl" => CharClass::Cntrl,
+            b"digit" => CharClass::Digit,
+            b"graph" => CharClass::Graph,
+            b"lower" => CharClass::Lower,
+            b"multibyte" => CharClass::Multibyte,
+            b"nonascii" => CharClass::Nonascii,
+            b"print" => CharClass::Print,
+            b"punct" => CharClass::Punct,
+            b"space" => CharClass::Space,
+            b"unibyte" => CharClass::Unibyte,
+            b"upper" => CharClass::Upper,
+            b"word" => CharClass::Word,
+            b"xdigit" => CharClass::Xdigit,
+            _ => return None,
+        };
+        Some(class)
+    }
+
+    /// Whether ASCII character C belongs to the class.  Syntax comes from
+    /// the buffer's own table, as it does for the bitmap `regex.c` builds.
+    fn contains_ascii(self, c: Codepoint) -> bool {
+        let b = c as u8;
+        match self {
+            CharClass::Alnum => b.is_ascii_alphanumeric(),
+            CharClass::Alpha => b.is_ascii_alphabetic(),
+            CharClass::Ascii | CharClass::Unibyte => true,
+            CharClass::Blank => b == b' ' || b == b'\t',
+            CharClass::Cntrl => b < b' ',
+            CharClass::Digit => b.is_ascii_digit(),
+            CharClass::Graph => b > b' ' && b != 0x7F,
+            CharClass::Lower => unsafe { lowercasep(c as c_int) },
+            CharClass::Multibyte | CharClass::Nonascii => false,
+            CharClass::Print => b >= b' ' && b != 0x7F,
+            CharClass::Punct => b > b' ' && b < 0x7F && !b.is_ascii_alphanumeric(),
+            CharClass::Space => syntax(c, false) == syntaxcode::Swhitespace,
+            CharClass::Upper => unsafe { uppercasep(c as c_int) },
+            CharClass::Word => syntax(c, false) == syntaxcode::Sword,
+            CharClass::Xdigit => b.is_ascii_hexdigit(),
+        }
+    }
+
+    /// Whether non-ASCII character C, the translation of CORIG, belongs
+    /// to the class.
+    fn contains_multibyte(self, c: Codepoint, corig: Codepoint) -> bool {
+        let ci = c as c_int;
+        unsafe {
+            match self {
+                CharClass::Multibyte | CharClass::Nonascii => true,
+                CharClass::Alnum => alphanumericp(ci),
+                CharClass::Alpha => alphabeticp(ci),
+                CharClass::Blank => blankp(ci),
+                CharClass::Graph => {
+                    if is_single_byte_char(c) {
+                        c > 0xA0
+                    } else {
+                        graphicp(ci)
+                    }
+                }
+                CharClass::Print => {
+                    if is_single_byte_char(c) {
+                        c > 0x9F
+                    } else {
+                        printablep(ci)
+                    }
+                }
+                CharClass::Punct => syntax(c, true) != syntaxcode::Sword,
+                CharClass::Space => syntax(c, true) == syntaxcode::Swhitespace,
+                CharClass::Word => syntax(c, true) == syntaxcode::Sword,
+                CharClass::Lower => {
+                    lowercasep(ci) || (corig != c && ci == upcase(corig as c_int) && uppercasep(ci))
+                }
+                CharClass::Upper => {
+                    uppercasep(ci)
+                        || (corig != c && ci == downcase(corig as c_int) && lowercasep(ci))
+                }
+                _ => false,
+            }
+        }
+    }
+}
+
+/// A bracket expression.
+#[derive(Clone)]
+struct Charset {
+    negated: bool,
+    ranges: Vec<(Codepoint, Codepoint)>,
+    classes: Vec<CharClass>,
+}
+
+impl Charset {
+    fn in_ranges(&self, c: Codepoint) -> bool {
+        self.ranges.iter().any(|&(lo, hi)| lo <= c && c <= hi)
+    }
+
+    /// Add the characters from LO to HI, splitting off the ASCII part the
+    /// way `regex.c` does so raw bytes never join a multibyte range.
+    fn add_range(&mut self, mut lo: Codepoint, hi: Codepoint) {
+        if lo < 0x80 {
+            let top = hi.min(0x7F);
+            self.ranges.push((lo, top));
+            lo = top + 1;
+            if char_byte8_p(hi) {
+                lo = unibyte_to_char(0x80);
+            }
+        }
+        if lo <= hi {
+            self.ranges.push((lo, hi));
+        }
+    }
+}
+
+/// Jump targets are absolute indices into the program.
+type Target = usize;
+
+#[derive(Clone)]
+enum Op {
+    NoOp,
+    Succeed,
+    Exact(Codepoint),
+    AnyChar,
+    Charset(Box<Charset>),
+    StartMemory(usize),
+    StopMemory(usize),
+    Duplicate(usize),
+    BegLine,
+    EndLine,
+    BegBuf,
+    EndBuf,
+    AtDot,
+    WordBound,
+    NotWordBound,
+    WordBeg,
+    WordEnd,
+    SymBeg,
+    SymEnd,
+    /// Syntax code and whether the test is negated.
+    SyntaxSpec(u8, bool),
+    /// Category and whether the test is negated.
+    CategorySpec(u8, bool),
+    Jump(Target),
+    OnFailureJump(Target),
+    OnFailureJumpSmart(Target),
+    OnFailureJumpLoop(Target),
+    OnFailureJumpNastyloop(Target),
+    SucceedN(Target),
+    JumpN(Target),
+    SetNumberAt(Target, i32),
+}
+
+impl Op {
+    fn target_mut(&mut self) -> Option<&mut Target> {
+        match *self {
+            Op::Jump(ref mut t)
+            | Op::OnFailureJump(ref mut t)
+            | Op::OnFailureJumpSmart(ref mut t)
+            | Op::OnFailureJumpLoop(ref mut t)
+            | Op::OnFailureJumpNastyloop(ref mut t)
+            | Op::SucceedN(ref mut t)
+            | Op::JumpN(ref mut t)
+            | Op::SetNumberAt(ref mut t, _) => Some(t),
+            _ => None,
+        }
+    }
+
+    /// Whether the operation matches exactly one character.
+    fn is_one_char(&self) -> bool {
+        match *self {
+            Op::Exact(_)
+            | Op::AnyChar
+            | Op::Charset(_)
+            | Op::SyntaxSpec(..)
+            | Op::CategorySpec(..) => true,
+            _ => false,
+        }
+    }
+}
+
+/// Characters that can start a match.
+#[derive(Clone)]
+struct Fastmap {
+    ascii: [bool; 128],
+    non_ascii: bool,
+}
+
+impl Fastmap {
+    fn new() -> Self {
+        Fastmap {
+            ascii: [false; 128],
+            non_ascii: false,
+        }
+    }
+
+    fn add(&mut self, c: Codepoint) {
+        if is_ascii(c) {
+            self.ascii[c as usize] = true;
+        } else {
+            self.non_ascii = true;
+        }
+    }
+
+    fn add_charset(&mut self, set: &Charset) {
+        if set.negated || !set.classes.is_empty() {
+            self.ascii = [true; 128];
+            self.non_ascii = true;
+            return;
+        }
+        for &(lo, hi) in &set.ranges {
+            for c in lo..=hi.min(0x7F) {
+                self.ascii[c as usize] = true;
+            }
+            if hi >= 0x80 {
+                self.non_ascii = true;
+            }
+        }
+    }
+
+    fn is_full(&self) -> bool {
+        self.non_ascii && self.ascii.iter().all(|&b| b)
+    }
+
+    fn contains(&self, c: Codepoint) -> bool {
+        if is_ascii(c) {
+            self.ascii[c as usize]
+        } else {
+            self.non_ascii
+        }
+    }
+}
+
+/// A compiled regexp.
+pub struct Program {
+    ops: Vec<Op>,
+    /// Number of groups in the pattern.
+    nsub: usize,
+    /// Possible first characters, unless the pattern can match the empty
+    /// string or its first characters can't be listed.
+    fastmap: Option<Fastmap>,
+}
+
+impl Program {
+    /// Number of registers a match fills, including the whole match.
+    pub fn num_regs(&self) -> usize {
+        self.nsub + 1
+    }
+}
+
+/// Whether the operations from START up to END can match the empty
+/// string: 1 if they can, 0 if they can't, and -1 if FASTMAP couldn't be
+/// filled in accurately.  Mirrors `analyze_first` in `regex.c`.
+fn analyze_first(ops: &[Op], mut p: usize, end: usize, mut fastmap: Option<&mut Fastmap>) -> i32 {
+    while p < end {
+        let p1 = p;
+        let mut op = &ops[p];
+        p += 1;
+
+        if let Op::Jump(t) = *op {
+            if t <= p1 {
+                // A backward jump: the loop body was already analyzed.
+                return 0;
+            }
+            p = t;
+            op = &ops[p];
+            match *op {
+                Op::OnFailureJump(_)
+                | Op::OnFailureJumpSmart(_)
+                | Op::OnFailureJumpLoop(_)
+                | Op::OnFailureJumpNastyloop(_) => p += 1,
+                _ => continue,
+            }
+        }
+
+        match *op {
+            Op::Succeed => return 1,
+            Op::Duplicate(_) => {
+                // The back reference may be empty, and its first character
+                // is whatever the group's was, so nothing to add.
+            }
+            Op::Exact(c) => {
+                if let Some(fm) = fastmap.as_mut() {
+                    fm.add(c);
+                }
+                return 0;
+            }
+            Op::Charset(ref set) => {
+                if let Some(fm) = fastmap.as_mut() {
+                    fm.add_charset(set);
+                }
+                return 0;
+            }
+            Op::AnyChar | Op::SyntaxSpec(..) | Op::CategorySpec(..) => {
+                return if fastmap.is_some() { -1 } else { 0 };
+            }
+            Op::OnFailureJump(t)
+            | Op::OnFailureJumpSmart(t)
+            | Op::OnFailureJumpLoop(t)
+            | Op::OnFailureJumpNastyloop(t) => {
+                if t > p1 {
+                    // Look down the straight path first, then the jump.
+                    let r = analyze_first(ops, p, end, fastmap.as_mut().map(|fm| &mut **fm));
+                    if r != 0 {
+                        return r;
+                    }
+                    p = t;
+                }
+            }
+            _ => {}
+        }
+    }
+    1
+}
+
+type CompileResult<T> = Result<T, &'static str>;
+
+struct Group {
+    begalt: usize,
+    fixup_alt_jump: Option<usize>,
+    laststart: usize,
+    regnum: isize,
+}
+
+struct Compiler<'a> {
+    /// The pattern being read: the regexp itself, or the whitespace
+    /// regexp while a run of spaces is being replaced.
+    src: &'a [u8],
+    p: usize,
+    main: &'a [u8],
+    main_p: usize,
+    whitespace: Option<&'a [u8]>,
+    in_subpattern: bool,
+    multibyte: bool,
+    ops: Vec<Op>,
+    re_nsub: usize,
+    stack: Vec<Group>,
+}
+
+impl<'a> Compiler<'a> {
+    fn fetch(&mut self) -> CompileResult<Codepoint> {
+        if self.p == self.src.len() {
+            return Err(REG_EEND);
+        }
+        let (c, len) = if self.multibyte {
+            multibyte_char_at(&self.src[self.p..])
+        } else {
+            (unibyte_to_char(Codepoint::from(self.src[self.p])), 1)
+        };
+        self.p += len;
+        Ok(c)
+    }
+
+    fn peek(&self, offset: usize) -> Option<u8> {
+        self.src.get(self.p + offset).cloned()
+    }
+
+    /// Insert OP at index AT, keeping the jumps that move along with the
+    /// rest of the program pointing at the same relative place.
+    fn insert(&mut self, at: usize, op: Op) {
+        for moved in &mut self.ops[at..] {
+            if let Some(t) = moved.target_mut() {
+                *t += 1;
+            }
+        }
+        self.ops.insert(at, op);
+    }
+
+    fn fixup_alt_jump(&mut self, slot: Option<usize>) {
+        if let Some(slot) = slot {
+            self.ops[slot] = Op::Jump(self.ops.len());
+        }
+    }
+
+    /// Whether the `^' just read is in a context where it is an anchor.
+    fn at_begline_loc_p(&self) -> bool {
+        let pat = self.src;
+        let mut prev = self.p - 2;
+        match pat[prev] {
+            b'(' | b'|' => {}
+            b':' => {
+                // A `\(?N:' group.
+                while prev > 0 && pat[prev - 1].is_ascii_digit() {
+                    prev -= 1;
+                }
+                if prev < 2 || pat[prev - 1] != b'?' || pat[prev - 2] != b'(' {
+                    return false;
+                }
+                prev -= 2;
+            }
+            _ => return false,
+        }
+        // The opening character must be preceded by an odd number of
+        // backslashes.
+        let mut start = prev;
+        while start > 0 && pat[start - 1] == b'\\' {
+            start -= 1;
+        }
+        (prev - start) % 2 == 1
+    }
+
+    /// Whether the `$' just read is in a context where it is an anchor.
+    fn at_endline_loc_p(&self) -> bool {
+        self.peek(0) == Some(b'\\') && (self.peek(1) == Some(b')') || self.peek(1) == Some(b'|'))
+    }
+
+    /// Parse a `[:name:]' class at the current position, if there is one.
+    fn parse_class(&mut self) -> CompileResult<Option<CharClass>> {
+        let pat = self.src;
+        let p = self.p;
+        if pat.len() - p < 4 || pat[p] != b'[' || pat[p + 1] != b':' {
+            return Ok(None);
+        }
+        let close = match (p + 2..pat.len() - 1).find(|&i| pat[i] == b':' && pat[i + 1] == b']') {
+            Some(i) => i,
+            None => return Ok(None),
+        };
+        self.p = close + 2;
+        CharClass::from_name(&pat[p + 2..close])
+            .map(Some)
+            .ok_or(REG_ECTYPE)
+    }
+
+    fn compile_bracket(&mut self) -> CompileResult<Charset> {
+        if self.p == self.src.len() {
+            return Err(REG_EBRACK);
+        }
+        let negated = self.peek(0) == Some(b'^');
+        if negated {
+            self.p += 1;
+        }
+        let first = self.p;
+        let mut set = Charset {
+            negated,
+            ranges: Vec::new(),
+            classes: Vec::new(),
+        };
+        loop {
+            if self.p == self.src.len() {
+                return Err(REG_EBRACK);
+            }
+            if let Some(class) = self.parse_class()? {
+                if self.p == self.src.len() {
+                    return Err(REG_EBRACK);
+                }
+                set.classes.push(class);
+                continue;
+            }
+
+            let start = self.p;
+            let c = self.fetch()?;
+            if c == Codepoint::from(b']') && start != first {
+                break;
+            }
+
+            let (mut lo, hi) = if self.peek(0) == Some(b'-') && self.peek(1) != Some(b']') {
+                self.p += 1;
+                (c, self.fetch()?)
+            } else {
+                (c, c)
+            };
+            if char_byte8_p(hi) && !is_ascii(lo) && !char_byte8_p(lo) {
+                // A range from a multibyte character to a raw byte is
+                // empty.
+                lo = hi + 1;
+            }
+            if lo <= hi {
+                set.add_range(lo, hi);
+            }
+        }
+        Ok(set)
+    }
+
+    /// Read a number inside `\{...\}` into NUM, returning the character
+    /// that follows it.
+    fn interval_count(&mut self, num: &mut i32) -> CompileResult<Codepoint> {
+        if self.p == self.src.len() {
+            return Err(REG_EBRACE);
+        }
+        let mut c = self.fetch()?;
+        while c >= Codepoint::from(b'0') && c <= Codepoint::from(b'9') {
+            let digit = (c - Codepoint::from(b'0')) as i32;
+            if *num < 0 {
+                *num = 0;
+            }
+            if RE_DUP_MAX / 10 - (if RE_DUP_MAX % 10 < digit { 1 } else { 0 }) < *num {
+                return Err(REG_ESIZEBR);
+            }
+            *num = *num * 10 + digit;
+            if self.p == self.src.len() {
+                return Err(REG_EBRACE);
+            }
+            c = self.fetch()?;
+        }
+        Ok(c)
+    }
+
+    fn compile(mut self, posix: bool) -> CompileResult<Program> {
+        let mut laststart: Option<usize> = None;
+        let mut begalt = 0;
+        let mut fixup_alt_jump: Option<usize> = None;
+
+        loop {
+            if self.p == self.src.len() {
+                if self.in_subpattern {
+                    // Done with the whitespace regexp; resume the pattern.
+                    self.in_subpattern = false;
+                    self.src = self.main;
+                    self.p = self.main_p;
+                    continue;
+                }
+                break;
+            }
+
+            let c = self.fetch()?;
+            let special = if c < 0x80 { c as u8 } else { 0xFF };
+            match special {
+                b' ' if self.whitespace.is_some() && !self.in_subpattern => {
+                    let src = self.src;
+                    let mut p1 = self.p;
+                    while p1 < src.len() && src[p1] == b' ' {
+                        p1 += 1;
+                    }
+                    let repeated = p1 < src.len()
+                        && (src[p1] == b'*'
+                            || src[p1] == b'+'
+                            || src[p1] == b'?'
+                            || (src[p1] == b'\\' && src.get(p1 + 1) == Some(&b'{')));
+                    if repeated {
+                        laststart = Some(self.ops.len());
+                        self.ops.push(Op::Exact(c));
+                    } else {
+                        self.in_subpattern = true;
+                        self.main_p = p1;
+                        self.src = self.whitespace.unwrap();
+                        self.p = 0;
+                    }
+                }
+
+                b'^' if self.p == 1 || self.at_begline_loc_p() => self.ops.push(Op::BegLine),
+
+                b'$' if self.p == self.src.len() || self.at_endline_loc_p() => {
+                    self.ops.push(Op::EndLine)
+                }
+
+                b'*' | b'+' | b'?' if laststart.is_some() => {
+                    let ls = laststart.unwrap();
+                    let (mut zero_ok, mut many_ok, mut greedy) = (false, false, true);
+                    let mut rep = special;
+                    loop {
+                        if rep == b'?' && (zero_ok || many_ok) {
+                            greedy = false;
+                        } else {
+                            zero_ok |= rep != b'+';
+                            many_ok |= rep != b'?';
+                        }
+                        match self.peek(0) {
+                            Some(next @ b'*') | Some(next @ b'+') | Some(next @ b'?') => {
+                                rep = next;
+                                self.p += 1;
+                            }
+                            _ => break,
+                        }
+                    }
+                    self.compile_repeat(ls, zero_ok, many_ok, greedy);
+                }
+
+                b'.' => {
+                    laststart = Some(self.ops.len());
+                    self.ops.push(Op::AnyChar);
+                }
+
+                b'[' => {
+                    laststart = Some(self.ops.len());
+                    let set = self.compile_bracket()?;
+                    self.ops.push(Op::Charset(Box::new(set)));
+                }
+
+                b'\\' => {
+                    if self.p == self.src.len() {
+                        return Err(REG_EESCAPE);
+                    }
+                    let c = self.fetch()?;
+                    let special = if c < 0x80 { c as u8 } else { 0xFF };
+                    match special {
+                        b'(' => {
+                            let mut shy = false;
+                            let mut regnum: isize = 0;
+                            if self.peek(0) == Some(b'?') && self.peek(1).is_some() {
+                                self.p += 1;
+                                while !shy {
+                                    let c = self.fetch()?;
+                                    match c {
+                                        0x3A => shy = true, // `:'
+                                        0x30 if regnum == 0 => return Err(REG_BADPAT),
+                                        0x30..=0x39 => {
+                                            regnum = regnum * 10 + (c - 0x30) as isize;
+                                            if regnum > RE_DUP_MAX as isize {
+                                                return Err(REG_BADPAT);
+                                            }
+                                        }
+                                        _ => return Err(REG_BADPAT),
+                                    }
+                                }
+                            }
+
+                            if !shy {
+                                self.re_nsub += 1;
+                                regnum = self.re_nsub as isize;
+                            } else if regnum > 0 {
+                                // Explicitly numbered, so not shy after all.
+                                if regnum as usize > self.re_nsub {
+                                    self.re_nsub = regnum as usize;
+                                } else if self.stack.iter().any(|g| g.regnum == regnum) {
+                                    return Err(REG_BADPAT);
+                                }
+                            } else {
+                                regnum = -(self.re_nsub as isize);
+                            }
+
+                            self.stack.push(Group {
+                                begalt,
+                                fixup_alt_jump,
+                                laststart: self.ops.len(),
+                                regnum,
+                            });
+                            if regnum > 0 && regnum as usize <= MAX_REGNUM {
+                                self.ops.push(Op::StartMemory(regnum as usize));
+                            }
+                            fixup_alt_jump = None;
+                            laststart = None;
+                            begalt = self.ops.len();
+                        }
+
+                        b')' => {
+                            if self.stack.is_empty() {
+                                return Err(REG_ERPAREN);
+                            }
+                            self.fixup_alt_jump(fixup_alt_jump);
+                            let group = self.stack.pop().unwrap();
+                            begalt = group.begalt;
+                            fixup_alt_jump = group.fixup_alt_jump;
+                            laststart = Some(group.laststart);
+                            if group.regnum > 0 && group.regnum as usize <= MAX_REGNUM {
+                                self.ops.push(Op::StopMemory(group.regnum as usize));
+                            }
+                        }
+
+                        b'|' => {
+                            let end = self.ops.len();
+                            self.insert(begalt, Op::OnFailureJump(end + 2));
+                            self.fixup_alt_jump(fixup_alt_jump);
+                            fixup_alt_jump = Some(self.ops.len());
+                            // Filled in by the next alternative or the end.
+                            self.ops.push(Op::Jump(0));
+                            laststart = None;
+                            begalt = self.ops.len();
+                        }
+
+                        b'{' => {
+                            let beg_interval = self.p;
+                            let mut lower = 0;
+                            let mut upper = -1;
+                            let mut c = self.interval_count(&mut lower)?;
+                            if c == Codepoint::from(b',') {
+                                c = self.interval_count(&mut upper)?;
+                            } else {
+                                upper = lower;
+                            }
+                            if lower < 0 || (0 <= upper && upper < lower) {
+                                return Err(REG_BADBR);
+                            }
+                            if c != Codepoint::from(b'\\') {
+                                return Err(REG_BADBR);
+                            }
+                            if self.p == self.src.len() {
+                                return Err(REG_EESCAPE);
+                            }
+                            if self.fetch()? != Codepoint::from(b'}') {
+                                return Err(REG_BADBR);
+                            }
+
+                            match laststart {
+                                Some(ls) => self.compile_interval(ls, lower, upper),
+                                None => {
+                                    // Without an operand, `\{' is literal.
+                                    self.p = beg_interval;
+                                    laststart = Some(self.ops.len());
+                                    self.ops.push(Op::Exact(Codepoint::from(b'{')));
+                                }
+                            }
+                        }
+
+                        b'=' => {
+                            laststart = Some(self.ops.len());
+                            self.ops.push(Op::AtDot);
+                        }
+
+                        b's' | b'S' => {
+                            laststart = Some(self.ops.len());
+                            let c = self.fetch()?;
+                            let code = if c < 0x100 {
+                                unsafe { syntax_spec_code[c as usize] }
+                            } else {
+                                0xFF
+                            };
+                            self.ops.push(Op::SyntaxSpec(code, special == b'S'));
+                        }
+
+                        b'c' | b'C' => {
+                            laststart = Some(self.ops.len());
+                            let c = self.fetch()?;
+                            self.ops.push(Op::CategorySpec(c as u8, special == b'C'));
+                        }
+
+                        b'w' | b'W' => {
+                            laststart = Some(self.ops.len());
+                            self.ops
+                                .push(Op::SyntaxSpec(syntaxcode::Sword as u8, special == b'W'));
+                        }
+
+                        b'<' => {
+                            laststart = Some(self.ops.len());
+                            self.ops.push(Op::WordBeg);
+                        }
+
+                        b'>' => {
+                            laststart = Some(self.ops.len());
+                            self.ops.push(Op::WordEnd);
+                        }
+
+                        b'_' => {
+                            laststart = Some(self.ops.len());
+                            let c = self.fetch()?;
+                            if c == Codepoint::from(b'<') {
+                                self.ops.push(Op::SymBeg);
+                            } else if c == Codepoint::from(b'>') {
+                                self.ops.push(Op::SymEnd);
+                            } else {
+                                return Err(REG_BADPAT);
+                            }
+                        }
+
+                        b'b' => self.ops.push(Op::WordBound),
+                        b'B' => self.ops.push(Op::NotWordBound),
+                        b'`' => self.ops.push(Op::BegBuf),
+                        b'\'' => self.ops.push(Op::EndBuf),
+
+                        b'1'..=b'9' => {
+                            let reg = (special - b'0') as usize;
+                            if reg > self.re_nsub
+                                || self.stack.iter().any(|g| g.regnum == reg as isize)
+                            {
+                                return Err(REG_ESUBREG);
+                            }
+                            laststart = Some(self.ops.len());
+                            self.ops.push(Op::Duplicate(reg));
+                        }
+
+                        _ => {
+                            laststart = Some(self.ops.len());
+                            self.ops.push(Op::Exact(c));
+                        }
+                    }
+                }
+
+                _ => {
+                    laststart = Some(self.ops.len());
+                    self.ops.push(Op::Exact(c));
+                }
+            }
+        }
+
+        self.fixup_alt_jump(fixup_alt_jump);
+        if !self.stack.is_empty() {
+            return Err(REG_EPAREN);
+        }
+        if !posix {
+            // POSIX patterns run off the end instead, to look for a longer
+            // match.
+            self.ops.push(Op::Succeed);
+        }
+
+        let mut fastmap = Fastmap::new();
+        let can_be_null = analyze_first(&self.ops, 0, self.ops.len(), Some(&mut fastmap)) != 0;
+        Ok(Program {
+            ops: self.ops,
+            nsub: self.re_nsub,
+            fastmap: if can_be_null || fastmap.is_full() {
+                None
+            } else {
+                Some(fastmap)
+            },
+        })
+    }
+
+    /// Apply `*', `+' or `?' (or their non-greedy forms) to the operations
+    /// from LS onwards.
+    fn compile_repeat(&mut self, ls: usize, zero_ok: bool, many_ok: bool, greedy: bool) {
+        let end = self.ops.len();
+        if ls == end {
+            // Repeating an empty pattern matches the empty string.
+            return;
+        }
+
+        if greedy {
+            if many_ok {
+                let simple = ls + 1 == end && self.ops[ls].is_one_char();
+                let loop_op: fn(Target) -> Op =
+                    if simple || analyze_first(&self.ops, ls, end, None) == 0 {
+                        Op::OnFailureJump
+                    } else {
+                        Op::OnFailureJumpLoop
+                    };
+                let mut zero_ok = zero_ok;
+                let mut startoffset = 0;
+                if !zero_ok && simple {
+                    // Turn a simple P+ into PP*, so the loop can use the
+                    // faster keep-string form.
+                    let body = self.ops[ls].clone();
+                    self.ops.push(body);
+                    startoffset = 1;
+                    zero_ok = true;
+                }
+
+                let len = self.ops.len();
+                if !zero_ok {
+                    self.ops.push(loop_op(len + 2));
+                } else {
+                    let op = if simple {
+                        Op::OnFailureJumpSmart(len + 2)
+                    } else {
+                        loop_op(len + 2)
+                    };
+                    self.insert(ls + startoffset, op);
+                }
+                self.ops.push(Op::Jump(ls + startoffset));
+            } else {
+                self.insert(ls, Op::OnFailureJump(end + 1));
+            }
+        } else if many_ok {
+            let emptyp = analyze_first(&self.ops, ls, end, None) != 0;
+            if emptyp {
+                self.ops.push(Op::NoOp);
+            }
+            let cond = self.ops.len();
+            self.ops.push(if emptyp {
+                Op::OnFailureJumpNastyloop(ls)
+            } else {
+                Op::OnFailureJump(ls)
+            });
+            if zero_ok {
+                // Enter the loop at its condition.
+                self.insert(ls, Op::Jump(cond + 1));
+            }
+        } else {
+            self.insert(ls, Op::Jump(end + 1));
+            self.insert(ls, Op::OnFailureJump(ls + 2));
+        }
+    }
+
+    /// Apply `\{LOWER,UPPER\}' to the operations from LS onwards.  A
+    /// negative UPPER means no upper bound.
+    fn compile_interval(&mut self, ls: usize, lower: i32, upper: i32) {
+        if upper == 0 {
+            self.ops.truncate(ls);
+            return;
+        }
+        if lower == 1 && upper == 1 {
+            return;
+        }
+
+        let end = self.ops.len();
+        let trailing = if upper == 1 { 0 } else { 1 };
+        let startoffset = if lower == 0 {
+            // A `succeed_n' starting at 0 is just a loop.
+            self.insert(ls, Op::OnFailureJumpLoop(end + 1 + trailing));
+            0
+        } else {
+            self.insert(ls, Op::SucceedN(end + 1 + trailing));
+            self.insert(ls, Op::SetNumberAt(ls + 1, lower));
+            1
+        };
+
+        if upper < 0 {
+            self.ops.push(Op::Jump(ls + startoffset));
+        } else if upper > 1 {
+            self.ops.push(Op::JumpN(ls + startoffset));
+            let jump_n = self.ops.len();
+            // Reset the repeat count each time the loop is entered.
+            self.insert(ls, Op::SetNumberAt(jump_n, upper - 1));
+        }
+    }
+}
+
+/// Compile PATTERN, splicing in WHITESPACE for each run of spaces if
+/// given.  Return the error message for an invalid pattern.
+fn compile(
+    pattern: &[u8],
+    multibyte: bool,
+    whitespace: Option<&[u8]>,
+    posix: bool,
+) -> CompileResult<Program> {
+    let compiler = Compiler {
+        src: pattern,
+        p: 0,
+        main: pattern,
+        main_p: 0,
+        whitespace,
+        in_subpattern: false,
+        multibyte,
+        ops: Vec::new(),
+        re_nsub: 0,
+        stack: Vec::new(),
+    };
+    compiler.compile(posix)
+}
+
+struct CacheEntry {
+    pattern: Vec<u8>,
+    multibyte: bool,
+    posix: bool,
+    whitespace: Option<Vec<u8>>,
+    program: Rc<Program>,
+}
+
+/// Recently compiled patterns, most recently used first.
+static mut REGEXP_CACHE: Option<Vec<CacheEntry>> = None;
+
+/// Return the compiled program for REGEXP, signaling `invalid-regexp' if
+/// it is malformed.  Runs of spaces are matched by `search-spaces-regexp'
+/// when that is set.
+pub fn compile_regexp(regexp: LispStringRef, posix: bool) -> Rc<Program> {
+    let whitespace = unsafe { globals.Vsearch_spaces_regexp }.as_string();
+    let whitespace = whitespace.as_ref().map(|s| s.as_slice());
+    let pattern = regexp.as_slice();
+    let multibyte = regexp.is_multibyte();
+
+    let cache = unsafe { REGEXP_CACHE.get_or_insert_with(Vec::new) };
+    let hit = cache.iter().position(|e| {
+        e.posix == posix
+            && e.multibyte == multibyte
+            && e.pattern.as_slice() == pattern
+            && e.whitespace.as_ref().map(|w| w.as_slice()) == whitespace
+    });
+    if let Some(i) = hit {
+        let entry = cache.remove(i);
+        let program = Rc::clone(&entry.program);
+        cache.insert(0, entry);
+        return program;
+    }
+
+    let program = match compile(pattern, multibyte, whitespace, posix) {
+        Ok(program) => Rc::new(program),
+        Err(msg) => xsignal!(Qinvalid_regexp, LispObject::from(msg)),
+    };
+    cache.truncate(REGEXP_CACHE_SIZE - 1);
+    cache.insert(
+        0,
+        CacheEntry {
+            pattern: pattern.to_vec(),
+            multibyte,
+            posix,
+            whitespace: whitespace.map(|w| w.to_vec()),
+            program: Rc::clone(&program),
+        },
+    );
+    program
+}
+
+/// The text a program runs over: the accessible portion of a buffer, in
+/// the two pieces on either side of the gap, or the contents of a string.
+/// Positions are byte offsets into the concatenation of the two pieces.
+pub struct Text<'a> {
+    first: &'a [u8],
+    second: &'a [u8],
+    multibyte: bool,
+    /// The string being matched, or nil for the current buffer.
+    object: LispObject,
+}
+
+impl<'a> Text<'a> {
+    /// The text of VIEW, which must be of the accessible portion of
+    /// the current buffer.
+    pub fn from_buffer(view: &'a BufferText) -> Self {
+        let (first, second) = view.as_slices();
+        Text {
+            first,
+            second,
+            multibyte: view.is_multibyte(),
+            object: Qnil,
+        }
+    }
+
+    pub fn from_string(string: &'a LispStringRef) -> Self {
+        Text {
+            first: string.as_slice(),
+            second: &[],
+            multibyte: string.is_multibyte(),
+            object: string.as_lisp_obj(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.first.len() + self.second.len()
+    }
+
+    fn byte(&self, pos: usize) -> u8 {
+        if pos < self.first.len() {
+            self.first[pos]
+        } else {
+            self.second[pos - self.first.len()]
+        }
+    }
+
+    /// The character at POS and its length in bytes.
+    pub fn char_at(&self, pos: usize) -> (Codepoint, usize) {
+        if !self.multibyte {
+            return (unibyte_to_char(Codepoint::from(self.byte(pos))), 1);
+        }
+        if pos < self.first.len() {
+            multibyte_char_at(&self.first[pos..])
+        } else {
+            multibyte_char_at(&self.second[pos - self.first.len()..])
+        }
+    }
+
+    /// The position of the character that ends at POS.
+    pub fn prev_char_pos(&self, pos: usize) -> usize {
+        if !self.multibyte {
+            return pos - 1;
+        }
+        let (seg, base) = if pos <= self.first.len() {
+            (self.first, 0)
+        } else {
+            (self.second, self.first.len())
+        };
+        let end = pos - base;
+        let limit = end.saturating_sub(MAX_MULTIBYTE_LENGTH);
+        let mut i = end - 1;
+        while i > limit && seg[i] & 0xC0 == 0x80 {
+            i -= 1;
+        }
+        base + i
+    }
+
+    fn char_before(&self, pos: usize) -> Codepoint {
+        self.char_at(self.prev_char_pos(pos)).0
+    }
+
+    fn next_char_len(&self, pos: usize) -> usize {
+        if self.multibyte {
+            multibyte_length_by_head(self.byte(pos))
+        } else {
+            1
+        }
+    }
+
+    /// POS as a syntax-table offset, where buffer offsets count from 1.
+    fn syntax_offset(&self, pos: usize) -> isize {
+        pos as isize + if self.object.is_nil() { 1 } else { 0 }
+    }
+}
+
+/// Registers filled in by a successful match, as byte offsets into the
+/// text.  Groups that did not take part are -1.
+pub struct Registers {
+    pub start: Vec<isize>,
+    pub end: Vec<isize>,
+}
+
+fn syntax(c: Codepoint, via_property: bool) -> syntaxcode {
+    unsafe { syntax_property(c as c_int, via_property) }
+}
+
+fn word_boundary(c1: Codepoint, c2: Codepoint) -> bool {
+    !(is_single_byte_char(c1) && is_single_byte_char(c2))
+        && unsafe { word_boundary_p(c1 as c_int, c2 as c_int) }
+}
+
+fn table_char(table: LispCharTableRef, c: Codepoint) -> Option<Codepoint> {
+    table
+        .get(c as isize)
+        .as_fixnum()
+        .filter(|&n| 0 <= n && n <= EmacsInt::from(MAX_CHAR))
+        .map(|n| n as Codepoint)
+}
+
+/// The current buffer's case tables, when `case-fold-search' is on.
+pub struct CaseFold {
+    canon: LispCharTableRef,
+    eqv: Option<LispCharTableRef>,
+    ascii: [Codepoint; 128],
+}
+
+impl CaseFold {
+    pub fn current() -> Option<Self> {
+        let buffer = ThreadState::current_buffer();
+        if buffer.case_fold_search().is_nil() {
+            return None;
+        }
+        buffer
+            .case_canon_table_
+            .as_char_table()
+            .map(|canon| CaseFold {
+                canon,
+                eqv: buffer.case_eqv_table_.as_char_table(),
+                ascii: [Codepoint::max_value(); 128],
+            })
+    }
+
+    pub fn translate(&mut self, c: Codepoint) -> Codepoint {
+        if is_ascii(c) {
+            let cached = self.ascii[c as usize];
+            if cached != Codepoint::max_value() {
+                return cached;
+            }
+            let t = table_char(self.canon, c).unwrap_or(c);
+            self.ascii[c as usize] = t;
+            t
+        } else if char_byte8_p(c) {
+            c
+        } else {
+            table_char(self.canon, c).unwrap_or(c)
+        }
+    }
+}
+
+struct FailPoint {
+    pc: usize,
+    /// Where matching resumes, or None to keep the current position.
+    pos: Option<usize>,
+    undo: usize,
+}
+
+enum Undo {
+    Reg(usize, isize, isize),
+    Counter(usize, i32),
+}
+
+/// The failure stack outgrew `emacs_re_max_failures'.
+struct Overflow;
+
+type MatchResult<T> = Result<T, Overflow>;
+
+/// Whether an `on_failure_jump_smart' loop has been resolved yet.
+#[derive(Clone, Copy, PartialEq)]
+enum Smart {
+    Unknown,
+    KeepString,
+    Plain,
+}
+
+struct Matcher<'a> {
+    ops: &'a [Op],
+    text: &'a Text<'a>,
+    stop: usize,
+    fold: Option<CaseFold>,
+    /// Point as an offset into the text, for `\='.
+    pt_offset: isize,
+    regstart: Vec<isize>,
+    regend: Vec<isize>,
+    counters: Vec<i32>,
+    smart: Vec<Smart>,
+    points: Vec<FailPoint>,
+    undo: Vec<Undo>,
+    max_items: usize,
+}
+
+impl<'a> Matcher<'a> {
+    fn new(prog: &'a Program, text: &'a Text<'a>, stop: usize) -> Self {
+        let buffer = ThreadState::current_buffer();
+        let num_regs = prog.num_regs();
+        Matcher {
+            ops: &prog.ops,
+            text,
+            stop,
+            fold: CaseFold::current(),
+            pt_offset: buffer.pt_byte - buffer.begv_byte,
+            regstart: vec![-1; num_regs],
+            regend: vec![-1; num_regs],
+            counters: vec![0; prog.ops.len()],
+            smart: vec![Smart::Unknown; prog.ops.len()],
+            points: Vec::new(),
+            undo: Vec::new(),
+            max_items: unsafe { emacs_re_max_failures } as usize * TYPICAL_FAILURE_SIZE,
+        }
+    }
+
+    fn tr(&mut self, c: Codepoint) -> Codepoint {
+        match self.fold {
+            Some(ref mut fold) => fold.translate(c),
+            None => c,
+        }
+    }
+
+    /// Call F on C's translation and each character that folds to it
+    /// until F returns true.
+    fn any_equivalent<F: Fn(Codepoint) -> bool>(&mut self, c: Codepoint, f: F) -> bool {
+        let t = self.tr(c);
+        if f(t) {
+            return true;
+        }
+        let eqv = match self.fold {
+            Some(CaseFold { eqv: Some(eqv), .. }) => eqv,
+            _ => return false,
+        };
+        let mut x = t;
+        // Case cycles are short; the bound only guards against a
+        // malformed table.
+        for _ in 0..8 {
+            x = match table_char(eqv, x) {
+                Some(next) if next != t => next,
+                _ => return false,
+            };
+            if f(x) {
+                return true;
+            }
+        }
+        false
+    }
+
+    fn charset_matches(&mut self, set: &Charset, c: Codepoint) -> bool {
+        let t = self.tr(c);
+        let found = if char_byte8_p(t) {
+            set.in_ranges(t)
+        } else if is_ascii(t) {
+            self.any_equivalent(c, |x| {
+                set.in_ranges(x)
+                    || (is_ascii(x) && set.classes.iter().any(|cl| cl.contains_ascii(x)))
+            })
+        } else {
+            set.classes.iter().any(|cl| cl.contains_multibyte(t, c))
+                || self.any_equivalent(c, |x| set.in_ranges(x))
+        };
+        found != set.negated
+    }
+
+    fn one_char_matches(&mut self, op: &Op, c: Codepoint) -> bool {
+        match *op {
+            Op::Exact(pc) => self.tr(c) == self.tr(pc),
+            Op::AnyChar => self.tr(c) != Codepoint::from(b'\n'),
+            Op::Charset(ref set) => self.charset_matches(set, c),
+            _ => false,
+        }
+    }
+
+    fn check_room(&self) -> MatchResult<()> {
+        if 3 * (self.points.len() + self.undo.len() + 1) > self.max_items {
+            Err(Overflow)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn push_point(&mut self, pc: usize, pos: Option<usize>) -> MatchResult<()> {
+        self.check_room()?;
+        self.points.push(FailPoint {
+            pc,
+            pos,
+            undo: self.undo.len(),
+        });
+        Ok(())
+    }
+
+    /// Record an undo entry, unless no failure point could restore it.
+    fn push_undo(&mut self, entry: Undo) -> MatchResult<()> {
+        if !self.points.is_empty() {
+            self.check_room()?;
+            self.undo.push(entry);
+        }
+        Ok(())
+    }
+
+    fn set_counter(&mut self, idx: usize, val: i32) -> MatchResult<()> {
+        let old = self.counters[idx];
+        self.push_undo(Undo::Counter(idx, old))?;
+        self.counters[idx] = val;
+        Ok(())
+    }
+
+    /// Whether a failure point for PC at position D is already on the
+    /// stack, meaning a loop went around without consuming anything.
+    fn in_infinite_loop(&self, pc: usize, d: usize) -> bool {
+        for point in self.points.iter().rev() {
+            if point.pos.map_or(false, |pos| pos != d) {
+                break;
+            }
+            if point.pc == pc {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Backtrack to the most recent failure point, returning where to
+    /// resume, or None if there is nothing left to try.
+    fn backtrack(&mut self, d: usize) -> MatchResult<Option<(usize, usize)>> {
+        loop {
+            unsafe { maybe_quit() };
+            let point = match self.points.pop() {
+                Some(point) => point,
+                None => return Ok(None),
+            };
+            while self.undo.len() > point.undo {
+                match self.undo.pop().unwrap() {
+                    Undo::Reg(n, start, end) => {
+                        self.regstart[n] = start;
+                        self.regend[n] = end;
+                    }
+                    Undo::Counter(idx, val) => self.counters[idx] = val,
+                }
+            }
+            let pos = point.pos.unwrap_or(d);
+            match self.ops[point.pc] {
+                Op::OnFailureJumpNastyloop(t) => {
+                    // Come back to the preceding no-op if this fails too.
+                    self.push_point(point.pc - 1, point.pos)?;
+                    return Ok(Some((t, pos)));
+                }
+                Op::OnFailureJump(t)
+                | Op::OnFailureJumpSmart(t)
+                | Op::OnFailureJumpLoop(t)
+                | Op::SucceedN(t) => return Ok(Some((t, pos))),
+                _ => {
+                    // The no-op of a nasty loop: just fail again.
+                }
+            }
+        }
+    }
+
+    /// Whether the simple loop body at P1 can never match what follows
+    /// the loop at P2, so the loop never needs to give characters back.
+    fn mutually_exclusive(&mut self, p1: usize, mut p2: usize) -> bool {
+        let ops = self.ops;
+        while p2 < ops.len() {
+            match ops[p2] {
+                Op::StartMemory(_) | Op::StopMemory(_) | Op::NoOp => p2 += 1,
+                Op::Jump(t) => p2 = t,
+                _ => break,
+            }
+        }
+        let after = if p2 == ops.len() {
+            &Op::Succeed
+        } else {
+            &ops[p2]
+        };
+        match (&ops[p1], after) {
+            (_, Op::Succeed) | (_, Op::EndBuf) => true,
+            (body, Op::EndLine) => !self.one_char_matches(body, Codepoint::from(b'\n')),
+            (body, Op::Exact(c)) => match *body {
+                Op::Exact(_) | Op::AnyChar | Op::Charset(_) => !self.one_char_matches(body, *c),
+                _ => false,
+            },
+            (Op::Exact(c), Op::Charset(set)) => !self.charset_matches(set, *c),
+            (Op::SyntaxSpec(s, false), Op::WordEnd) => *s == syntaxcode::Sword as u8,
+            (Op::SyntaxSpec(s, false), Op::SymEnd) => {
+                *s == syntaxcode::Sword as u8 || *s == syntaxcode::Ssymbol as u8
+            }
+            (Op::SyntaxSpec(s1, false), Op::SyntaxSpec(s2, true)) => s1 == s2,
+            (Op::SyntaxSpec(s, true), Op::WordBeg) => *s == syntaxcode::Sword as u8,
+            (Op::SyntaxSpec(s, true), Op::SymBeg) => {
+                *s == syntaxcode::Sword as u8 || *s == syntaxcode::Ssymbol as u8
+            }
+            (Op::SyntaxSpec(s1, true), Op::SyntaxSpec(s2, false)) => s1 == s2,
+            (Op::SyntaxSpec(s, _), Op::WordBound) => *s == syntaxcode::Sword as u8,
+            (Op::CategorySpec(c1, n1), Op::CategorySpec(c2, n2)) => c1 == c2 && n1 != n2,
+            _ => false,
+        }
+    }
+
+    /// Run the program at position START, returning the end of the match.
+    fn run(&mut self, start: usize) -> MatchResult<Option<usize>> {
+        let ops = self.ops;
+        let text = self.text;
+        let size = text.len();
+        let stop = self.stop;
+        let mut pc = 0;
+        let mut d = start;
+        let mut best: Option<(usize, Vec<isize>, Vec<isize>)> = None;
+
+        self.regstart.iter_mut().for_each(|r| *r = -1);
+        self.regend.iter_mut().for_each(|r| *r = -1);
+        self.points.clear();
+        self.undo.clear();
+
+        loop {
+            let matched = if pc == ops.len() {
+                // Only POSIX programs run off the end: keep looking for
+                // the longest match while there are alternatives left.
+                if d != stop && !self.points.is_empty() {
+                    if best.as_ref().map_or(true, |b| d > b.0) {
+                        best = Some((d, self.regstart.clone(), self.regend.clone()));
+                    }
+                    false
+                } else {
+                    if d != stop {
+                        if let Some((end, regstart, regend)) = best.take() {
+                            if end >= d {
+                                d = end;
+                                self.regstart = regstart;
+                                self.regend = regend;
+                            }
+                        }
+                    }
+                    return Ok(Some(d));
+                }
+            } else {
+                match ops[pc] {
+                    Op::Succeed => return Ok(Some(d)),
+
+                    Op::NoOp => {
+                        pc += 1;
+                        true
+                    }
+
+                    Op::Exact(_) | Op::AnyChar | Op::Charset(_) => {
+                        if d >= stop {
+                            false
+                        } else {
+                            let (c, len) = text.char_at(d);
+                            if self.one_char_matches(&ops[pc], c) {
+                                d += len;
+                                pc += 1;
+                                true
+                            } else {
+                                false
+                            }
+                        }
+                    }
+
+                    Op::StartMemory(n) => {
+                        self.push_undo(Undo::Reg(n, self.regstart[n], self.regend[n]))?;
+                        self.regstart[n] = d as isize;
+                        self.regend[n] = -1;
+                        pc += 1;
+                        true
+                    }
+
+                    Op::StopMemory(n) => {
+                        self.push_undo(Undo::Reg(n, self.regstart[n], self.regend[n]))?;
+                        self.regend[n] = d as isize;
+                        pc += 1;
+                        true
+                    }
+
+                    Op::Duplicate(n) => {
+                        let (start, end) = (self.regstart[n], self.regend[n]);
+                        if start < 0 || end < 0 {
+                            false
+                        } else {
+                            let mut r = start as usize;
+                            let mut dd = d;
+                            let mut ok = true;
+                            while r < end as usize {
+                                if dd >= stop {
+                                    ok = false;
+                                    break;
+                                }
+                                let (c1, l1) = text.char_at(r);
+                                let (c2, l2) = text.char_at(dd);
+                                if self.tr(c1) != self.tr(c2) {
+                                    ok = false;
+                                    break;
+                                }
+                                r += l1;
+                                dd += l2;
+                            }
+                            if ok {
+                                d = dd;
+                                pc += 1;
+                            }
+                            ok
+                        }
+                    }
+
+                    Op::BegLine => {
+                        pc += 1;
+                        d == 0 || text.byte(d - 1) == b'\n'
+                    }
+
+                    Op::EndLine => {
+                        pc += 1;
+                        d == size || text.byte(d) == b'\n'
+                    }
+
+                    Op::BegBuf => {
+                        pc += 1;
+                        d == 0
+                    }
+
+                    Op::EndBuf => {
+                        pc += 1;
+                        d == size
+                    }
+
+                    Op::AtDot => {
+                        pc += 1;
+                        d as isize == self.pt_offset
+                    }
+
+                    Op::OnFailureJump(_) => {
+                        self.push_point(pc, Some(d))?;
+                        pc += 1;
+                        true
+                    }
+
+                    Op::OnFailureJumpSmart(t) => {
+                        if self.smart[pc] == Smart::Unknown {
+                            self.smart[pc] = if self.mutually_exclusive(pc + 1, t) {
+                                Smart::KeepString
+                            } else {
+                                Smart::Plain
+                            };
+                        }
+                        let pos = if self.smart[pc] == Smart::KeepString {
+                            None
+                        } else {
+                            Some(d)
+                        };
+                        self.push_point(pc, pos)?;
+                        pc += 1;
+                        true
+                    }
+
+                    Op::OnFailureJumpLoop(t) => {
+                        if self.in_infinite_loop(pc, d) {
+                            pc = t;
+                        } else {
+                            self.push_point(pc, Some(d))?;
+                            pc += 1;
+                        }
+                        true
+                    }
+
+                    Op::OnFailureJumpNastyloop(t) => {
+                        if self.in_infinite_loop(pc - 1, d) {
+                            pc = t;
+                        } else {
+                            self.push_point(pc, Some(d))?;
+                            pc += 1;
+                        }
+                        true
+                    }
+
+                    Op::Jump(t) => {
+                        unsafe { maybe_quit() };
+                        // The back edge of a resolved keep-string loop goes
+                        // straight to its body, leaving its single failure
+                        // point alone.
+                        pc = match ops[t] {
+                            Op::OnFailureJumpSmart(exit)
+                                if exit == pc + 1 && self.smart[t] == Smart::KeepString =>
+                            {
+                                t + 1
+                            }
+                            _ => t,
+                        };
+                        true
+                    }
+
+                    Op::SucceedN(t) => {
+                        let count = self.counters[pc];
+                        if count > 0 {
+                            self.set_counter(pc, count - 1)?;
+                            pc += 1;
+                        } else if self.in_infinite_loop(pc, d) {
+                            pc = t;
+                        } else {
+                            self.push_point(pc, Some(d))?;
+                            pc += 1;
+                        }
+                        true
+                    }
+
+                    Op::JumpN(t) => {
+                        let count = self.counters[pc];
+                        if count > 0 {
+                            self.set_counter(pc, count - 1)?;
+                            unsafe { maybe_quit() };
+                            pc = t;
+                        } else {
+                            pc += 1;
+                        }
+                        true
+                    }
+
+                    Op::SetNumberAt(t, n) => {
+                        self.set_counter(t, n)?;
+                        pc += 1;
+                        true
+                    }
+
+                    Op::WordBound | Op::NotWordBound => {
+                        let mut not = match ops[pc] {
+                            Op::NotWordBound => true,
+                            _ => false,
+                        };
+                        if d == 0 || d == size {
+                            not = !not;
+                        } else {
+                            unsafe {
+                                let charpos = SYNTAX_TABLE_BYTE_TO_CHAR(text.syntax_offset(d - 1));
+                                UPDATE_SYNTAX_TABLE_FAST(charpos);
+                                let c1 = text.char_before(d);
+                                let s1 = syntax(c1, true);
+                                UPDATE_SYNTAX_TABLE_FORWARD_FAST(charpos + 1);
+                                let c2 = text.char_at(d).0;
+                                let s2 = syntax(c2, true);
+                                if (s1 == syntaxcode::Sword) != (s2 == syntaxcode::Sword)
+                                    || (s1 == syntaxcode::Sword && word_boundary(c1, c2))
+                                {
+                                    not = !not;
+                                }
+                            }
+                        }
+                        pc += 1;
+                        not
+                    }
+
+                    Op::WordBeg | Op::SymBeg => {
+                        let symbol = match ops[pc] {
+                            Op::SymBeg => true,
+                            _ => false,
+                        };
+                        pc += 1;
+                        if d == size || d >= stop {
+                            false
+                        } else {
+                            unsafe {
+                                let charpos = SYNTAX_TABLE_BYTE_TO_CHAR(text.syntax_offset(d));
+                                UPDATE_SYNTAX_TABLE_FAST(charpos);
+                                let c2 = text.char_at(d).0;
+                                let s2 = syntax(c2, true);
+                                let constituent = |s| {
+                                    s == syntaxcode::Sword || (symbol && s == syntaxcode::Ssymbol)
+                                };
+                                if !constituent(s2) {
+                                    false
+                                } else if d == 0 {
+                                    true
+                                } else {
+                                    let c1 = text.char_before(d);
+                                    UPDATE_SYNTAX_TABLE_BACKWARD(charpos - 1);
+                                    let s1 = syntax(c1, true);
+                                    if symbol {
+                                        !constituent(s1)
+                                    } else {
+                                        s1 != syntaxcode::Sword || word_boundary(c1, c2)
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    Op::WordEnd | Op::SymEnd => {
+                        let symbol = match ops[pc] {
+                            Op::SymEnd => true,
+                            _ => false,
+                        };
+                        pc += 1;
+                        if d == 0 {
+                            false
+                        } else {
+                            unsafe {
+                                let charpos = SYNTAX_TABLE_BYTE_TO_CHAR(text.syntax_offset(d) - 1);
+                                UPDATE_SYNTAX_TABLE_FAST(charpos);
+                                let c1 = text.char_before(d);
+                                let s1 = syntax(c1, true);
+                                let constituent = |s| {
+                                    s == syntaxcode::Sword || (symbol && s == syntaxcode::Ssymbol)
+                                };
+                                if !constituent(s1) {
+                                    false
+                                } else if d == size {
+                                    true
+                                } else {
+                                    let c2 = text.char_at(d).0;
+                                    UPDATE_SYNTAX_TABLE_FORWARD_FAST(if symbol {
+                                        charpos + 1
+                                    } else {
+                                        charpos
+                                    });
+                                    let s2 = syntax(c2, true);
+                                    if symbol {
+                                        !constituent(s2)
+                                    } else {
+                                        s2 != syntaxcode::Sword || word_boundary(c1, c2)
+                                    }
+                                }
+                            }
+                        }
+                    }
+
+                    Op::SyntaxSpec(code, not) => {
+                        if d >= stop {
+                            false
+                        } else {
+                            let (c, len) = text.char_at(d);
+                            let s = unsafe {
+                                let charpos = SYNTAX_TABLE_BYTE_TO_CHAR(text.syntax_offset(d));
+                                UPDATE_SYNTAX_TABLE_FAST(charpos);
+                                syntax(c, true)
+                            };
+                            if (s as u8 != code) != not {
+                                false
+                            } else {
+                                d += len;
+                                pc += 1;
+                                true
+                            }
+                        }
+                    }
+
+                    Op::CategorySpec(category, not) => {
+                        if d >= stop {
+                            false
+                        } else {
+                            let (c, len) = text.char_at(d);
+                            let has = category < 128
+                                && unsafe { CHAR_HAS_CATEGORY(c as c_int, c_int::from(category)) };
+                            if has == not {
+                                false
+                            } else {
+                                d += len;
+                                pc += 1;
+                                true
+                            }
+                        }
+                    }
+                }
+            };
+
+            if !matched {
+                match self.backtrack(d)? {
+                    Some((resume_pc, resume_d)) => {
+                        pc = resume_pc;
+                        d = resume_d;
+                    }
+                    None => {
+                        if let Some((end, regstart, regend)) = best.take() {
+                            self.regstart = regstart;
+                            self.regend = regend;
+                            return Ok(Some(end));
+                        }
+                        return Ok(None);
+                    }
+                }
+            }
+        }
+    }
+
+    fn registers(&self, start: usize, end: usize) -> Registers {
+        let mut regs = Registers {
+            start: self.regstart.clone(),
+            end: self.regend.clone(),
+        };
+        regs.start[0] = start as isize;
+        regs.end[0] = end as isize;
+        for (start, end) in regs.start.iter_mut().zip(regs.end.iter_mut()).skip(1) {
+            if *start < 0 || *end < 0 {
+                *start = -1;
+                *end = -1;
+            }
+        }
+        regs
+    }
+
+    /// The fastmap to test translated characters against.
+    fn search_fastmap(&mut self, prog: &Program) -> Option<Fastmap> {
+        let fastmap = prog.fastmap.as_ref()?;
+        if self.fold.is_none() {
+            return Some(fastmap.clone());
+        }
+        if fastmap.non_ascii {
+            // Non-ASCII characters can fold to ASCII ones.
+            return None;
+        }
+        let mut folded = Fastmap::new();
+        for (c, _) in fastmap.ascii.iter().enumerate().filter(|&(_, &set)| set) {
+            let t = self.tr(c as Codepoint);
+            folded.add(t);
+        }
+        Some(folded)
+    }
+
+    fn fastmap_allows(&mut self, fastmap: &Fastmap, pos: usize) -> bool {
+        let c = self.text.char_at(pos).0;
+        let c = self.tr(c);
+        fastmap.contains(c)
+    }
+}
+
+/// Point the syntax-table machinery at TEXT, starting from byte offset
+/// POS.
+fn setup_syntax_table(text: &Text, pos: usize) {
+    unsafe {
+        gl_state.object = text.object;
+        let charpos = SYNTAX_TABLE_BYTE_TO_CHAR(text.syntax_offset(pos));
+        SETUP_SYNTAX_TABLE_FOR_OBJECT(text.object, charpos, 1);
+    }
+}
+
+fn matcher_overflow() -> ! {
+    error!("Stack overflow in regexp matcher")
+}
+
+/// Match PROG against TEXT starting exactly at byte offset POS, without
+/// looking past STOP.
+pub fn re_match(prog: &Program, text: &Text, pos: usize, stop: usize) -> Option<Registers> {
+    if pos > text.len() {
+        return None;
+    }
+    setup_syntax_table(text, pos);
+    let result = {
+        let mut matcher = Matcher::new(prog, text, stop.min(text.len()));
+        matcher
+            .run(pos)
+            .map(|end| end.map(|end| matcher.registers(pos, end)))
+    };
+    result.unwrap_or_else(|_| matcher_overflow())
+}
+
+/// Search TEXT for PROG from byte offset STARTPOS, trying starting
+/// positions up to RANGE bytes away (backward if RANGE is negative).  A
+/// match may not extend past STOP.
+pub fn re_search(
+    prog: &Program,
+    text: &Text,
+    startpos: usize,
+    range: isize,
+    stop: usize,
+) -> Option<Registers> {
+    let total = text.len() as isize;
+    let mut startpos = startpos as isize;
+    let mut range = range;
+    if startpos > total {
+        return None;
+    }
+    let endpos = startpos + range;
+    if endpos < 0 {
+        range = -startpos;
+    } else if endpos > total {
+        range = total - startpos;
+    }
+
+    match prog.ops.first() {
+        Some(Op::BegBuf) if range > 0 => {
+            if startpos > 0 {
+                return None;
+            }
+            range = 0;
+        }
+        Some(Op::AtDot) if range > 0 => {
+            let buffer = ThreadState::current_buffer();
+            range = buffer.pt_byte - buffer.begv_byte - startpos;
+            if range < 0 {
+                return None;
+            }
+        }
+        _ => {}
+    }
+    let anchored = match prog.ops.first() {
+        Some(Op::BegLine) => true,
+        _ => false,
+    };
+
+    setup_syntax_table(text, startpos as usize);
+    let result = {
+        let mut matcher = Matcher::new(prog, text, stop.min(text.len()));
+        let fastmap = matcher.search_fastmap(prog);
+        search_loop(&mut matcher, fastmap, anchored, startpos, range)
+    };
+    result.unwrap_or_else(|_| matcher_overflow())
+}
+
+fn search_loop(
+    matcher: &mut Matcher,
+    fastmap: Option<Fastmap>,
+    anchored: bool,
+    mut startpos: isize,
+    mut range: isize,
+) -> MatchResult<Option<Registers>> {
+    let text = matcher.text;
+    let total = text.len() as isize;
+    loop {
+        let mut skip = anchored && startpos > 0 && text.byte(startpos as usize - 1) != b'\n';
+
+        if !skip && startpos < total {
+            if let Some(ref fastmap) = fastmap {
+                if range > 0 {
+                    while range > 0 && !matcher.fastmap_allows(fastmap, startpos as usize) {
+                        let len = text.next_char_len(startpos as usize) as isize;
+                        range -= len;
+                        startpos += len;
+                    }
+                    if range < 0 {
+                        return Ok(None);
+                    }
+                } else if !matcher.fastmap_allows(fastmap, startpos as usize) {
+                    skip = true;
+                }
+            }
+        }
+
+        if !skip {
+            if range >= 0 && startpos == total && fastmap.is_some() {
+                return Ok(None);
+            }
+            if let Some(end) = matcher.run(startpos as usize)? {
+                return Ok(Some(matcher.registers(startpos as usize, end)));
+            }
+        }
+
+        if range == 0 {
+            return Ok(None);
+        } else if range > 0 {
+            let len = text.next_char_len(startpos as usize) as isize;
+            range -= len;
+            if range < 0 {
+                return Ok(None);
+            }
+            startpos += len;
+        } else {
+            let prev = text.prev_char_pos(startpos as usize) as isize;
+            range += startpos - prev;
+            if range > 0 {
+                return Ok(None);
+            }
+            startpos = prev;
+        }
+    }
+}
@@ -18,7 +18,7 @@
 use libc::{self, c_char, c_void, ptrdiff_t};
 use std;
 
-use libc::timespec;
+use libc::{rusage, timespec};
 use remacs_lib::current_timespec;
 
 use crate::{
@@ -50,6 +50,7 @@ extern "C" {
     pub fn error(m: *const u8, ...) -> !;
     pub fn Fsignal(error_symbol: Lisp_Object, data: Lisp_Object) -> !;
     pub fn memory_full(nbytes: libc::size_t) -> !;
+    pub fn emacs_abort() -> !;
     pub fn wrong_choice(choice: LispObject, wrong: LispObject) -> !;
     pub fn wrong_range(min: LispObject, max: LispObject, wrong: LispObject) -> !;
 }
@@ -1,20 +1,367 @@
 //! String search routines
 
+use std::{mem, slice};
+
+use libc::{c_int, c_uint};
+
 use remacs_macros::lisp_fn;
 
 use crate::{
+    aho_corasick::Automaton,
+    buffer_text::BufferText,
+    buffers::LispBufferRef,
     lisp::defsubr,
     lisp::LispObject,
-    remacs_sys::{looking_at_1, match_limit, search_command, string_match_1},
+    marker::{buf_bytepos_to_charpos, buf_charpos_to_bytepos},
+    multibyte::LispStringRef,
+    regex::{compile_regexp, re_match, re_search, Registers, Text},
+    remacs_sys::{
+        globals, match_limit, maybe_quit, regoff_t, running_asynch_code, save_search_regs,
+        search_command, set_point_both, string_byte_to_char, string_char_to_byte, xmalloc,
+        xrealloc,
+    },
+    remacs_sys::{EmacsInt, Qnil, Qsearch_failed, Qt},
+    threads::ThreadState,
 };
 
+/// Number of registers allocated the first time match data is stored.
+const RE_NREGS: usize = 30;
+
+/// Whether searching may change the match data.
+fn changing_match_data() -> bool {
+    unsafe { globals.Vinhibit_changing_match_data }.is_nil()
+}
+
+/// Store REGS as the match data, converting each offset with
+/// TO_CHARPOS, and record LAST_THING as what was searched.
+fn set_match_data<F: FnMut(isize) -> isize>(
+    regs: &Registers,
+    mut to_charpos: F,
+    last_thing: LispObject,
+) {
+    let mut thread = ThreadState::current_thread();
+    let needed = regs.start.len() + 1;
+    let search_regs = &mut thread.m_search_regs;
+    let size = |n: usize| n * mem::size_of::<regoff_t>();
+    unsafe {
+        if search_regs.num_regs == 0 {
+            let n = RE_NREGS.max(needed);
+            search_regs.start = xmalloc(size(n)) as *mut regoff_t;
+            search_regs.end = xmalloc(size(n)) as *mut regoff_t;
+            search_regs.num_regs = n as c_uint;
+        } else if (search_regs.num_regs as usize) < needed {
+            search_regs.start =
+                xrealloc(search_regs.start as *mut _, size(needed)) as *mut regoff_t;
+            search_regs.end = xrealloc(search_regs.end as *mut _, size(needed)) as *mut regoff_t;
+            search_regs.num_regs = needed as c_uint;
+        }
+
+        let num_regs = search_regs.num_regs as usize;
+        let starts = slice::from_raw_parts_mut(search_regs.start, num_regs);
+        let ends = slice::from_raw_parts_mut(search_regs.end, num_regs);
+        for (i, (start, end)) in starts.iter_mut().zip(ends.iter_mut()).enumerate() {
+            match (regs.start.get(i), regs.end.get(i)) {
+                (Some(&s), Some(&e)) if s >= 0 => {
+                    *start = to_charpos(s);
+                    *end = to_charpos(e);
+                }
+                _ => {
+                    *start = -1;
+                    *end = -1;
+                }
+            }
+        }
+    }
+    thread.m_last_thing_searched = last_thing;
+}
+
+/// Store REGS, offsets from the start of the accessible portion of
+/// BUFFER, as the match data.
+fn set_buffer_match_data(regs: &Registers, mut buffer: LispBufferRef) {
+    let begv_byte = buffer.begv_byte;
+    let last_thing = buffer.as_lisp_obj();
+    set_match_data(
+        regs,
+        |pos| unsafe { buf_bytepos_to_charpos(buffer.as_mut(), pos + begv_byte) },
+        last_thing,
+    );
+}
+
+fn looking_at_1(regexp: LispStringRef, posix: bool) -> bool {
+    if unsafe { running_asynch_code } {
+        unsafe { save_search_regs() };
+    }
+
+    let program = compile_regexp(regexp, posix);
+    // Do a pending quit right away, to avoid paradoxical behavior.
+    unsafe { maybe_quit() };
+
+    let buffer = ThreadState::current_buffer();
+    let view = BufferText::new(buffer);
+    let text = Text::from_buffer(&view);
+    let pos = (buffer.pt_byte - buffer.begv_byte) as usize;
+    match re_match(&program, &text, pos, text.len()) {
+        Some(regs) => {
+            if changing_match_data() {
+                set_buffer_match_data(&regs, buffer);
+            }
+            true
+        }
+        None => false,
+    }
+}
+
+fn string_match_1(
+    regexp: LispStringRef,
+    string: LispStringRef,
+    start: LispObject,
+    posix: bool,
+) -> LispObject {
+    if unsafe { running_asynch_code } {
+        unsafe { save_search_regs() };
+    }
+
+    let string_obj = string.as_lisp_obj();
+    let pos_byte = if start.is_nil() {
+        0
+    } else {
+        let len = string.len_chars();
+        let mut pos = start.as_fixnum_or_error() as isize;
+        if pos < 0 && -pos <= len {
+            pos += len;
+        } else if pos < 0 || pos > len {
+            args_out_of_range!(string_obj, start);
+        }
+        unsafe { string_char_to_byte(string_obj, pos) }
+    };
+
+    let program = compile_regexp(regexp, posix);
+    let text = Text::from_string(&string);
+    let size = string.len_bytes();
+    let found = re_search(
+        &program,
+        &text,
+        pos_byte as usize,
+        size - pos_byte,
+        size as usize,
+    );
+
+    let changing = changing_match_data();
+    if changing {
+        ThreadState::current_thread().m_last_thing_searched = Qt;
+    }
+    match found {
+        Some(regs) => {
+            if changing {
+                set_match_data(
+                    &regs,
+                    |pos| unsafe { string_byte_to_char(string_obj, pos) },
+                    Qt,
+                );
+            }
+            let index = unsafe { string_byte_to_char(string_obj, regs.start[0]) };
+            LispObject::from(index as EmacsInt)
+        }
+        None => Qnil,
+    }
+}
+
+/// Return true if REGEXP matches just one constant string.
+fn trivial_regexp_p(regexp: LispStringRef) -> bool {
+    let mut bytes = regexp.as_slice().iter();
+    while let Some(&b) = bytes.next() {
+        match b {
+            b'.' | b'*' | b'+' | b'?' | b'[' | b'^' | b'$' => return false,
+            b'\\' => match bytes.next() {
+                None => return false,
+                Some(&c) => {
+                    if b"|()`'bB<>wWsS={}_cC123456789".contains(&c) {
+                        return false;
+                    }
+                }
+            },
+            _ => {}
+        }
+    }
+    true
+}
+
+/// The character and byte positions where a search of BUFFER from
+/// point stops, given the BOUND argument of a search command.
+fn search_limit(buffer: &mut LispBufferRef, bound: LispObject, forward: bool) -> (isize, isize) {
+    if bound.is_nil() {
+        return if forward {
+            (buffer.zv, buffer.zv_byte)
+        } else {
+            (buffer.begv, buffer.begv_byte)
+        };
+    }
+    let lim = bound.as_fixnum_coerce_marker_or_error() as isize;
+    let wrong_side = if forward {
+        lim < buffer.pt
+    } else {
+        lim > buffer.pt
+    };
+    if wrong_side {
+        error!("Invalid search bound (wrong side of point)");
+    }
+    if lim > buffer.zv {
+        (buffer.zv, buffer.zv_byte)
+    } else if lim < buffer.begv {
+        (buffer.begv, buffer.begv_byte)
+    } else {
+        (lim, unsafe { buf_charpos_to_bytepos(buffer.as_mut(), lim) })
+    }
+}
+
+/// Search the current buffer for the COUNTth match of REGEXP in
+/// DIRECTION, as the regexp search commands do.
+fn re_search_command(
+    regexp: LispObject,
+    bound: LispObject,
+    noerror: LispObject,
+    count: LispObject,
+    direction: c_int,
+    posix: bool,
+) -> LispObject {
+    let mut n = EmacsInt::from(direction);
+    if count.is_not_nil() {
+        n *= count.as_fixnum_or_error();
+    }
+    let string = regexp.as_string_or_error();
+
+    if n == 0
+        || string.len_chars() == 0
+        || (trivial_regexp_p(string) && unsafe { globals.Vsearch_spaces_regexp }.is_nil())
+    {
+        // Nothing here needs the regexp engine.
+        return unsafe { search_command(regexp, bound, noerror, count, direction, 1, posix) };
+    }
+
+    let mut buffer = ThreadState::current_buffer();
+    let (lim, lim_byte) = search_limit(&mut buffer, bound, n > 0);
+
+    if unsafe { running_asynch_code } {
+        unsafe { save_search_regs() };
+    }
+
+    let program = compile_regexp(string, posix);
+    // Do a pending quit right away, to avoid paradoxical behavior.
+    unsafe { maybe_quit() };
+
+    let changing = changing_match_data();
+    let view = BufferText::new(buffer);
+    let text = Text::from_buffer(&view);
+    let begv_byte = buffer.begv_byte;
+    let mut pos_byte = buffer.pt_byte;
+    while n != 0 {
+        // Backward matches may not extend past the starting point.
+        let stop = if n < 0 { pos_byte } else { lim_byte };
+        let regs = match re_search(
+            &program,
+            &text,
+            (pos_byte - begv_byte) as usize,
+            lim_byte - pos_byte,
+            (stop - begv_byte) as usize,
+        ) {
+            Some(regs) => regs,
+            None => {
+                if noerror.is_nil() {
+                    xsignal!(Qsearch_failed, regexp);
+                }
+                if !noerror.eq(Qt) {
+                    unsafe { set_point_both(lim, lim_byte) };
+                }
+                return Qnil;
+            }
+        };
+        pos_byte = begv_byte + if n < 0 { regs.start[0] } else { regs.end[0] };
+        if changing {
+            set_buffer_match_data(&regs, buffer);
+        }
+        n -= n.signum();
+        unsafe { maybe_quit() };
+    }
+
+    let pos = unsafe { buf_bytepos_to_charpos(buffer.as_mut(), pos_byte) };
+    unsafe { set_point_both(pos, pos_byte) };
+    LispObject::from(pos as EmacsInt)
+}
+
+/// Search the current buffer from point in DIRECTION for the nearest
+/// occurrence of any string in the list STRINGS.
+fn search_any_command(
+    strings: LispObject,
+    bound: LispObject,
+    noerror: LispObject,
+    direction: c_int,
+) -> LispObject {
+    let patterns: Vec<LispStringRef> = strings
+        .iter_cars()
+        .map(LispObject::as_string_or_error)
+        .collect();
+
+    let forward = direction > 0;
+    let mut buffer = ThreadState::current_buffer();
+    let (lim, lim_byte) = search_limit(&mut buffer, bound, forward);
+
+    if unsafe { running_asynch_code } {
+        unsafe { save_search_regs() };
+    }
+
+    let mut automaton = Automaton::new(&patterns, !forward);
+    // Do a pending quit right away, to avoid paradoxical behavior.
+    unsafe { maybe_quit() };
+
+    let view = BufferText::new(buffer);
+    let text = Text::from_buffer(&view);
+    let begv_byte = buffer.begv_byte;
+    let found = automaton.find(
+        &text,
+        (buffer.pt_byte - begv_byte) as usize,
+        (lim_byte - begv_byte) as usize,
+    );
+    let found = match found {
+        Some(found) => found,
+        None => {
+            if noerror.is_nil() {
+                xsignal!(Qsearch_failed, strings);
+            }
+            if !noerror.eq(Qt) {
+                unsafe { set_point_both(lim, lim_byte) };
+            }
+            return Qnil;
+        }
+    };
+
+    if changing_match_data() {
+        // Group 0 is the whole occurrence and group N + 1 the Nth
+        // string, so callers can tell which string was found.
+        let mut regs = Registers {
+            start: vec![-1; patterns.len() + 1],
+            end: vec![-1; patterns.len() + 1],
+        };
+        for &i in &[0, found.pattern + 1] {
+            regs.start[i] = found.start as isize;
+            regs.end[i] = found.end as isize;
+        }
+        set_buffer_match_data(&regs, buffer);
+    }
+
+    let offset = if forward { found.end } else { found.start };
+    let pos_byte = begv_byte + offset as isize;
+    let pos = unsafe { buf_bytepos_to_charpos(buffer.as_mut(), pos_byte) };
+    unsafe { set_point_both(pos, pos_byte) };
+    LispObject::from(pos as EmacsInt)
+}
+
 /// Return t if text after point matches regular expression REGEXP.
 /// This function modifies the match data that `match-beginning',
 /// `match-end' and `match-data' access; save and restore the match
 /// data if you want to preserve them.
 #[lisp_fn]
-pub fn looking_at(regexp: LispObject) -> LispObject {
-    unsafe { looking_at_1(regexp, false) }
+pub fn looking_at(regexp: LispStringRef) -> bool {
+    looking_at_1(regexp, false)
 }
 
 /// Return t if text after point matches regular expression REGEXP.
@@ -23,8 +370,8 @@ pub fn looking_at(regexp: LispObject) -> LispObject {
 /// `match-end' and `match-data' access; save and restore the match
 /// data if you want to preserve them.
 #[lisp_fn]
-pub fn posix_looking_at(regexp: LispObject) -> LispObject {
-    unsafe { looking_at_1(regexp, true) }
+pub fn posix_looking_at(regexp: LispStringRef) -> bool {
+    looking_at_1(regexp, true)
 }
 
 /// Return index of start of first match for REGEXP in STRING, or nil.
@@ -37,8 +384,8 @@ pub fn posix_looking_at(regexp: LispObject) -> LispObject {
 /// You can use the function `match-string' to extract the substrings
 /// matched by the parenthesis constructions in REGEXP.
 #[lisp_fn(min = "2")]
-pub fn string_match(regexp: LispObject, string: LispObject, start: LispObject) -> LispObject {
-    unsafe { string_match_1(regexp, string, start, false) }
+pub fn string_match(regexp: LispStringRef, string: LispStringRef, start: LispObject) -> LispObject {
+    string_match_1(regexp, string, start, false)
 }
 
 /// Return index of start of first match for REGEXP in STRING, or nil.
@@ -49,8 +396,12 @@ pub fn string_match(regexp: LispObject, string: LispObject, start: LispObject) -
 /// `match-end' and `match-beginning' also give indices of substrings
 /// matched by parenthesis constructs in the pattern.
 #[lisp_fn(min = "2")]
-pub fn posix_string_match(regexp: LispObject, string: LispObject, start: LispObject) -> LispObject {
-    unsafe { string_match_1(regexp, string, start, true) }
+pub fn posix_string_match(
+    regexp: LispStringRef,
+    string: LispStringRef,
+    start: LispObject,
+) -> LispObject {
+    string_match_1(regexp, string, start, true)
 }
 
 /// Search backward from point for STRING.
@@ -111,6 +462,56 @@ pub fn search_forward(
     unsafe { search_command(string, bound, noerror, count, 1, 0, false) }
 }
 
+/// Search backward from point for any of the strings in the list STRINGS.
+/// Set point to the beginning of the occurrence found, and return point.
+/// The occurrence found is the one ending nearest to point; if several
+///   end there, the one listed first in STRINGS wins.
+/// An optional second argument bounds the search; it is a buffer position.
+///   The match found must not begin before that position.  A value of nil
+///   means search to the beginning of the accessible portion of the buffer.
+/// Optional third argument, if t, means if fail just return nil (no error).
+///   If not nil and not t, position at limit of search and return nil.
+///
+/// Search case-sensitivity is determined by the value of the variable
+/// `case-fold-search', which see.
+///
+/// In the match data, subexpression N + 1 is set for the Nth string in
+/// STRINGS, counting from zero, when that is the string found.
+#[lisp_fn(min = "1")]
+pub fn search_backward_any(
+    strings: LispObject,
+    bound: LispObject,
+    noerror: LispObject,
+) -> LispObject {
+    search_any_command(strings, bound, noerror, -1)
+}
+
+/// Search forward from point for any of the strings in the list STRINGS.
+/// Set point to the end of the occurrence found, and return point.
+/// The occurrence found is the one beginning nearest to point; if several
+///   begin there, the one listed first in STRINGS wins.
+/// An optional second argument bounds the search; it is a buffer position.
+///   The match found must not end after that position.  A value of nil
+///   means search to the end of the accessible portion of the buffer.
+/// Optional third argument, if t, means if fail just return nil (no error).
+///   If not nil and not t, move to limit of search and return nil.
+///
+/// Search case-sensitivity is determined by the value of the variable
+/// `case-fold-search', which see.
+///
+/// In the match data, subexpression N + 1 is set for the Nth string in
+/// STRINGS, counting from zero, when that is the string found.  For
+/// instance, after finding "bar" with (search-forward-any '("foo" "bar")),
+/// (match-beginning 2) is non-nil and (match-beginning 1) is nil.
+#[lisp_fn(min = "1")]
+pub fn search_forward_any(
+    strings: LispObject,
+    bound: LispObject,
+    noerror: LispObject,
+) -> LispObject {
+    search_any_command(strings, bound, noerror, 1)
+}
+
 /// Search backward from point for regular expression REGEXP.
 /// This function is almost identical to `re-search-forward', except that
 /// by default it searches backward instead of forward, and the sign of
@@ -124,7 +525,7 @@ pub fn re_search_backward(
     noerror: LispObject,
     count: LispObject,
 ) -> LispObject {
-    unsafe { search_command(regexp, bound, noerror, count, -1, 1, false) }
+    re_search_command(regexp, bound, noerror, count, -1, false)
 }
 
 /// Search forward from point for regular expression REGEXP.
@@ -158,7 +559,7 @@ pub fn re_search_forward(
     noerror: LispObject,
     count: LispObject,
 ) -> LispObject {
-    unsafe { search_command(regexp, bound, noerror, count, 1, 1, false) }
+    re_search_command(regexp, bound, noerror, count, 1, false)
 }
 
 /// Search backward from point for match for regular expression REGEXP.
@@ -189,7 +590,7 @@ pub fn posix_search_backward(
     noerror: LispObject,
     count: LispObject,
 ) -> LispObject {
-    unsafe { search_command(regexp, bound, noerror, count, -1, 1, true) }
+    re_search_command(regexp, bound, noerror, count, -1, true)
 }
 
 /// Search forward from point for regular expression REGEXP.
@@ -220,7 +621,7 @@ pub fn posix_search_forward(
     noerror: LispObject,
     count: LispObject,
 ) -> LispObject {
-    unsafe { search_command(regexp, bound, noerror, count, 1, 1, true) }
+    re_search_command(regexp, bound, noerror, count, 1, true)
 }
 
 /// Return position of start of text matched by last search.
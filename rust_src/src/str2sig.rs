@@ -7,11 +7,53 @@ use libc::{c_char, c_int};
 use std::ffi::CStr;
 use std::str::FromStr;
 
+use remacs_macros::lisp_fn;
+
+use crate::{
+    lisp::{defsubr, LispObject},
+    lists::list,
+    remacs_sys::{EmacsInt, Qnil, Qstringp},
+};
+
 #[cfg(not(unix))]
 const numname: [(&'static str, c_int); 0] = [];
 
-#[cfg(all(unix, not(target_os = "macos")))]
+#[cfg(all(unix, not(target_os = "linux"), not(target_os = "macos")))]
 const numname: [(&str, c_int); 30] = [
+    ("HUP", libc::SIGHUP),
+    ("INT", libc::SIGINT),
+    ("QUIT", libc::SIGQUIT),
+    ("ILL", libc::SIGILL),
+    ("TRAP", libc::SIGTRAP),
+    ("ABRT", libc::SIGABRT),
+    ("IOT", libc::SIGIOT),
+    ("BUS", libc::SIGBUS),
+    ("FPE", libc::SIGFPE),
+    ("KILL", libc::SIGKILL),
+    ("USR1", libc::SIGUSR1),
+    ("USR2", libc::SIGUSR2),
+    ("SEGV", libc::SIGSEGV),
+    ("PIPE", libc::SIGPIPE),
+    ("ALRM", libc::SIGALRM),
+    ("TERM", libc::SIGTERM),
+    ("CHLD", libc::SIGCHLD),
+    ("CONT", libc::SIGCONT),
+    ("STOP", libc::SIGSTOP),
+    ("TSTP", libc::SIGTSTP),
+    ("TTIN", libc::SIGTTIN),
+    ("TTOU", libc::SIGTTOU),
+    ("URG", libc::SIGURG),
+    ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
+    ("PROF", libc::SIGPROF),
+    ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("SYS", libc::SIGSYS),
+];
+
+#[cfg(target_os = "linux")]
+const numname: [(&str, c_int); 33] = [
     ("HUP", libc::SIGHUP),
     ("INT", libc::SIGINT),
     ("QUIT", libc::SIGQUIT),
@@ -37,15 +79,18 @@ const numname: [(&str, c_int); 30] = [
     ("TTOU", libc::SIGTTOU),
     ("URG", libc::SIGURG),
     ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
     ("PROF", libc::SIGPROF),
     ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
     ("POLL", libc::SIGPOLL),
     ("PWR", libc::SIGPWR),
     ("SYS", libc::SIGSYS),
 ];
 
 #[cfg(target_os = "macos")]
-const numname: [(&str, c_int); 27] = [
+const numname: [(&str, c_int); 32] = [
     ("HUP", libc::SIGHUP),
     ("INT", libc::SIGINT),
     ("QUIT", libc::SIGQUIT),
@@ -53,6 +98,7 @@ const numname: [(&str, c_int); 27] = [
     ("TRAP", libc::SIGTRAP),
     ("ABRT", libc::SIGABRT),
     ("IOT", libc::SIGIOT),
+    ("EMT", libc::SIGEMT),
     ("BUS", libc::SIGBUS),
     ("FPE", libc::SIGFPE),
     ("KILL", libc::SIGKILL),
@@ -70,29 +116,175 @@ const numname: [(&str, c_int); 27] = [
     ("TTOU", libc::SIGTTOU),
     ("URG", libc::SIGURG),
     ("XCPU", libc::SIGXCPU),
+    ("XFSZ", libc::SIGXFSZ),
+    ("VTALRM", libc::SIGVTALRM),
     ("PROF", libc::SIGPROF),
     ("WINCH", libc::SIGWINCH),
+    ("IO", libc::SIGIO),
+    ("INFO", libc::SIGINFO),
     ("SYS", libc::SIGSYS),
 ];
 
+#[cfg(target_os = "linux")]
+extern "C" {
+    // These are what the SIGRTMIN and SIGRTMAX macros expand to.  The
+    // values are not constants, because the C library reserves some of
+    // the realtime signals for itself.
+    fn __libc_current_sigrtmin() -> c_int;
+    fn __libc_current_sigrtmax() -> c_int;
+}
+
+/// Return the range of realtime signal numbers, if there are any.
+#[cfg(target_os = "linux")]
+fn realtime_signals() -> Option<(c_int, c_int)> {
+    let (min, max) = unsafe { (__libc_current_sigrtmin(), __libc_current_sigrtmax()) };
+    if 0 < min && min <= max {
+        Some((min, max))
+    } else {
+        None
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn realtime_signals() -> Option<(c_int, c_int)> {
+    None
+}
+
+/// Return the largest valid signal number, which is SIGRTMAX if there
+/// are realtime signals and the largest number in `numname' otherwise.
+fn max_signum() -> c_int {
+    realtime_signals().map_or_else(
+        || numname.iter().map(|&(_, num)| num).max().unwrap_or(0),
+        |(_, max)| max,
+    )
+}
+
+/// Return the number of the signal named NAME, which must be upper case
+/// and have no "SIG" prefix.  Decimal numbers up to the largest signal
+/// number and the realtime signal names "RTMIN", "RTMIN+N", "RTMAX" and
+/// "RTMAX-N" are accepted too.
+pub fn str2signum(name: &str) -> Option<c_int> {
+    if let Ok(signum) = FromStr::from_str(name) {
+        return if 0 <= signum && signum <= max_signum() {
+            Some(signum)
+        } else {
+            None
+        };
+    }
+    if let Some(&(_, signum)) = numname.iter().find(|&&(n, _)| n == name) {
+        return Some(signum);
+    }
+    let (min, max) = realtime_signals()?;
+    let offset = |suffix: &str, sign: char| -> Option<c_int> {
+        if suffix.is_empty() {
+            return Some(0);
+        }
+        if !suffix.starts_with(sign) || !suffix[1..].bytes().all(|c| c.is_ascii_digit()) {
+            return None;
+        }
+        let n: c_int = suffix[1..].parse().ok()?;
+        if 0 <= n && n <= max - min {
+            Some(n)
+        } else {
+            None
+        }
+    };
+    if name.starts_with("RTMIN") {
+        offset(&name[5..], '+').map(|n| min + n)
+    } else if name.starts_with("RTMAX") {
+        offset(&name[5..], '-').map(|n| max - n)
+    } else {
+        None
+    }
+}
+
+/// Return the name of signal number SIGNUM, without the "SIG" prefix.
+/// Realtime signals are named relative to the nearer of RTMIN and RTMAX,
+/// as in "RTMIN+2" or "RTMAX-1".
+pub fn sig2str(signum: c_int) -> Option<String> {
+    if let Some(&(name, _)) = numname.iter().find(|&&(_, num)| num == signum) {
+        return Some(name.to_string());
+    }
+    let (min, max) = realtime_signals()?;
+    if signum < min || max < signum {
+        None
+    } else if signum == min {
+        Some("RTMIN".to_string())
+    } else if signum == max {
+        Some("RTMAX".to_string())
+    } else if signum - min <= (max - min) / 2 {
+        Some(format!("RTMIN+{}", signum - min))
+    } else {
+        Some(format!("RTMAX-{}", max - signum))
+    }
+}
+
 /// Convert the signal name SIGNAME to the signal number
 /// *SIGNUM. Return 0 if successful, -1 otherwise.
 #[no_mangle]
 pub unsafe extern "C" fn str2sig(signame: *const c_char, signum: *mut c_int) -> c_int {
     let s = CStr::from_ptr(signame).to_string_lossy();
-    match FromStr::from_str(s.as_ref()) {
-        Ok(i) => {
-            *signum = i;
-            return 0;
-        }
-        Err(_) => {
-            for &(name, num) in &numname {
-                if name == s {
-                    *signum = num;
-                    return 0;
-                }
-            }
+    match str2signum(s.as_ref()) {
+        Some(num) => {
+            *signum = num;
+            0
         }
+        None => -1,
+    }
+}
+
+/// Return the number of the signal named NAME, or nil if there is none.
+/// NAME is a string or a symbol, such as "TERM", "SIGTERM" or `sigterm';
+/// case does not matter.  On systems with realtime signals, NAME may
+/// also be "RTMIN", "RTMIN+N", "RTMAX" or "RTMAX-N".
+#[lisp_fn]
+pub fn signal_number(name: LispObject) -> Option<c_int> {
+    let name = name.as_symbol().map_or(name, |symbol| symbol.symbol_name());
+    let name = name
+        .as_string()
+        .unwrap_or_else(|| wrong_type!(Qstringp, name));
+    let name = String::from_utf8_lossy(name.as_slice()).to_uppercase();
+    let name = if name.starts_with("SIG") {
+        &name[3..]
+    } else {
+        &name[..]
+    };
+    // Don't let "SIG-1" or "SIG+1" through as numbers.
+    if name.starts_with('-') || name.starts_with('+') {
+        return None;
     }
-    -1
+    str2signum(name)
 }
+
+/// Return the name of the signal numbered NUMBER, or nil if it has none.
+/// The name has no "SIG" prefix, for instance "TERM".  Realtime signals
+/// are named relative to the nearer end of their range, for instance
+/// "RTMIN+2" or "RTMAX-1".
+#[lisp_fn]
+pub fn signal_name(number: EmacsInt) -> LispObject {
+    if number < EmacsInt::from(c_int::min_value()) || EmacsInt::from(c_int::max_value()) < number {
+        return Qnil;
+    }
+    sig2str(number as c_int).map_or(Qnil, |name| LispObject::from(name.as_str()))
+}
+
+/// Return an alist of the signals this system supports.
+/// Each element has the form (NAME . NUMBER), where NAME is a string
+/// such as "TERM", as returned by `signal-name'.  Signals with several
+/// names, such as IOT and ABRT, appear once for each name.  The realtime
+/// signals are included in the forms "RTMIN", "RTMIN+N" and "RTMAX-N".
+#[lisp_fn]
+pub fn signal_names() -> LispObject {
+    let mut names: Vec<LispObject> = numname
+        .iter()
+        .map(|&(name, num)| LispObject::cons(LispObject::from(name), LispObject::from(num)))
+        .collect();
+    if let Some((min, max)) = realtime_signals() {
+        names.extend((min..=max).filter_map(|num| {
+            sig2str(num).map(|name| LispObject::cons(LispObject::from(name.as_str()), num.into()))
+        }));
+    }
+    list(&names)
+}
+
+include!(concat!(env!("OUT_DIR"), "/str2sig_exports.rs"));
@@ -1,8 +1,8 @@
 //! Threading code.
 
-use std::mem;
+use std::{mem, ptr};
 
-use libc;
+use libc::{self, c_uint, c_void};
 
 use remacs_macros::lisp_fn;
 
@@ -10,10 +10,19 @@ use crate::{
     buffers::LispBufferRef,
     lisp::defsubr,
     lisp::{ExternalPtr, LispObject},
-    remacs_sys::Qthreadp,
+    multibyte::LispStringRef,
     remacs_sys::{
-        current_thread as current_thread_pointer, pvec_type, thread_state, Lisp_Type, SPECPDL_INDEX,
+        acquire_global_lock, all_threads, code_convert_string, emacs_abort, flush_stack_call_func,
+        global_lock, initialized, post_acquire_global_lock, record_unwind_protect_void,
+        release_global_lock, run_thread, specbinding, sys_cond_broadcast, sys_cond_destroy,
+        sys_cond_init, sys_cond_signal, sys_cond_wait, sys_thread_create, sys_thread_t,
+        sys_thread_yield, unbind_to, xmalloc, Fsignal,
     },
+    remacs_sys::{
+        current_thread as current_thread_pointer, lisp_mutex_t, pvec_type, thread_state,
+        Lisp_CondVar, Lisp_Mutex, Lisp_Type, SPECPDL_INDEX,
+    },
+    remacs_sys::{Qcondition_variable_p, Qmutexp, Qnil, Qt, Qthreadp, Qutf_8_unix},
 };
 
 pub type ThreadStateRef = ExternalPtr<thread_state>;
@@ -28,6 +37,32 @@ impl ThreadState {
     pub fn current_thread() -> ThreadStateRef {
         unsafe { mem::transmute(current_thread_pointer) }
     }
+
+    /// Iterate over every thread known to Emacs, dead or alive, most
+    /// recently created first.
+    pub fn iter_all() -> ThreadsIter {
+        ThreadsIter {
+            next: unsafe { all_threads },
+        }
+    }
+}
+
+pub struct ThreadsIter {
+    next: *mut thread_state,
+}
+
+impl Iterator for ThreadsIter {
+    type Item = ThreadStateRef;
+
+    fn next(&mut self) -> Option<ThreadStateRef> {
+        if self.next.is_null() {
+            None
+        } else {
+            let thread = ThreadStateRef::new(self.next);
+            self.next = thread.next_thread;
+            Some(thread)
+        }
+    }
 }
 
 impl ThreadStateRef {
@@ -39,6 +74,10 @@ impl ThreadStateRef {
         !self.m_specpdl.is_null()
     }
 
+    pub fn is_current(self) -> bool {
+        self.as_ptr() == unsafe { current_thread_pointer as *const thread_state }
+    }
+
     pub fn as_lisp_obj(self) -> LispObject {
         LispObject::tag_ptr(self, Lisp_Type::Lisp_Vectorlike)
     }
@@ -50,6 +89,12 @@ impl From<LispObject> for ThreadStateRef {
     }
 }
 
+impl From<ThreadStateRef> for LispObject {
+    fn from(thread: ThreadStateRef) -> Self {
+        thread.as_lisp_obj()
+    }
+}
+
 impl LispObject {
     pub fn is_thread(self) -> bool {
         self.as_vectorlike()
@@ -64,6 +109,34 @@ impl LispObject {
         self.as_thread()
             .unwrap_or_else(|| wrong_type!(Qthreadp, self))
     }
+
+    pub fn as_mutex(self) -> Option<LispMutexRef> {
+        if self.is_mutex() {
+            Some(LispMutexRef::new(self.get_untaggedptr() as *mut Lisp_Mutex))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_mutex_or_error(self) -> LispMutexRef {
+        self.as_mutex()
+            .unwrap_or_else(|| wrong_type!(Qmutexp, self))
+    }
+
+    pub fn as_condition_variable(self) -> Option<LispCondVarRef> {
+        if self.is_condition_variable() {
+            Some(LispCondVarRef::new(
+                self.get_untaggedptr() as *mut Lisp_CondVar
+            ))
+        } else {
+            None
+        }
+    }
+
+    pub fn as_condition_variable_or_error(self) -> LispCondVarRef {
+        self.as_condition_variable()
+            .unwrap_or_else(|| wrong_type!(Qcondition_variable_p, self))
+    }
 }
 
 // FIXME: The right thing to do is start indexing thread.m_specpdl as
@@ -103,4 +176,582 @@ pub fn thread_blocker(thread: ThreadStateRef) -> LispObject {
     thread.event_object
 }
 
+/// A mutex in lisp is represented by a system condition variable.
+/// The system mutex associated with this condition variable is the
+/// global lock.
+///
+/// Using a condition variable lets us implement interruptibility for
+/// lisp mutexes.
+impl lisp_mutex_t {
+    fn init(&mut self) {
+        self.owner = ptr::null_mut();
+        self.count = 0;
+        unsafe { sys_cond_init(&mut self.condition) };
+    }
+
+    fn is_owned(&self) -> bool {
+        self.owner == unsafe { current_thread_pointer }
+    }
+}
+
+/// Lock MUTEX for thread LOCKER, setting its lock count to NEW_COUNT,
+/// if non-zero, or to 1 otherwise.
+///
+/// If MUTEX is locked by LOCKER, NEW_COUNT must be zero, and the
+/// MUTEX's lock count will be incremented.
+///
+/// If MUTEX is locked by another thread, this function will release
+/// the global lock, giving other threads a chance to run, and will
+/// wait for the MUTEX to become unlocked; when MUTEX becomes unlocked,
+/// and will then re-acquire the global lock.
+///
+/// Return true if the function waited for the MUTEX to become unlocked
+/// (meaning other threads could have run during the wait).
+///
+/// MUTEX is a raw pointer because other threads change it while we
+/// wait on its condition.
+unsafe fn lisp_mutex_lock_for_thread(
+    mutex: *mut lisp_mutex_t,
+    locker: *mut thread_state,
+    new_count: c_uint,
+) -> bool {
+    if (*mutex).owner.is_null() {
+        (*mutex).owner = locker;
+        (*mutex).count = if new_count == 0 { 1 } else { new_count };
+        return false;
+    }
+    if (*mutex).owner == locker {
+        debug_assert!(new_count == 0);
+        (*mutex).count += 1;
+        return false;
+    }
+
+    (*locker).wait_condvar = &mut (*mutex).condition;
+    while !(*mutex).owner.is_null() && (new_count != 0 || (*locker).error_symbol.is_nil()) {
+        sys_cond_wait(&mut (*mutex).condition, &mut global_lock);
+    }
+    (*locker).wait_condvar = ptr::null_mut();
+
+    if new_count == 0 && (*locker).error_symbol.is_not_nil() {
+        return true;
+    }
+
+    (*mutex).owner = locker;
+    (*mutex).count = if new_count == 0 { 1 } else { new_count };
+
+    true
+}
+
+unsafe fn lisp_mutex_lock(mutex: *mut lisp_mutex_t, new_count: c_uint) -> bool {
+    lisp_mutex_lock_for_thread(mutex, current_thread_pointer, new_count)
+}
+
+/// Decrement MUTEX's lock count.  If the lock count becomes zero after
+/// decrementing it, meaning the mutex is now unlocked, broadcast that
+/// to all the threads that might be waiting to lock the mutex.  This
+/// function signals an error if MUTEX is locked by a thread other than
+/// the current one.  Return true if the mutex becomes unlocked.
+unsafe fn lisp_mutex_unlock(mutex: *mut lisp_mutex_t) -> bool {
+    if !(*mutex).is_owned() {
+        error!("Cannot unlock mutex owned by another thread");
+    }
+
+    (*mutex).count -= 1;
+    if (*mutex).count > 0 {
+        return false;
+    }
+
+    (*mutex).owner = ptr::null_mut();
+    sys_cond_broadcast(&mut (*mutex).condition);
+
+    true
+}
+
+/// Like `lisp_mutex_unlock`, but sets MUTEX's lock count to zero
+/// regardless of its value.  Return the previous lock count.
+unsafe fn lisp_mutex_unlock_for_wait(mutex: *mut lisp_mutex_t) -> c_uint {
+    let result = (*mutex).count;
+
+    // Ensured by condvar code.
+    debug_assert!((*mutex).is_owned());
+
+    (*mutex).count = 0;
+    (*mutex).owner = ptr::null_mut();
+    sys_cond_broadcast(&mut (*mutex).condition);
+
+    result
+}
+
+pub type LispMutexRef = ExternalPtr<Lisp_Mutex>;
+
+impl LispMutexRef {
+    fn lock_ptr(mut self) -> *mut lisp_mutex_t {
+        unsafe { &mut (*self.as_mut()).mutex }
+    }
+}
+
+impl From<LispObject> for LispMutexRef {
+    fn from(o: LispObject) -> Self {
+        o.as_mutex_or_error()
+    }
+}
+
+impl From<LispMutexRef> for LispObject {
+    fn from(mutex: LispMutexRef) -> Self {
+        LispObject::tag_ptr(mutex, Lisp_Type::Lisp_Vectorlike)
+    }
+}
+
+/// Destroy the system condition variable of a mutex being garbage
+/// collected.
+#[no_mangle]
+pub extern "C" fn finalize_one_mutex(mutex: *mut Lisp_Mutex) {
+    unsafe { sys_cond_destroy(&mut (*mutex).mutex.condition) };
+}
+
+/// Create a mutex.
+/// A mutex provides a synchronization point for threads.
+/// Only one thread at a time can hold a mutex.  Other threads attempting
+/// to acquire it will block until the mutex is available.
+///
+/// A thread can acquire a mutex any number of times.
+///
+/// NAME, if given, is used as the name of the mutex.  The name is
+/// informational only.
+#[lisp_fn(min = "0")]
+pub fn make_mutex(name: Option<LispStringRef>) -> LispMutexRef {
+    let mut mutex = LispMutexRef::new(allocate_pseudovector!(
+        Lisp_Mutex,
+        mutex,
+        pvec_type::PVEC_MUTEX
+    ));
+    mutex.name = name.into();
+    mutex.mutex.init();
+    mutex
+}
+
+unsafe extern "C" fn mutex_lock_callback(arg: *mut c_void) {
+    let mutex = LispMutexRef::new(arg as *mut Lisp_Mutex);
+    let self_ = current_thread_pointer;
+
+    // Calling lisp_mutex_lock might yield to other threads while this
+    // one waits for the mutex to become unlocked, so we need to
+    // announce us as the current thread by calling
+    // post_acquire_global_lock.
+    if lisp_mutex_lock(mutex.lock_ptr(), 0) {
+        post_acquire_global_lock(self_);
+    }
+}
+
+extern "C" fn do_unwind_mutex_lock() {
+    ThreadState::current_thread().event_object = Qnil;
+}
+
+/// Acquire a mutex.
+/// If the current thread already owns MUTEX, increment the count and
+/// return.
+/// Otherwise, if no thread owns MUTEX, make the current thread own it.
+/// Otherwise, block until MUTEX is available, or until the current thread
+/// is signaled using `thread-signal'.
+/// Note that calls to `mutex-lock' and `mutex-unlock' must be paired.
+#[lisp_fn]
+pub fn mutex_lock(mutex: LispMutexRef) -> LispObject {
+    let count = c_specpdl_index();
+
+    ThreadState::current_thread().event_object = mutex.into();
+    unsafe {
+        record_unwind_protect_void(Some(do_unwind_mutex_lock));
+        flush_stack_call_func(Some(mutex_lock_callback), mutex.as_ptr() as *mut c_void);
+        unbind_to(count, Qnil)
+    }
+}
+
+unsafe extern "C" fn mutex_unlock_callback(arg: *mut c_void) {
+    let mutex = LispMutexRef::new(arg as *mut Lisp_Mutex);
+    let self_ = current_thread_pointer;
+
+    if lisp_mutex_unlock(mutex.lock_ptr()) {
+        // FIXME: is this call needed?
+        post_acquire_global_lock(self_);
+    }
+}
+
+/// Release the mutex.
+/// If this thread does not own MUTEX, signal an error.
+/// Otherwise, decrement the mutex's count.  If the count is zero,
+/// release MUTEX.
+#[lisp_fn]
+pub fn mutex_unlock(mutex: LispMutexRef) {
+    unsafe { flush_stack_call_func(Some(mutex_unlock_callback), mutex.as_ptr() as *mut c_void) };
+}
+
+/// Return the name of MUTEX.
+/// If no name was given when MUTEX was created, return nil.
+#[lisp_fn]
+pub fn mutex_name(mutex: LispMutexRef) -> LispObject {
+    mutex.name
+}
+
+pub type LispCondVarRef = ExternalPtr<Lisp_CondVar>;
+
+impl LispCondVarRef {
+    fn mutex(self) -> LispMutexRef {
+        self.mutex.as_mutex_or_error()
+    }
+
+    /// Signal an error unless the current thread holds the mutex
+    /// associated with this condition variable.
+    fn check_mutex_owned(self) {
+        if !self.mutex().mutex.is_owned() {
+            error!("Condition variable's mutex is not held by current thread");
+        }
+    }
+}
+
+impl From<LispObject> for LispCondVarRef {
+    fn from(o: LispObject) -> Self {
+        o.as_condition_variable_or_error()
+    }
+}
+
+impl From<LispCondVarRef> for LispObject {
+    fn from(cvar: LispCondVarRef) -> Self {
+        LispObject::tag_ptr(cvar, Lisp_Type::Lisp_Vectorlike)
+    }
+}
+
+/// Destroy the system condition variable of a condition variable
+/// being garbage collected.
+#[no_mangle]
+pub extern "C" fn finalize_one_condvar(condvar: *mut Lisp_CondVar) {
+    unsafe { sys_cond_destroy(&mut (*condvar).cond) };
+}
+
+/// Make a condition variable associated with MUTEX.
+/// A condition variable provides a way for a thread to sleep while
+/// waiting for a state change.
+///
+/// MUTEX is the mutex associated with this condition variable.
+/// NAME, if given, is the name of this condition variable.  The name is
+/// informational only.
+#[lisp_fn(min = "1")]
+pub fn make_condition_variable(mutex: LispMutexRef, name: Option<LispStringRef>) -> LispCondVarRef {
+    let mut cvar = LispCondVarRef::new(allocate_pseudovector!(
+        Lisp_CondVar,
+        cond,
+        pvec_type::PVEC_CONDVAR
+    ));
+    unsafe {
+        let offset = offset_of!(Lisp_CondVar, cond);
+        ptr::write_bytes(
+            (cvar.as_mut() as *mut u8).add(offset),
+            0,
+            mem::size_of::<Lisp_CondVar>() - offset,
+        );
+    }
+    cvar.mutex = mutex.into();
+    cvar.name = name.into();
+    unsafe { sys_cond_init(&mut cvar.cond) };
+    cvar
+}
+
+unsafe extern "C" fn condition_wait_callback(arg: *mut c_void) {
+    let cvar = LispCondVarRef::new(arg as *mut Lisp_CondVar);
+    let mutex = cvar.mutex();
+    let self_ = current_thread_pointer;
+
+    (*self_).event_object = cvar.into();
+    let saved_count = lisp_mutex_unlock_for_wait(mutex.lock_ptr());
+    // If signaled while unlocking, skip the wait but reacquire the lock.
+    if (*self_).error_symbol.is_nil() {
+        let cond = &mut (*(arg as *mut Lisp_CondVar)).cond as *mut _;
+        (*self_).wait_condvar = cond;
+        // This call could switch to another thread.
+        sys_cond_wait(cond, &mut global_lock);
+        (*self_).wait_condvar = ptr::null_mut();
+    }
+    (*self_).event_object = Qnil;
+    // Since sys_cond_wait could switch threads, we need to lock the
+    // mutex for the thread which was the current when we were called,
+    // otherwise lisp_mutex_lock will record the wrong thread as the
+    // owner of the mutex lock.
+    lisp_mutex_lock_for_thread(mutex.lock_ptr(), self_, saved_count);
+    // Calling lisp_mutex_lock_for_thread might yield to other threads
+    // while this one waits for the mutex to become unlocked, so we need
+    // to announce us as the current thread by calling
+    // post_acquire_global_lock.
+    post_acquire_global_lock(self_);
+}
+
+/// Wait for the condition variable COND to be notified.
+/// COND is the condition variable to wait on.
+///
+/// The mutex associated with COND must be held when this is called.
+/// It is an error if it is not held.
+///
+/// This releases the mutex and waits for COND to be notified or for
+/// this thread to be signaled with `thread-signal'.  When
+/// `condition-wait' returns, COND's mutex will again be locked by
+/// this thread.
+#[lisp_fn]
+pub fn condition_wait(cond: LispCondVarRef) {
+    cond.check_mutex_owned();
+    unsafe { flush_stack_call_func(Some(condition_wait_callback), cond.as_ptr() as *mut c_void) };
+}
+
+/// Used to communicate arguments to `condition_notify_callback`.
+struct NotifyArgs {
+    cvar: LispCondVarRef,
+    all: bool,
+}
+
+unsafe extern "C" fn condition_notify_callback(arg: *mut c_void) {
+    let args = &*(arg as *const NotifyArgs);
+    let mut cvar = args.cvar;
+    let mutex = cvar.mutex();
+    let self_ = current_thread_pointer;
+
+    let saved_count = lisp_mutex_unlock_for_wait(mutex.lock_ptr());
+    if args.all {
+        sys_cond_broadcast(&mut cvar.cond);
+    } else {
+        sys_cond_signal(&mut cvar.cond);
+    }
+    // Calling lisp_mutex_lock might yield to other threads while this
+    // one waits for the mutex to become unlocked, so we need to
+    // announce us as the current thread by calling
+    // post_acquire_global_lock.
+    lisp_mutex_lock(mutex.lock_ptr(), saved_count);
+    post_acquire_global_lock(self_);
+}
+
+/// Notify COND, a condition variable.
+/// This wakes a thread waiting on COND.
+/// If ALL is non-nil, all waiting threads are awoken.
+///
+/// The mutex associated with COND must be held when this is called.
+/// It is an error if it is not held.
+///
+/// This releases COND's mutex when notifying COND.  When
+/// `condition-notify' returns, the mutex will again be locked by this
+/// thread.
+#[lisp_fn(min = "1")]
+pub fn condition_notify(cond: LispCondVarRef, all: bool) {
+    cond.check_mutex_owned();
+    let mut args = NotifyArgs { cvar: cond, all };
+    unsafe {
+        flush_stack_call_func(
+            Some(condition_notify_callback),
+            &mut args as *mut NotifyArgs as *mut c_void,
+        )
+    };
+}
+
+/// Return the mutex associated with condition variable COND.
+#[lisp_fn]
+pub fn condition_mutex(cond: LispCondVarRef) -> LispObject {
+    cond.mutex
+}
+
+/// Return the name of condition variable COND.
+/// If no name was given when COND was created, return nil.
+#[lisp_fn]
+pub fn condition_name(cond: LispCondVarRef) -> LispObject {
+    cond.name
+}
+
+unsafe extern "C" fn yield_callback(_ignore: *mut c_void) {
+    let self_ = current_thread_pointer;
+
+    release_global_lock();
+    sys_thread_yield();
+    acquire_global_lock(self_);
+}
+
+/// Yield the CPU to another thread.
+#[lisp_fn]
+pub fn thread_yield() {
+    unsafe { flush_stack_call_func(Some(yield_callback), ptr::null_mut()) };
+}
+
+declare_GC_protected_static!(last_thread_error, Qnil);
+
+/// Remember the error that killed a thread, for `thread-last-error'.
+/// This is the handler `run_thread' passes to `internal_condition_case'.
+#[no_mangle]
+pub extern "C" fn record_thread_error(error_form: LispObject) -> LispObject {
+    unsafe { last_thread_error = error_form };
+    error_form
+}
+
+/// Start a new thread and run FUNCTION in it.
+/// When the function exits, the thread dies.
+/// If NAME is given, it must be a string; it names the new thread.
+#[lisp_fn(min = "1")]
+pub fn make_thread(function: LispObject, name: Option<LispStringRef>) -> ThreadStateRef {
+    // Can't start a thread in temacs.
+    if !unsafe { initialized } {
+        unsafe { emacs_abort() };
+    }
+
+    let mut new_thread = ThreadStateRef::new(allocate_pseudovector!(
+        thread_state,
+        m_stack_bottom,
+        pvec_type::PVEC_THREAD
+    ));
+    unsafe {
+        let offset = offset_of!(thread_state, m_stack_bottom);
+        ptr::write_bytes(
+            (new_thread.as_mut() as *mut u8).add(offset),
+            0,
+            mem::size_of::<thread_state>() - offset,
+        );
+    }
+
+    new_thread.function = function;
+    new_thread.name = name.into();
+    // copy from parent?
+    new_thread.m_last_thing_searched = Qnil;
+    new_thread.m_saved_last_thing_searched = Qnil;
+    new_thread.m_current_buffer = unsafe { (*current_thread_pointer).m_current_buffer };
+    new_thread.error_symbol = Qnil;
+    new_thread.error_data = Qnil;
+    new_thread.event_object = Qnil;
+
+    new_thread.m_specpdl_size = 50;
+    unsafe {
+        let specpdl =
+            xmalloc((1 + new_thread.m_specpdl_size as usize) * mem::size_of::<specbinding>())
+                as *mut specbinding;
+        // Skip the dummy entry.
+        new_thread.m_specpdl = specpdl.add(1);
+        new_thread.m_specpdl_ptr = new_thread.m_specpdl;
+
+        sys_cond_init(&mut new_thread.thread_condvar);
+
+        // We'll need locking here eventually.
+        new_thread.next_thread = all_threads;
+        all_threads = new_thread.as_mut();
+    }
+
+    let c_name = name.map_or(ptr::null(), |name| {
+        let encoded =
+            unsafe { code_convert_string(name.into(), Qutf_8_unix, Qt, true, true, true) };
+        encoded.as_string_or_error().const_sdata_ptr()
+    });
+
+    let mut thr: sys_thread_t = unsafe { mem::zeroed() };
+    if unsafe {
+        sys_thread_create(
+            &mut thr,
+            c_name,
+            Some(run_thread),
+            new_thread.as_mut() as *mut c_void,
+        )
+    } == 0
+    {
+        // Restore the previous situation.
+        unsafe { all_threads = (*all_threads).next_thread };
+        error!("Could not start a new thread");
+    }
+
+    // FIXME: race here where new thread might not be filled in?
+    new_thread
+}
+
+unsafe extern "C" fn thread_signal_callback(arg: *mut c_void) {
+    let tstate = arg as *mut thread_state;
+    let self_ = current_thread_pointer;
+
+    sys_cond_broadcast((*tstate).wait_condvar);
+    post_acquire_global_lock(self_);
+}
+
+/// Signal an error in a thread.
+/// This acts like `signal', but arranges for the signal to be raised
+/// in THREAD.  If THREAD is the current thread, acts just like `signal'.
+/// This will interrupt a blocked call to `mutex-lock', `condition-wait',
+/// or `thread-join' in the target thread.
+#[lisp_fn]
+pub fn thread_signal(mut thread: ThreadStateRef, error_symbol: LispObject, data: LispObject) {
+    if thread.is_current() {
+        unsafe { Fsignal(error_symbol, data) };
+    }
+
+    // What to do if thread is already signaled?
+    // What if error_symbol is nil?
+    thread.error_symbol = error_symbol;
+    thread.error_data = data;
+
+    if !thread.wait_condvar.is_null() {
+        unsafe {
+            flush_stack_call_func(Some(thread_signal_callback), thread.as_mut() as *mut c_void)
+        };
+    }
+}
+
+unsafe extern "C" fn thread_join_callback(arg: *mut c_void) {
+    let tstate = ThreadStateRef::new(arg as *mut thread_state);
+    let self_ = current_thread_pointer;
+
+    (*self_).event_object = tstate.into();
+    (*self_).wait_condvar = &mut (*(arg as *mut thread_state)).thread_condvar;
+    while tstate.is_alive() && (*self_).error_symbol.is_nil() {
+        sys_cond_wait((*self_).wait_condvar, &mut global_lock);
+    }
+
+    (*self_).wait_condvar = ptr::null_mut();
+    (*self_).event_object = Qnil;
+    post_acquire_global_lock(self_);
+}
+
+/// Wait for THREAD to exit.
+/// This blocks the current thread until THREAD exits or until
+/// the current thread is signaled.
+/// It is an error for a thread to try to join itself.
+#[lisp_fn]
+pub fn thread_join(mut thread: ThreadStateRef) {
+    if thread.is_current() {
+        error!("Cannot join current thread");
+    }
+
+    if thread.is_alive() {
+        unsafe {
+            flush_stack_call_func(Some(thread_join_callback), thread.as_mut() as *mut c_void)
+        };
+    }
+}
+
+/// Return a list of all the live threads.
+#[lisp_fn(name = "all-threads", c_name = "all_threads")]
+pub fn all_threads_lisp() -> LispObject {
+    ThreadState::iter_all()
+        .filter(|thread| thread.is_alive())
+        .fold(Qnil, |result, thread| {
+            LispObject::cons(thread.into(), result)
+        })
+}
+
+/// Return a list describing all the live threads.
+/// Each element has the form (THREAD BLOCKER BUFFER), where BLOCKER is
+/// what `thread--blocker' returns for THREAD and BUFFER is the buffer
+/// that is current in THREAD.  The threads are in the same order as in
+/// `all-threads'.
+#[lisp_fn]
+pub fn thread_list_all() -> LispObject {
+    ThreadState::iter_all()
+        .filter(|thread| thread.is_alive())
+        .fold(Qnil, |result, thread| {
+            let buffer = LispBufferRef::from_ptr(thread.m_current_buffer as *mut c_void)
+                .map_or(Qnil, LispObject::from);
+            LispObject::cons(list!(thread.into(), thread.event_object, buffer), result)
+        })
+}
+
+/// Return the last error form recorded by a dying thread.
+#[lisp_fn]
+pub fn thread_last_error() -> LispObject {
+    unsafe { last_thread_error }
+}
+
 include!(concat!(env!("OUT_DIR"), "/threads_exports.rs"));
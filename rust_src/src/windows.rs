@@ -373,7 +373,7 @@ pub fn windowp(object: LispObject) -> bool {
 ///
 /// A live window is a window that displays a buffer.
 /// Internal windows and deleted windows are not live.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn window_live_p(object: Option<LispWindowRef>) -> bool {
     object.map_or(false, |m| m.is_live())
 }
@@ -422,7 +422,7 @@ pub fn window_buffer(window: LispWindowValidOrSelected) -> LispObject {
 /// Return t if OBJECT is a valid window and nil otherwise.
 /// A valid window is either a window that displays a buffer or an internal
 /// window.  Windows that have been deleted are not valid.
-#[lisp_fn]
+#[lisp_fn(min = "1")]
 pub fn window_valid_p(object: Option<LispWindowRef>) -> bool {
     object.map_or(false, |w| w.is_valid())
 }
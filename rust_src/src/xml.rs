@@ -1,14 +1,1173 @@
-//! Interface to libxml2.
+//! Parsing of XML and HTML documents.
+//!
+//! This used to be an interface to libxml2.  The parser below is
+//! written to produce the same trees libxml2 did: XML documents are
+//! parsed strictly and any well-formedness error makes the whole parse
+//! return nil, while HTML is parsed leniently, the way libxml2's HTML
+//! parser recovers from broken markup.
+
+use libc::{c_char, ptrdiff_t};
 
 use remacs_macros::lisp_fn;
 
 use crate::{
+    buffer_text::BufferText,
+    buffers::validate_region,
     lisp::defsubr,
     lisp::LispObject,
-    remacs_sys::Qnil,
-    remacs_sys::{init_libxml2_functions, parse_region},
+    lists::list,
+    obarray::intern,
+    remacs_sys::{buf_charpos_to_bytepos, make_specified_string},
+    remacs_sys::{Qnil, Qtop},
+    threads::ThreadState,
 };
 
+/// Documents nested deeper than this are rejected, as libxml2 does
+/// without its `XML_PARSE_HUGE' option.
+const MAX_DEPTH: usize = 256;
+
+enum Node {
+    Element(Element),
+    Text(String),
+    CData(String),
+    Comment(String),
+}
+
+struct Element {
+    /// The name the element is reported under.
+    name: String,
+    /// The name as written in the start tag, which the end tag has to
+    /// match.
+    qname: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Node>,
+    /// Namespace prefixes declared on this element.
+    prefixes: Vec<String>,
+    /// Whether `xml:space="preserve"' is in effect.
+    preserve_space: bool,
+}
+
+impl Element {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            qname: name.to_string(),
+            attributes: Vec::new(),
+            children: Vec::new(),
+            prefixes: Vec::new(),
+            preserve_space: false,
+        }
+    }
+}
+
+/// A fatal well-formedness error in an XML document.
+struct SyntaxError;
+
+type ParseResult<T> = Result<T, SyntaxError>;
+
+struct Parser<'a> {
+    text: &'a str,
+    pos: usize,
+    html: bool,
+    has_doctype: bool,
+    seen_head: bool,
+    seen_body: bool,
+    /// The elements that are currently open, innermost last.
+    open: Vec<Element>,
+    /// The top-level nodes of the document.
+    document: Vec<Node>,
+}
+
+impl<'a> Parser<'a> {
+    fn new(text: &'a str, html: bool) -> Self {
+        Self {
+            text,
+            pos: 0,
+            html,
+            has_doctype: false,
+            seen_head: false,
+            seen_body: false,
+            open: Vec::new(),
+            document: Vec::new(),
+        }
+    }
+
+    fn parse(mut self) -> ParseResult<Vec<Node>> {
+        if self.text.starts_with('\u{FEFF}') {
+            self.pos = '\u{FEFF}'.len_utf8();
+        }
+
+        while self.pos < self.text.len() {
+            if self.looking_at("<!--") {
+                self.comment()?;
+            } else if self.looking_at("<![CDATA[") && !self.html {
+                self.cdata()?;
+            } else if self.looking_at_ignore_case("<!doctype") {
+                self.doctype()?;
+            } else if self.looking_at("<!") && self.html {
+                // Bogus markup declarations are dropped.
+                self.skip_past(">");
+            } else if self.looking_at("<?") {
+                self.processing_instruction()?;
+            } else if self.looking_at("</") && self.is_name_start(self.pos + 2) {
+                self.end_tag()?;
+            } else if self.looking_at("<") && self.is_name_start(self.pos + 1) {
+                self.start_tag()?;
+            } else {
+                self.text()?;
+            }
+        }
+
+        if self.html {
+            while !self.open.is_empty() {
+                self.close_element();
+            }
+        } else if !self.open.is_empty() || !self.has_root() {
+            return Err(SyntaxError);
+        }
+
+        Ok(self.document)
+    }
+
+    // Low-level scanning.
+
+    fn rest(&self) -> &'a str {
+        &self.text[self.pos..]
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.text.as_bytes().get(self.pos).cloned()
+    }
+
+    fn looking_at(&self, s: &str) -> bool {
+        self.rest().starts_with(s)
+    }
+
+    fn looking_at_ignore_case(&self, s: &str) -> bool {
+        let rest = self.rest().as_bytes();
+        rest.len() >= s.len() && rest[..s.len()].eq_ignore_ascii_case(s.as_bytes())
+    }
+
+    fn is_name_start(&self, pos: usize) -> bool {
+        match self.text.as_bytes().get(pos) {
+            Some(&b) => b.is_ascii_alphabetic() || b == b'_' || b == b':' || b >= 0x80,
+            None => false,
+        }
+    }
+
+    fn is_name_char(b: u8) -> bool {
+        b.is_ascii_alphanumeric() || b"_:-.".contains(&b) || b >= 0x80
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().map_or(false, is_blank) {
+            self.pos += 1;
+        }
+    }
+
+    /// Move past the next occurrence of DELIMITER and return the text
+    /// before it, or return None and move to the end of the input if
+    /// there is no such occurrence.
+    fn skip_past(&mut self, delimiter: &str) -> Option<&'a str> {
+        let rest = self.rest();
+        match rest.find(delimiter) {
+            Some(i) => {
+                self.pos += i + delimiter.len();
+                Some(&rest[..i])
+            }
+            None => {
+                self.pos = self.text.len();
+                None
+            }
+        }
+    }
+
+    fn name(&mut self) -> &'a str {
+        let start = self.pos;
+        while self.peek().map_or(false, Self::is_name_char) {
+            self.pos += 1;
+        }
+        &self.text[start..self.pos]
+    }
+
+    // Markup.
+
+    fn comment(&mut self) -> ParseResult<()> {
+        self.pos += "<!--".len();
+        let start = self.pos;
+        let content = match self.skip_past("-->") {
+            Some(content) => content,
+            None if self.html => &self.text[start..],
+            None => return Err(SyntaxError),
+        };
+        let comment = Node::Comment(content.to_string());
+        self.append(comment);
+        Ok(())
+    }
+
+    fn cdata(&mut self) -> ParseResult<()> {
+        self.pos += "<![CDATA[".len();
+        let content = self.skip_past("]]>").ok_or(SyntaxError)?;
+        if self.open.is_empty() {
+            return Err(SyntaxError);
+        }
+        self.append(Node::CData(content.to_string()));
+        Ok(())
+    }
+
+    fn doctype(&mut self) -> ParseResult<()> {
+        if !self.html && (self.has_doctype || self.has_root()) {
+            return Err(SyntaxError);
+        }
+        self.has_doctype = true;
+
+        // Skip the declaration, including any internal subset.
+        let bytes = self.text.as_bytes();
+        let mut quote = None;
+        let mut in_subset = false;
+        while let Some(&b) = bytes.get(self.pos) {
+            self.pos += 1;
+            match (quote, b) {
+                (Some(q), _) if q == b => quote = None,
+                (Some(_), _) => {}
+                (None, b'"') | (None, b'\'') => quote = Some(b),
+                (None, b'[') if !self.html => in_subset = true,
+                (None, b']') => in_subset = false,
+                (None, b'>') if !in_subset => return Ok(()),
+                _ => {}
+            }
+        }
+
+        if self.html {
+            Ok(())
+        } else {
+            Err(SyntaxError)
+        }
+    }
+
+    fn processing_instruction(&mut self) -> ParseResult<()> {
+        // The XML declaration and other processing instructions do not
+        // show up in the tree.
+        let terminator = if self.html { ">" } else { "?>" };
+        match self.skip_past(terminator) {
+            Some(_) => Ok(()),
+            None if self.html => Ok(()),
+            None => Err(SyntaxError),
+        }
+    }
+
+    fn start_tag(&mut self) -> ParseResult<()> {
+        self.pos += 1;
+        let qname = self.name();
+        let mut attributes: Vec<(String, String)> = Vec::new();
+        let mut empty = false;
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                None if self.html => return Ok(()),
+                None => return Err(SyntaxError),
+                Some(b'>') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'/') if self.looking_at("/>") => {
+                    self.pos += 2;
+                    empty = true;
+                    break;
+                }
+                _ => {}
+            }
+
+            let attribute = if self.html {
+                self.html_attribute()
+            } else {
+                Some(self.xml_attribute()?)
+            };
+
+            if let Some((name, value)) = attribute {
+                if attributes.iter().any(|&(ref n, _)| *n == name) {
+                    // libxml2 keeps the first occurrence of a repeated
+                    // HTML attribute; in XML it is an error.
+                    if !self.html {
+                        return Err(SyntaxError);
+                    }
+                } else {
+                    attributes.push((name, value));
+                }
+            }
+        }
+
+        if self.html {
+            self.html_start_element(qname.to_ascii_lowercase(), attributes, empty);
+            Ok(())
+        } else {
+            self.xml_start_element(qname, attributes, empty)
+        }
+    }
+
+    fn xml_attribute(&mut self) -> ParseResult<(String, String)> {
+        if !self.is_name_start(self.pos) {
+            return Err(SyntaxError);
+        }
+        let name = self.name().to_string();
+        self.skip_whitespace();
+        if self.peek() != Some(b'=') {
+            return Err(SyntaxError);
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+        let quote = match self.peek() {
+            Some(q) if q == b'"' || q == b'\'' => q,
+            _ => return Err(SyntaxError),
+        };
+        self.pos += 1;
+        let raw = self
+            .skip_past(if quote == b'"' { "\"" } else { "'" })
+            .ok_or(SyntaxError)?;
+        if raw.contains('<') {
+            return Err(SyntaxError);
+        }
+        Ok((name, self.decode(raw, true)?))
+    }
+
+    /// Parse an HTML attribute.  Attributes without a value are
+    /// dropped, except for the boolean ones, which get their own name
+    /// as value.
+    fn html_attribute(&mut self) -> Option<(String, String)> {
+        let start = self.pos;
+        while let Some(b) = self.peek() {
+            if is_blank(b) || b == b'=' || b == b'>' || (b == b'/' && self.looking_at("/>")) {
+                break;
+            }
+            self.pos += 1;
+        }
+        if self.pos == start {
+            // A stray `=' or `/'; skip it.
+            self.pos += 1;
+            return None;
+        }
+        let name = self.text[start..self.pos].to_ascii_lowercase();
+
+        self.skip_whitespace();
+        if self.peek() != Some(b'=') {
+            return if HTML_BOOLEAN_ATTRIBUTES.contains(&name.as_str()) {
+                Some((name.clone(), name))
+            } else {
+                None
+            };
+        }
+        self.pos += 1;
+        self.skip_whitespace();
+
+        let raw = match self.peek() {
+            Some(q) if q == b'"' || q == b'\'' => {
+                self.pos += 1;
+                let start = self.pos;
+                let quote = if q == b'"' { "\"" } else { "'" };
+                self.skip_past(quote).unwrap_or(&self.text[start..])
+            }
+            _ => {
+                let start = self.pos;
+                while self.peek().map_or(false, |b| !is_blank(b) && b != b'>') {
+                    self.pos += 1;
+                }
+                &self.text[start..self.pos]
+            }
+        };
+        let value = self.decode(raw, true).unwrap_or_default();
+        Some((name, value))
+    }
+
+    fn end_tag(&mut self) -> ParseResult<()> {
+        self.pos += 2;
+        let qname = self.name();
+        self.skip_whitespace();
+        if self.peek() == Some(b'>') {
+            self.pos += 1;
+        } else if self.html {
+            self.skip_past(">");
+        } else {
+            return Err(SyntaxError);
+        }
+
+        if self.html {
+            self.html_end_element(&qname.to_ascii_lowercase());
+            Ok(())
+        } else {
+            match self.open.last() {
+                Some(element) if element.qname == qname => {
+                    self.close_element();
+                    Ok(())
+                }
+                _ => Err(SyntaxError),
+            }
+        }
+    }
+
+    fn text(&mut self) -> ParseResult<()> {
+        let start = self.pos;
+        // Always consume at least one character, so that a `<' that
+        // does not start any markup is taken as text in HTML.
+        self.pos += self.rest().chars().next().map_or(1, char::len_utf8);
+        match self.rest().find('<') {
+            Some(i) => self.pos += i,
+            None => self.pos = self.text.len(),
+        }
+        let raw = &self.text[start..self.pos];
+
+        if raw.bytes().all(is_blank) {
+            if self.blank_is_ignorable() {
+                return Ok(());
+            }
+        } else if self.html {
+            self.html_check_paragraph();
+        } else if self.open.is_empty() || raw.starts_with('<') || raw.contains("]]>") {
+            return Err(SyntaxError);
+        }
+
+        let text = self.decode(raw, false)?;
+        self.append_text(text);
+        Ok(())
+    }
+
+    /// Decode the character and entity references in RAW.  In
+    /// attribute values, literal whitespace is normalized to spaces.
+    fn decode(&self, raw: &str, attribute: bool) -> ParseResult<String> {
+        let mut result = String::with_capacity(raw.len());
+        let mut rest = raw;
+
+        let normalize = attribute && !self.html;
+        while let Some(i) = rest.find(|c: char| c == '&' || (normalize && is_blank_char(c))) {
+            result.push_str(&rest[..i]);
+            if rest.as_bytes()[i] != b'&' {
+                result.push(' ');
+                rest = &rest[i + 1..];
+                continue;
+            }
+            rest = &rest[i..];
+
+            let reference = rest[1..]
+                .find(';')
+                .map(|end| &rest[1..=end])
+                .filter(|r| r.len() < 32 && r.bytes().all(|b| b == b'#' || Self::is_name_char(b)));
+            let reference = match reference {
+                Some(reference) => reference,
+                None if self.html => {
+                    result.push('&');
+                    rest = &rest[1..];
+                    continue;
+                }
+                None => return Err(SyntaxError),
+            };
+
+            match self.resolve_reference(reference)? {
+                Some(c) => result.push(c),
+                None if self.html => {
+                    result.push('&');
+                    rest = &rest[1..];
+                    continue;
+                }
+                None => {}
+            }
+            rest = &rest[reference.len() + 2..];
+        }
+
+        result.push_str(rest);
+        Ok(result)
+    }
+
+    /// Return the character REFERENCE (without the surrounding `&'
+    /// and `;') stands for.  Undeclared entities yield None in HTML
+    /// and in XML documents with a DTD, where they are kept literally
+    /// and dropped, respectively.
+    fn resolve_reference(&self, reference: &str) -> ParseResult<Option<char>> {
+        if reference.starts_with('#') {
+            let digits = &reference[1..];
+            let code = if digits.starts_with('x') || digits.starts_with('X') {
+                u32::from_str_radix(&digits[1..], 16)
+            } else {
+                digits.parse::<u32>()
+            };
+            let c = code.ok().and_then(::std::char::from_u32).filter(|&c| {
+                self.html
+                    || c == '\t'
+                    || c == '\n'
+                    || c == '\r'
+                    || c >= ' ' && c != '\u{FFFE}' && c != '\u{FFFF}'
+            });
+            return match c {
+                Some(c) => Ok(Some(c)),
+                None if self.html => Ok(Some('\u{FFFD}')),
+                None => Err(SyntaxError),
+            };
+        }
+
+        let c = match reference {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ if self.html => html_entity(reference),
+            _ if self.has_doctype => None,
+            _ => return Err(SyntaxError),
+        };
+        Ok(c)
+    }
+
+    // Tree construction.
+
+    fn has_root(&self) -> bool {
+        self.document.iter().any(|node| match *node {
+            Node::Element(_) => true,
+            _ => false,
+        })
+    }
+
+    fn append(&mut self, node: Node) {
+        match self.open.last_mut() {
+            Some(element) => element.children.push(node),
+            None => self.document.push(node),
+        }
+    }
+
+    /// Append TEXT, merging it with a preceding text node.
+    fn append_text(&mut self, text: String) {
+        let merged = match self.open.last_mut().and_then(|e| e.children.last_mut()) {
+            Some(&mut Node::Text(ref mut previous)) => {
+                previous.push_str(&text);
+                true
+            }
+            _ => false,
+        };
+        if !merged {
+            self.append(Node::Text(text));
+        }
+    }
+
+    fn close_element(&mut self) {
+        if let Some(element) = self.open.pop() {
+            self.append(Node::Element(element));
+        }
+    }
+
+    /// Whether a run of blanks just before the current position can be
+    /// dropped.  These are the heuristics libxml2 applies when asked
+    /// to remove blank nodes.
+    fn blank_is_ignorable(&self) -> bool {
+        let element = match self.open.last() {
+            Some(element) => element,
+            None => return true,
+        };
+        let at_end = self.pos >= self.text.len();
+
+        if self.html {
+            if at_end || element.name == "html" || element.name == "head" {
+                return true;
+            }
+            let last = element.children.iter().rev().find(|node| match **node {
+                Node::Comment(_) => false,
+                _ => true,
+            });
+            match last {
+                None => !HTML_ALLOW_PCDATA.contains(&element.name.as_str()),
+                Some(&Node::Text(_)) => false,
+                Some(&Node::Element(ref e)) => !HTML_ALLOW_PCDATA.contains(&e.name.as_str()),
+                Some(_) => true,
+            }
+        } else {
+            if element.preserve_space || at_end {
+                return false;
+            }
+            if element.children.is_empty() && self.looking_at("</") {
+                return false;
+            }
+            match (element.children.first(), element.children.last()) {
+                (_, Some(&Node::Text(_))) | (Some(&Node::Text(_)), _) => false,
+                _ => true,
+            }
+        }
+    }
+
+    fn xml_start_element(
+        &mut self,
+        qname: &str,
+        attributes: Vec<(String, String)>,
+        empty: bool,
+    ) -> ParseResult<()> {
+        if (self.open.is_empty() && self.has_root()) || self.open.len() >= MAX_DEPTH {
+            return Err(SyntaxError);
+        }
+
+        let mut element = Element::new(qname);
+        element.preserve_space = self.open.last().map_or(false, |e| e.preserve_space);
+
+        for (name, value) in attributes {
+            if name == "xmlns" {
+                continue;
+            } else if name.starts_with("xmlns:") {
+                element.prefixes.push(name["xmlns:".len()..].to_string());
+                continue;
+            } else if name == "xml:space" {
+                element.preserve_space = value == "preserve";
+            }
+            element.attributes.push((name, value));
+        }
+
+        // Report names without their namespace prefix, as long as the
+        // prefix is declared.
+        element.name = self.local_name(&element, qname).to_string();
+        let attributes = element
+            .attributes
+            .iter()
+            .map(|&(ref name, ref value)| {
+                (self.local_name(&element, name).to_string(), value.clone())
+            })
+            .collect();
+        element.attributes = attributes;
+
+        self.open.push(element);
+        if empty {
+            self.close_element();
+        }
+        Ok(())
+    }
+
+    fn local_name<'b>(&self, element: &Element, qname: &'b str) -> &'b str {
+        match qname.find(':') {
+            Some(i) => {
+                let prefix = &qname[..i];
+                let declared = prefix == "xml"
+                    || element.prefixes.iter().any(|p| p == prefix)
+                    || self
+                        .open
+                        .iter()
+                        .any(|e| e.prefixes.iter().any(|p| p == prefix));
+                if declared {
+                    &qname[i + 1..]
+                } else {
+                    qname
+                }
+            }
+            None => qname,
+        }
+    }
+
+    fn html_start_element(&mut self, name: String, attributes: Vec<(String, String)>, empty: bool) {
+        if name == "html" {
+            if self.open.is_empty() && !self.has_root() {
+                let mut element = Element::new("html");
+                element.attributes = attributes;
+                self.open.push(element);
+            }
+            return;
+        }
+
+        self.html_auto_close(&name);
+        self.html_check_implied(&name);
+
+        match name.as_str() {
+            "head" if self.seen_head || self.seen_body => return,
+            "head" => self.seen_head = true,
+            "body" if self.seen_body => return,
+            "body" => self.seen_body = true,
+            _ => {}
+        }
+
+        if self.open.len() >= MAX_DEPTH {
+            return;
+        }
+
+        let mut element = Element::new(&name);
+        element.attributes = attributes;
+        self.open.push(element);
+
+        if empty || HTML_VOID_ELEMENTS.contains(&name.as_str()) {
+            self.close_element();
+        } else if name == "script" || name == "style" {
+            // The content of these is not parsed.
+            let end = format!("</{}", name);
+            let rest = self.rest().as_bytes();
+            let len = (0..rest.len())
+                .find(|&i| {
+                    rest[i..].len() >= end.len()
+                        && rest[i..i + end.len()].eq_ignore_ascii_case(end.as_bytes())
+                })
+                .unwrap_or(rest.len());
+            if len > 0 {
+                let content = self.rest()[..len].to_string();
+                self.append(Node::Text(content));
+            }
+            self.pos += len;
+        }
+    }
+
+    fn html_end_element(&mut self, name: &str) {
+        // Keep these open until the end, so that stray content after
+        // them still ends up in the body.
+        if name == "html" || name == "body" || HTML_VOID_ELEMENTS.contains(&name) {
+            return;
+        }
+
+        let priority = html_end_priority(name);
+        for i in (0..self.open.len()).rev() {
+            if self.open[i].name == name {
+                while self.open.len() > i {
+                    self.close_element();
+                }
+                return;
+            }
+            if html_end_priority(&self.open[i].name) > priority {
+                return;
+            }
+        }
+    }
+
+    /// Close the open elements that an element NAME cannot be nested
+    /// in, like a paragraph when a new paragraph starts.
+    fn html_auto_close(&mut self, name: &str) {
+        while self
+            .open
+            .last()
+            .map_or(false, |e| html_start_closes(name, &e.name))
+        {
+            self.close_element();
+        }
+    }
+
+    /// Open the `html', `head' and `body' elements an element NAME
+    /// implies.
+    fn html_check_implied(&mut self, name: &str) {
+        if self.open.is_empty() {
+            self.open.push(Element::new("html"));
+        }
+        if name == "head" || name == "body" {
+            return;
+        }
+        if self.open.len() <= 1 && HTML_HEAD_ELEMENTS.contains(&name) {
+            if !self.seen_head && !self.seen_body {
+                self.open.push(Element::new("head"));
+                self.seen_head = true;
+            }
+        } else if name != "noframes" && name != "frame" && name != "frameset" {
+            if self.seen_body
+                || self
+                    .open
+                    .iter()
+                    .any(|e| e.name == "body" || e.name == "head")
+            {
+                return;
+            }
+            self.open.push(Element::new("body"));
+            self.seen_body = true;
+        }
+    }
+
+    /// Text that is not inside the body gets a paragraph of its own.
+    fn html_check_paragraph(&mut self) {
+        let needs_paragraph = match self.open.last() {
+            Some(element) => element.name == "html" || element.name == "head",
+            None => true,
+        };
+        if needs_paragraph {
+            self.html_auto_close("p");
+            self.html_check_implied("p");
+            self.open.push(Element::new("p"));
+        }
+    }
+}
+
+fn is_blank(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\n' || b == b'\r'
+}
+
+fn is_blank_char(c: char) -> bool {
+    c.is_ascii() && is_blank(c as u8)
+}
+
+const HTML_VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "basefont", "br", "col", "embed", "frame", "hr", "img", "input", "isindex",
+    "keygen", "link", "meta", "param", "source", "track", "wbr",
+];
+
+const HTML_HEAD_ELEMENTS: &[&str] = &["base", "link", "meta", "script", "style", "title"];
+
+const HTML_BOOLEAN_ATTRIBUTES: &[&str] = &[
+    "checked", "compact", "declare", "defer", "disabled", "ismap", "multiple", "nohref",
+    "noresize", "noshade", "nowrap", "readonly", "selected",
+];
+
+/// Elements in which blanks are significant.
+const HTML_ALLOW_PCDATA: &[&str] = &[
+    "a",
+    "abbr",
+    "acronym",
+    "address",
+    "applet",
+    "b",
+    "bdo",
+    "big",
+    "blockquote",
+    "body",
+    "button",
+    "caption",
+    "center",
+    "cite",
+    "code",
+    "dd",
+    "del",
+    "dfn",
+    "div",
+    "dt",
+    "em",
+    "font",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "i",
+    "iframe",
+    "ins",
+    "kbd",
+    "label",
+    "legend",
+    "li",
+    "map",
+    "menu",
+    "object",
+    "ol",
+    "p",
+    "pre",
+    "q",
+    "s",
+    "samp",
+    "small",
+    "span",
+    "strike",
+    "strong",
+    "td",
+    "th",
+    "tt",
+    "u",
+    "ul",
+    "var",
+];
+
+/// Elements that end an open paragraph.
+const HTML_PARAGRAPH_CLOSERS: &[&str] = &[
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "center",
+    "dd",
+    "dir",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "listing",
+    "main",
+    "menu",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "ul",
+    "xmp",
+];
+
+const HTML_TABLE_SECTIONS: &[&str] = &["thead", "tbody", "tfoot"];
+
+/// Whether starting an element NEW implicitly ends the element OPEN.
+fn html_start_closes(new: &str, open: &str) -> bool {
+    match open {
+        "head" => !HTML_HEAD_ELEMENTS.contains(&new),
+        "p" => HTML_PARAGRAPH_CLOSERS.contains(&new),
+        "li" => new == "li",
+        "dt" | "dd" => new == "dt" || new == "dd",
+        "option" => new == "option" || new == "optgroup",
+        "optgroup" => new == "optgroup",
+        "a" => new == "a",
+        "tr" => new == "tr" || HTML_TABLE_SECTIONS.contains(&new),
+        "td" | "th" => {
+            new == "td" || new == "th" || new == "tr" || HTML_TABLE_SECTIONS.contains(&new)
+        }
+        "thead" | "tbody" | "tfoot" => HTML_TABLE_SECTIONS.contains(&new),
+        _ => false,
+    }
+}
+
+/// An end tag only closes the elements nested in its element that have
+/// no higher priority than it has itself.
+fn html_end_priority(name: &str) -> u32 {
+    match name {
+        "div" => 150,
+        "td" | "th" => 160,
+        "tr" => 170,
+        "thead" | "tbody" | "tfoot" => 180,
+        "table" => 190,
+        "head" | "body" => 200,
+        "html" => 220,
+        _ => 100,
+    }
+}
+
+/// The entities of ISO 8859-1, starting at U+00A0.
+const HTML_LATIN1_ENTITIES: [&str; 96] = [
+    "nbsp", "iexcl", "cent", "pound", "curren", "yen", "brvbar", "sect", "uml", "copy", "ordf",
+    "laquo", "not", "shy", "reg", "macr", "deg", "plusmn", "sup2", "sup3", "acute", "micro",
+    "para", "middot", "cedil", "sup1", "ordm", "raquo", "frac14", "frac12", "frac34", "iquest",
+    "Agrave", "Aacute", "Acirc", "Atilde", "Auml", "Aring", "AElig", "Ccedil", "Egrave", "Eacute",
+    "Ecirc", "Euml", "Igrave", "Iacute", "Icirc", "Iuml", "ETH", "Ntilde", "Ograve", "Oacute",
+    "Ocirc", "Otilde", "Ouml", "times", "Oslash", "Ugrave", "Uacute", "Ucirc", "Uuml", "Yacute",
+    "THORN", "szlig", "agrave", "aacute", "acirc", "atilde", "auml", "aring", "aelig", "ccedil",
+    "egrave", "eacute", "ecirc", "euml", "igrave", "iacute", "icirc", "iuml", "eth", "ntilde",
+    "ograve", "oacute", "ocirc", "otilde", "ouml", "divide", "oslash", "ugrave", "uacute", "ucirc",
+    "uuml", "yacute", "thorn", "yuml",
+];
+
+/// The remaining entities of HTML 4.
+const HTML_ENTITIES: &[(&str, u32)] = &[
+    ("OElig", 338),
+    ("oelig", 339),
+    ("Scaron", 352),
+    ("scaron", 353),
+    ("Yuml", 376),
+    ("fnof", 402),
+    ("circ", 710),
+    ("tilde", 732),
+    ("Alpha", 913),
+    ("Beta", 914),
+    ("Gamma", 915),
+    ("Delta", 916),
+    ("Epsilon", 917),
+    ("Zeta", 918),
+    ("Eta", 919),
+    ("Theta", 920),
+    ("Iota", 921),
+    ("Kappa", 922),
+    ("Lambda", 923),
+    ("Mu", 924),
+    ("Nu", 925),
+    ("Xi", 926),
+    ("Omicron", 927),
+    ("Pi", 928),
+    ("Rho", 929),
+    ("Sigma", 931),
+    ("Tau", 932),
+    ("Upsilon", 933),
+    ("Phi", 934),
+    ("Chi", 935),
+    ("Psi", 936),
+    ("Omega", 937),
+    ("alpha", 945),
+    ("beta", 946),
+    ("gamma", 947),
+    ("delta", 948),
+    ("epsilon", 949),
+    ("zeta", 950),
+    ("eta", 951),
+    ("theta", 952),
+    ("iota", 953),
+    ("kappa", 954),
+    ("lambda", 955),
+    ("mu", 956),
+    ("nu", 957),
+    ("xi", 958),
+    ("omicron", 959),
+    ("pi", 960),
+    ("rho", 961),
+    ("sigmaf", 962),
+    ("sigma", 963),
+    ("tau", 964),
+    ("upsilon", 965),
+    ("phi", 966),
+    ("chi", 967),
+    ("psi", 968),
+    ("omega", 969),
+    ("thetasym", 977),
+    ("upsih", 978),
+    ("piv", 982),
+    ("ensp", 8194),
+    ("emsp", 8195),
+    ("thinsp", 8201),
+    ("zwnj", 8204),
+    ("zwj", 8205),
+    ("lrm", 8206),
+    ("rlm", 8207),
+    ("ndash", 8211),
+    ("mdash", 8212),
+    ("lsquo", 8216),
+    ("rsquo", 8217),
+    ("sbquo", 8218),
+    ("ldquo", 8220),
+    ("rdquo", 8221),
+    ("bdquo", 8222),
+    ("dagger", 8224),
+    ("Dagger", 8225),
+    ("bull", 8226),
+    ("hellip", 8230),
+    ("permil", 8240),
+    ("prime", 8242),
+    ("Prime", 8243),
+    ("lsaquo", 8249),
+    ("rsaquo", 8250),
+    ("oline", 8254),
+    ("frasl", 8260),
+    ("euro", 8364),
+    ("image", 8465),
+    ("weierp", 8472),
+    ("real", 8476),
+    ("trade", 8482),
+    ("alefsym", 8501),
+    ("larr", 8592),
+    ("uarr", 8593),
+    ("rarr", 8594),
+    ("darr", 8595),
+    ("harr", 8596),
+    ("crarr", 8629),
+    ("lArr", 8656),
+    ("uArr", 8657),
+    ("rArr", 8658),
+    ("dArr", 8659),
+    ("hArr", 8660),
+    ("forall", 8704),
+    ("part", 8706),
+    ("exist", 8707),
+    ("empty", 8709),
+    ("nabla", 8711),
+    ("isin", 8712),
+    ("notin", 8713),
+    ("ni", 8715),
+    ("prod", 8719),
+    ("sum", 8721),
+    ("minus", 8722),
+    ("lowast", 8727),
+    ("radic", 8730),
+    ("prop", 8733),
+    ("infin", 8734),
+    ("ang", 8736),
+    ("and", 8743),
+    ("or", 8744),
+    ("cap", 8745),
+    ("cup", 8746),
+    ("int", 8747),
+    ("there4", 8756),
+    ("sim", 8764),
+    ("cong", 8773),
+    ("asymp", 8776),
+    ("ne", 8800),
+    ("equiv", 8801),
+    ("le", 8804),
+    ("ge", 8805),
+    ("sub", 8834),
+    ("sup", 8835),
+    ("nsub", 8836),
+    ("sube", 8838),
+    ("supe", 8839),
+    ("oplus", 8853),
+    ("otimes", 8855),
+    ("perp", 8869),
+    ("sdot", 8901),
+    ("lceil", 8968),
+    ("rceil", 8969),
+    ("lfloor", 8970),
+    ("rfloor", 8971),
+    ("lang", 9001),
+    ("rang", 9002),
+    ("loz", 9674),
+    ("spades", 9824),
+    ("clubs", 9827),
+    ("hearts", 9829),
+    ("diams", 9830),
+];
+
+fn html_entity(name: &str) -> Option<char> {
+    if let Some(i) = HTML_LATIN1_ENTITIES.iter().position(|&e| e == name) {
+        return ::std::char::from_u32(0xA0 + i as u32);
+    }
+    HTML_ENTITIES
+        .iter()
+        .find(|&&(e, _)| e == name)
+        .and_then(|&(_, code)| ::std::char::from_u32(code))
+}
+
+/// Return the text between START and END in the current buffer.
+/// Text that is not valid UTF-8 is replaced.
+fn region_text(mut start: LispObject, mut end: LispObject) -> String {
+    unsafe { validate_region(&mut start, &mut end) };
+
+    let mut buffer = ThreadState::current_buffer();
+    let (start_byte, end_byte) = unsafe {
+        (
+            buf_charpos_to_bytepos(buffer.as_mut(), start.as_fixnum_or_error() as ptrdiff_t),
+            buf_charpos_to_bytepos(buffer.as_mut(), end.as_fixnum_or_error() as ptrdiff_t),
+        )
+    };
+    let bytes = BufferText::region(buffer, start_byte, end_byte).to_vec();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn make_string(s: &str) -> LispObject {
+    unsafe {
+        make_specified_string(
+            s.as_ptr() as *const c_char,
+            s.chars().count() as ptrdiff_t,
+            s.len() as ptrdiff_t,
+            true,
+        )
+    }
+}
+
+fn make_dom(node: &Node, discard_comments: bool) -> Option<LispObject> {
+    match *node {
+        Node::Element(ref element) => {
+            let attributes: Vec<LispObject> = element
+                .attributes
+                .iter()
+                .map(|&(ref name, ref value)| {
+                    LispObject::cons(LispObject::from(intern(name)), make_string(value))
+                })
+                .collect();
+            let mut items = vec![LispObject::from(intern(&element.name)), list(&attributes)];
+            items.extend(
+                element
+                    .children
+                    .iter()
+                    .filter_map(|child| make_dom(child, discard_comments)),
+            );
+            Some(list(&items))
+        }
+        Node::Text(ref text) | Node::CData(ref text) => Some(make_string(text)),
+        Node::Comment(_) if discard_comments => None,
+        Node::Comment(ref text) => Some(list!(
+            LispObject::from(intern("comment")),
+            Qnil,
+            make_string(text)
+        )),
+    }
+}
+
 fn libxml_parse_region(
     start: LispObject,
     end: LispObject,
@@ -16,17 +1175,47 @@ fn libxml_parse_region(
     discard_comments: LispObject,
     htmlp: bool,
 ) -> LispObject {
-    unsafe {
-        if init_libxml2_functions() {
-            parse_region(start, end, base_url, discard_comments, htmlp)
-        } else {
-            Qnil
-        }
+    let mut text = region_text(start, end);
+
+    // BASE-URL only names the document; it does not change the tree.
+    if base_url.is_not_nil() {
+        base_url.as_string_or_error();
+    }
+
+    if !htmlp && text.contains('\r') {
+        text = text.replace("\r\n", "\n").replace('\r', "\n");
+    }
+
+    let document = match Parser::new(&text, htmlp).parse() {
+        Ok(document) => document,
+        Err(SyntaxError) => return Qnil,
+    };
+
+    let discard_comments = discard_comments.is_not_nil();
+    let nodes: Vec<LispObject> = document
+        .iter()
+        .filter_map(|node| make_dom(node, discard_comments))
+        .collect();
+
+    if nodes.len() > 1 {
+        let mut items = vec![Qtop, Qnil];
+        items.extend(nodes);
+        list(&items)
+    } else {
+        document
+            .iter()
+            .find(|node| match **node {
+                Node::Element(_) => true,
+                _ => false,
+            })
+            .and_then(|node| make_dom(node, discard_comments))
+            .unwrap_or(Qnil)
     }
 }
 
 /// Parse the region as an HTML document and return the parse tree.
-/// If BASE-URL is non-nil, it is used to expand relative URLs.
+/// If BASE-URL is non-nil, it should be a string naming the location of
+/// the document; it does not affect the parse tree.
 /// If DISCARD-COMMENTS is non-nil, all HTML comments are discarded.
 #[lisp_fn(min = "2")]
 pub fn libxml_parse_html_region(
@@ -39,8 +1228,9 @@ pub fn libxml_parse_html_region(
 }
 
 /// Parse the region as an XML document and return the parse tree.
-/// If BASE-URL is non-nil, it is used to expand relative URLs.
-/// If DISCARD-COMMENTS is non-nil, all HTML comments are discarded.
+/// If BASE-URL is non-nil, it should be a string naming the location of
+/// the document; it does not affect the parse tree.
+/// If DISCARD-COMMENTS is non-nil, all XML comments are discarded.
 #[lisp_fn(min = "2")]
 pub fn libxml_parse_xml_region(
     start: LispObject,
@@ -52,10 +1242,10 @@ pub fn libxml_parse_xml_region(
 }
 
 /// Return t if libxml2 support is available in this instance of Emacs.
+/// XML and HTML parsing is built in, so this always returns t.
 #[lisp_fn]
 pub fn libxml_available_p() -> bool {
-    // TODO(db48x)
-    false
+    true
 }
 
 include!(concat!(env!("OUT_DIR"), "/xml_exports.rs"));
@@ -3136,6 +3136,12 @@ cleanup_vector (struct Lisp_Vector *vector)
     finalize_one_mutex ((struct Lisp_Mutex *) vector);
   else if (PSEUDOVECTOR_TYPEP (&vector->header, PVEC_CONDVAR))
     finalize_one_condvar ((struct Lisp_CondVar *) vector);
+  else if (PSEUDOVECTOR_TYPEP (&vector->header, PVEC_HASH_CONTEXT))
+    finalize_hash_context (vector);
+  else if (PSEUDOVECTOR_TYPEP (&vector->header, PVEC_BIGNUM))
+    finalize_bignum (vector);
+  else if (PSEUDOVECTOR_TYPEP (&vector->header, PVEC_RANDOM_STATE))
+    finalize_random_state (vector);
 }
 
 /* Reclaim space used by unmarked vectors.  */
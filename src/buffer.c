@@ -440,6 +440,8 @@ even if it is dead.  The return value is never nil.  */)
   *(BUF_GPT_ADDR (b)) = *(BUF_Z_ADDR (b)) = 0; /* Put an anchor '\0'.  */
   b->text->inhibit_shrinking = false;
   b->text->redisplay = false;
+  b->text->charpos_index = NULL;
+  b->text->readers = 0;
 
   b->newline_cache = 0;
   b->width_run_cache = 0;
@@ -1292,7 +1294,7 @@ compact_buffer (struct buffer *buffer)
 	truncate_undo_list (buffer);
 
       /* Shrink buffer gaps.  */
-      if (!buffer->text->inhibit_shrinking)
+      if (!buffer->text->inhibit_shrinking && buffer->text->readers == 0)
 	{
 	  /* If a buffer's gap size is more than 10% of the buffer
 	     size, or larger than GAP_BYTES_DFL bytes, then shrink it
@@ -1346,6 +1348,9 @@ cleaning up all windows currently displaying the buffer to be killed. */)
   if (thread_check_current_buffer (b))
     return Qnil;
 
+  if (b->text->readers > 0)
+    error ("Buffer text is being read");
+
   /* Run hooks with the buffer to be killed the current buffer.  */
   {
     ptrdiff_t count = SPECPDL_INDEX ();
@@ -1880,6 +1885,9 @@ results, see Info node `(elisp)Swapping Text'.  */)
       || current_buffer->base_buffer)
     error ("Cannot swap indirect buffers's text");
 
+  if (other_buffer->text->readers > 0 || current_buffer->text->readers > 0)
+    error ("Buffer text is being read");
+
   { /* This is probably harder to make work.  */
     struct buffer *other;
     FOR_EACH_BUFFER (other)
@@ -4545,6 +4553,7 @@ free_buffer_text (struct buffer *b)
 #endif
 
   BUF_BEG_ADDR (b) = NULL;
+  clear_charpos_cache (b);
   unblock_input ();
 }
 
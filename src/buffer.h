@@ -495,6 +495,16 @@ struct buffer_text
 
     /* True if it needs to be redisplayed.  */
     bool_bf redisplay : 1;
+
+    /* Checkpoints for converting between character and byte
+       positions, maintained by marker.rs.  NULL until first needed.  */
+    void *charpos_index;
+
+    /* Number of live read-only views of this text held by Rust code
+       (see buffer_text.rs).  While it is positive, the characters of
+       the text must not be changed, moved or freed; its text
+       properties may still change.  */
+    int readers;
   };
 
 /* Most code should use this macro to access Lisp fields in struct buffer.  */
@@ -962,17 +962,19 @@ exec_byte_code (Lisp_Object bytestr, Lisp_Object vector, Lisp_Object maxdepth,
 	  NEXT;
 
 	CASE (Bsub1):
-	  TOP = INTEGERP (TOP) ? make_number (XINT (TOP) - 1) : Fsub1 (TOP);
+	  TOP = (INTEGERP (TOP) && XINT (TOP) != MOST_NEGATIVE_FIXNUM
+		 ? make_number (XINT (TOP) - 1) : Fsub1 (TOP));
 	  NEXT;
 
 	CASE (Badd1):
-	  TOP = INTEGERP (TOP) ? make_number (XINT (TOP) + 1) : Fadd1 (TOP);
+	  TOP = (INTEGERP (TOP) && XINT (TOP) != MOST_POSITIVE_FIXNUM
+		 ? make_number (XINT (TOP) + 1) : Fadd1 (TOP));
 	  NEXT;
 
 	CASE (Beqlsign):
 	  {
 	    Lisp_Object v2 = POP, v1 = TOP;
-	    if (FLOATP (v1) || FLOATP (v2))
+	    if (FLOATP (v1) || FLOATP (v2) || BIGNUMP (v1) || BIGNUMP (v2))
 	      TOP = arithcompare (v1, v2, ARITH_EQUAL);
 	    else
 	      {
@@ -1017,7 +1019,8 @@ exec_byte_code (Lisp_Object bytestr, Lisp_Object vector, Lisp_Object maxdepth,
 	  NEXT;
 
 	CASE (Bnegate):
-	  TOP = INTEGERP (TOP) ? make_number (- XINT (TOP)) : Fminus (1, &TOP);
+	  TOP = (INTEGERP (TOP) && XINT (TOP) != MOST_NEGATIVE_FIXNUM
+		 ? make_number (- XINT (TOP)) : Fminus (1, &TOP));
 	  NEXT;
 
 	CASE (Bplus):
@@ -1314,11 +1317,11 @@ exec_byte_code (Lisp_Object bytestr, Lisp_Object vector, Lisp_Object maxdepth,
 	  NEXT;
 
 	CASE (Bnumberp):
-	  TOP = NUMBERP (TOP) ? Qt : Qnil;
+	  TOP = NUMBERP (TOP) || BIGNUMP (TOP) ? Qt : Qnil;
 	  NEXT;
 
 	CASE (Bintegerp):
-	  TOP = INTEGERP (TOP) ? Qt : Qnil;
+	  TOP = INTEGERP (TOP) || BIGNUMP (TOP) ? Qt : Qnil;
 	  NEXT;
 
 #if BYTE_CODE_SAFE
@@ -1481,6 +1481,14 @@ syms_of_callproc (void)
 #endif
   staticpro (&Vtemp_file_name_pattern);
 
+  /* Keywords of `call-process*', defined in rust_src/src/callproc.rs.  */
+  DEFSYM (QCenv, ":env");
+  DEFSYM (QCcwd, ":cwd");
+  DEFSYM (QCtimeout, ":timeout");
+  DEFSYM (QCstdin_string, ":stdin-string");
+  DEFSYM (QCstdout, ":stdout");
+  DEFSYM (QCtimed_out, ":timed-out");
+
   DEFVAR_LISP ("shell-file-name", Vshell_file_name,
 	       doc: /* File name to load inferior shells from.
 Initialized from the SHELL environment variable, or to a system-dependent
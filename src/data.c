@@ -497,63 +497,6 @@ set_internal (Lisp_Object symbol, Lisp_Object newval, Lisp_Object where,
   return;
 }
 
-static void
-set_symbol_trapped_write (Lisp_Object symbol, enum symbol_trapped_write trap)
-{
-  struct Lisp_Symbol *sym = XSYMBOL (symbol);
-  if (sym->u.s.trapped_write == SYMBOL_NOWRITE)
-    xsignal1 (Qtrapping_constant, symbol);
-  sym->u.s.trapped_write = trap;
-}
-
-static void
-restore_symbol_trapped_write (Lisp_Object symbol)
-{
-  set_symbol_trapped_write (symbol, SYMBOL_TRAPPED_WRITE);
-}
-
-void
-notify_variable_watchers (Lisp_Object symbol,
-                          Lisp_Object newval,
-                          Lisp_Object operation,
-                          Lisp_Object where)
-{
-  symbol = Findirect_variable (symbol);
-
-  ptrdiff_t count = SPECPDL_INDEX ();
-  record_unwind_protect (restore_symbol_trapped_write, symbol);
-  /* Avoid recursion.  */
-  set_symbol_trapped_write (symbol, SYMBOL_UNTRAPPED_WRITE);
-
-  if (NILP (where)
-      && !EQ (operation, Qset_default) && !EQ (operation, Qmakunbound)
-      && !NILP (Flocal_variable_if_set_p (symbol, Fcurrent_buffer ())))
-    {
-      XSETBUFFER (where, current_buffer);
-    }
-
-  if (EQ (operation, Qset_default))
-    operation = Qset;
-
-  for (Lisp_Object watchers = Fget (symbol, Qwatchers);
-       CONSP (watchers);
-       watchers = XCDR (watchers))
-    {
-      Lisp_Object watcher = XCAR (watchers);
-      /* Call subr directly to avoid gc.  */
-      if (SUBRP (watcher))
-        {
-          Lisp_Object args[] = { symbol, newval, operation, where };
-          funcall_subr (XSUBR (watcher), ARRAYELTS (args), args);
-        }
-      else
-        CALLN (Ffuncall, watcher, symbol, newval, operation, where);
-    }
-
-  unbind_to (count, Qnil);
-}
-
-
 /* Access or set a buffer-local symbol's default value.  */
 
 void
@@ -1601,6 +1544,9 @@ NUMBER may be an integer or a floating point number.  */)
   char buffer[max (FLOAT_TO_STRING_BUFSIZE, INT_BUFSIZE_BOUND (EMACS_INT))];
   int len;
 
+  if (BIGNUMP (number))
+    return bignum_to_string (number, 10);
+
   CHECK_NUMBER_OR_FLOAT (number);
 
   if (FLOATP (number))
@@ -1646,12 +1592,12 @@ If the base used is not 10, STRING is always parsed as an integer.  */)
 }
 
 
-static Lisp_Object
-ash_lsh_impl (Lisp_Object value, Lisp_Object count, bool lsh)
+DEFUN ("lsh", Flsh, Slsh, 2, 2, 0,
+       doc: /* Return VALUE with its bits shifted left by COUNT.
+If COUNT is negative, shifting is actually to the right.
+In this case, zeros are shifted in on the left.  */)
+  (register Lisp_Object value, Lisp_Object count)
 {
-  /* This code assumes that signed right shifts are arithmetic.  */
-  verify ((EMACS_INT) -1 >> 1 == -1);
-
   Lisp_Object val;
 
   CHECK_NUMBER (value);
@@ -1662,31 +1608,12 @@ ash_lsh_impl (Lisp_Object value, Lisp_Object count, bool lsh)
   else if (XINT (count) > 0)
     XSETINT (val, XUINT (value) << XINT (count));
   else if (XINT (count) <= -EMACS_INT_WIDTH)
-    XSETINT (val, lsh ? 0 : XINT (value) < 0 ? -1 : 0);
+    XSETINT (val, 0);
   else
-    XSETINT (val, (lsh ? XUINT (value) >> -XINT (count)
-		   : XINT (value) >> -XINT (count)));
+    XSETINT (val, XUINT (value) >> -XINT (count));
   return val;
 }
 
-DEFUN ("ash", Fash, Sash, 2, 2, 0,
-       doc: /* Return VALUE with its bits shifted left by COUNT.
-If COUNT is negative, shifting is actually to the right.
-In this case, the sign bit is duplicated.  */)
-  (register Lisp_Object value, Lisp_Object count)
-{
-  return ash_lsh_impl (value, count, false);
-}
-
-DEFUN ("lsh", Flsh, Slsh, 2, 2, 0,
-       doc: /* Return VALUE with its bits shifted left by COUNT.
-If COUNT is negative, shifting is actually to the right.
-In this case, zeros are shifted in on the left.  */)
-  (register Lisp_Object value, Lisp_Object count)
-{
-  return ash_lsh_impl (value, count, true);
-}
-
 /* Because we round up the bool vector allocate size to word_size
    units, we can safely read past the "end" of the vector in the
    operations below.  These extra bits are always zero.  */
@@ -2129,6 +2056,8 @@ syms_of_data (void)
   DEFSYM (Quser_ptr, "user-ptr");
 #endif
   DEFSYM (Qfloat, "float");
+  DEFSYM (Qhash_context, "hash-context");
+  DEFSYM (Qrandom_state, "random-state");
   DEFSYM (Qwindow_configuration, "window-configuration");
   DEFSYM (Qprocess, "process");
   DEFSYM (Qwindow, "window");
@@ -2170,7 +2099,6 @@ syms_of_data (void)
   defsubr (&Snumber_to_string);
   defsubr (&Sstring_to_number);
   defsubr (&Slsh);
-  defsubr (&Sash);
 #ifdef HAVE_MODULES
   defsubr (&Suser_ptrp);
 #endif
@@ -2194,6 +2122,7 @@ This variable cannot be set; trying to do so will signal an error.  */);
   make_symbol_constant (intern_c_string ("most-negative-fixnum"));
 
   DEFSYM (Qwatchers, "watchers");
+  DEFSYM (Qwatcher_filters, "watcher-filters");
   DEFSYM (Qmakunbound, "makunbound");
   DEFSYM (Qunlet, "unlet");
   DEFSYM (Qset, "set");
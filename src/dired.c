@@ -1080,6 +1080,15 @@ syms_of_dired (void)
 {
   DEFSYM (Qdirectory_files, "directory-files");
   DEFSYM (Qdirectory_files_and_attributes, "directory-files-and-attributes");
+  DEFSYM (Qdirectory_walk, "directory-walk");
+  DEFSYM (QCmatch, ":match");
+  DEFSYM (QCexclude, ":exclude");
+  DEFSYM (QCfollow_symlinks, ":follow-symlinks");
+  DEFSYM (QCmax_depth, ":max-depth");
+  DEFSYM (QCattributes, ":attributes");
+  DEFSYM (QCrespect_gitignore, ":respect-gitignore");
+  DEFSYM (QCinclude_directories, ":include-directories");
+  DEFSYM (QCfunction, ":function");
   DEFSYM (Qfile_name_completion, "file-name-completion");
   DEFSYM (Qfile_name_all_completions, "file-name-all-completions");
   DEFSYM (Qfile_attributes, "file-attributes");
@@ -2820,6 +2820,20 @@ styled_format (ptrdiff_t nargs, Lisp_Object *args, bool message)
 		conversion = 's';
 	      zero_flag = false;
 	    }
+	  else if (BIGNUMP (arg)
+		   && (conversion == 'd' || conversion == 'i'
+		       || conversion == 'o' || conversion == 'x'
+		       || conversion == 'X'))
+	    {
+	      /* Bignums are printed exactly, as their digit strings.  */
+	      int base = (conversion == 'o' ? 8
+			  : conversion == 'x' || conversion == 'X' ? 16 : 10);
+	      spec->argument = arg = bignum_to_string (arg, base);
+	      if (conversion == 'X')
+		spec->argument = arg = Fupcase (arg);
+	      conversion = 's';
+	      zero_flag = false;
+	    }
 
 	  if (SYMBOLP (arg))
 	    {
@@ -3770,6 +3784,12 @@ Transposing beyond buffer boundaries is an error.  */)
       update_compositions (end2 - len1, end2, CHECK_BORDER);
     }
 
+  /* The regions traded places, so positions between START1 and END2
+     no longer correspond, but the total length is the same.  */
+  adjust_charpos_index (current_buffer, start1, end2 - start1,
+			end2_byte - start1_byte, end2 - start1,
+			end2_byte - start1_byte);
+
   /* When doing multiple transpositions, it might be nice
      to optimize this.  Perhaps the markers in any one buffer
      should be organized in some sorted data tree.  */
@@ -1216,10 +1216,6 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
   running_asynch_code = 0;
   init_random ();
 
-#if defined HAVE_JSON && !defined WINDOWSNT
-  init_json ();
-#endif
-
   no_loadup
     = argmatch (argv, argc, "-nl", "--no-loadup", 6, NULL, &skip_args);
 
@@ -1432,6 +1428,7 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
       syms_of_ccl ();
       syms_of_character ();
       syms_of_cmds ();
+      syms_of_decompress ();
       syms_of_dired ();
       syms_of_display ();
       syms_of_doc ();
@@ -1545,9 +1542,7 @@ Using an Emacs configured with --with-x-toolkit=lucid does not have this problem
       syms_of_threads ();
       syms_of_profiler ();
 
-#ifdef HAVE_JSON
       syms_of_json ();
-#endif
 
       keys_of_casefiddle ();
       keys_of_cmds ();
@@ -2056,10 +2051,6 @@ shut_down_emacs (int sig, Lisp_Object stuff)
   ns_term_shutdown (sig);
 #endif
 
-#ifdef HAVE_LIBXML2
-  xml_cleanup_parser ();
-#endif
-
 #ifdef WINDOWSNT
   term_ntproc (0);
 #endif
@@ -1401,6 +1401,8 @@ internal_equal (Lisp_Object o1, Lisp_Object o2, enum equal_kind equal_kind,
 	   same size.  */
 	if (ASIZE (o2) != size)
 	  return false;
+	if (BIGNUMP (o1))
+	  return bignum_equal (o1, o2);
 	/* Boolvectors are compared much like strings.  */
 	if (BOOL_VECTOR_P (o1))
 	  {
@@ -2176,9 +2178,10 @@ cmpfn_eql (struct hash_table_test *ht,
 	   Lisp_Object key1,
 	   Lisp_Object key2)
 {
-  return (FLOATP (key1)
-	  && FLOATP (key2)
-	  && XFLOAT_DATA (key1) == XFLOAT_DATA (key2));
+  return ((FLOATP (key1)
+	   && FLOATP (key2)
+	   && XFLOAT_DATA (key1) == XFLOAT_DATA (key2))
+	  || (BIGNUMP (key1) && bignum_equal (key1, key2)));
 }
 
 
@@ -2234,7 +2237,8 @@ hashfn_equal (struct hash_table_test *ht, Lisp_Object key)
 static EMACS_UINT
 hashfn_eql (struct hash_table_test *ht, Lisp_Object key)
 {
-  return FLOATP (key) ? hashfn_equal (ht, key) : hashfn_eq (ht, key);
+  return (FLOATP (key) || BIGNUMP (key)
+	  ? hashfn_equal (ht, key) : hashfn_eq (ht, key));
 }
 
 /* Value is a hash code for KEY for use in hash table H which uses as
@@ -2876,6 +2880,8 @@ sxhash (Lisp_Object obj, int depth)
 	hash = sxhash_vector (obj, depth);
       else if (BOOL_VECTOR_P (obj))
 	hash = sxhash_bool_vector (obj);
+      else if (BIGNUMP (obj))
+	hash = bignum_hash (obj);
       else
 	/* Others are `equal' if they are `eq', so let's take their
 	   address as hash.  */
@@ -3072,20 +3078,6 @@ DEFUN ("hash-table-rehash-size", Fhash_table_rehash_size,
     return make_float (rehash_size + 1);
 }
 
-DEFUN ("secure-hash-algorithms", Fsecure_hash_algorithms,
-       Ssecure_hash_algorithms, 0, 0, 0,
-       doc: /* Return a list of all the supported `secure_hash' algorithms. */)
-  (void)
-{
-  return listn (CONSTYPE_HEAP, 6,
-                Qmd5,
-                Qsha1,
-                Qsha224,
-                Qsha256,
-                Qsha384,
-                Qsha512);
-}
-
 /* Extract data from a string or a buffer. SPEC is a list of
 (BUFFER-OR-STRING-OR-SYMBOL START END CODING-SYSTEM NOERROR) which behave as
 specified with `secure-hash' and in Info node
@@ -3311,6 +3303,12 @@ syms_of_fns (void)
   DEFSYM (Qsha256, "sha256");
   DEFSYM (Qsha384, "sha384");
   DEFSYM (Qsha512, "sha512");
+  DEFSYM (Qsha3_256, "sha3-256");
+  DEFSYM (Qsha3_512, "sha3-512");
+  DEFSYM (Qblake2b, "blake2b");
+  DEFSYM (Qblake2s, "blake2s");
+  DEFSYM (Qhash_context_p, "hash-context-p");
+  DEFSYM (Qrandom_state_p, "random-state-p");
 
   /* Miscellaneous stuff.  */
 
@@ -3395,6 +3393,5 @@ this variable.  */);
   defsubr (&Swidget_apply);
   defsubr (&Sbase64_encode_region);
   defsubr (&Sbase64_decode_region);
-  defsubr (&Ssecure_hash_algorithms);
   defsubr (&Slocale_info);
 }
@@ -82,6 +82,18 @@ check_markers (void)
 
 #endif /* MARKER_DEBUG */
 
+/* Signal an error if Rust code holds a view of the current buffer's
+   text (see buffer_text.rs).  Call this before changing the
+   characters of the buffer or moving its gap, but not for changes
+   that only touch text properties.  */
+
+static void
+check_no_readers (void)
+{
+  if (current_buffer->text->readers > 0)
+    error ("Buffer text is being read");
+}
+
 /* Move gap to byte position BYTEPOS, which is also char position CHARPOS.
    Note that this can quit!  */
 
@@ -108,6 +120,7 @@ gap_left (ptrdiff_t charpos, ptrdiff_t bytepos, bool newgap)
   ptrdiff_t i;
   ptrdiff_t new_s1;
 
+  check_no_readers ();
   if (!newgap)
     BUF_COMPUTE_UNCHANGED (current_buffer, charpos, GPT);
 
@@ -165,6 +178,7 @@ gap_right (ptrdiff_t charpos, ptrdiff_t bytepos)
   register ptrdiff_t i;
   ptrdiff_t new_s1;
 
+  check_no_readers ();
   BUF_COMPUTE_UNCHANGED (current_buffer, charpos, GPT);
 
   i = GPT_BYTE;
@@ -241,6 +255,8 @@ adjust_markers_for_delete (ptrdiff_t from, ptrdiff_t from_byte,
   ptrdiff_t charpos;
 
   adjust_suspend_auto_hscroll (from, to);
+  adjust_charpos_index (current_buffer, from, to - from, to_byte - from_byte,
+			0, 0);
   for (m = BUF_MARKERS (current_buffer); m; m = m->next)
     {
       charpos = m->charpos;
@@ -281,6 +297,7 @@ adjust_markers_for_insert (ptrdiff_t from, ptrdiff_t from_byte,
   ptrdiff_t nbytes = to_byte - from_byte;
 
   adjust_suspend_auto_hscroll (from, to);
+  adjust_charpos_index (current_buffer, from, 0, 0, nchars, nbytes);
   for (m = BUF_MARKERS (current_buffer); m; m = m->next)
     {
       eassert (m->bytepos >= m->charpos
@@ -347,6 +364,8 @@ adjust_markers_for_replace (ptrdiff_t from, ptrdiff_t from_byte,
   ptrdiff_t diff_bytes = new_bytes - old_bytes;
 
   adjust_suspend_auto_hscroll (from, from + old_chars);
+  adjust_charpos_index (current_buffer, from, old_chars, old_bytes,
+			new_chars, new_bytes);
   for (m = BUF_MARKERS (current_buffer); m; m = m->next)
     {
       if (m->bytepos >= prev_to_byte)
@@ -559,6 +578,7 @@ make_gap_smaller (ptrdiff_t nbytes_removed)
 void
 make_gap (ptrdiff_t nbytes_added)
 {
+  check_no_readers ();
   if (nbytes_added >= 0)
     /* With set-buffer-multibyte on a large buffer, we can end up growing the
      * buffer *many* times.  Avoid an O(N^2) behavior by increasing by an
@@ -1983,6 +2003,7 @@ void
 prepare_to_modify_buffer (ptrdiff_t start, ptrdiff_t end,
 			  ptrdiff_t *preserve_ptr)
 {
+  check_no_readers ();
   prepare_to_modify_buffer_1 (start, end, preserve_ptr);
   invalidate_buffer_caches (current_buffer, start, end);
 }
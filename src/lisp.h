@@ -969,6 +969,9 @@ enum pvec_type
   PVEC_MUTEX,
   PVEC_CONDVAR,
   PVEC_MODULE_FUNCTION,
+  PVEC_HASH_CONTEXT,
+  PVEC_BIGNUM,
+  PVEC_RANDOM_STATE,
 
   /* These should be last, check internal_equal to see why.  */
   PVEC_COMPILED,
@@ -2891,6 +2894,12 @@ CHECK_RECORD (Lisp_Object x)
   CHECK_TYPE (RECORDP (x), Qrecordp, x);
 }
 
+INLINE bool
+BIGNUMP (Lisp_Object x)
+{
+  return PSEUDOVECTORP (x, PVEC_BIGNUM);
+}
+
 /* Test for image (image . spec)  */
 INLINE bool
 IMAGEP (Lisp_Object x)
@@ -3437,8 +3446,6 @@ set_sub_char_table_contents (Lisp_Object table, ptrdiff_t idx, Lisp_Object val)
 
 /* Defined in data.c.  */
 extern _Noreturn void wrong_choice (Lisp_Object, Lisp_Object);
-extern void notify_variable_watchers (Lisp_Object, Lisp_Object,
-				      Lisp_Object, Lisp_Object);
 extern Lisp_Object indirect_function (Lisp_Object);
 extern Lisp_Object find_symbol_value (Lisp_Object);
 enum Arith_Comparison {
@@ -3576,11 +3583,28 @@ extern int x_bitmap_mask (struct frame *, ptrdiff_t);
 extern void reset_image_types (void);
 extern void syms_of_image (void);
 
-#ifdef HAVE_JSON
-/* Defined in json.c.  */
-extern void init_json (void);
+/* Defined in rust_src/src/bignum.rs.  */
+extern void finalize_bignum (struct Lisp_Vector *);
+extern bool bignum_equal (Lisp_Object, Lisp_Object);
+extern EMACS_UINT bignum_hash (Lisp_Object);
+extern Lisp_Object bignum_to_string (Lisp_Object, int);
+extern Lisp_Object bignum_from_digits (const char *, ptrdiff_t, int, bool);
+
+/* Defined in rust_src/src/crypto/mod.rs.  */
+extern void finalize_hash_context (struct Lisp_Vector *);
+
+/* Defined in rust_src/src/numbers.rs.  */
+extern void finalize_random_state (struct Lisp_Vector *);
+
+/* Defined in rust_src/src/data.rs.  */
+extern void notify_variable_watchers (Lisp_Object, Lisp_Object,
+				      Lisp_Object, Lisp_Object);
+
+/* Defined in rust_src/src/decompress.rs.  */
+extern void syms_of_decompress (void);
+
+/* Defined in rust_src/src/json.rs.  */
 extern void syms_of_json (void);
-#endif
 
 /* Defined in insdel.c.  */
 extern void move_gap_both (ptrdiff_t, ptrdiff_t);
@@ -4148,6 +4172,8 @@ extern void keys_of_buffer (void);
 extern ptrdiff_t marker_position (Lisp_Object);
 extern ptrdiff_t marker_byte_position (Lisp_Object);
 extern void clear_charpos_cache (struct buffer *);
+extern void adjust_charpos_index (struct buffer *, ptrdiff_t, ptrdiff_t,
+				  ptrdiff_t, ptrdiff_t, ptrdiff_t);
 extern ptrdiff_t buf_charpos_to_bytepos (struct buffer *, ptrdiff_t);
 extern ptrdiff_t buf_bytepos_to_charpos (struct buffer *, ptrdiff_t);
 extern void unchain_marker (struct Lisp_Marker *marker);
@@ -4220,10 +4246,9 @@ extern ptrdiff_t find_before_next_newline (ptrdiff_t, ptrdiff_t,
 extern void syms_of_search (void);
 extern void clear_regexp_cache (void);
 
-Lisp_Object looking_at_1 (Lisp_Object string, bool posix);
+extern void save_search_regs (void);
 Lisp_Object match_limit (Lisp_Object num, bool beginningp);
 Lisp_Object search_command (Lisp_Object string, Lisp_Object bound, Lisp_Object noerror, Lisp_Object count, int direction, int RE, bool posix);
-Lisp_Object string_match_1 (Lisp_Object regexp, Lisp_Object string, Lisp_Object start, bool posix);
 
 /* Defined in minibuf.c.  */
 
@@ -4570,13 +4595,6 @@ extern void syms_of_xterm (void);
 extern char *x_get_keysym_name (int);
 #endif /* HAVE_WINDOW_SYSTEM */
 
-/* Defined in xml.c.  */
-#ifdef HAVE_LIBXML2
-extern void xml_cleanup_parser (void);
-bool init_libxml2_functions (void);
-Lisp_Object parse_region (Lisp_Object start, Lisp_Object end, Lisp_Object base_url, Lisp_Object discard_comments, bool htmlp);
-#endif
-
 #ifdef HAVE_LCMS2
 /* Defined in lcms.c.  */
 extern void syms_of_lcms2 (void);
@@ -4591,11 +4609,12 @@ void syms_of_dbusbind (void);
 
 /* Defined in profiler.c.  */
 extern bool profiler_memory_running;
-extern Lisp_Object memory_log;
-extern Lisp_Object make_log (EMACS_INT heap_size, EMACS_INT max_stack_depth);
-extern void malloc_probe (size_t);
+extern struct hash_table_test hashtest_profiler;
 extern void syms_of_profiler (void);
 
+/* Defined in rust_src/src/profiler.rs.  */
+extern void malloc_probe (size_t);
+
 
 #ifdef DOS_NT
 /* Defined in w32.c.  */
@@ -3618,6 +3618,7 @@ string_to_number (char const *string, int base, bool ignore_trailing)
 	  n += digit;
 	}
     }
+  char const *int_end = cp;
   if (*cp == '.')
     {
       state |= DOT_CHAR;
@@ -3677,30 +3678,23 @@ string_to_number (char const *string, int base, bool ignore_trailing)
 		     || float_syntax))))
     return Qnil;
 
-  /* If the number uses integer and not float syntax, and is in C-language
-     range, use its value, preferably as a fixnum.  */
+  /* If the number uses integer and not float syntax, use its value as
+     a fixnum, or as a bignum if it does not fit into one.  */
   if (leading_digit >= 0 && ! float_syntax)
     {
-      if (state & INTOVERFLOW)
-	{
-	  /* Unfortunately there's no simple and accurate way to convert
-	     non-base-10 numbers that are out of C-language range.  */
-	  if (base != 10)
-	    xsignal1 (Qoverflow_error, build_string (string));
-	}
-      else if (n <= (negative ? -MOST_NEGATIVE_FIXNUM : MOST_POSITIVE_FIXNUM))
+      if (! (state & INTOVERFLOW)
+	  && n <= (negative ? -MOST_NEGATIVE_FIXNUM : MOST_POSITIVE_FIXNUM))
 	{
 	  EMACS_INT signed_n = n;
 	  return make_number (negative ? -signed_n : signed_n);
 	}
-      else
-	value = n;
+      return bignum_from_digits (string + signedp, int_end - (string + signedp),
+				 base, negative);
     }
 
-  /* Either the number uses float syntax, or it does not fit into a fixnum.
-     Convert it from string to floating point, unless the value is already
-     known because it is an infinity, a NAN, or its absolute value fits in
-     uintmax_t.  */
+  /* The number uses float syntax.  Convert it from string to floating
+     point, unless the value is already known because it is an infinity
+     or a NAN.  */
   if (! value)
     value = atof (string + signedp);
 
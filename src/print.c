@@ -1615,6 +1615,28 @@ print_vectorlike (Lisp_Object obj, Lisp_Object printcharfun, bool escapeflag,
       printchar ('>', printcharfun);
       break;
 
+    case PVEC_HASH_CONTEXT:
+      /* The first slot holds the algorithm symbol.  */
+      print_c_string ("#<hash-context ", printcharfun);
+      print_object (AREF (obj, 0), printcharfun, escapeflag);
+      printchar ('>', printcharfun);
+      break;
+
+    case PVEC_BIGNUM:
+      {
+	Lisp_Object digits = bignum_to_string (obj, 10);
+	strout (SSDATA (digits), SCHARS (digits), SBYTES (digits),
+		printcharfun);
+      }
+      break;
+
+    case PVEC_RANDOM_STATE:
+      {
+	int len = sprintf (buf, "#<random-state %p>", XVECTOR (obj));
+	strout (buf, len, len, printcharfun);
+      }
+      break;
+
     case PVEC_RECORD:
       {
 	ptrdiff_t size = PVSIZE (obj);
@@ -255,15 +255,12 @@ static int process_output_delay_count;
 
 static bool process_output_skip;
 
-static void start_process_unwind (Lisp_Object);
-static void create_process (Lisp_Object, char **, Lisp_Object);
 #ifdef USABLE_SIGIO
 static bool keyboard_bit_set (fd_set *);
 #endif
 static void deactivate_process (Lisp_Object);
 static int status_notify (struct Lisp_Process *, struct Lisp_Process *);
 static int read_process_output (Lisp_Object, int);
-static void create_pty (Lisp_Object);
 static void exec_sentinel (Lisp_Object, Lisp_Object);
 
 void add_process_read_fd (int);
@@ -282,7 +279,7 @@ static int max_desc;
 static int external_sock_fd;
 
 /* Indexed by descriptor, gives the process (if any) for that descriptor.  */
-static Lisp_Object chan_process[FD_SETSIZE];
+Lisp_Object chan_process[FD_SETSIZE];
 static void wait_for_socket_fds (Lisp_Object, char const *);
 
 /* Alist of elements (NAME . PROCESS).  */
@@ -781,13 +778,11 @@ status_message (struct Lisp_Process *p)
     return Fcopy_sequence (Fsymbol_name (symbol));
 }
 
-enum { PTY_NAME_SIZE = 24 };
-
 /* Open an available pty, returning a file descriptor.
    Store into PTY_NAME the file name of the terminal corresponding to the pty.
    Return -1 on failure.  */
 
-static int
+int
 allocate_pty (char pty_name[PTY_NAME_SIZE])
 {
 #ifdef HAVE_PTYS
@@ -854,7 +849,7 @@ allocate_process (void)
   return ALLOCATE_ZEROED_PSEUDOVECTOR (struct Lisp_Process, pid, PVEC_PROCESS);
 }
 
-static Lisp_Object
+Lisp_Object
 make_process (Lisp_Object name)
 {
   struct Lisp_Process *p = allocate_process ();
@@ -900,7 +895,7 @@ make_process (Lisp_Object name)
   return val;
 }
 
-static void
+void
 remove_process (register Lisp_Object proc)
 {
   register Lisp_Object pair;
@@ -1252,326 +1247,8 @@ Return nil if format of ADDRESS is invalid.  */)
   return Qnil;
 }
 
-/* Starting asynchronous inferior processes.  */
-
-DEFUN ("make-process", Fmake_process, Smake_process, 0, MANY, 0,
-       doc: /* Start a program in a subprocess.  Return the process object for it.
-
-This is similar to `start-process', but arguments are specified as
-keyword/argument pairs.  The following arguments are defined:
-
-:name NAME -- NAME is name for process.  It is modified if necessary
-to make it unique.
-
-:buffer BUFFER -- BUFFER is the buffer (or buffer-name) to associate
-with the process.  Process output goes at end of that buffer, unless
-you specify a filter function to handle the output.  BUFFER may be
-also nil, meaning that this process is not associated with any buffer.
-
-:command COMMAND -- COMMAND is a list starting with the program file
-name, followed by strings to give to the program as arguments.
-
-:coding CODING -- If CODING is a symbol, it specifies the coding
-system used for both reading and writing for this process.  If CODING
-is a cons (DECODING . ENCODING), DECODING is used for reading, and
-ENCODING is used for writing.
-
-:noquery BOOL -- When exiting Emacs, query the user if BOOL is nil and
-the process is running.  If BOOL is not given, query before exiting.
-
-:stop BOOL -- Start process in the `stopped' state if BOOL non-nil.
-In the stopped state, a process does not accept incoming data, but you
-can send outgoing data.  The stopped state is cleared by
-`continue-process' and set by `stop-process'.
-
-:connection-type TYPE -- TYPE is control type of device used to
-communicate with subprocesses.  Values are `pipe' to use a pipe, `pty'
-to use a pty, or nil to use the default specified through
-`process-connection-type'.
-
-:filter FILTER -- Install FILTER as the process filter.
-
-:sentinel SENTINEL -- Install SENTINEL as the process sentinel.
-
-:stderr STDERR -- STDERR is either a buffer or a pipe process attached
-to the standard error of subprocess.  Specifying this implies
-`:connection-type' is set to `pipe'.
-
-usage: (make-process &rest ARGS)  */)
-  (ptrdiff_t nargs, Lisp_Object *args)
-{
-  Lisp_Object buffer, name, command, program, proc, contact, current_dir, tem;
-  Lisp_Object xstderr, stderrproc;
-  ptrdiff_t count = SPECPDL_INDEX ();
-
-  if (nargs == 0)
-    return Qnil;
-
-  /* Save arguments for process-contact and clone-process.  */
-  contact = Flist (nargs, args);
-
-  buffer = Fplist_get (contact, QCbuffer);
-  if (!NILP (buffer))
-    buffer = Fget_buffer_create (buffer);
-
-  /* Make sure that the child will be able to chdir to the current
-     buffer's current directory, or its unhandled equivalent.  We
-     can't just have the child check for an error when it does the
-     chdir, since it's in a vfork.  */
-  current_dir = encode_current_directory ();
-
-  name = Fplist_get (contact, QCname);
-  CHECK_STRING (name);
-
-  command = Fplist_get (contact, QCcommand);
-  if (CONSP (command))
-    program = XCAR (command);
-  else
-    program = Qnil;
-
-  if (!NILP (program))
-    CHECK_STRING (program);
-
-  bool query_on_exit = NILP (Fplist_get (contact, QCnoquery));
-
-  stderrproc = Qnil;
-  xstderr = Fplist_get (contact, QCstderr);
-  if (PROCESSP (xstderr))
-    {
-      if (!PIPECONN_P (xstderr))
-	error ("Process is not a pipe process");
-      stderrproc = xstderr;
-    }
-  else if (!NILP (xstderr))
-    {
-      CHECK_STRING (program);
-      stderrproc = CALLN (Fmake_pipe_process,
-			  QCname,
-			  concat2 (name, build_string (" stderr")),
-			  QCbuffer,
-			  Fget_buffer_create (xstderr),
-			  QCnoquery,
-			  query_on_exit ? Qnil : Qt);
-    }
-
-  proc = make_process (name);
-  record_unwind_protect (start_process_unwind, proc);
-
-  pset_childp (XPROCESS (proc), Qt);
-  eassert (NILP (XPROCESS (proc)->plist));
-  pset_type (XPROCESS (proc), Qreal);
-  pset_buffer (XPROCESS (proc), buffer);
-  pset_sentinel (XPROCESS (proc), Fplist_get (contact, QCsentinel));
-  pset_filter (XPROCESS (proc), Fplist_get (contact, QCfilter));
-  pset_command (XPROCESS (proc), Fcopy_sequence (command));
-
-  if (!query_on_exit)
-    XPROCESS (proc)->kill_without_query = 1;
-  if (tem = Fplist_get (contact, QCstop), !NILP (tem))
-    pset_command (XPROCESS (proc), Qt);
-
-  tem = Fplist_get (contact, QCconnection_type);
-  if (EQ (tem, Qpty))
-    XPROCESS (proc)->pty_flag = true;
-  else if (EQ (tem, Qpipe))
-    XPROCESS (proc)->pty_flag = false;
-  else if (NILP (tem))
-    XPROCESS (proc)->pty_flag = !NILP (Vprocess_connection_type);
-  else
-    report_file_error ("Unknown connection type", tem);
-
-  if (!NILP (stderrproc))
-    {
-      pset_stderrproc (XPROCESS (proc), stderrproc);
-
-      XPROCESS (proc)->pty_flag = false;
-    }
-
-#ifdef HAVE_GNUTLS
-  /* AKA GNUTLS_INITSTAGE(proc).  */
-  verify (GNUTLS_STAGE_EMPTY == 0);
-  eassert (XPROCESS (proc)->gnutls_initstage == GNUTLS_STAGE_EMPTY);
-  eassert (NILP (XPROCESS (proc)->gnutls_cred_type));
-#endif
-
-  XPROCESS (proc)->adaptive_read_buffering
-    = (NILP (Vprocess_adaptive_read_buffering) ? 0
-       : EQ (Vprocess_adaptive_read_buffering, Qt) ? 1 : 2);
-
-  /* Make the process marker point into the process buffer (if any).  */
-  if (BUFFERP (buffer))
-    set_marker_both (XPROCESS (proc)->mark, buffer,
-		     BUF_ZV (XBUFFER (buffer)),
-		     BUF_ZV_BYTE (XBUFFER (buffer)));
-
-  USE_SAFE_ALLOCA;
-
-  {
-    /* Decide coding systems for communicating with the process.  Here
-       we don't setup the structure coding_system nor pay attention to
-       unibyte mode.  They are done in create_process.  */
-
-    /* Qt denotes we have not yet called Ffind_operation_coding_system.  */
-    Lisp_Object coding_systems = Qt;
-    Lisp_Object val, *args2;
-
-    tem = Fplist_get (contact, QCcoding);
-    if (!NILP (tem))
-      {
-	val = tem;
-	if (CONSP (val))
-	  val = XCAR (val);
-      }
-    else
-      val = Vcoding_system_for_read;
-    if (NILP (val))
-      {
-	ptrdiff_t nargs2 = 3 + XINT (Flength (command));
-	Lisp_Object tem2;
-	SAFE_ALLOCA_LISP (args2, nargs2);
-	ptrdiff_t i = 0;
-	args2[i++] = Qstart_process;
-	args2[i++] = name;
-	args2[i++] = buffer;
-	for (tem2 = command; CONSP (tem2); tem2 = XCDR (tem2))
-	  args2[i++] = XCAR (tem2);
-	if (!NILP (program))
-	  coding_systems = Ffind_operation_coding_system (nargs2, args2);
-	if (CONSP (coding_systems))
-	  val = XCAR (coding_systems);
-	else if (CONSP (Vdefault_process_coding_system))
-	  val = XCAR (Vdefault_process_coding_system);
-      }
-    pset_decode_coding_system (XPROCESS (proc), val);
-
-    if (!NILP (tem))
-      {
-	val = tem;
-	if (CONSP (val))
-	  val = XCDR (val);
-      }
-    else
-      val = Vcoding_system_for_write;
-    if (NILP (val))
-      {
-	if (EQ (coding_systems, Qt))
-	  {
-	    ptrdiff_t nargs2 = 3 + XINT (Flength (command));
-	    Lisp_Object tem2;
-	    SAFE_ALLOCA_LISP (args2, nargs2);
-	    ptrdiff_t i = 0;
-	    args2[i++] = Qstart_process;
-	    args2[i++] = name;
-	    args2[i++] = buffer;
-	    for (tem2 = command; CONSP (tem2); tem2 = XCDR (tem2))
-	      args2[i++] = XCAR (tem2);
-	    if (!NILP (program))
-	      coding_systems = Ffind_operation_coding_system (nargs2, args2);
-	  }
-	if (CONSP (coding_systems))
-	  val = XCDR (coding_systems);
-	else if (CONSP (Vdefault_process_coding_system))
-	  val = XCDR (Vdefault_process_coding_system);
-      }
-    pset_encode_coding_system (XPROCESS (proc), val);
-    /* Note: At this moment, the above coding system may leave
-       text-conversion or eol-conversion unspecified.  They will be
-       decided after we read output from the process and decode it by
-       some coding system, or just before we actually send a text to
-       the process.  */
-  }
-
-
-  pset_decoding_buf (XPROCESS (proc), empty_unibyte_string);
-  eassert (XPROCESS (proc)->decoding_carryover == 0);
-  pset_encoding_buf (XPROCESS (proc), empty_unibyte_string);
-
-  XPROCESS (proc)->inherit_coding_system_flag
-    = !(NILP (buffer) || !inherit_process_coding_system);
-
-  if (!NILP (program))
-    {
-      Lisp_Object program_args = XCDR (command);
-
-      /* If program file name is not absolute, search our path for it.
-	 Put the name we will really use in TEM.  */
-      if (!IS_DIRECTORY_SEP (SREF (program, 0))
-	  && !(SCHARS (program) > 1
-	       && IS_DEVICE_SEP (SREF (program, 1))))
-	{
-	  tem = Qnil;
-	  openp (Vexec_path, program, Vexec_suffixes, &tem,
-		 make_number (X_OK), false);
-	  if (NILP (tem))
-	    report_file_error ("Searching for program", program);
-	  tem = Fexpand_file_name (tem, Qnil);
-	}
-      else
-	{
-	  if (!NILP (Ffile_directory_p (program)))
-	    error ("Specified program for new process is a directory");
-	  tem = program;
-	}
-
-      /* Remove "/:" from TEM.  */
-      tem = remove_slash_colon (tem);
-
-      Lisp_Object arg_encoding = Qnil;
-
-      /* Encode the file name and put it in NEW_ARGV.
-	 That's where the child will use it to execute the program.  */
-      tem = list1 (ENCODE_FILE (tem));
-      ptrdiff_t new_argc = 1;
-
-      /* Here we encode arguments by the coding system used for sending
-	 data to the process.  We don't support using different coding
-	 systems for encoding arguments and for encoding data sent to the
-	 process.  */
-
-      for (Lisp_Object tem2 = program_args; CONSP (tem2); tem2 = XCDR (tem2))
-	{
-	  Lisp_Object arg = XCAR (tem2);
-	  CHECK_STRING (arg);
-	  if (STRING_MULTIBYTE (arg))
-	    {
-	      if (NILP (arg_encoding))
-		arg_encoding = (complement_process_encoding_system
-				(XPROCESS (proc)->encode_coding_system));
-	      arg = code_convert_string_norecord (arg, arg_encoding, 1);
-	    }
-	  tem = Fcons (arg, tem);
-	  new_argc++;
-	}
-
-      /* Now that everything is encoded we can collect the strings into
-	 NEW_ARGV.  */
-      char **new_argv;
-      SAFE_NALLOCA (new_argv, 1, new_argc + 1);
-      new_argv[new_argc] = 0;
-
-      for (ptrdiff_t i = new_argc - 1; i >= 0; i--)
-	{
-	  new_argv[i] = SSDATA (XCAR (tem));
-	  tem = XCDR (tem);
-	}
-
-      create_process (proc, new_argv, current_dir);
-    }
-  else
-    create_pty (proc);
-
-  SAFE_FREE ();
-  return unbind_to (count, proc);
-}
-
-/* If PROC doesn't have its pid set, then an error was signaled and
-   the process wasn't started successfully, so remove it.  */
-static void
-start_process_unwind (Lisp_Object proc)
-{
-  if (XPROCESS (proc)->pid <= 0 && XPROCESS (proc)->pid != -2)
-    remove_process (proc);
-}
+/* Starting asynchronous inferior processes.  `make-process' itself is
+   defined in rust_src/src/process.rs.  */
 
 /* If *FD_ADDR is nonnegative, close it, and mark it as closed.  */
 
@@ -1586,309 +1263,9 @@ close_process_fd (int *fd_addr)
     }
 }
 
-/* Indexes of file descriptors in open_fds.  */
-enum
-  {
-    /* The pipe from Emacs to its subprocess.  */
-    SUBPROCESS_STDIN,
-    WRITE_TO_SUBPROCESS,
-
-    /* The main pipe from the subprocess to Emacs.  */
-    READ_FROM_SUBPROCESS,
-    SUBPROCESS_STDOUT,
-
-    /* The pipe from the subprocess to Emacs that is closed when the
-       subprocess execs.  */
-    READ_FROM_EXEC_MONITOR,
-    EXEC_MONITOR_OUTPUT
-  };
-
 verify (PROCESS_OPEN_FDS == EXEC_MONITOR_OUTPUT + 1);
 
-static void
-create_process (Lisp_Object process, char **new_argv, Lisp_Object current_dir)
-{
-  struct Lisp_Process *p = XPROCESS (process);
-  int inchannel, outchannel;
-  pid_t pid;
-  int vfork_errno;
-  int forkin, forkout, forkerr = -1;
-  bool pty_flag = 0;
-  char pty_name[PTY_NAME_SIZE];
-  Lisp_Object lisp_pty_name = Qnil;
-  sigset_t oldset;
-
-  inchannel = outchannel = -1;
-
-  if (p->pty_flag)
-    outchannel = inchannel = allocate_pty (pty_name);
-
-  if (inchannel >= 0)
-    {
-      p->open_fd[READ_FROM_SUBPROCESS] = inchannel;
-#if ! defined (USG) || defined (USG_SUBTTY_WORKS)
-      /* On most USG systems it does not work to open the pty's tty here,
-	 then close it and reopen it in the child.  */
-      /* Don't let this terminal become our controlling terminal
-	 (in case we don't have one).  */
-      forkout = forkin = emacs_open (pty_name, O_RDWR | O_NOCTTY, 0);
-      if (forkin < 0)
-	report_file_error ("Opening pty", Qnil);
-      p->open_fd[SUBPROCESS_STDIN] = forkin;
-#else
-      forkin = forkout = -1;
-#endif /* not USG, or USG_SUBTTY_WORKS */
-      pty_flag = 1;
-      lisp_pty_name = build_string (pty_name);
-    }
-  else
-    {
-      if (emacs_pipe (p->open_fd + SUBPROCESS_STDIN) != 0
-	  || emacs_pipe (p->open_fd + READ_FROM_SUBPROCESS) != 0)
-	report_file_error ("Creating pipe", Qnil);
-      forkin = p->open_fd[SUBPROCESS_STDIN];
-      outchannel = p->open_fd[WRITE_TO_SUBPROCESS];
-      inchannel = p->open_fd[READ_FROM_SUBPROCESS];
-      forkout = p->open_fd[SUBPROCESS_STDOUT];
-
-      if (!NILP (p->stderrproc))
-	{
-	  struct Lisp_Process *pp = XPROCESS (p->stderrproc);
-
-	  forkerr = pp->open_fd[SUBPROCESS_STDOUT];
-
-	  /* Close unnecessary file descriptors.  */
-	  close_process_fd (&pp->open_fd[WRITE_TO_SUBPROCESS]);
-	  close_process_fd (&pp->open_fd[SUBPROCESS_STDIN]);
-	}
-    }
-
-#ifndef WINDOWSNT
-  if (emacs_pipe (p->open_fd + READ_FROM_EXEC_MONITOR) != 0)
-    report_file_error ("Creating pipe", Qnil);
-#endif
-
-  fcntl (inchannel, F_SETFL, O_NONBLOCK);
-  fcntl (outchannel, F_SETFL, O_NONBLOCK);
-
-  /* Record this as an active process, with its channels.  */
-  chan_process[inchannel] = process;
-  p->infd = inchannel;
-  p->outfd = outchannel;
-
-  /* Previously we recorded the tty descriptor used in the subprocess.
-     It was only used for getting the foreground tty process, so now
-     we just reopen the device (see emacs_get_tty_pgrp) as this is
-     more portable (see USG_SUBTTY_WORKS above).  */
-
-  p->pty_flag = pty_flag;
-  pset_status (p, Qrun);
-
-  if (!EQ (p->command, Qt))
-    add_process_read_fd (inchannel);
-
-  /* This may signal an error.  */
-  setup_process_coding_systems (process);
-
-  block_input ();
-  block_child_signal (&oldset);
-
-#ifndef WINDOWSNT
-  /* vfork, and prevent local vars from being clobbered by the vfork.  */
-  Lisp_Object volatile current_dir_volatile = current_dir;
-  Lisp_Object volatile lisp_pty_name_volatile = lisp_pty_name;
-  char **volatile new_argv_volatile = new_argv;
-  int volatile forkin_volatile = forkin;
-  int volatile forkout_volatile = forkout;
-  int volatile forkerr_volatile = forkerr;
-  struct Lisp_Process *p_volatile = p;
-
-#ifdef DARWIN_OS
-  /* Darwin doesn't let us run setsid after a vfork, so use fork when
-     necessary.  Also, reset SIGCHLD handling after a vfork, as
-     apparently macOS can mistakenly deliver SIGCHLD to the child.  */
-  if (pty_flag)
-    pid = fork ();
-  else
-    {
-      pid = vfork ();
-      if (pid == 0)
-	signal (SIGCHLD, SIG_DFL);
-    }
-#else
-  pid = vfork ();
-#endif
-
-  current_dir = current_dir_volatile;
-  lisp_pty_name = lisp_pty_name_volatile;
-  new_argv = new_argv_volatile;
-  forkin = forkin_volatile;
-  forkout = forkout_volatile;
-  forkerr = forkerr_volatile;
-  p = p_volatile;
-
-  pty_flag = p->pty_flag;
-
-  if (pid == 0)
-#endif /* not WINDOWSNT */
-    {
-      /* Make the pty be the controlling terminal of the process.  */
-#ifdef HAVE_PTYS
-      /* First, disconnect its current controlling terminal.  */
-      if (pty_flag)
-	setsid ();
-      /* Make the pty's terminal the controlling terminal.  */
-      if (pty_flag && forkin >= 0)
-	{
-#ifdef TIOCSCTTY
-	  /* We ignore the return value
-	     because faith@cs.unc.edu says that is necessary on Linux.  */
-	  ioctl (forkin, TIOCSCTTY, 0);
-#endif
-	}
-#if defined (LDISC1)
-      if (pty_flag && forkin >= 0)
-	{
-	  struct termios t;
-	  tcgetattr (forkin, &t);
-	  t.c_lflag = LDISC1;
-	  if (tcsetattr (forkin, TCSANOW, &t) < 0)
-	    emacs_perror ("create_process/tcsetattr LDISC1");
-	}
-#else
-#if defined (NTTYDISC) && defined (TIOCSETD)
-      if (pty_flag && forkin >= 0)
-	{
-	  /* Use new line discipline.  */
-	  int ldisc = NTTYDISC;
-	  ioctl (forkin, TIOCSETD, &ldisc);
-	}
-#endif
-#endif
-#ifdef TIOCNOTTY
-      /* In 4.3BSD, the TIOCSPGRP bug has been fixed, and now you
-	 can do TIOCSPGRP only to the process's controlling tty.  */
-      if (pty_flag)
-	{
-	  /* I wonder: would just ioctl (0, TIOCNOTTY, 0) work here?
-	     I can't test it since I don't have 4.3.  */
-	  int j = emacs_open (DEV_TTY, O_RDWR, 0);
-	  if (j >= 0)
-	    {
-	      ioctl (j, TIOCNOTTY, 0);
-	      emacs_close (j);
-	    }
-	}
-#endif /* TIOCNOTTY */
-
-#if !defined (DONT_REOPEN_PTY)
-/*** There is a suggestion that this ought to be a
-     conditional on TIOCSPGRP, or !defined TIOCSCTTY.
-     Trying the latter gave the wrong results on Debian GNU/Linux 1.1;
-     that system does seem to need this code, even though
-     both TIOCSCTTY is defined.  */
-	/* Now close the pty (if we had it open) and reopen it.
-	   This makes the pty the controlling terminal of the subprocess.  */
-      if (pty_flag)
-	{
-
-	  /* I wonder if emacs_close (emacs_open (SSDATA (lisp_pty_name), ...))
-	     would work?  */
-	  if (forkin >= 0)
-	    emacs_close (forkin);
-	  forkout = forkin = emacs_open (SSDATA (lisp_pty_name), O_RDWR, 0);
-
-	  if (forkin < 0)
-	    {
-	      emacs_perror (SSDATA (lisp_pty_name));
-	      _exit (EXIT_CANCELED);
-	    }
-
-	}
-#endif /* not DONT_REOPEN_PTY */
-
-#ifdef SETUP_SLAVE_PTY
-      if (pty_flag)
-	{
-	  SETUP_SLAVE_PTY;
-	}
-#endif /* SETUP_SLAVE_PTY */
-#endif /* HAVE_PTYS */
-
-      signal (SIGINT, SIG_DFL);
-      signal (SIGQUIT, SIG_DFL);
-#ifdef SIGPROF
-      signal (SIGPROF, SIG_DFL);
-#endif
-
-      /* Emacs ignores SIGPIPE, but the child should not.  */
-      signal (SIGPIPE, SIG_DFL);
-
-      /* Stop blocking SIGCHLD in the child.  */
-      unblock_child_signal (&oldset);
-
-      if (pty_flag)
-	child_setup_tty (forkout);
-
-      if (forkerr < 0)
-	forkerr = forkout;
-#ifdef WINDOWSNT
-      pid = child_setup (forkin, forkout, forkerr, new_argv, 1, current_dir);
-#else  /* not WINDOWSNT */
-      child_setup (forkin, forkout, forkerr, new_argv, 1, current_dir);
-#endif /* not WINDOWSNT */
-    }
-
-  /* Back in the parent process.  */
-
-  vfork_errno = errno;
-  p->pid = pid;
-  if (pid >= 0)
-    p->alive = 1;
-
-  /* Stop blocking in the parent.  */
-  unblock_child_signal (&oldset);
-  unblock_input ();
-
-  if (pid < 0)
-    report_file_errno ("Doing vfork", Qnil, vfork_errno);
-  else
-    {
-      /* vfork succeeded.  */
-
-      /* Close the pipe ends that the child uses, or the child's pty.  */
-      close_process_fd (&p->open_fd[SUBPROCESS_STDIN]);
-      close_process_fd (&p->open_fd[SUBPROCESS_STDOUT]);
-
-#ifdef WINDOWSNT
-      register_child (pid, inchannel);
-#endif /* WINDOWSNT */
-
-      pset_tty_name (p, lisp_pty_name);
-
-#ifndef WINDOWSNT
-      /* Wait for child_setup to complete in case that vfork is
-	 actually defined as fork.  The descriptor
-	 XPROCESS (proc)->open_fd[EXEC_MONITOR_OUTPUT]
-	 of a pipe is closed at the child side either by close-on-exec
-	 on successful execve or the _exit call in child_setup.  */
-      {
-	char dummy;
-
-	close_process_fd (&p->open_fd[EXEC_MONITOR_OUTPUT]);
-	emacs_read (p->open_fd[READ_FROM_EXEC_MONITOR], &dummy, 1);
-	close_process_fd (&p->open_fd[READ_FROM_EXEC_MONITOR]);
-      }
-#endif
-      if (!NILP (p->stderrproc))
-	{
-	  struct Lisp_Process *pp = XPROCESS (p->stderrproc);
-	  close_process_fd (&pp->open_fd[SUBPROCESS_STDOUT]);
-	}
-    }
-}
-
-static void
+void
 create_pty (Lisp_Object process)
 {
   struct Lisp_Process *p = XPROCESS (process);
@@ -6405,7 +5782,9 @@ DEFUN ("signal-process", Fsignal_process, Ssignal_process,
 PROCESS may also be a number specifying the process id of the
 process to signal; in this case, the process need not be a child of
 this Emacs.
-SIGCODE may be an integer, or a symbol whose name is a signal name.  */)
+SIGCODE may be an integer, or a symbol or string naming a signal in any
+of the forms `signal-number' accepts, such as `SIGTERM', "term" or
+"RTMIN+1".  */)
   (Lisp_Object process, Lisp_Object sigcode)
 {
   pid_t pid;
@@ -6448,8 +5827,13 @@ SIGCODE may be an integer, or a symbol whose name is a signal name.  */)
     {
       char *name;
 
-      CHECK_SYMBOL (sigcode);
-      name = SSDATA (SYMBOL_NAME (sigcode));
+      if (STRINGP (sigcode))
+	name = SSDATA (sigcode);
+      else
+	{
+	  CHECK_SYMBOL (sigcode);
+	  name = SSDATA (SYMBOL_NAME (sigcode));
+	}
 
       signo = abbr_to_signal (name);
       if (signo < 0)
@@ -6647,9 +6031,15 @@ handle_child_signal (int sig)
     {
       struct Lisp_Process *p = XPROCESS (proc);
       int status;
+      struct rusage *usage = NULL;
+#ifdef HAVE_WAIT4
+      usage = &p->rusage;
+#endif
 
       if (p->alive
-	  && child_status_changed (p->pid, &status, WUNTRACED | WCONTINUED))
+	  && child_status_and_usage_changed (p->pid, &status,
+					     WUNTRACED | WCONTINUED,
+					     usage))
 	{
 	  /* Change the status of the process that was found.  */
 	  p->tick = ++process_tick;
@@ -6661,6 +6051,7 @@ handle_child_signal (int sig)
 	    {
 	      bool clear_desc_flag = 0;
 	      p->alive = 0;
+	      p->end_time = current_timespec ();
 	      if (p->infd >= 0)
 		clear_desc_flag = 1;
 
@@ -7426,6 +6817,11 @@ syms_of_process (void)
   staticpro (&deleted_pid_list);
 
   DEFSYM (QCname, ":name");
+  DEFSYM (QCsignaled, ":signaled");
+  DEFSYM (QCexit_code, ":exit-code");
+  DEFSYM (QCsignal, ":signal");
+  DEFSYM (QCsignal_name, ":signal-name");
+  DEFSYM (QCcore_dumped, ":core-dumped");
   DEFSYM (QCtype, ":type");
 
   DEFSYM (Qeuid, "euid");
@@ -7459,6 +6855,7 @@ syms_of_process (void)
   DEFSYM (Qstart, "start");
   DEFSYM (Qvsize, "vsize");
   DEFSYM (Qrss, "rss");
+  DEFSYM (Qmaxrss, "maxrss");
   DEFSYM (Qetime, "etime");
   DEFSYM (Qpcpu, "pcpu");
   DEFSYM (Qpmem, "pmem");
@@ -7510,7 +6907,6 @@ returns non-`nil'.  */);
   defsubr (&Sset_process_window_size);
   defsubr (&Sset_process_inherit_coding_system_flag);
   defsubr (&Sprocess_contact);
-  defsubr (&Smake_process);
   defsubr (&Smake_pipe_process);
   defsubr (&Sserial_process_configure);
   defsubr (&Smake_serial_process);
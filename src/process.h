@@ -24,6 +24,10 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 #endif
 
 #include <unistd.h>
+#ifdef HAVE_WAIT4
+#include <sys/resource.h>
+#endif
+#include <time.h>
 
 #ifdef HAVE_GNUTLS
 #include "gnutls.h"
@@ -36,6 +40,26 @@ INLINE_HEADER_BEGIN
 
 enum { PROCESS_OPEN_FDS = 6 };
 
+/* Indexes of file descriptors in open_fds.  */
+enum process_open_fd
+  {
+    /* The pipe from Emacs to its subprocess.  */
+    SUBPROCESS_STDIN,
+    WRITE_TO_SUBPROCESS,
+
+    /* The main pipe from the subprocess to Emacs.  */
+    READ_FROM_SUBPROCESS,
+    SUBPROCESS_STDOUT,
+
+    /* The pipe from the subprocess to Emacs that is closed when the
+       subprocess execs.  */
+    READ_FROM_EXEC_MONITOR,
+    EXEC_MONITOR_OUTPUT
+  };
+
+/* Size of the buffer allocate_pty stores the pty's file name in.  */
+enum pty_name_size { PTY_NAME_SIZE = 24 };
+
 /* This structure records information about a subprocess
    or network connection.  */
 
@@ -174,6 +198,15 @@ struct Lisp_Process
     /* Whether this is a server or a client socket. */
     bool_bf is_server : 1;
     int raw_status;
+#ifdef HAVE_WAIT4
+    /* Resources used by the process, as reported by `wait4' when it
+       terminated.  Meaningless while the process is still alive.  */
+    struct rusage rusage;
+#endif
+    /* When the process was started, and when it was found to have
+       terminated.  END_TIME is zero while the process is alive.  */
+    struct timespec start_time;
+    struct timespec end_time;
     /* The length of the socket backlog. */
     int backlog;
     /* The port number. */
@@ -297,6 +330,11 @@ extern Lisp_Object network_interface_info (Lisp_Object);
 extern Lisp_Object remove_slash_colon (Lisp_Object);
 
 extern void update_processes_for_thread_death (Lisp_Object);
+extern Lisp_Object chan_process[FD_SETSIZE];
+extern int allocate_pty (char pty_name[PTY_NAME_SIZE]);
+extern Lisp_Object make_process (Lisp_Object);
+extern void remove_process (Lisp_Object);
+extern void create_pty (Lisp_Object);
 
 INLINE_HEADER_END
 
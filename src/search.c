@@ -82,7 +82,6 @@ static struct regexp_cache *searchbuf_head;
 /* static Lisp_Object last_thing_searched; */
 
 static void set_search_regs (ptrdiff_t, ptrdiff_t);
-static void save_search_regs (void);
 static EMACS_INT simple_search (EMACS_INT, unsigned char *, ptrdiff_t,
 				ptrdiff_t, Lisp_Object, ptrdiff_t, ptrdiff_t,
                                 ptrdiff_t, ptrdiff_t);
@@ -270,154 +269,6 @@ compile_pattern (Lisp_Object pattern, struct re_registers *regp,
 }
 
 
-Lisp_Object
-looking_at_1 (Lisp_Object string, bool posix)
-{
-  Lisp_Object val;
-  unsigned char *p1, *p2;
-  ptrdiff_t s1, s2;
-  register ptrdiff_t i;
-  struct re_pattern_buffer *bufp;
-
-  if (running_asynch_code)
-    save_search_regs ();
-
-  /* This is so set_image_of_range_1 in regex.c can find the EQV table.  */
-  set_char_table_extras (BVAR (current_buffer, case_canon_table), 2,
-			 BVAR (current_buffer, case_eqv_table));
-
-  CHECK_STRING (string);
-  bufp = compile_pattern (string,
-			  (NILP (Vinhibit_changing_match_data)
-			   ? &search_regs : NULL),
-			  (!NILP (BVAR (current_buffer, case_fold_search))
-			   ? BVAR (current_buffer, case_canon_table) : Qnil),
-			  posix,
-			  !NILP (BVAR (current_buffer, enable_multibyte_characters)));
-
-  /* Do a pending quit right away, to avoid paradoxical behavior */
-  maybe_quit ();
-
-  /* Get pointers and sizes of the two strings
-     that make up the visible portion of the buffer. */
-
-  p1 = BEGV_ADDR;
-  s1 = GPT_BYTE - BEGV_BYTE;
-  p2 = GAP_END_ADDR;
-  s2 = ZV_BYTE - GPT_BYTE;
-  if (s1 < 0)
-    {
-      p2 = p1;
-      s2 = ZV_BYTE - BEGV_BYTE;
-      s1 = 0;
-    }
-  if (s2 < 0)
-    {
-      s1 = ZV_BYTE - BEGV_BYTE;
-      s2 = 0;
-    }
-
-  re_match_object = Qnil;
-
-  freeze_buffer_relocation ();
-  i = re_match_2 (bufp, (char *) p1, s1, (char *) p2, s2,
-		  PT_BYTE - BEGV_BYTE,
-		  (NILP (Vinhibit_changing_match_data)
-		   ? &search_regs : NULL),
-		  ZV_BYTE - BEGV_BYTE);
-  thaw_buffer_relocation ();
-
-  if (i == -2)
-    matcher_overflow ();
-
-  val = (i >= 0 ? Qt : Qnil);
-  if (NILP (Vinhibit_changing_match_data) && i >= 0)
-  {
-    for (i = 0; i < search_regs.num_regs; i++)
-      if (search_regs.start[i] >= 0)
-	{
-	  search_regs.start[i]
-	    = BYTE_TO_CHAR (search_regs.start[i] + BEGV_BYTE);
-         search_regs.end[i]
-           = BYTE_TO_CHAR (search_regs.end[i] + BEGV_BYTE);
-       }
-    /* Set last_thing_searched only when match data is changed.  */
-    XSETBUFFER (last_thing_searched, current_buffer);
-  }
-
-  return val;
-}
-
-Lisp_Object
-string_match_1 (Lisp_Object regexp, Lisp_Object string, Lisp_Object start,
-		bool posix)
-{
-  ptrdiff_t val;
-  struct re_pattern_buffer *bufp;
-  EMACS_INT pos;
-  ptrdiff_t pos_byte, i;
-
-  if (running_asynch_code)
-    save_search_regs ();
-
-  CHECK_STRING (regexp);
-  CHECK_STRING (string);
-
-  if (NILP (start))
-    pos = 0, pos_byte = 0;
-  else
-    {
-      ptrdiff_t len = SCHARS (string);
-
-      CHECK_NUMBER (start);
-      pos = XINT (start);
-      if (pos < 0 && -pos <= len)
-	pos = len + pos;
-      else if (0 > pos || pos > len)
-	args_out_of_range (string, start);
-      pos_byte = string_char_to_byte (string, pos);
-    }
-
-  /* This is so set_image_of_range_1 in regex.c can find the EQV table.  */
-  set_char_table_extras (BVAR (current_buffer, case_canon_table), 2,
-			 BVAR (current_buffer, case_eqv_table));
-
-  bufp = compile_pattern (regexp,
-			  (NILP (Vinhibit_changing_match_data)
-			   ? &search_regs : NULL),
-			  (!NILP (BVAR (current_buffer, case_fold_search))
-			   ? BVAR (current_buffer, case_canon_table) : Qnil),
-			  posix,
-			  STRING_MULTIBYTE (string));
-  re_match_object = string;
-
-  val = re_search (bufp, SSDATA (string),
-		   SBYTES (string), pos_byte,
-		   SBYTES (string) - pos_byte,
-		   (NILP (Vinhibit_changing_match_data)
-		    ? &search_regs : NULL));
-
-  /* Set last_thing_searched only when match data is changed.  */
-  if (NILP (Vinhibit_changing_match_data))
-    last_thing_searched = Qt;
-
-  if (val == -2)
-    matcher_overflow ();
-  if (val < 0) return Qnil;
-
-  if (NILP (Vinhibit_changing_match_data))
-    for (i = 0; i < search_regs.num_regs; i++)
-      if (search_regs.start[i] >= 0)
-	{
-	  search_regs.start[i]
-	    = string_byte_to_char (string, search_regs.start[i]);
-	  search_regs.end[i]
-	    = string_byte_to_char (string, search_regs.end[i]);
-	}
-
-  return make_number (string_byte_to_char (string, val));
-}
-
 /* Match REGEXP against STRING using translation table TABLE,
    searching all of STRING, and return the index of the match,
    or negative on failure.  This does not clobber the match data.  */
@@ -2837,7 +2688,7 @@ If optional arg RESEAT is non-nil, make markers on LIST point nowhere.  */)
 
 /* Called from Flooking_at, Fstring_match, search_buffer, Fstore_match_data
    if asynchronous code (filter or sentinel) is running. */
-static void
+void
 save_search_regs (void)
 {
   if (!search_regs_saved)
@@ -410,11 +410,14 @@ init_baud_rate (int fd)
    and tell wait_reading_process_output that it needs to look around.
    Use waitpid-style OPTIONS when waiting.
    If INTERRUPTIBLE, this function is interruptible by a signal.
+   If USAGE is non-null and wait4 is available, use it instead of
+   waitpid and store the resource usage of the child into *USAGE.
 
    Return CHILD if successful, 0 if no status is available, and a
    negative value (setting errno) if waitpid is buggy.  */
 static pid_t
-get_child_status (pid_t child, int *status, int options, bool interruptible)
+get_child_status (pid_t child, int *status, int options, bool interruptible,
+		  struct rusage *usage)
 {
   pid_t pid;
 
@@ -432,7 +435,13 @@ get_child_status (pid_t child, int *status, int options, bool interruptible)
       if (interruptible)
 	maybe_quit ();
 
+#ifdef HAVE_WAIT4
+      pid = (usage
+	     ? wait4 (child, status, options, usage)
+	     : waitpid (child, status, options));
+#else
       pid = waitpid (child, status, options);
+#endif
       if (0 <= pid)
 	break;
       if (errno != EINTR)
@@ -463,7 +472,7 @@ get_child_status (pid_t child, int *status, int options, bool interruptible)
 bool
 wait_for_termination (pid_t child, int *status, bool interruptible)
 {
-  return 0 <= get_child_status (child, status, 0, interruptible);
+  return 0 <= get_child_status (child, status, 0, interruptible, NULL);
 }
 
 /* Report whether the subprocess with process id CHILD has changed status.
@@ -478,7 +487,16 @@ wait_for_termination (pid_t child, int *status, bool interruptible)
 pid_t
 child_status_changed (pid_t child, int *status, int options)
 {
-  return get_child_status (child, status, WNOHANG | options, 0);
+  return get_child_status (child, status, WNOHANG | options, 0, NULL);
+}
+
+/* Like child_status_changed, but also store the resource usage of
+   CHILD into *USAGE when its status has changed.  */
+pid_t
+child_status_and_usage_changed (pid_t child, int *status, int options,
+				struct rusage *usage)
+{
+  return get_child_status (child, status, WNOHANG | options, 0, usage);
 }
 
 
@@ -58,5 +58,8 @@ along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.  */
 /* Defined in sysdep.c.  */
 extern bool wait_for_termination (pid_t, int *, bool);
 extern pid_t child_status_changed (pid_t, int *, int);
+struct rusage;
+extern pid_t child_status_and_usage_changed (pid_t, int *, int,
+					     struct rusage *);
 
 #endif /* EMACS_SYSWAIT_H */
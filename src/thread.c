@@ -30,22 +30,16 @@ static struct thread_state main_thread;
 
 struct thread_state *current_thread = &main_thread;
 
-static struct thread_state *all_threads = &main_thread;
+struct thread_state *all_threads = &main_thread;
 
-static sys_mutex_t global_lock;
+sys_mutex_t global_lock;
 
 extern int poll_suppress_count;
 extern volatile int interrupt_input_blocked;
 
 
 
-/* m_specpdl is set when the thread is created and cleared when the
-   thread dies.  */
-#define thread_alive_p(STATE) ((STATE)->m_specpdl != NULL)
-
-
-
-static void
+void
 release_global_lock (void)
 {
   sys_mutex_unlock (&global_lock);
@@ -53,7 +47,7 @@ release_global_lock (void)
 
 /* You must call this after acquiring the global lock.
    acquire_global_lock does it for you.  */
-static void
+void
 post_acquire_global_lock (struct thread_state *self)
 {
   struct thread_state *prev_thread = current_thread;
@@ -94,7 +88,7 @@ post_acquire_global_lock (struct thread_state *self)
     }
 }
 
-static void
+void
 acquire_global_lock (struct thread_state *self)
 {
   sys_mutex_lock (&global_lock);
@@ -126,425 +120,6 @@ maybe_reacquire_global_lock (void)
 
 
 
-static void
-lisp_mutex_init (lisp_mutex_t *mutex)
-{
-  mutex->owner = NULL;
-  mutex->count = 0;
-  sys_cond_init (&mutex->condition);
-}
-
-/* Lock MUTEX for thread LOCKER, setting its lock count to COUNT, if
-   non-zero, or to 1 otherwise.
-
-   If MUTEX is locked by LOCKER, COUNT must be zero, and the MUTEX's
-   lock count will be incremented.
-
-   If MUTEX is locked by another thread, this function will release
-   the global lock, giving other threads a chance to run, and will
-   wait for the MUTEX to become unlocked; when MUTEX becomes unlocked,
-   and will then re-acquire the global lock.
-
-   Return value is 1 if the function waited for the MUTEX to become
-   unlocked (meaning other threads could have run during the wait),
-   zero otherwise.  */
-static int
-lisp_mutex_lock_for_thread (lisp_mutex_t *mutex, struct thread_state *locker,
-			    int new_count)
-{
-  struct thread_state *self;
-
-  if (mutex->owner == NULL)
-    {
-      mutex->owner = locker;
-      mutex->count = new_count == 0 ? 1 : new_count;
-      return 0;
-    }
-  if (mutex->owner == locker)
-    {
-      eassert (new_count == 0);
-      ++mutex->count;
-      return 0;
-    }
-
-  self = locker;
-  self->wait_condvar = &mutex->condition;
-  while (mutex->owner != NULL && (new_count != 0
-				  || NILP (self->error_symbol)))
-    sys_cond_wait (&mutex->condition, &global_lock);
-  self->wait_condvar = NULL;
-
-  if (new_count == 0 && !NILP (self->error_symbol))
-    return 1;
-
-  mutex->owner = self;
-  mutex->count = new_count == 0 ? 1 : new_count;
-
-  return 1;
-}
-
-static int
-lisp_mutex_lock (lisp_mutex_t *mutex, int new_count)
-{
-  return lisp_mutex_lock_for_thread (mutex, current_thread, new_count);
-}
-
-/* Decrement MUTEX's lock count.  If the lock count becomes zero after
-   decrementing it, meaning the mutex is now unlocked, broadcast that
-   to all the threads that might be waiting to lock the mutex.  This
-   function signals an error if MUTEX is locked by a thread other than
-   the current one.  Return value is 1 if the mutex becomes unlocked,
-   zero otherwise.  */
-static int
-lisp_mutex_unlock (lisp_mutex_t *mutex)
-{
-  if (mutex->owner != current_thread)
-    error ("Cannot unlock mutex owned by another thread");
-
-  if (--mutex->count > 0)
-    return 0;
-
-  mutex->owner = NULL;
-  sys_cond_broadcast (&mutex->condition);
-
-  return 1;
-}
-
-/* Like lisp_mutex_unlock, but sets MUTEX's lock count to zero
-   regardless of its value.  Return the previous lock count.  */
-static unsigned int
-lisp_mutex_unlock_for_wait (lisp_mutex_t *mutex)
-{
-  unsigned int result = mutex->count;
-
-  /* Ensured by condvar code.  */
-  eassert (mutex->owner == current_thread);
-
-  mutex->count = 0;
-  mutex->owner = NULL;
-  sys_cond_broadcast (&mutex->condition);
-
-  return result;
-}
-
-static void
-lisp_mutex_destroy (lisp_mutex_t *mutex)
-{
-  sys_cond_destroy (&mutex->condition);
-}
-
-static int
-lisp_mutex_owned_p (lisp_mutex_t *mutex)
-{
-  return mutex->owner == current_thread;
-}
-
-
-
-DEFUN ("make-mutex", Fmake_mutex, Smake_mutex, 0, 1, 0,
-       doc: /* Create a mutex.
-A mutex provides a synchronization point for threads.
-Only one thread at a time can hold a mutex.  Other threads attempting
-to acquire it will block until the mutex is available.
-
-A thread can acquire a mutex any number of times.
-
-NAME, if given, is used as the name of the mutex.  The name is
-informational only.  */)
-  (Lisp_Object name)
-{
-  struct Lisp_Mutex *mutex;
-  Lisp_Object result;
-
-  if (!NILP (name))
-    CHECK_STRING (name);
-
-  mutex = ALLOCATE_PSEUDOVECTOR (struct Lisp_Mutex, mutex, PVEC_MUTEX);
-  memset ((char *) mutex + offsetof (struct Lisp_Mutex, mutex),
-	  0, sizeof (struct Lisp_Mutex) - offsetof (struct Lisp_Mutex,
-						    mutex));
-  mutex->name = name;
-  lisp_mutex_init (&mutex->mutex);
-
-  XSETMUTEX (result, mutex);
-  return result;
-}
-
-static void
-mutex_lock_callback (void *arg)
-{
-  struct Lisp_Mutex *mutex = arg;
-  struct thread_state *self = current_thread;
-
-  /* Calling lisp_mutex_lock might yield to other threads while this
-     one waits for the mutex to become unlocked, so we need to
-     announce us as the current thread by calling
-     post_acquire_global_lock.  */
-  if (lisp_mutex_lock (&mutex->mutex, 0))
-    post_acquire_global_lock (self);
-}
-
-static void
-do_unwind_mutex_lock (void)
-{
-  current_thread->event_object = Qnil;
-}
-
-DEFUN ("mutex-lock", Fmutex_lock, Smutex_lock, 1, 1, 0,
-       doc: /* Acquire a mutex.
-If the current thread already owns MUTEX, increment the count and
-return.
-Otherwise, if no thread owns MUTEX, make the current thread own it.
-Otherwise, block until MUTEX is available, or until the current thread
-is signaled using `thread-signal'.
-Note that calls to `mutex-lock' and `mutex-unlock' must be paired.  */)
-  (Lisp_Object mutex)
-{
-  struct Lisp_Mutex *lmutex;
-  ptrdiff_t count = SPECPDL_INDEX ();
-
-  CHECK_MUTEX (mutex);
-  lmutex = XMUTEX (mutex);
-
-  current_thread->event_object = mutex;
-  record_unwind_protect_void (do_unwind_mutex_lock);
-  flush_stack_call_func (mutex_lock_callback, lmutex);
-  return unbind_to (count, Qnil);
-}
-
-static void
-mutex_unlock_callback (void *arg)
-{
-  struct Lisp_Mutex *mutex = arg;
-  struct thread_state *self = current_thread;
-
-  if (lisp_mutex_unlock (&mutex->mutex))
-    post_acquire_global_lock (self); /* FIXME: is this call needed? */
-}
-
-DEFUN ("mutex-unlock", Fmutex_unlock, Smutex_unlock, 1, 1, 0,
-       doc: /* Release the mutex.
-If this thread does not own MUTEX, signal an error.
-Otherwise, decrement the mutex's count.  If the count is zero,
-release MUTEX.   */)
-  (Lisp_Object mutex)
-{
-  struct Lisp_Mutex *lmutex;
-
-  CHECK_MUTEX (mutex);
-  lmutex = XMUTEX (mutex);
-
-  flush_stack_call_func (mutex_unlock_callback, lmutex);
-  return Qnil;
-}
-
-DEFUN ("mutex-name", Fmutex_name, Smutex_name, 1, 1, 0,
-       doc: /* Return the name of MUTEX.
-If no name was given when MUTEX was created, return nil.  */)
-  (Lisp_Object mutex)
-{
-  struct Lisp_Mutex *lmutex;
-
-  CHECK_MUTEX (mutex);
-  lmutex = XMUTEX (mutex);
-
-  return lmutex->name;
-}
-
-void
-finalize_one_mutex (struct Lisp_Mutex *mutex)
-{
-  lisp_mutex_destroy (&mutex->mutex);
-}
-
-
-
-DEFUN ("make-condition-variable",
-       Fmake_condition_variable, Smake_condition_variable,
-       1, 2, 0,
-       doc: /* Make a condition variable associated with MUTEX.
-A condition variable provides a way for a thread to sleep while
-waiting for a state change.
-
-MUTEX is the mutex associated with this condition variable.
-NAME, if given, is the name of this condition variable.  The name is
-informational only.  */)
-  (Lisp_Object mutex, Lisp_Object name)
-{
-  struct Lisp_CondVar *condvar;
-  Lisp_Object result;
-
-  CHECK_MUTEX (mutex);
-  if (!NILP (name))
-    CHECK_STRING (name);
-
-  condvar = ALLOCATE_PSEUDOVECTOR (struct Lisp_CondVar, cond, PVEC_CONDVAR);
-  memset ((char *) condvar + offsetof (struct Lisp_CondVar, cond),
-	  0, sizeof (struct Lisp_CondVar) - offsetof (struct Lisp_CondVar,
-						      cond));
-  condvar->mutex = mutex;
-  condvar->name = name;
-  sys_cond_init (&condvar->cond);
-
-  XSETCONDVAR (result, condvar);
-  return result;
-}
-
-static void
-condition_wait_callback (void *arg)
-{
-  struct Lisp_CondVar *cvar = arg;
-  struct Lisp_Mutex *mutex = XMUTEX (cvar->mutex);
-  struct thread_state *self = current_thread;
-  unsigned int saved_count;
-  Lisp_Object cond;
-
-  XSETCONDVAR (cond, cvar);
-  self->event_object = cond;
-  saved_count = lisp_mutex_unlock_for_wait (&mutex->mutex);
-  /* If signaled while unlocking, skip the wait but reacquire the lock.  */
-  if (NILP (self->error_symbol))
-    {
-      self->wait_condvar = &cvar->cond;
-      /* This call could switch to another thread.  */
-      sys_cond_wait (&cvar->cond, &global_lock);
-      self->wait_condvar = NULL;
-    }
-  self->event_object = Qnil;
-  /* Since sys_cond_wait could switch threads, we need to lock the
-     mutex for the thread which was the current when we were called,
-     otherwise lisp_mutex_lock will record the wrong thread as the
-     owner of the mutex lock.  */
-  lisp_mutex_lock_for_thread (&mutex->mutex, self, saved_count);
-  /* Calling lisp_mutex_lock_for_thread might yield to other threads
-     while this one waits for the mutex to become unlocked, so we need
-     to announce us as the current thread by calling
-     post_acquire_global_lock.  */
-  post_acquire_global_lock (self);
-}
-
-DEFUN ("condition-wait", Fcondition_wait, Scondition_wait, 1, 1, 0,
-       doc: /* Wait for the condition variable COND to be notified.
-COND is the condition variable to wait on.
-
-The mutex associated with COND must be held when this is called.
-It is an error if it is not held.
-
-This releases the mutex and waits for COND to be notified or for
-this thread to be signaled with `thread-signal'.  When
-`condition-wait' returns, COND's mutex will again be locked by
-this thread.  */)
-  (Lisp_Object cond)
-{
-  struct Lisp_CondVar *cvar;
-  struct Lisp_Mutex *mutex;
-
-  CHECK_CONDVAR (cond);
-  cvar = XCONDVAR (cond);
-
-  mutex = XMUTEX (cvar->mutex);
-  if (!lisp_mutex_owned_p (&mutex->mutex))
-    error ("Condition variable's mutex is not held by current thread");
-
-  flush_stack_call_func (condition_wait_callback, cvar);
-
-  return Qnil;
-}
-
-/* Used to communicate arguments to condition_notify_callback.  */
-struct notify_args
-{
-  struct Lisp_CondVar *cvar;
-  int all;
-};
-
-static void
-condition_notify_callback (void *arg)
-{
-  struct notify_args *na = arg;
-  struct Lisp_Mutex *mutex = XMUTEX (na->cvar->mutex);
-  struct thread_state *self = current_thread;
-  unsigned int saved_count;
-  Lisp_Object cond;
-
-  XSETCONDVAR (cond, na->cvar);
-  saved_count = lisp_mutex_unlock_for_wait (&mutex->mutex);
-  if (na->all)
-    sys_cond_broadcast (&na->cvar->cond);
-  else
-    sys_cond_signal (&na->cvar->cond);
-  /* Calling lisp_mutex_lock might yield to other threads while this
-     one waits for the mutex to become unlocked, so we need to
-     announce us as the current thread by calling
-     post_acquire_global_lock.  */
-  lisp_mutex_lock (&mutex->mutex, saved_count);
-  post_acquire_global_lock (self);
-}
-
-DEFUN ("condition-notify", Fcondition_notify, Scondition_notify, 1, 2, 0,
-       doc: /* Notify COND, a condition variable.
-This wakes a thread waiting on COND.
-If ALL is non-nil, all waiting threads are awoken.
-
-The mutex associated with COND must be held when this is called.
-It is an error if it is not held.
-
-This releases COND's mutex when notifying COND.  When
-`condition-notify' returns, the mutex will again be locked by this
-thread.  */)
-  (Lisp_Object cond, Lisp_Object all)
-{
-  struct Lisp_CondVar *cvar;
-  struct Lisp_Mutex *mutex;
-  struct notify_args args;
-
-  CHECK_CONDVAR (cond);
-  cvar = XCONDVAR (cond);
-
-  mutex = XMUTEX (cvar->mutex);
-  if (!lisp_mutex_owned_p (&mutex->mutex))
-    error ("Condition variable's mutex is not held by current thread");
-
-  args.cvar = cvar;
-  args.all = !NILP (all);
-  flush_stack_call_func (condition_notify_callback, &args);
-
-  return Qnil;
-}
-
-DEFUN ("condition-mutex", Fcondition_mutex, Scondition_mutex, 1, 1, 0,
-       doc: /* Return the mutex associated with condition variable COND.  */)
-  (Lisp_Object cond)
-{
-  struct Lisp_CondVar *cvar;
-
-  CHECK_CONDVAR (cond);
-  cvar = XCONDVAR (cond);
-
-  return cvar->mutex;
-}
-
-DEFUN ("condition-name", Fcondition_name, Scondition_name, 1, 1, 0,
-       doc: /* Return the name of condition variable COND.
-If no name was given when COND was created, return nil.  */)
-  (Lisp_Object cond)
-{
-  struct Lisp_CondVar *cvar;
-
-  CHECK_CONDVAR (cond);
-  cvar = XCONDVAR (cond);
-
-  return cvar->name;
-}
-
-void
-finalize_one_condvar (struct Lisp_CondVar *condvar)
-{
-  sys_cond_destroy (&condvar->cond);
-}
-
-
-
 struct select_args
 {
   select_func *func;
@@ -658,24 +233,6 @@ mark_threads (void)
 
 
 
-static void
-yield_callback (void *ignore)
-{
-  struct thread_state *self = current_thread;
-
-  release_global_lock ();
-  sys_thread_yield ();
-  acquire_global_lock (self);
-}
-
-DEFUN ("thread-yield", Fthread_yield, Sthread_yield, 0, 0, 0,
-       doc: /* Yield the CPU to another thread.  */)
-     (void)
-{
-  flush_stack_call_func (yield_callback, NULL);
-  return Qnil;
-}
-
 static Lisp_Object
 invoke_thread_function (void)
 {
@@ -685,16 +242,7 @@ invoke_thread_function (void)
   return unbind_to (count, Qnil);
 }
 
-static Lisp_Object last_thread_error;
-
-static Lisp_Object
-record_thread_error (Lisp_Object error_form)
-{
-  last_thread_error = error_form;
-  return error_form;
-}
-
-static void *
+void *
 run_thread (void *state)
 {
   /* Make sure stack_top and m_stack_bottom are properly aligned as GC
@@ -760,175 +308,6 @@ finalize_one_thread (struct thread_state *state)
   sys_cond_destroy (&state->thread_condvar);
 }
 
-DEFUN ("make-thread", Fmake_thread, Smake_thread, 1, 2, 0,
-       doc: /* Start a new thread and run FUNCTION in it.
-When the function exits, the thread dies.
-If NAME is given, it must be a string; it names the new thread.  */)
-  (Lisp_Object function, Lisp_Object name)
-{
-  sys_thread_t thr;
-  struct thread_state *new_thread;
-  Lisp_Object result;
-  const char *c_name = NULL;
-  size_t offset = offsetof (struct thread_state, m_stack_bottom);
-
-  /* Can't start a thread in temacs.  */
-  if (!initialized)
-    emacs_abort ();
-
-  if (!NILP (name))
-    CHECK_STRING (name);
-
-  new_thread = ALLOCATE_PSEUDOVECTOR (struct thread_state, m_stack_bottom,
-				      PVEC_THREAD);
-  memset ((char *) new_thread + offset, 0,
-	  sizeof (struct thread_state) - offset);
-
-  new_thread->function = function;
-  new_thread->name = name;
-  new_thread->m_last_thing_searched = Qnil; /* copy from parent? */
-  new_thread->m_saved_last_thing_searched = Qnil;
-  new_thread->m_current_buffer = current_thread->m_current_buffer;
-  new_thread->error_symbol = Qnil;
-  new_thread->error_data = Qnil;
-  new_thread->event_object = Qnil;
-
-  new_thread->m_specpdl_size = 50;
-  new_thread->m_specpdl = xmalloc ((1 + new_thread->m_specpdl_size)
-				   * sizeof (union specbinding));
-  /* Skip the dummy entry.  */
-  ++new_thread->m_specpdl;
-  new_thread->m_specpdl_ptr = new_thread->m_specpdl;
-
-  sys_cond_init (&new_thread->thread_condvar);
-
-  /* We'll need locking here eventually.  */
-  new_thread->next_thread = all_threads;
-  all_threads = new_thread;
-
-  if (!NILP (name))
-    c_name = SSDATA (ENCODE_UTF_8 (name));
-
-  if (! sys_thread_create (&thr, c_name, run_thread, new_thread))
-    {
-      /* Restore the previous situation.  */
-      all_threads = all_threads->next_thread;
-#ifdef THREADS_ENABLED
-      error ("Could not start a new thread");
-#else
-      error ("Concurrency is not supported in this configuration");
-#endif
-    }
-
-  /* FIXME: race here where new thread might not be filled in?  */
-  XSETTHREAD (result, new_thread);
-  return result;
-}
-
-static void
-thread_signal_callback (void *arg)
-{
-  struct thread_state *tstate = arg;
-  struct thread_state *self = current_thread;
-
-  sys_cond_broadcast (tstate->wait_condvar);
-  post_acquire_global_lock (self);
-}
-
-DEFUN ("thread-signal", Fthread_signal, Sthread_signal, 3, 3, 0,
-       doc: /* Signal an error in a thread.
-This acts like `signal', but arranges for the signal to be raised
-in THREAD.  If THREAD is the current thread, acts just like `signal'.
-This will interrupt a blocked call to `mutex-lock', `condition-wait',
-or `thread-join' in the target thread.  */)
-  (Lisp_Object thread, Lisp_Object error_symbol, Lisp_Object data)
-{
-  struct thread_state *tstate;
-
-  CHECK_THREAD (thread);
-  tstate = XTHREAD (thread);
-
-  if (tstate == current_thread)
-    Fsignal (error_symbol, data);
-
-  /* What to do if thread is already signaled?  */
-  /* What if error_symbol is Qnil?  */
-  tstate->error_symbol = error_symbol;
-  tstate->error_data = data;
-
-  if (tstate->wait_condvar)
-    flush_stack_call_func (thread_signal_callback, tstate);
-
-  return Qnil;
-}
-
-static void
-thread_join_callback (void *arg)
-{
-  struct thread_state *tstate = arg;
-  struct thread_state *self = current_thread;
-  Lisp_Object thread;
-
-  XSETTHREAD (thread, tstate);
-  self->event_object = thread;
-  self->wait_condvar = &tstate->thread_condvar;
-  while (thread_alive_p (tstate) && NILP (self->error_symbol))
-    sys_cond_wait (self->wait_condvar, &global_lock);
-
-  self->wait_condvar = NULL;
-  self->event_object = Qnil;
-  post_acquire_global_lock (self);
-}
-
-DEFUN ("thread-join", Fthread_join, Sthread_join, 1, 1, 0,
-       doc: /* Wait for THREAD to exit.
-This blocks the current thread until THREAD exits or until
-the current thread is signaled.
-It is an error for a thread to try to join itself.  */)
-  (Lisp_Object thread)
-{
-  struct thread_state *tstate;
-
-  CHECK_THREAD (thread);
-  tstate = XTHREAD (thread);
-
-  if (tstate == current_thread)
-    error ("Cannot join current thread");
-
-  if (thread_alive_p (tstate))
-    flush_stack_call_func (thread_join_callback, tstate);
-
-  return Qnil;
-}
-
-DEFUN ("all-threads", Fall_threads, Sall_threads, 0, 0, 0,
-       doc: /* Return a list of all the live threads.  */)
-  (void)
-{
-  Lisp_Object result = Qnil;
-  struct thread_state *iter;
-
-  for (iter = all_threads; iter; iter = iter->next_thread)
-    {
-      if (thread_alive_p (iter))
-	{
-	  Lisp_Object thread;
-
-	  XSETTHREAD (thread, iter);
-	  result = Fcons (thread, result);
-	}
-    }
-
-  return result;
-}
-
-DEFUN ("thread-last-error", Fthread_last_error, Sthread_last_error, 0, 0, 0,
-       doc: /* Return the last error form recorded by a dying thread.  */)
-  (void)
-{
-  return last_thread_error;
-}
-
 
 
 bool
@@ -1000,28 +379,19 @@ void
 syms_of_threads (void)
 {
 #ifndef THREADS_ENABLED
-  if (0)
+  /* The thread primitives are defined in rust_src/src/threads.rs;
+     hide the ones that need real concurrency.  */
+  static char const *const concurrency_fns[] = {
+    "thread-yield", "make-thread", "thread-signal", "thread-join",
+    "all-threads", "thread-list-all", "make-mutex", "mutex-lock",
+    "mutex-unlock", "mutex-name", "make-condition-variable",
+    "condition-wait", "condition-notify", "condition-mutex",
+    "condition-name", "thread-last-error"
+  };
+
+  for (int i = 0; i < ARRAYELTS (concurrency_fns); i++)
+    Ffset (intern_c_string (concurrency_fns[i]), Qnil);
 #endif
-    {
-      defsubr (&Sthread_yield);
-      defsubr (&Smake_thread);
-      defsubr (&Sthread_signal);
-      defsubr (&Sthread_join);
-      defsubr (&Sall_threads);
-      defsubr (&Smake_mutex);
-      defsubr (&Smutex_lock);
-      defsubr (&Smutex_unlock);
-      defsubr (&Smutex_name);
-      defsubr (&Smake_condition_variable);
-      defsubr (&Scondition_wait);
-      defsubr (&Scondition_notify);
-      defsubr (&Scondition_mutex);
-      defsubr (&Scondition_name);
-      defsubr (&Sthread_last_error);
-
-      staticpro (&last_thread_error);
-      last_thread_error = Qnil;
-    }
 
   DEFSYM (Qthreadp, "threadp");
   DEFSYM (Qmutexp, "mutexp");
@@ -293,11 +293,15 @@ XCONDVAR (Lisp_Object a)
 }
 
 extern struct thread_state *current_thread;
+extern struct thread_state *all_threads;
+extern sys_mutex_t global_lock;
 
 extern void finalize_one_thread (struct thread_state *state);
-extern void finalize_one_mutex (struct Lisp_Mutex *);
-extern void finalize_one_condvar (struct Lisp_CondVar *);
 extern void maybe_reacquire_global_lock (void);
+extern void release_global_lock (void);
+extern void acquire_global_lock (struct thread_state *);
+extern void post_acquire_global_lock (struct thread_state *);
+extern void *run_thread (void *);
 
 extern void init_threads_once (void);
 extern void init_threads (void);
@@ -314,4 +318,9 @@ int thread_select  (select_func *func, int max_fds, fd_set *rfds,
 
 bool thread_check_current_buffer (struct buffer *);
 
+/* Defined in rust_src/src/threads.rs.  */
+extern void finalize_one_mutex (struct Lisp_Mutex *);
+extern void finalize_one_condvar (struct Lisp_CondVar *);
+extern Lisp_Object record_thread_error (Lisp_Object);
+
 #endif /* THREAD_H */
@@ -0,0 +1,105 @@
+;;; charpos-bench.el --- benchmark char/byte position conversion -*- lexical-binding: t; coding: utf-8 -*-
+
+;; Copyright (C) 2019 Free Software Foundation, Inc.
+
+;; This file is part of GNU Emacs.
+
+;; This program is free software; you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; This program is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with this program.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Commentary:
+
+;; Time `goto-char', `char-after' and `position-bytes' at random
+;; positions of a large buffer of mixed ASCII and multibyte text.
+;; These all convert between character and byte positions, which is
+;; what the checkpoint index in marker.rs speeds up.  Run it with
+;;
+;;   src/remacs -Q --batch -l test/manual/charpos-bench.el \
+;;     -f charpos-bench-run
+;;
+;; and compare the times printed before and after a change.  Set
+;; `charpos-bench-size' to change the size of the buffer.
+
+;;; Code:
+
+(require 'benchmark)
+
+(defvar charpos-bench-size (* 16 1024 1024)
+  "Number of characters in the benchmark buffer.")
+
+(defvar charpos-bench-lookups 100000
+  "Number of random positions each benchmark looks up.")
+
+(defun charpos-bench--fill ()
+  "Fill the current buffer with `charpos-bench-size' characters.
+Every line has some multibyte characters, so that no long stretch
+of the buffer is all ASCII."
+  (let ((line "The quick brown fox jumps over the lazy dog, \
+à ß 日本語 ∀x∈ℝ 😀.\n"))
+    (dotimes (_ (/ charpos-bench-size (length line)))
+      (insert line))))
+
+(defun charpos-bench--positions ()
+  "Return a vector of random positions in the current buffer.
+The same positions are returned every time."
+  (random "charpos-bench")
+  (let ((positions (make-vector charpos-bench-lookups 0)))
+    (dotimes (i charpos-bench-lookups)
+      (aset positions i (+ (point-min) (random (buffer-size)))))
+    positions))
+
+(defun charpos-bench--report (name time)
+  "Print the result TIME of `benchmark-run' for the benchmark NAME."
+  (message "%-28s %8.3fs (%d GCs, %.3fs in GC)"
+           name (nth 0 time) (nth 1 time) (nth 2 time)))
+
+(defun charpos-bench-run ()
+  "Run the benchmarks and print their times."
+  (with-temp-buffer
+    (charpos-bench--fill)
+    (message "%d characters, %d bytes, %d lookups"
+             (buffer-size) (1- (position-bytes (point-max)))
+             charpos-bench-lookups)
+    (let ((positions (charpos-bench--positions)))
+      (garbage-collect)
+      (charpos-bench--report
+       "goto-char"
+       (benchmark-run 1
+         (dotimes (i charpos-bench-lookups)
+           (goto-char (aref positions i)))))
+      (charpos-bench--report
+       "char-after"
+       (benchmark-run 1
+         (dotimes (i charpos-bench-lookups)
+           (char-after (aref positions i)))))
+      (charpos-bench--report
+       "position-bytes"
+       (benchmark-run 1
+         (dotimes (i charpos-bench-lookups)
+           (position-bytes (aref positions i)))))
+      ;; Editing invalidates cached positions, so interleave lookups
+      ;; with insertions and deletions near the start of the buffer.
+      (charpos-bench--report
+       "position-bytes while editing"
+       (benchmark-run 1
+         (dotimes (i charpos-bench-lookups)
+           (if (zerop (% i 2))
+               (save-excursion
+                 (goto-char (+ (point-min) (% i 1000)))
+                 (insert "é"))
+             (delete-region (point-min) (1+ (point-min))))
+           (position-bytes (min (aref positions i) (point-max)))))))))
+
+(provide 'charpos-bench)
+
+;;; charpos-bench.el ends here
@@ -0,0 +1,99 @@
+;;; bignum-tests.el --- Tests for bignum.rs
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest bignum-overflow-promotion ()
+  (let ((big (1+ most-positive-fixnum)))
+    (should (bignump big))
+    (should-not (fixnump big))
+    (should (integerp big))
+    (should (eq (type-of big) 'integer))
+    (should (fixnump (1- big)))
+    (should (= (1- big) most-positive-fixnum))
+    (should (bignump (* most-positive-fixnum 2)))
+    (should (= (/ (* most-positive-fixnum 2) 2) most-positive-fixnum))
+    (should (bignump (- most-negative-fixnum 1)))
+    (should (bignump (- most-negative-fixnum)))
+    (should (bignump (abs most-negative-fixnum)))))
+
+(ert-deftest bignum-equality ()
+  (let ((a (expt 2 100))
+        (b (expt 2 100)))
+    (should-not (eq a b))
+    (should (= a b))
+    (should (eql a b))
+    (should (equal a b))
+    (should (memql a (list 1 b)))
+    (should (= (sxhash a) (sxhash b)))
+    (let ((table (make-hash-table :test #'eql)))
+      (puthash a 'found table)
+      (should (eq (gethash b table) 'found)))
+    (should-not (= a (1+ b)))
+    (should (< a (1+ b)))
+    (should (> a 1.0))
+    (should (= (expt 2 100) (expt 2.0 100)))
+    (should (< (expt 2.0 100) (1+ (expt 2 100))))))
+
+(ert-deftest bignum-read-print ()
+  (let ((s "123456789012345678901234567890"))
+    (should (bignump (read s)))
+    (should (equal (number-to-string (read s)) s))
+    (should (equal (prin1-to-string (read (concat "-" s))) (concat "-" s)))
+    (should (= (read "#x10000000000000000") (expt 2 64)))
+    (should (equal (format "%d %x" (expt 2 64) (expt 2 64))
+                   "18446744073709551616 10000000000000000"))))
+
+(ert-deftest bignum-arithmetic ()
+  (should (= (expt 2 100) (ash 1 100)))
+  (should (= (ash (expt 2 100) -99) 2))
+  (should (= (ash (- (expt 2 100)) -200) -1))
+  (should (= (ash -5 -1) -3))
+  (should (= (+ (expt 2 64) (- (expt 2 64))) 0))
+  (should (fixnump (+ (expt 2 64) (- (expt 2 64)))))
+  (should (= (logand (1- (expt 2 70)) (expt 2 65)) (expt 2 65)))
+  (should (= (logior (expt 2 70) 1) (1+ (expt 2 70))))
+  (should (= (logxor (expt 2 70) (expt 2 70)) 0))
+  (should (= (lognot (expt 2 70)) (- -1 (expt 2 70))))
+  (should (= (% (1+ (expt 2 70)) (expt 2 35)) 1))
+  (should (= (% (- (1+ (expt 2 70))) 7) (- (% (1+ (expt 2 70)) 7))))
+  (should (= (mod (- (expt 2 70)) 3) (- 3 (% (expt 2 70) 3))))
+  (should (= (floor (expt 2 70) 3) (/ (expt 2 70) 3)))
+  (should (= (truncate 1e20) 100000000000000000000))
+  (should (= (float (expt 2 70)) (expt 2.0 70)))
+  (should (= (logb (expt 2 70)) 70))
+  (should-error (ash 1 most-positive-fixnum) :type 'overflow-error)
+  (should-error (/ (expt 2 70) 0) :type 'arith-error)
+  (should-error (% 5.0 2) :type 'wrong-type-argument))
+
+(ert-deftest bignum-shift-right-far ()
+  (should (= (ash 5 most-negative-fixnum) 0))
+  (should (= (ash -5 most-negative-fixnum) -1)))
+
+(ert-deftest bignum-position ()
+  (with-temp-buffer
+    (insert "abc")
+    (should-error (goto-char (expt 2 70)) :type 'args-out-of-range)
+    (should-error (goto-char (- (expt 2 70))) :type 'args-out-of-range)
+    (should-error (position-bytes (expt 2 70)) :type 'args-out-of-range)
+    (should (= (point) 4))))
+
+(provide 'bignum-tests)
+
+;;; bignum-tests.el ends here
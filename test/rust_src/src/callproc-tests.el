@@ -0,0 +1,71 @@
+;;; callproc-tests.el --- Tests for callproc.rs -*- lexical-binding: t; -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest callproc-tests--call-process*-output ()
+  (skip-unless (executable-find "sh"))
+  (let ((result (call-process* :command '("sh" "-c" "echo out; echo err >&2; exit 3"))))
+    (should (equal (plist-get result :exit-code) 3))
+    (should-not (plist-get result :signal))
+    (should-not (plist-get result :timed-out))
+    (should (equal (plist-get result :stdout) "out\n"))
+    (should (equal (plist-get result :stderr) "err\n"))))
+
+(ert-deftest callproc-tests--call-process*-env-and-cwd ()
+  (skip-unless (executable-find "sh"))
+  (let* ((dir (file-name-as-directory (file-truename temporary-file-directory)))
+         (process-environment (cons "CALLPROC_UNSET=1" process-environment))
+         (result (call-process* :command '("sh" "-c" "pwd; echo \"$CALLPROC_TEST${CALLPROC_UNSET-unset}\"")
+                                :env '(("CALLPROC_TEST" . "value")
+                                       ("CALLPROC_UNSET"))
+                                :cwd dir)))
+    (should (equal (plist-get result :stdout)
+                   (concat (directory-file-name dir) "\nvalueunset\n")))))
+
+(ert-deftest callproc-tests--call-process*-stdin-string ()
+  (skip-unless (executable-find "cat"))
+  (let ((result (call-process* :command '("cat") :stdin-string "hello")))
+    (should (equal (plist-get result :exit-code) 0))
+    (should (equal (plist-get result :stdout) "hello"))))
+
+(ert-deftest callproc-tests--call-process*-timeout ()
+  (skip-unless (executable-find "sleep"))
+  (let ((result (call-process* :command '("sleep" "10") :timeout 0.1)))
+    (should (plist-get result :timed-out))
+    (should-not (plist-get result :exit-code))
+    (should (plist-get result :signal))))
+
+(ert-deftest callproc-tests--call-process*-timeout-kills-children ()
+  (skip-unless (executable-find "sleep"))
+  (let* ((start (float-time))
+         (result (call-process* :command '("sh" "-c" "sleep 10 & sleep 10")
+                                :timeout 0.1)))
+    (should (plist-get result :timed-out))
+    (should (< (- (float-time) start) 5))))
+
+(ert-deftest callproc-tests--call-process*-timeout-escaped-child ()
+  (skip-unless (and (executable-find "sleep") (executable-find "setsid")))
+  (let* ((start (float-time))
+         (result (call-process* :command '("sh" "-c" "echo out; setsid sleep 10; true")
+                                :timeout 0.1)))
+    (should (plist-get result :timed-out))
+    (should (equal (plist-get result :stdout) "out\n"))
+    (should (< (- (float-time) start) 5))))
+
+(ert-deftest callproc-tests--call-process*-errors ()
+  (should-error (call-process* :command '("callproc-tests-no-such-program"))
+                :type 'file-error)
+  (should-error (call-process* :command '("true") :cwd "/callproc-tests-no-such-dir/")
+                :type 'file-error)
+  (should-error (call-process* :command '("true") :timeout -1)
+                :type 'args-out-of-range)
+  (dolist (timeout (list 1.0e+INF -1.0e+INF 0.0e+NaN 1e300))
+    (should-error (call-process* :command '("true") :timeout timeout)
+                  :type 'args-out-of-range))
+  (should-error (call-process* :command '("true") :env '(("A=B" . "value")))
+                :type 'error))
+
+(provide 'callproc-tests)
+;;; callproc-tests.el ends here
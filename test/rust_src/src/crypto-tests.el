@@ -0,0 +1,91 @@
+;;; crypto-tests.el --- Tests for crypto.rs
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest secure-hash-algorithms ()
+  (dolist (alg '(md5 sha1 sha224 sha256 sha384 sha512
+                 sha3-256 sha3-512 blake2b blake2s))
+    (should (memq alg (secure-hash-algorithms)))))
+
+(ert-deftest secure-hash-sha3 ()
+  (should (string= (secure-hash 'sha3-256 "")
+                   "a7ffc6f8bf1ed76651c14756a061d662f580ff4de43b49fa82d80a4b80f8434a"))
+  (should (string= (secure-hash 'sha3-512 "abc")
+                   (concat "b751850b1a57168a5693cd924b6b096e08f621827444f70d884f5d0240d2712e"
+                           "10e116e9192af3c91a7ec57647e3934057340b4cf408d5a56592f8274eec53f0"))))
+
+(ert-deftest secure-hash-blake2 ()
+  (should (string= (secure-hash 'blake2b "abc")
+                   (concat "ba80a53f981c4d0d6a2797b69f12f6e94c212f14685ac4b74b12bb6fdbffa2d1"
+                           "7d87c5392aab792dc252d5de4533cc9518d38aa8dbf1925ab92386edd4009923")))
+  (should (string= (secure-hash 'blake2s "abc")
+                   "508c5e8c327c14e2e1a72ba34eeb452f37458b209ed63a294d999b4c86675982"))
+  (should (= (length (secure-hash 'blake2s "abc" nil nil t)) 32)))
+
+(ert-deftest secure-hmac ()
+  ;; RFC 2104 and RFC 4231 test vectors.
+  (should (string= (secure-hmac 'md5 "Jefe" "what do ya want for nothing?")
+                   "750c783e6ab0b503eaa86e310a5db738"))
+  (should (string= (secure-hmac 'sha256 "Jefe" "what do ya want for nothing?")
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"))
+  (should (string= (secure-hmac 'sha256 (make-string 131 #xaa t)
+                                "Test Using Larger Than Block-Size Key - Hash Key First")
+                   "60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54"))
+  (should (string= (with-temp-buffer
+                     (insert "what do ya want for nothing?")
+                     (secure-hmac 'sha256 "Jefe" (current-buffer)))
+                   "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"))
+  (should (= (length (secure-hmac 'sha1 "key" "data" nil nil t)) 20))
+  (should-error (secure-hmac 'sha256 nil "data") :type 'wrong-type-argument))
+
+(ert-deftest hash-context ()
+  (let ((ctx (make-hash-context 'sha256)))
+    (should (hash-context-p ctx))
+    (should (eq (type-of ctx) 'hash-context))
+    (hash-context-update ctx "what do ya ")
+    (with-temp-buffer
+      (insert "want for nothing?")
+      (hash-context-update ctx (current-buffer)))
+    (should (string= (hash-context-finish ctx)
+                     (secure-hash 'sha256 "what do ya want for nothing?")))
+    (should-error (hash-context-update ctx "more"))
+    (should-error (hash-context-finish ctx)))
+  (let ((ctx (make-hash-context 'blake2s)))
+    (hash-context-update ctx "abcdef" 1 3)
+    (should (string= (hash-context-finish ctx t)
+                     (secure-hash 'blake2s "bc" nil nil t))))
+  (should-error (make-hash-context 'crc32))
+  (should-error (hash-context-update "not a context" "abc")
+                :type 'wrong-type-argument))
+
+(ert-deftest crypto-tests-buffer-hash-gap ()
+  (with-temp-buffer
+    (insert "hello, wörld")
+    (let ((hash (buffer-hash)))
+      (should (string= hash (sha1 (buffer-string))))
+      ;; Moving the gap doesn't change what is hashed.
+      (goto-char 5)
+      (insert "x")
+      (delete-char -1)
+      (should (string= (buffer-hash) hash)))))
+
+(provide 'crypto-tests)
+
+;;; crypto-tests.el ends here
@@ -136,5 +136,56 @@
   ;; Defined in Rust
   (should (consp (find-definition-noselect 'post-self-insert-hook 'defvar))))
 
+(defvar data-tests--watched 0)
+(defvar data-tests--watch-log nil)
+
+(defun data-tests--watcher (_symbol newval operation where)
+  (push (list newval operation where) data-tests--watch-log))
+
+(ert-deftest data-test--variable-watcher-operations ()
+  (setq data-tests--watch-log nil)
+  (add-variable-watcher 'data-tests--watched #'data-tests--watcher '(let unlet))
+  (unwind-protect
+      (progn
+        (setq data-tests--watched 1)
+        (let ((data-tests--watched 2))
+          (setq data-tests--watched 3))
+        (should (equal (reverse data-tests--watch-log)
+                       '((2 let nil) (1 unlet nil))))
+        (should (equal (get-variable-watchers 'data-tests--watched)
+                       '(data-tests--watcher))))
+    (remove-variable-watcher 'data-tests--watched #'data-tests--watcher))
+  (should-not (get 'data-tests--watched 'watcher-filters)))
+
+(ert-deftest data-test--variable-watcher-where ()
+  (setq data-tests--watch-log nil)
+  (with-temp-buffer
+    (let ((buffer (current-buffer)))
+      (add-variable-watcher 'data-tests--watched #'data-tests--watcher nil buffer)
+      (unwind-protect
+          (progn
+            (setq data-tests--watched 1)
+            (setq-local data-tests--watched 2)
+            (with-temp-buffer
+              (setq-local data-tests--watched 3))
+            (should (equal data-tests--watch-log `((2 set ,buffer)))))
+        (remove-variable-watcher 'data-tests--watched #'data-tests--watcher)))))
+
+(ert-deftest data-test--variable-watcher-refilter ()
+  (setq data-tests--watch-log nil)
+  (add-variable-watcher 'data-tests--watched #'data-tests--watcher '(makunbound))
+  (unwind-protect
+      (progn
+        (setq data-tests--watched 1)
+        (should-not data-tests--watch-log)
+        ;; Adding the watcher again replaces its filters.
+        (add-variable-watcher 'data-tests--watched #'data-tests--watcher)
+        (setq data-tests--watched 2)
+        (should (equal data-tests--watch-log '((2 set nil))))
+        (should (equal (get-variable-watchers 'data-tests--watched)
+                       '(data-tests--watcher))))
+    (remove-variable-watcher 'data-tests--watched #'data-tests--watcher))
+  (should-error (add-variable-watcher 'data-tests--watched #'ignore '(frob))))
+
 (provide 'data-tests)
 ;;; data-tests.el ends here
@@ -62,6 +62,193 @@
                    (set-buffer-multibyte nil)
                    (zlib-decompress-region (point-min) (point-max)))))))
 
+(ert-deftest zlib--compress-roundtrip ()
+  "Test compressing and decompressing a region in each format."
+  (when (and (fboundp 'zlib-available-p)
+	     (zlib-available-p))
+    (let ((text (concat "foo\n" (make-string (* 64 1024) ?a) "bar\n")))
+      (dolist (format '(nil zlib gzip raw))
+        (with-temp-buffer
+          (set-buffer-multibyte nil)
+          (insert text)
+          (should (zlib-compress-region (point-min) (point-max) format 9))
+          (should (< (buffer-size) (length text)))
+          (zlib-decompress-region (point-min) (point-max))
+          (should (string= (buffer-string) text)))))))
+
+(ert-deftest zlib--compress-gzip-header ()
+  "Test that gzip output starts with the gzip magic number."
+  (when (and (fboundp 'zlib-available-p)
+	     (zlib-available-p))
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (insert "foo\n")
+      (zlib-compress-region (point-min) (point-max) 'gzip)
+      (should (string-prefix-p "\x1f\x8b" (buffer-string))))))
+
+(ert-deftest zlib--compress-partial-region ()
+  "Test compressing only part of a buffer."
+  (when (and (fboundp 'zlib-available-p)
+	     (zlib-available-p))
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (insert "head" (make-string 1000 ?b) "tail")
+      (zlib-compress-region 5 1005 'zlib 0)
+      (should (string-prefix-p "head" (buffer-string)))
+      (should (string-suffix-p "tail" (buffer-string)))
+      (zlib-decompress-region 5 (- (point-max) 4))
+      (should (string= (buffer-string)
+                       (concat "head" (make-string 1000 ?b) "tail"))))))
+
+(ert-deftest zlib--compress-invalid-arguments ()
+  (when (and (fboundp 'zlib-available-p)
+	     (zlib-available-p))
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (insert "foo")
+      (should-error (zlib-compress-region (point-min) (point-max) 'lz4))
+      (should-error (zlib-compress-region (point-min) (point-max) nil 10)
+                    :type 'args-out-of-range)
+      (should (string= (buffer-string) "foo")))
+    (with-temp-buffer
+      (insert "foo")
+      (should-error (zlib-compress-region (point-min) (point-max))))))
+
+(ert-deftest decompress--autodetect ()
+  "Test decompressing files in each supported format."
+  (dolist (file '("foo.gz" "foo.zst" "foo.xz" "foo.lzma" "foo.bz2"))
+    (should (string=
+             (with-temp-buffer
+               (set-buffer-multibyte nil)
+               (insert-file-contents-literally
+                (expand-file-name file zlib-tests-data-directory))
+               (should (decompress-region (point-min) (point-max)))
+               (buffer-string))
+             "foo\n"))))
+
+(ert-deftest decompress--explicit-format ()
+  "Test decompressing with an explicit format."
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert-file-contents-literally
+     (expand-file-name "foo.xz" zlib-tests-data-directory))
+    (should (decompress-region (point-min) (point-max) 'xz))
+    (should (string= (buffer-string) "foo\n")))
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert-file-contents-literally
+     (expand-file-name "foo.xz" zlib-tests-data-directory))
+    (let ((contents (buffer-string)))
+      (should-not (decompress-region (point-min) (point-max) 'bzip2))
+      (should (string= (buffer-string) contents))))
+  (with-temp-buffer
+    (set-buffer-multibyte nil)
+    (insert "foo")
+    (should-error (decompress-region (point-min) (point-max) 'rar))))
+
+(ert-deftest decompress--available-formats ()
+  (dolist (format '(zlib gzip raw zstd xz lzma bzip2))
+    (should (memq format (decompress-available-formats)))))
+
+(ert-deftest decompress--to-buffer ()
+  "Test decompressing from a buffer into another buffer."
+  (let ((source (generate-new-buffer " *compressed*")))
+    (unwind-protect
+        (progn
+          (with-current-buffer source
+            (set-buffer-multibyte nil)
+            (insert-file-contents-literally
+             (expand-file-name "32k-a.gz" zlib-tests-data-directory)))
+          (with-temp-buffer
+            (let ((calls 0))
+              (should (= (decompress-to-buffer
+                          source (current-buffer) nil nil
+                          (lambda (read written)
+                            (should (<= read (buffer-size source)))
+                            (should (<= written (* 32 1024)))
+                            (setq calls (1+ calls))))
+                         (* 32 1024)))
+              (should (> calls 0)))
+            (should (string= (buffer-string) (make-string (* 32 1024) ?a)))))
+      (kill-buffer source))))
+
+(ert-deftest decompress--to-buffer-gap-in-magic ()
+  "Test detecting the format when the gap splits the magic number."
+  (let ((source (generate-new-buffer " *compressed*")))
+    (unwind-protect
+        (progn
+          (with-current-buffer source
+            (set-buffer-multibyte nil)
+            (insert-file-contents-literally
+             (expand-file-name "foo.xz" zlib-tests-data-directory))
+            ;; Move the gap to just after the third byte.
+            (goto-char 4)
+            (insert "x")
+            (delete-char -1))
+          (with-temp-buffer
+            (decompress-to-buffer source (current-buffer))
+            (should (string= (buffer-string) "foo\n"))))
+      (kill-buffer source))))
+
+(ert-deftest decompress--to-buffer-coding-system ()
+  "Test decoding the decompressed text into a multibyte buffer."
+  (let ((compressed (with-temp-buffer
+                      (set-buffer-multibyte nil)
+                      (insert (encode-coding-string "grüße\n" 'utf-8))
+                      (zlib-compress-region (point-min) (point-max) 'gzip)
+                      (buffer-string))))
+    (with-temp-buffer
+      (insert "<>")
+      (goto-char 2)
+      (decompress-to-buffer compressed (current-buffer) 'gzip 'utf-8)
+      (should (string= (buffer-string) "<grüße\n>")))))
+
+;; The text is decoded one chunk at a time, so characters are split
+;; across chunk boundaries.
+(ert-deftest decompress--to-buffer-coding-system-streamed ()
+  "Test decoding text whose characters straddle chunks."
+  (let ((text (apply #'concat (make-list 20000 "aé")))
+        compressed)
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (insert (encode-coding-string text 'utf-8))
+      (zlib-compress-region (point-min) (point-max) 'gzip)
+      (setq compressed (buffer-string)))
+    (with-temp-buffer
+      (insert "<>")
+      (goto-char 2)
+      (decompress-to-buffer compressed (current-buffer) 'gzip 'utf-8)
+      (should (string= (buffer-string) (concat "<" text ">")))
+      (should (= (point) (+ 2 (length text)))))))
+
+(ert-deftest decompress--to-buffer-progress-moves-point ()
+  "Test that the progress function can move point and edit the buffer."
+  (let (compressed)
+    (with-temp-buffer
+      (set-buffer-multibyte nil)
+      (insert (make-string (* 64 1024) ?a))
+      (zlib-compress-region (point-min) (point-max) 'zlib)
+      (setq compressed (buffer-string)))
+    (with-temp-buffer
+      (insert "<>")
+      (goto-char 2)
+      (decompress-to-buffer compressed (current-buffer) 'zlib nil
+                            (lambda (_read _written)
+                              (goto-char (point-min))
+                              (insert "x")))
+      (goto-char (point-min))
+      (should (> (skip-chars-forward "x") 0))
+      (should (string= (buffer-substring (point) (point-max))
+                       (concat "<" (make-string (* 64 1024) ?a) ">"))))))
+
+(ert-deftest decompress--to-buffer-error ()
+  "Test that a failed decompression leaves the target untouched."
+  (with-temp-buffer
+    (insert "unchanged")
+    (should-error (decompress-to-buffer "not compressed" (current-buffer) 'xz))
+    (should (string= (buffer-string) "unchanged"))
+    (should-error (decompress-to-buffer (current-buffer) (current-buffer)))))
+
 (provide 'decompress-tests)
 
 ;;; decompress-tests.el ends here.
@@ -35,3 +35,86 @@
         (should (= (length (system-users)) 1)))
     (progn
       (should (>= (length (system-users)) 1)))))
+
+(defmacro dired-tests--with-tree (files &rest body)
+  "Create FILES in a temporary directory bound to `dir', then run BODY."
+  (declare (indent 1))
+  `(let ((dir (file-name-as-directory (make-temp-file "dired-tests" t))))
+     (unwind-protect
+         (progn
+           (dolist (file ,files)
+             (let ((name (expand-file-name file dir)))
+               (if (string-suffix-p "/" file)
+                   (make-directory name t)
+                 (make-directory (file-name-directory name) t)
+                 (write-region "" nil name nil 'silent))))
+           ,@body)
+       (delete-directory dir t))))
+
+(ert-deftest test-directory-walk ()
+  (dired-tests--with-tree '("a.el" "b.txt" "sub/c.el" "sub/deeper/d.el" "empty/")
+    (let ((relative (lambda (files)
+                      (mapcar (lambda (f) (file-relative-name f dir)) files))))
+      (should (equal (funcall relative (directory-walk dir))
+                     '("a.el" "b.txt" "sub/c.el" "sub/deeper/d.el")))
+      (should (equal (funcall relative (directory-walk dir :match "\\.el\\'"))
+                     '("a.el" "sub/c.el" "sub/deeper/d.el")))
+      (should (equal (funcall relative (directory-walk dir :exclude "\\`deeper\\'"))
+                     '("a.el" "b.txt" "sub/c.el")))
+      (should (equal (funcall relative (directory-walk dir :max-depth 2))
+                     '("a.el" "b.txt" "sub/c.el")))
+      (should (equal (funcall relative (directory-walk dir :include-directories t
+                                                       :max-depth 1))
+                     '("a.el" "b.txt" "empty" "sub")))
+      (should-not (directory-walk dir :max-depth 0)))))
+
+(ert-deftest test-directory-walk-attributes-and-function ()
+  (dired-tests--with-tree '("a" "sub/b")
+    (let ((entries (directory-walk dir :attributes t)))
+      (should (= (length entries) 2))
+      (dolist (entry entries)
+        (should (equal (cdr entry) (file-attributes (car entry))))))
+    (let (seen)
+      (should-not (directory-walk dir :function (lambda (f) (push f seen))))
+      (should (equal (nreverse seen) (directory-walk dir))))))
+
+(ert-deftest test-directory-walk-gitignore ()
+  (dired-tests--with-tree '(".git/config" "keep.el" "build/out.o" "src/x.o"
+                            "src/keep.o" "src/y.el" "src/.gitignore")
+    (write-region "build/\n*.o\n" nil (expand-file-name ".gitignore" dir) nil 'silent)
+    (write-region "!keep.o\n" nil (expand-file-name "src/.gitignore" dir) nil 'silent)
+    (should (equal (mapcar (lambda (f) (file-relative-name f dir))
+                           (directory-walk dir :respect-gitignore t))
+                   '(".gitignore" "keep.el" "src/.gitignore" "src/keep.o" "src/y.el")))))
+
+(ert-deftest test-directory-walk-symlinks ()
+  (skip-unless (not (memq system-type '(windows-nt ms-dos))))
+  (dired-tests--with-tree '("other/file" "real/file")
+    (make-symbolic-link (expand-file-name "other" dir) (expand-file-name "real/link" dir))
+    (make-symbolic-link (expand-file-name "real" dir) (expand-file-name "real/loop" dir))
+    (let ((relative (lambda (files)
+                      (mapcar (lambda (f) (file-relative-name f dir)) files))))
+      (should (equal (funcall relative (directory-walk dir))
+                     '("other/file" "real/file")))
+      (should (equal (funcall relative (directory-walk dir :follow-symlinks t))
+                     '("other/file" "real/file" "real/link/file"))))))
+
+(ert-deftest test-directory-walk-missing ()
+  (should-error (directory-walk "/dired-tests-no-such-directory/")
+                :type 'file-missing))
+
+(ert-deftest test-directory-walk-not-a-directory ()
+  (dired-tests--with-tree '("file")
+    (let ((err (should-error (directory-walk (expand-file-name "file" dir))
+                             :type 'file-error)))
+      (should-not (eq (car err) 'file-missing)))))
+
+(ert-deftest test-directory-walk-function-uses-regexps ()
+  (dired-tests--with-tree '("a.el" "b.txt" "sub/c.el" "sub/d.txt")
+    (let (seen)
+      (directory-walk dir :match "\\.el\\'" :exclude "\\`sub\\'"
+                      :function (lambda (f)
+                                  (dotimes (i 30)
+                                    (string-match (format "x%d" i) f))
+                                  (push (file-relative-name f dir) seen)))
+      (should (equal (nreverse seen) '("a.el"))))))
@@ -20,10 +20,11 @@
 (require 'ert)
 
 (ert-deftest divide-extreme-sign ()
-  (should-error (ceiling most-negative-fixnum -1.0))
-  (should-error (floor most-negative-fixnum -1.0))
-  (should-error (round most-negative-fixnum -1.0))
-  (should-error (truncate most-negative-fixnum -1.0)))
+  (should (= (ceiling most-negative-fixnum -1.0) (- most-negative-fixnum)))
+  (should (= (floor most-negative-fixnum -1.0) (- most-negative-fixnum)))
+  (should (= (round most-negative-fixnum -1.0) (- most-negative-fixnum)))
+  (should (= (truncate most-negative-fixnum -1.0) (- most-negative-fixnum)))
+  (should (bignump (ceiling most-negative-fixnum -1.0))))
 
 (ert-deftest logb-extreme-fixnum ()
   (should (= (logb most-negative-fixnum) (1+ (logb most-positive-fixnum)))))
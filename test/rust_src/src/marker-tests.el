@@ -57,4 +57,63 @@
     (set-marker marker-2 marker-1)
     (should (goto-char marker-2))))
 
+;; Position conversions far from point, the gap and the buffer ends
+;; go through the checkpoint index, which edits must keep up to date.
+
+(defun marker-tests--check-positions (positions)
+  (save-restriction
+    (widen)
+    (dolist (pos positions)
+      (let ((bytes (1+ (string-bytes
+                        (buffer-substring-no-properties (point-min) pos)))))
+        (should (= (position-bytes pos) bytes))
+        (should (= (byte-to-position bytes) pos))))))
+
+(defun marker-tests--fill-buffer ()
+  (dotimes (i 5000)
+    (insert (format "%d: Grüße, 世界 — ünïcödé\n" i)))
+  ;; Keep point and the gap away from the positions checked.
+  (goto-char (point-min))
+  (insert "x")
+  (goto-char (point-min)))
+
+(ert-deftest marker-tests-charpos-index ()
+  (with-temp-buffer
+    (marker-tests--fill-buffer)
+    (let ((positions '(20000 50000 50001 90000 120000)))
+      (marker-tests--check-positions positions)
+      ;; Replace a stretch with text of a different byte length.
+      (save-excursion
+        (goto-char 40000)
+        (delete-region 40000 45000)
+        (insert (make-string 3000 ?λ)))
+      (goto-char (point-min))
+      (marker-tests--check-positions positions)
+      ;; Delete across several checkpoints.
+      (delete-region 10000 60000)
+      (goto-char (point-min))
+      (marker-tests--check-positions '(5000 9999 10000 10001 30000 70000)))))
+
+(ert-deftest marker-tests-charpos-index-transpose ()
+  (with-temp-buffer
+    (marker-tests--fill-buffer)
+    (marker-tests--check-positions '(30000 60000 90000))
+    (transpose-regions 20000 30000 60000 61000)
+    (goto-char (point-min))
+    (marker-tests--check-positions '(25000 30000 60000 90000))))
+
+(ert-deftest marker-tests-charpos-index-indirect ()
+  (with-temp-buffer
+    (marker-tests--fill-buffer)
+    (let ((base (current-buffer)))
+      (marker-tests--check-positions '(30000 60000))
+      (with-current-buffer (make-indirect-buffer base " *marker-tests*")
+        (unwind-protect
+            (progn
+              (goto-char 20000)
+              (insert "ĀĀĀĀ")
+              (goto-char (point-min)))
+          (kill-buffer)))
+      (marker-tests--check-positions '(30000 60000)))))
+
 ;;; marker-tests.el ends here.
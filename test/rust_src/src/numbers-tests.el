@@ -0,0 +1,73 @@
+;;; numbers-tests.el --- Tests for numbers.rs
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defun numbers-tests--draw (state)
+  (list (random 1000 state) (random (expt 2 80) state)
+        (random-float state) (random-bytes 8 state)))
+
+(ert-deftest make-random-state-seeded ()
+  (let ((a (make-random-state "seed"))
+        (b (make-random-state "seed")))
+    (should (random-state-p a))
+    (should (eq (type-of a) 'random-state))
+    (should (equal (numbers-tests--draw a) (numbers-tests--draw b))))
+  (should (equal (numbers-tests--draw (make-random-state 42))
+                 (numbers-tests--draw (make-random-state 42))))
+  (should-not (equal (numbers-tests--draw (make-random-state 42))
+                     (numbers-tests--draw (make-random-state 43))))
+  (should-error (make-random-state 'foo) :type 'wrong-type-argument))
+
+(ert-deftest random-state-copy ()
+  (let* ((a (make-random-state "seed"))
+         (_ (random 10 a))
+         (b (random-state-copy a)))
+    (should-not (eq a b))
+    (should (equal (numbers-tests--draw a) (numbers-tests--draw b))))
+  (should (random-state-p (random-state-copy))))
+
+(ert-deftest random-state-independent-of-global ()
+  (random "global")
+  (let ((expected (list (random) (random))))
+    (random "global")
+    (random 10 (make-random-state "seed"))
+    (random "other" (make-random-state))
+    (should (equal (list (random) (random)) expected))))
+
+(ert-deftest random-string-seed-matches-random-state ()
+  (random "seed")
+  (should (= (random 1000) (random 1000 (make-random-state "seed")))))
+
+(ert-deftest random-float-and-bytes ()
+  (let ((state (make-random-state 1)))
+    (dotimes (_ 100)
+      (let ((f (random-float state)))
+        (should (and (floatp f) (<= 0.0 f) (< f 1.0)))))
+    (should (equal (random-bytes 0 state) ""))
+    (let ((bytes (random-bytes 16 state)))
+      (should (= (length bytes) 16))
+      (should-not (multibyte-string-p bytes))))
+  (should-error (random-bytes -1) :type 'wrong-type-argument)
+  (should-error (random-bytes most-positive-fixnum))
+  (should-error (random 10 'not-a-state) :type 'wrong-type-argument))
+
+(provide 'numbers-tests)
+
+;;; numbers-tests.el ends here
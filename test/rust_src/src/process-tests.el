@@ -26,3 +26,80 @@
     (delete-process network-proc)
     (delete-process pipe-proc)
     (delete-process buffer-proc)))
+
+(ert-deftest process-tests--make-process-pipe ()
+  (skip-unless (executable-find "echo"))
+  (with-temp-buffer
+    (let ((proc (make-process :name "test-make-process"
+                              :buffer (current-buffer)
+                              :command '("echo" "hello")
+                              :connection-type 'pipe
+                              :sentinel #'ignore)))
+      (should (eq 'real (process-type proc)))
+      (should (eq #'ignore (process-sentinel proc)))
+      (should-not (process-tty-name proc))
+      (while (accept-process-output proc 1))
+      (should (equal "hello\n" (buffer-string))))))
+
+(ert-deftest process-tests--make-process-stderr ()
+  (skip-unless (executable-find "sh"))
+  (let ((stderr (generate-new-buffer " *test-stderr*")))
+    (unwind-protect
+        (with-temp-buffer
+          (let ((proc (make-process :name "test-make-process-stderr"
+                                    :buffer (current-buffer)
+                                    :command '("sh" "-c" "echo out; echo err >&2")
+                                    :stderr stderr
+                                    :noquery t)))
+            (should-not (process-query-on-exit-flag proc))
+            (while (accept-process-output proc 1))
+            (should (equal "out\n" (buffer-string)))
+            (with-current-buffer stderr
+              (while (accept-process-output (get-buffer-process stderr) 0.1))
+              (should (string-prefix-p "err\n" (buffer-string))))))
+      (kill-buffer stderr))))
+
+(ert-deftest process-tests--make-process-pty ()
+  (skip-unless (executable-find "cat"))
+  (let ((proc (make-process :name "test-make-process-pty"
+                            :command '("cat")
+                            :connection-type 'pty
+                            :noquery t)))
+    (unwind-protect
+        (should (stringp (process-tty-name proc)))
+      (delete-process proc))))
+
+(ert-deftest process-tests--make-process-bad-connection-type ()
+  (should-error (make-process :name "test-make-process-bad"
+                              :command '("true")
+                              :connection-type 'socket)
+                :type 'file-error))
+
+(ert-deftest process-tests--process-resource-usage ()
+  (skip-unless (executable-find "true"))
+  (let ((proc (make-process :name "test-resource-usage"
+                            :command '("true")
+                            :noquery t)))
+    (while (accept-process-output proc 1))
+    (let ((usage (process-resource-usage proc)))
+      (should (consp (alist-get 'utime usage)))
+      (should (consp (alist-get 'stime usage)))
+      (should (integerp (alist-get 'maxrss usage)))
+      (should (>= (float-time (alist-get 'etime usage)) 0)))
+    (should (equal (process-exit-details proc)
+                   '(:exit-code 0 :signaled nil :signal nil
+                     :signal-name nil :core-dumped nil)))))
+
+(ert-deftest process-tests--process-exit-details-signal ()
+  (skip-unless (executable-find "sleep"))
+  (let ((proc (make-process :name "test-exit-details"
+                            :command '("sleep" "10")
+                            :noquery t)))
+    (should-not (process-resource-usage proc))
+    (should-not (process-exit-details proc))
+    (signal-process proc 'SIGKILL)
+    (while (accept-process-output proc 1))
+    (let ((details (process-exit-details proc)))
+      (should (plist-get details :signaled))
+      (should-not (plist-get details :exit-code))
+      (should (equal (plist-get details :signal-name) "KILL")))))
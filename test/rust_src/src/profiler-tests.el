@@ -18,5 +18,97 @@
   (should (not (profiler-memory-running-p)))
   (should (profiler-memory-log)))
 
+(ert-deftest test-profiler-cpu ()
+  (should (not (profiler-cpu-running-p)))
+  (should (not (profiler-cpu-log)))
+  (should (not (profiler-cpu-stop)))
+
+  (should-error (profiler-cpu-start 0))
+  (should (not (profiler-cpu-running-p)))
+
+  (should (profiler-cpu-start 1000000))
+  (should-error (profiler-cpu-start 1000000))
+  (should (profiler-cpu-running-p))
+
+  (should (profiler-cpu-stop))
+  (should (not (profiler-cpu-running-p)))
+  (let ((log (profiler-cpu-log)))
+    (should (hash-table-p log))
+    (should (gethash [Automatic\ GC] log))))
+
+(defun profiler-tests--export (log format &optional kind)
+  (let ((file (make-temp-file "profiler-tests")))
+    (unwind-protect
+        (progn
+          (profiler-export-log log file format kind)
+          (with-temp-buffer
+            (set-buffer-multibyte nil)
+            (insert-file-contents-literally file)
+            (buffer-string)))
+      (delete-file file))))
+
+(ert-deftest test-profiler-export-log ()
+  (let ((log (make-hash-table :test 'equal)))
+    (puthash [inner outer nil nil] 3 log)
+    (puthash [outer nil nil nil] 2 log)
+    (should (equal (profiler-tests--export log 'collapsed)
+                   "outer 2\nouter;inner 3\n"))
+    (should (string-prefix-p "\x1f\x8b" (profiler-tests--export log 'pprof)))
+    (should-error (profiler-export-log log "/dev/null" 'svg))
+    (should-error (profiler-export-log log "/dev/null" 'pprof 'disk))))
+
+(ert-deftest test-profiler-export-log-sample-type ()
+  (let ((log (make-hash-table :test 'equal)))
+    (puthash [outer nil nil nil] 2 log)
+    (dolist (case '((cpu "samples" "count") (memory "space" "bytes")))
+      (let ((profile (with-temp-buffer
+                       (set-buffer-multibyte nil)
+                       (insert (profiler-tests--export log 'pprof (car case)))
+                       (zlib-decompress-region (point-min) (point-max))
+                       (buffer-string))))
+        (should (string-match-p (nth 1 case) profile))
+        (should (string-match-p (nth 2 case) profile))))))
+
+(defun profiler-tests--snapshot (counts time &rest entries)
+  (let ((log (make-hash-table :test 'equal)))
+    (while entries
+      (puthash (pop entries) (pop entries) log))
+    (list :log log :counts counts :time time)))
+
+(ert-deftest test-profiler-memory-snapshot ()
+  (let ((snapshot (profiler-memory-snapshot)))
+    (should (hash-table-p (plist-get snapshot :log)))
+    (should (= (length (plist-get snapshot :counts))
+               (length (memory-use-counts))))
+    (should (floatp (plist-get snapshot :time))))
+  (should (profiler-memory-start))
+  (unwind-protect
+      (let* ((before (profiler-memory-snapshot))
+             (_ (make-list 100000 nil))
+             (after (profiler-memory-snapshot))
+             (diff (profiler-memory-diff before after)))
+        (should (profiler-memory-running-p))
+        (should (>= (car (plist-get diff :counts)) 100000))
+        (should (hash-table-p (plist-get diff :log)))
+        (should (>= (plist-get diff :elapsed) 0)))
+    (profiler-memory-stop)
+    (profiler-memory-log)))
+
+(ert-deftest test-profiler-memory-diff ()
+  (let* ((a (profiler-tests--snapshot '(10 5) 100.0
+                                      [f g nil] 8 [g nil nil] 4 [h nil nil] 2))
+         (b (profiler-tests--snapshot '(15 5) 102.5
+                                      [f g nil] 20 [g nil nil] 4 [i nil nil] 1))
+         (diff (profiler-memory-diff a b))
+         (log (plist-get diff :log)))
+    (should (equal (plist-get diff :counts) '(5 0)))
+    (should (= (plist-get diff :elapsed) 2.5))
+    (should (= (hash-table-count log) 3))
+    (should (= (gethash [f g nil] log) 12))
+    (should (= (gethash [h nil nil] log) -2))
+    (should (= (gethash [i nil nil] log) 1))
+    (should-not (gethash [g nil nil] log)))
+  (should-error (profiler-memory-diff nil (profiler-memory-snapshot))))
+
 (provide 'profiler-tests)
 ;;; profiler-tests.el ends here
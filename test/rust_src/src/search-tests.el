@@ -0,0 +1,170 @@
+;;; search-tests.el --- tests for search.rs functions
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest search-tests-looking-at ()
+  (with-temp-buffer
+    (insert "foo bar")
+    (goto-char (point-min))
+    (should (looking-at "fo+"))
+    (should (= (match-end 0) 4))
+    (should-not (looking-at "bar"))
+    (should (looking-at "\\(f\\)\\(o\\)\\2"))
+    (should (equal (match-string 2) "o"))))
+
+(ert-deftest search-tests-match-across-gap ()
+  (with-temp-buffer
+    (insert "hello world")
+    (goto-char 6)
+    (insert "-")
+    (goto-char (point-min))
+    (should (re-search-forward "o-w" nil t))
+    (should (= (point) 9))
+    (should (= (match-beginning 0) 5))))
+
+(ert-deftest search-tests-re-search-backward ()
+  (with-temp-buffer
+    (insert "abc abc abc")
+    (should (re-search-backward "b\\(c\\)" nil t))
+    (should (= (point) 10))
+    (should (= (match-beginning 1) 11))
+    (should (re-search-backward "abc" nil t 2))
+    (should (= (point) 1))))
+
+(ert-deftest search-tests-search-failed ()
+  (with-temp-buffer
+    (insert "abc")
+    (goto-char (point-min))
+    (should-error (re-search-forward "x+") :type 'search-failed)
+    (should-not (re-search-forward "x+" nil t))
+    (should (= (point) 1))
+    (should-not (re-search-forward "x+" nil 'move))
+    (should (= (point) 4))))
+
+(ert-deftest search-tests-point-anchor ()
+  (with-temp-buffer
+    (insert "aaa")
+    (goto-char 2)
+    (should (re-search-forward "\\=a" nil t))
+    (should (= (point) 3))
+    (should (looking-at "\\=a"))))
+
+(ert-deftest search-tests-syntax-and-categories ()
+  (with-temp-buffer
+    (insert "foo   bar")
+    (goto-char (point-min))
+    (should (re-search-forward "\\s-+" nil t))
+    (should (= (match-beginning 0) 4))
+    (should (looking-at "\\sw+\\>"))
+    (should (string-match "\\cg" "abcα"))
+    (should (= (match-beginning 0) 3))))
+
+(ert-deftest search-tests-string-match ()
+  (should (= (string-match "b+" "abbbc") 1))
+  (should (= (match-end 0) 4))
+  (should (= (string-match "c" "abbbc" -1) 4))
+  (should-not (string-match "a" "abbbc" 1))
+  (should (= (string-match "[[:digit:]]+" "x42y") 1))
+  (should (equal (match-string 0 "x42y") "42"))
+  (should-error (string-match "a" "abc" 10) :type 'args-out-of-range))
+
+(ert-deftest search-tests-case-fold ()
+  (let ((case-fold-search t))
+    (should (= (string-match "FOO" "xfoo") 1)))
+  (let ((case-fold-search nil))
+    (should-not (string-match "FOO" "xfoo"))))
+
+(ert-deftest search-tests-posix-longest-match ()
+  (should (= (string-match "a\\|ab" "abc") 0))
+  (should (= (match-end 0) 1))
+  (should (= (posix-string-match "a\\|ab" "abc") 0))
+  (should (= (match-end 0) 2)))
+
+(ert-deftest search-tests-invalid-regexp ()
+  (should-error (string-match "\\(" "abc") :type 'invalid-regexp)
+  (should-error (string-match "[a" "abc") :type 'invalid-regexp)
+  (should-error (string-match "a\\{2,1\\}" "aa") :type 'invalid-regexp))
+
+(ert-deftest search-tests-explicit-group-numbers ()
+  (should (string-match "\\(?2:b\\)\\(c\\)" "abc"))
+  (should (equal (match-string 2 "abc") "b"))
+  (should (equal (match-string 3 "abc") "c"))
+  (should-not (match-beginning 1)))
+
+;; search-forward-any / search-backward-any
+
+(ert-deftest search-tests-search-forward-any ()
+  (with-temp-buffer
+    (insert "one two three two one")
+    (goto-char (point-min))
+    (should (= (search-forward-any '("three" "two")) 8))
+    (should (= (match-beginning 0) 5))
+    (should-not (match-beginning 1))
+    (should (= (match-beginning 2) 5))
+    (should (= (search-forward-any '("three" "two")) 14))
+    (should (match-beginning 1))))
+
+(ert-deftest search-tests-search-forward-any-overlapping ()
+  (with-temp-buffer
+    (insert "xabcd")
+    (goto-char (point-min))
+    ;; The occurrence beginning first wins over one ending first.
+    (should (= (search-forward-any '("bc" "abcd")) 6))
+    (should (match-beginning 2))
+    (goto-char (point-min))
+    ;; Among occurrences beginning together, the first listed wins.
+    (should (= (search-forward-any '("ab" "abc")) 4))
+    (should (match-beginning 1))))
+
+(ert-deftest search-tests-search-backward-any ()
+  (with-temp-buffer
+    (insert "one two three two one")
+    (should (= (search-backward-any '("two" "three")) 15))
+    (should (match-beginning 1))
+    (should (= (search-backward-any '("two" "three")) 9))
+    (should (= (match-end 2) 14))))
+
+(ert-deftest search-tests-search-any-across-gap ()
+  (with-temp-buffer
+    (insert "foobar")
+    (goto-char 4)
+    (insert "é")
+    (goto-char (point-min))
+    (should (= (search-forward-any '("oéb" "xyz")) 6))
+    (should (= (search-backward-any '("foo" "oéb")) 3))))
+
+(ert-deftest search-tests-search-any-bound-and-noerror ()
+  (with-temp-buffer
+    (insert "abc abc")
+    (goto-char (point-min))
+    (should-not (search-forward-any '("c a") 3 t))
+    (should (= (point) 1))
+    (should-not (search-forward-any '("zz") 5 'move))
+    (should (= (point) 5))
+    (should-error (search-forward-any '("zz")) :type 'search-failed)
+    (should-error (search-forward-any '("a") 2) :type 'error)))
+
+(ert-deftest search-tests-search-any-case-fold ()
+  (with-temp-buffer
+    (insert "Hello World")
+    (goto-char (point-min))
+    (let ((case-fold-search t))
+      (should (= (search-forward-any '("WORLD")) 12)))
+    (goto-char (point-min))
+    (let ((case-fold-search nil))
+      (should-not (search-forward-any '("WORLD") nil t)))))
+
+(ert-deftest search-tests-buffer-editable-after-failure ()
+  ;; A failed search exits non-locally while it is reading the text;
+  ;; the buffer must not stay locked afterwards.
+  (with-temp-buffer
+    (insert "abc")
+    (should-error (re-search-forward "x+") :type 'search-failed)
+    (should-error (search-forward-any '("x")) :type 'search-failed)
+    (insert "def")
+    (should (equal (buffer-string) "abcdef"))
+    (should (kill-buffer))))
+
+;;; search-tests.el ends here
@@ -0,0 +1,59 @@
+;;; str2sig-tests.el --- Tests for str2sig.rs -*- lexical-binding: t; -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest str2sig-tests--signal-number ()
+  (should (equal (signal-number "TERM") 15))
+  (should (equal (signal-number "SIGTERM") 15))
+  (should (equal (signal-number 'sigkill) 9))
+  (should (equal (signal-number "9") 9))
+  (should-not (signal-number "NOSUCHSIGNAL"))
+  (should-not (signal-number "SIG-1"))
+  (let ((max (apply #'max (mapcar #'cdr (signal-names)))))
+    (should (equal (signal-number (number-to-string max)) max))
+    (should-not (signal-number (number-to-string (1+ max)))))
+  (should-not (signal-number "99999999999"))
+  (should-error (signal-number 15) :type 'wrong-type-argument))
+
+(ert-deftest str2sig-tests--signal-name ()
+  (should (equal (signal-name 9) "KILL"))
+  (should (equal (signal-name 15) "TERM"))
+  (should-not (signal-name -1))
+  (should-not (signal-name most-positive-fixnum)))
+
+(ert-deftest str2sig-tests--signal-names ()
+  (let ((names (signal-names)))
+    (should (equal (cdr (assoc "HUP" names)) 1))
+    (dolist (entry names)
+      (should (equal (signal-number (car entry)) (cdr entry))))))
+
+(ert-deftest str2sig-tests--linux-signals ()
+  (skip-unless (eq system-type 'gnu/linux))
+  (should (equal (signal-number "STKFLT") 16))
+  (should (equal (signal-name 16) "STKFLT")))
+
+(ert-deftest str2sig-tests--realtime-signals ()
+  (skip-unless (signal-number "RTMIN"))
+  (let ((min (signal-number "RTMIN"))
+        (max (signal-number "RTMAX")))
+    (should (<= min max))
+    (should (equal (signal-number "RTMIN+1") (1+ min)))
+    (should (equal (signal-number "rtmax-1") (1- max)))
+    (should (equal (signal-name (1+ min)) "RTMIN+1"))
+    (should (equal (signal-name (1- max)) "RTMAX-1"))
+    (should-not (signal-number (format "RTMIN+%d" (1+ (- max min)))))
+    (should-not (signal-number "RTMIN++1"))))
+
+(ert-deftest str2sig-tests--signal-process-names ()
+  (skip-unless (executable-find "sleep"))
+  (let ((proc (make-process :name "str2sig-test"
+                            :command '("sleep" "10")
+                            :noquery t)))
+    (should (equal (signal-process proc "term") 0))
+    (while (accept-process-output proc 1))
+    (should (equal (plist-get (process-exit-details proc) :signal-name) "TERM"))))
+
+(provide 'str2sig-tests)
+;;; str2sig-tests.el ends here
@@ -0,0 +1,51 @@
+;;; threads-tests.el --- -*- lexical-binding: t; -*-
+
+;;; Code:
+
+(require 'ert)
+
+(ert-deftest threads-list-all-current ()
+  (skip-unless (fboundp 'make-thread))
+  (let ((entry (assq (current-thread) (thread-list-all))))
+    (should entry)
+    (should (null (nth 1 entry)))
+    (should (eq (nth 2 entry) (current-buffer)))))
+
+(ert-deftest threads-list-all-blocked ()
+  (skip-unless (fboundp 'make-thread))
+  (let* ((mutex (make-mutex "list-all"))
+         (buffer (get-buffer-create " *threads-list-all*"))
+         thread)
+    (unwind-protect
+        (progn
+          (mutex-lock mutex)
+          (setq thread (make-thread (lambda ()
+                                      (with-current-buffer buffer
+                                        (mutex-lock mutex)
+                                        (mutex-unlock mutex)))))
+          (while (not (thread--blocker thread))
+            (thread-yield))
+          (let ((entry (assq thread (thread-list-all))))
+            (should (eq (nth 1 entry) mutex))
+            (should (eq (nth 2 entry) buffer)))
+          (should (equal (mapcar #'car (thread-list-all)) (all-threads)))
+          (mutex-unlock mutex)
+          (thread-join thread)
+          (should-not (assq thread (thread-list-all))))
+      (kill-buffer buffer))))
+
+(ert-deftest threads-mutex-wrong-type ()
+  (skip-unless (fboundp 'make-thread))
+  (should-error (mutex-lock (current-thread)) :type 'wrong-type-argument)
+  (should-error (condition-wait (make-mutex)) :type 'wrong-type-argument)
+  (should-error (make-mutex 'name) :type 'wrong-type-argument))
+
+(ert-deftest threads-condition-notify-unowned ()
+  (skip-unless (fboundp 'make-thread))
+  (let ((cv (make-condition-variable (make-mutex) "cv")))
+    (should (equal (condition-name cv) "cv"))
+    (should-error (condition-notify cv))
+    (should-error (condition-wait cv))))
+
+(provide 'threads-tests)
+;;; threads-tests.el ends here
@@ -0,0 +1,76 @@
+;;; xml-tests.el --- Tests for xml.rs
+
+;; This file is part of GNU Emacs.
+
+;; GNU Emacs is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU General Public License as published by
+;; the Free Software Foundation, either version 3 of the License, or
+;; (at your option) any later version.
+
+;; GNU Emacs is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU General Public License for more details.
+
+;; You should have received a copy of the GNU General Public License
+;; along with GNU Emacs.  If not, see <https://www.gnu.org/licenses/>.
+
+;;; Code:
+
+(require 'ert)
+
+(defun xml-tests--parse (string &optional html discard-comments)
+  (with-temp-buffer
+    (insert string)
+    (if html
+        (libxml-parse-html-region (point-min) (point-max) nil discard-comments)
+      (libxml-parse-xml-region (point-min) (point-max) nil discard-comments))))
+
+(ert-deftest libxml-available-p ()
+  (should (eq (libxml-available-p) t)))
+
+(ert-deftest libxml-parse-xml-region-entities ()
+  (should (equal (xml-tests--parse "<a x='1 &lt; 2'>&#65;<![CDATA[<b>]]></a>")
+                 '(a ((x . "1 < 2")) "A" "<b>"))))
+
+(ert-deftest libxml-parse-xml-region-blanks ()
+  (should (equal (xml-tests--parse "<a>\n  <b/>\n</a>")
+                 '(a nil (b nil))))
+  (should (equal (xml-tests--parse "<a> </a>")
+                 '(a nil " "))))
+
+(ert-deftest libxml-parse-xml-region-namespaces ()
+  (should (equal (xml-tests--parse "<x:a xmlns:x=\"urn:x\" x:b=\"c\"/>")
+                 '(a ((b . "c"))))))
+
+(ert-deftest libxml-parse-xml-region-malformed ()
+  (should-not (xml-tests--parse "<a><b></a>"))
+  (should-not (xml-tests--parse "<a>&undefined;</a>"))
+  (should-not (xml-tests--parse "")))
+
+(ert-deftest libxml-parse-xml-region-discard-comments ()
+  (should (equal (xml-tests--parse "<a><!--c-->b</a>" nil t)
+                 '(a nil "b"))))
+
+(ert-deftest libxml-parse-html-region-implied ()
+  (should (equal (xml-tests--parse "hello" t)
+                 '(html nil (body nil (p nil "hello")))))
+  (should (equal (xml-tests--parse "<title>T</title><p>x" t)
+                 '(html nil (head nil (title nil "T")) (body nil (p nil "x"))))))
+
+(ert-deftest libxml-parse-html-region-recovery ()
+  (should (equal (xml-tests--parse "<UL><li>a<li>b</ul>" t)
+                 '(html nil (body nil (ul nil (li nil "a") (li nil "b"))))))
+  (should (equal (xml-tests--parse "<p>Hello &amp; <b>world</b>&nbsp;</p>" t)
+                 '(html nil (body nil (p nil "Hello & " (b nil "world")
+                                         "\u00a0"))))))
+
+(ert-deftest libxml-parse-html-region-attributes ()
+  (should (equal (xml-tests--parse "<input type=checkbox checked><br>" t)
+                 '(html nil (body nil (input ((type . "checkbox")
+                                              (checked . "checked")))
+                                  (br nil))))))
+
+(provide 'xml-tests)
+
+;;; xml-tests.el ends here